@@ -0,0 +1,75 @@
+//! Benchmarks of saving and loading a cache of 10k tasks, in the different formats it supports
+
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use url::Url;
+
+use kitchen_fridge::cache::{CacheCompression, CacheFormat};
+use kitchen_fridge::calendar::SupportedComponents;
+use kitchen_fridge::traits::CalDavSource;
+use kitchen_fridge::{Cache, Item, Task};
+
+const TASK_COUNT: usize = 10_000;
+
+fn bench_folder(name: &str) -> PathBuf {
+    std::env::temp_dir().join("kitchen-fridge-benches").join(name)
+}
+
+/// Create a cache of `TASK_COUNT` tasks in a single calendar, and save it
+fn populate_cache(runtime: &tokio::runtime::Runtime, folder: &Path, format: CacheFormat, compression: CacheCompression) -> Cache {
+    let _ = std::fs::remove_dir_all(folder);
+    std::fs::create_dir_all(folder).unwrap();
+    let mut cache = Cache::builder(folder).format(format).compression(compression).build();
+    let cal_url: Url = "https://my.server.com/dav/calendars/john/bench/".parse().unwrap();
+    runtime.block_on(async {
+        let calendar = cache.create_calendar(cal_url.clone(), "Bench".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut calendar = calendar.write().await;
+        let tasks = (0..TASK_COUNT)
+            .map(|i| Item::Task(Task::new(format!("Task number {}", i), i % 3 == 0, &cal_url)))
+            .collect();
+        calendar.add_items_sync(tasks).unwrap();
+    });
+    cache.save_to_folder().unwrap();
+    cache
+}
+
+fn cache_benchmarks(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let settings = [
+        ("json", CacheFormat::Json, CacheCompression::None),
+        ("cbor", CacheFormat::Cbor, CacheCompression::None),
+        ("json+gzip", CacheFormat::Json, CacheCompression::Gzip),
+    ];
+
+    let mut group = c.benchmark_group("cache 10k tasks");
+    group.sample_size(10);
+    for (name, format, compression) in settings.iter() {
+        let folder = bench_folder(name);
+        let cache = populate_cache(&runtime, &folder, *format, *compression);
+
+        group.bench_function(format!("save {}", name), |b| b.iter(|| {
+            cache.save_to_folder().unwrap()
+        }));
+
+        // Items are loaded lazily, the first time they are used
+        group.bench_function(format!("load {}", name), |b| b.iter_batched(
+            || (),
+            |_| {
+                let cache = Cache::from_folder(&folder).unwrap();
+                runtime.block_on(async {
+                    for calendar in cache.get_calendars().await.unwrap().values() {
+                        assert_eq!(calendar.read().await.get_items_sync().unwrap().len(), TASK_COUNT);
+                    }
+                });
+                // The cache is saved when it is dropped, which must not be part of the measurement
+                cache
+            },
+            BatchSize::PerIteration,
+        ));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cache_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//! Benchmarks of the iCal parser and builder, on a calendar of 10k tasks (e.g. an imported `.ics` file)
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use url::Url;
+
+use kitchen_fridge::item::SyncStatus;
+
+const TASK_COUNT: usize = 10_000;
+
+fn calendar_content(task_count: usize) -> String {
+    let mut content = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Benchmarks//kitchen-fridge//EN\r\n");
+    for i in 0..task_count {
+        content.push_str(&format!("BEGIN:VTODO\r\n\
+            UID:{}-bench@kitchen-fridge\r\n\
+            DTSTAMP:20240315T093000Z\r\n\
+            CREATED:20240301T080000Z\r\n\
+            LAST-MODIFIED:20240315T093000Z\r\n\
+            SUMMARY:Task number {}\r\n\
+            DESCRIPTION:Something to do\\, with an escaped comma\\nand a second line\r\n\
+            CATEGORIES:work,bench\r\n\
+            DUE;VALUE=DATE:20240401\r\n\
+            PRIORITY:{}\r\n\
+            STATUS:{}\r\n\
+            END:VTODO\r\n", i, i, i % 10, if i % 3 == 0 { "COMPLETED" } else { "NEEDS-ACTION" }));
+    }
+    content.push_str("END:VCALENDAR\r\n");
+    content
+}
+
+fn parse_benchmarks(c: &mut Criterion) {
+    let calendar_url: Url = "https://my.server.com/dav/calendars/john/bench/".parse().unwrap();
+    let content = calendar_content(TASK_COUNT);
+    let items = kitchen_fridge::ical::parse_all(&content, &calendar_url).unwrap();
+    let single = calendar_content(1);
+    let item_url = calendar_url.join("task.ics").unwrap();
+
+    let mut group = c.benchmark_group("ical");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TASK_COUNT as u64));
+    group.bench_function("parse_all 10k tasks", |b| b.iter(|| {
+        kitchen_fridge::ical::parse_all(&content, &calendar_url).unwrap()
+    }));
+    group.bench_function("build_calendar 10k tasks", |b| b.iter(|| {
+        kitchen_fridge::ical::build_calendar(&items, "-//Benchmarks//kitchen-fridge//EN", None).unwrap()
+    }));
+    group.finish();
+
+    // Items downloaded from a server are parsed one at a time
+    c.bench_function("ical/parse single task", |b| b.iter(|| {
+        kitchen_fridge::ical::parse(&single, item_url.clone(), SyncStatus::NotSynced).unwrap()
+    }));
+}
+
+criterion_group!(benches, parse_benchmarks);
+criterion_main!(benches);
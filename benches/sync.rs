@@ -0,0 +1,100 @@
+//! Benchmarks of a sync between two caches of 10k tasks, a few hundred of them having changed on either side.
+//!
+//! The "remote" cache mocks a server (this requires the `local_calendar_mocks_remote_calendars` feature), so that this measures
+//! how the provider finds out what has to be synced and applies it, rather than any network latency.
+
+use std::sync::{Arc, Mutex};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use url::Url;
+
+use kitchen_fridge::calendar::cached_calendar::CachedCalendar;
+use kitchen_fridge::calendar::SupportedComponents;
+use kitchen_fridge::item::SyncStatus;
+use kitchen_fridge::mock_behaviour::MockBehaviour;
+use kitchen_fridge::provider::Provider;
+use kitchen_fridge::traits::CalDavSource;
+use kitchen_fridge::{Cache, Item, Task};
+
+const TASK_COUNT: usize = 10_000;
+/// How many items are changed on each side (of each kind of change)
+const CHANGE_COUNT: usize = 100;
+
+type CacheProvider = Provider<Cache, CachedCalendar, Cache, CachedCalendar>;
+
+/// Two caches that have been synced, and then changed on both sides
+async fn changed_provider() -> CacheProvider {
+    let folder = std::env::temp_dir().join("kitchen-fridge-benches").join("sync");
+    let mut local = Cache::new(&folder.join("local"));
+    let mut remote = Cache::new(&folder.join("remote"));
+    remote.set_mock_behaviour(Some(Arc::new(Mutex::new(MockBehaviour::new()))));
+
+    let cal_url: Url = "https://my.server.com/dav/calendars/john/bench/".parse().unwrap();
+    let local_cal = local.create_calendar(cal_url.clone(), "Bench".to_string(), SupportedComponents::TODO, None).await.unwrap();
+    let remote_cal = remote.create_calendar(cal_url.clone(), "Bench".to_string(), SupportedComponents::TODO, None).await.unwrap();
+    let mut local_cal = local_cal.write().await;
+    let mut remote_cal = remote_cal.write().await;
+
+    let mut urls: Vec<Url> = Vec::with_capacity(TASK_COUNT);
+    for i in 0..TASK_COUNT {
+        let mut task = Task::new(format!("Task number {}", i), false, &cal_url);
+        task.set_sync_status(SyncStatus::random_synced());
+        urls.push(task.url().clone());
+        local_cal.add_item_sync(Item::Task(task.clone())).unwrap();
+        remote_cal.add_item_sync(Item::Task(task)).unwrap();
+    }
+
+    let mut changed = urls.chunks(CHANGE_COUNT);
+    for url in changed.next().unwrap() {
+        local_cal.get_item_by_url_mut_sync(url).unwrap().unwrap_task_mut().set_name("Renamed locally".to_string());
+    }
+    for url in changed.next().unwrap() {
+        local_cal.mark_for_deletion_sync(url).unwrap();
+    }
+    for url in changed.next().unwrap() {
+        remote_cal.get_item_by_url_mut_sync(url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Renamed remotely".to_string());
+    }
+    for url in changed.next().unwrap() {
+        remote_cal.immediately_delete_item_sync(url).unwrap();
+    }
+    for i in 0..CHANGE_COUNT {
+        local_cal.add_item_sync(Item::Task(Task::new(format!("New local task {}", i), false, &cal_url))).unwrap();
+        remote_cal.add_item_sync(Item::Task(Task::new(format!("New remote task {}", i), false, &cal_url))).unwrap();
+    }
+
+    drop(local_cal);
+    drop(remote_cal);
+    Provider::new(remote, local)
+}
+
+fn sync_benchmarks(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("sync 10k tasks");
+    group.sample_size(10);
+    group.bench_function("with changes on both sides", |b| b.iter_batched(
+        || runtime.block_on(changed_provider()),
+        |mut provider| {
+            assert!(runtime.block_on(provider.sync()));
+            // The caches are saved when they are dropped, which must not be part of the measurement
+            provider
+        },
+        BatchSize::PerIteration,
+    ));
+    group.bench_function("without any change", |b| b.iter_batched(
+        || runtime.block_on(async {
+            let mut provider = changed_provider().await;
+            provider.sync().await;
+            provider
+        }),
+        |mut provider| {
+            assert!(runtime.block_on(provider.sync()));
+            provider
+        },
+        BatchSize::PerIteration,
+    ));
+    group.finish();
+}
+
+criterion_group!(benches, sync_benchmarks);
+criterion_main!(benches);
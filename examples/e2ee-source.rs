@@ -0,0 +1,185 @@
+//! This is an example of how kitchen-fridge can sync with a remote source that is not a CalDAV server.
+//!
+//! Here, the "server" only stores opaque (end-to-end encrypted) blobs, like EteSync/Etebase would do.
+//! Items are decrypted client-side, before they enter the local cache, and encrypted before they are uploaded.
+//!
+//! This example keeps the "server" in memory, so that it can run without any account.
+//! A real adapter would issue network requests, and call the actual decryption routines of the protocol in an `ItemCipher` implementation.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use csscolorparser::Color;
+use tokio::sync::RwLock;
+use url::Url;
+
+use kitchen_fridge::cache::Cache;
+use kitchen_fridge::calendar::SupportedComponents;
+use kitchen_fridge::calendar::cached_calendar::CachedCalendar;
+use kitchen_fridge::item::{SyncStatus, VersionTag};
+use kitchen_fridge::provider::Provider;
+use kitchen_fridge::resource::Resource;
+use kitchen_fridge::traits::{BaseCalendar, CalDavSource, DavCalendar};
+use kitchen_fridge::{Item, Task};
+
+const CACHE_FOLDER: &str = "test_cache/e2ee_source";
+const CALENDAR_URL: &str = "e2ee://example/calendars/personal/";
+
+
+/// Encrypts items before they leave this device, and decrypts them when they are received
+trait ItemCipher: Send + Sync {
+    fn encrypt(&self, ical: &str) -> Vec<u8>;
+    fn decrypt(&self, blob: &[u8]) -> Result<String, Box<dyn Error>>;
+}
+
+/// A placeholder for the actual cipher of the protocol (e.g. the one from the Etebase SDK).
+/// It does NOT encrypt anything, and must not be used for real data.
+struct PlaintextCipher;
+
+impl ItemCipher for PlaintextCipher {
+    fn encrypt(&self, ical: &str) -> Vec<u8> {
+        ical.as_bytes().to_vec()
+    }
+    fn decrypt(&self, blob: &[u8]) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8(blob.to_vec())?)
+    }
+}
+
+
+/// A calendar whose items are stored encrypted on the "server"
+struct E2eeCalendar {
+    name: String,
+    resource: Resource,
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+    cipher: Arc<dyn ItemCipher>,
+
+    /// What the server knows: opaque blobs and their version tags
+    blobs: HashMap<Url, (VersionTag, Vec<u8>)>,
+}
+
+impl E2eeCalendar {
+    fn store(&mut self, item: &Item) -> Result<SyncStatus, Box<dyn Error>> {
+        let ical = kitchen_fridge::ical::build_from(item)?;
+        let version_tag = VersionTag::from(uuid::Uuid::new_v4().to_hyphenated().to_string());
+        self.blobs.insert(item.url().clone(), (version_tag.clone(), self.cipher.encrypt(&ical)));
+        Ok(SyncStatus::Synced(version_tag))
+    }
+}
+
+#[async_trait]
+impl BaseCalendar for E2eeCalendar {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> SupportedComponents { self.supported_components }
+    fn color(&self) -> Option<&Color> { self.color.as_ref() }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        if self.blobs.contains_key(item.url()) {
+            return Err(format!("Item {} already exists", item.url()).into());
+        }
+        self.store(&item)
+    }
+
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        if !self.blobs.contains_key(item.url()) {
+            return Err(format!("Item {} does not exist", item.url()).into());
+        }
+        self.store(&item)
+    }
+}
+
+#[async_trait]
+impl DavCalendar for E2eeCalendar {
+    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self { name, resource, supported_components, color, cipher: Arc::new(PlaintextCipher), blobs: HashMap::new() }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        Ok(self.blobs.iter()
+            .map(|(url, (vt, _blob))| (url.clone(), vt.clone()))
+            .collect())
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        let (vt, blob) = match self.blobs.get(url) {
+            None => return Ok(None),
+            Some(entry) => entry,
+        };
+        // Decryption happens here, before the item is handed to the provider (and to the local cache)
+        let ical = self.cipher.decrypt(blob)?;
+        Ok(Some(kitchen_fridge::ical::parse(&ical, url.clone(), SyncStatus::Synced(vt.clone()))?))
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+        let mut items = Vec::new();
+        for url in urls {
+            items.push(self.get_item_by_url(url).await?);
+        }
+        Ok(items)
+    }
+
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        match self.blobs.remove(item_url) {
+            None => Err(format!("Item {} does not exist", item_url).into()),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+
+/// The remote source, that contains every E2EE calendar of an account
+#[derive(Default)]
+struct E2eeSource {
+    calendars: HashMap<Url, Arc<RwLock<E2eeCalendar>>>,
+}
+
+#[async_trait]
+impl CalDavSource<E2eeCalendar> for E2eeSource {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<E2eeCalendar>>>, Box<dyn Error>> {
+        Ok(self.calendars.clone())
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<E2eeCalendar>>> {
+        self.calendars.get(url).cloned()
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<E2eeCalendar>>, Box<dyn Error>> {
+        let resource = Resource::new(url.clone(), String::new(), String::new());
+        let calendar = Arc::new(RwLock::new(E2eeCalendar::new(name, resource, supported_components, color)));
+        self.calendars.insert(url, calendar.clone());
+        Ok(calendar)
+    }
+}
+
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    println!("This example shows how to sync a local cache with a custom (here: end-to-end encrypted) remote source.");
+
+    let cache = Cache::new(Path::new(CACHE_FOLDER));
+    let mut provider: Provider<Cache, CachedCalendar, E2eeSource, E2eeCalendar> = Provider::new(E2eeSource::default(), cache);
+
+    let cal_url: Url = CALENDAR_URL.parse().unwrap();
+    let calendar = provider.local_mut().create_calendar(cal_url.clone(), "Personal".to_string(), SupportedComponents::TODO, None).await.unwrap();
+    calendar.write().await.add_item(Item::Task(Task::new("Renew my passport".to_string(), false, &cal_url))).await.unwrap();
+
+    if !provider.sync().await {
+        println!("Sync did not complete, see the previous log lines for more info.");
+        return;
+    }
+
+    let remote_calendar = provider.remote().get_calendar(&cal_url).await.unwrap();
+    let remote_calendar = remote_calendar.read().await;
+    println!("The server now holds {} opaque blob(s):", remote_calendar.blobs.len());
+    for (url, (vt, blob)) in &remote_calendar.blobs {
+        println!("  * {} (version {}, {} bytes)", url, vt.as_str(), blob.len());
+    }
+
+    provider.local().save_to_folder().unwrap();
+}
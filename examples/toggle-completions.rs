@@ -6,7 +6,6 @@ use std::error::Error;
 use chrono::Utc;
 
 use kitchen_fridge::item::Item;
-use kitchen_fridge::task::CompletionStatus;
 use kitchen_fridge::CalDavProvider;
 use kitchen_fridge::utils::pause;
 
@@ -38,12 +37,12 @@ async fn toggle_all_tasks_and_sync_again(provider: &mut CalDavProvider) -> Resul
     let mut n_toggled = 0;
 
     for (_url, cal) in provider.local().get_calendars_sync()?.iter() {
-        for (_url, item) in cal.lock().unwrap().get_items_mut_sync()?.iter_mut() {
+        for (_url, item) in cal.write().await.get_items_mut_sync()?.iter_mut() {
             match item {
                 Item::Task(task) => {
                     match task.completed() {
-                        false => task.set_completion_status(CompletionStatus::Completed(Some(Utc::now()))),
-                        true => task.set_completion_status(CompletionStatus::Uncompleted),
+                        false => task.complete(Utc::now()),
+                        true => task.uncomplete(),
                     };
                     n_toggled += 1;
                 }
@@ -0,0 +1,37 @@
+//! Feed arbitrary bytes to the iCal parser, the way imported `.ics` files (e.g. email attachments) are.
+//!
+//! Run it with `cargo fuzz run parse_ical` (this requires a nightly toolchain and `cargo install cargo-fuzz`).
+//! Parsing may fail, but must never panic. The items that can be parsed are also used and serialized again.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use kitchen_fridge::item::SyncStatus;
+use kitchen_fridge::Item;
+use url::Url;
+
+fuzz_target!(|data: &[u8]| {
+    let content = kitchen_fridge::ical::encoding::decode_bytes(data);
+    let calendar_url: Url = "https://my.server.com/dav/calendars/john/fuzz/".parse().unwrap();
+    let item_url = calendar_url.join("item.ics").unwrap();
+
+    if let Ok(item) = kitchen_fridge::ical::parse(&content, item_url, SyncStatus::NotSynced) {
+        exercise(&item);
+    }
+    if let Ok(items) = kitchen_fridge::ical::parse_all(&content, &calendar_url) {
+        items.iter().for_each(exercise);
+    }
+    let _ = kitchen_fridge::ical::parse_calendar_metadata(&content);
+});
+
+/// Use every getter that parses a property
+fn exercise(item: &Item) {
+    if let Item::Task(task) = item {
+        let _ = (task.start(), task.due(), task.duration(), task.sequence(), task.status(), task.classification());
+        let _ = (task.categories(), task.color(), task.validate());
+        for time in task.start_time().iter().chain(task.due_time().iter()) {
+            let _ = time.span(&chrono::Utc);
+        }
+    }
+    let _ = kitchen_fridge::ical::build_from(item);
+}
@@ -0,0 +1,365 @@
+//! CardDAV address books, available with the `addressbook` feature
+//!
+//! Address books are discovered with [`Client::get_address_books`](crate::client::Client::get_address_books). \
+//! They are synced by the [`Provider`](crate::provider::Provider) along with calendars: every address book of the remote source gets a [`LocalAddressBook`]
+//! counterpart in the local [`Cache`](crate::cache::Cache), where contacts can be read and edited offline (see [`Cache::get_address_book_sync`](crate::cache::Cache::get_address_book_sync)).
+//! Conflicts are resolved in favour of the server. Address books that only exist locally are not created on the server.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+#[cfg(feature = "local_calendar_mocks_remote_calendars")]
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::contact::Contact;
+use crate::item::{SyncStatus, VersionTag};
+use crate::resource::Resource;
+use crate::utils::redact;
+#[cfg(feature = "local_calendar_mocks_remote_calendars")]
+use crate::mock_behaviour::MockBehaviour;
+
+static CONTACTS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+        <d:prop>
+            <d:getetag />
+            <d:resourcetype />
+        </d:prop>
+    </d:propfind>
+"#;
+
+/// What the [`Provider`](crate::provider::Provider) needs to sync the contacts of an address book.
+///
+/// This is implemented by CardDAV address books ([`RemoteAddressBook`]) and by the address books of the local cache ([`LocalAddressBook`])
+#[async_trait]
+pub trait AddressBook {
+    /// Returns the address book name
+    fn name(&self) -> &str;
+
+    /// Returns the address book URL
+    fn url(&self) -> &Url;
+
+    /// Returns the current version tag of every contact of this address book
+    async fn get_contact_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>>;
+
+    /// Returns the sync status of every contact of this address book.
+    /// Sources that do not track local changes (e.g. servers) return their contacts as synced with their current version tag (which is the default)
+    async fn get_contact_sync_statuses(&self) -> Result<HashMap<Url, SyncStatus>, Box<dyn Error>> {
+        Ok(self.get_contact_version_tags().await?.into_iter()
+            .map(|(url, tag)| (url, SyncStatus::Synced(tag)))
+            .collect())
+    }
+
+    /// Returns a contact
+    async fn get_contact_by_url(&self, url: &Url) -> Result<Contact, Box<dyn Error>>;
+
+    /// Add a contact, and return its new sync status.
+    /// Local address books store it with its current sync status, servers return the sync status they have stored it with
+    async fn add_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>>;
+
+    /// Replace a contact that already exists, and return its new sync status (see [`Self::add_contact`])
+    async fn update_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>>;
+
+    /// Remove a contact
+    async fn delete_contact(&mut self, url: &Url) -> Result<(), Box<dyn Error>>;
+}
+
+
+/// A source of address books (e.g. a CardDAV server, or a local cache), see [`CalDavSource::address_book_source`](crate::traits::CalDavSource::address_book_source)
+#[async_trait]
+pub trait AddressBookSource {
+    /// Returns the address books that this source contains
+    async fn get_address_books(&self) -> Result<HashMap<Url, SharedAddressBook>, Box<dyn Error>>;
+
+    /// Create an address book if it did not exist, and return it.
+    /// Sources that are not able to do this return an error (which is the default)
+    async fn create_address_book(&mut self, url: Url, _name: String) -> Result<SharedAddressBook, Box<dyn Error>> {
+        Err(format!("Unable to create address book {}: this source does not support it", redact(&url)).into())
+    }
+}
+
+/// An address book that can be shared across `.await` points, whatever its type
+pub type SharedAddressBook = Arc<RwLock<dyn AddressBook + Send + Sync>>;
+
+
+/// A CardDAV address book
+#[derive(Clone, Debug)]
+pub struct RemoteAddressBook {
+    name: String,
+    resource: Resource,
+}
+
+impl RemoteAddressBook {
+    pub fn new(name: String, resource: Resource) -> Self {
+        Self { name, resource }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn url(&self) -> &Url { self.resource.url() }
+
+    /// Returns the current version tag of every contact of this address book
+    pub async fn get_contact_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "PROPFIND", CONTACTS_BODY.to_string(), "response").await?;
+
+        let mut contacts = HashMap::new();
+        for response in responses {
+            // The address book itself is part of the response
+            let is_collection = crate::utils::find_elem(&response, "resourcetype")
+                .map(|rt| rt.children().any(|child| child.name() == "collection"))
+                .unwrap_or(false);
+            if is_collection {
+                continue;
+            }
+
+            let contact_url = match crate::utils::find_elem(&response, "href") {
+                None => {
+                    log::warn!("Unable to extract HREF");
+                    continue;
+                },
+                Some(href) => self.resource.resolve_href(&href.text()).url().clone(),
+            };
+
+            match crate::utils::find_elem(&response, "getetag") {
+                None => log::warn!("Unable to extract ETAG for contact {}, ignoring it", redact(&contact_url)),
+                Some(etag) => { contacts.insert(contact_url, VersionTag::from(etag.text())); },
+            }
+        }
+        Ok(contacts)
+    }
+
+    /// Download a contact
+    pub async fn get_contact_by_url(&self, url: &Url) -> Result<Contact, Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .get(url.clone())
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let version_tag = response.headers().get("ETag")
+            .ok_or_else(|| format!("No ETag for contact {}", url))?
+            .to_str()?
+            .to_string();
+        let text = response.text().await?;
+        self.resource.counters().record_received(text.len());
+        crate::contact::parse(&text, url.clone(), SyncStatus::Synced(VersionTag::from(version_tag)))
+    }
+
+    /// Upload a contact that does not exist on the server yet
+    pub async fn add_contact(&self, contact: &Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        self.put_contact(contact, "If-None-Match", "*").await
+    }
+
+    /// Upload a new version of a contact that has been locally modified
+    pub async fn update_contact(&self, contact: &Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        let old_etag = match contact.sync_status() {
+            SyncStatus::NotSynced => return Err("Cannot update a contact that has not been synced already".into()),
+            SyncStatus::Synced(_) => return Err("Cannot update a contact that has not changed".into()),
+            SyncStatus::LocallyModified(etag) => etag,
+            SyncStatus::LocallyDeleted(etag) => etag,
+        };
+        self.put_contact(contact, "If-Match", old_etag.as_str()).await
+    }
+
+    async fn put_contact(&self, contact: &Contact, condition: &str, condition_value: &str) -> Result<SyncStatus, Box<dyn Error>> {
+        let vcard = crate::contact::build_vcard(contact);
+
+        let request = self.resource.http_client()
+            .put(contact.url().clone())
+            .header(condition, condition_value)
+            .header(CONTENT_TYPE, "text/vcard")
+            .header(CONTENT_LENGTH, vcard.len())
+            .basic_auth(self.resource.username(), Some(self.resource.password()))
+            .body(vcard);
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let reply_hdrs = response.headers();
+        match reply_hdrs.get("ETag") {
+            None => Err(format!("No ETag in these response headers: {:?} (request was {:?})", reply_hdrs, contact.url()).into()),
+            Some(etag) => {
+                let vtag = VersionTag::from(String::from(etag.to_str()?));
+                Ok(SyncStatus::Synced(vtag))
+            }
+        }
+    }
+
+    /// Delete a contact
+    pub async fn delete_contact(&self, contact_url: &Url) -> Result<(), Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .delete(contact_url.clone())
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let del_response = self.resource.send(request).await?;
+
+        if !del_response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", del_response.status()).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AddressBook for RemoteAddressBook {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+
+    async fn get_contact_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        RemoteAddressBook::get_contact_version_tags(self).await
+    }
+
+    async fn get_contact_by_url(&self, url: &Url) -> Result<Contact, Box<dyn Error>> {
+        RemoteAddressBook::get_contact_by_url(self, url).await
+    }
+
+    async fn add_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        RemoteAddressBook::add_contact(self, &contact).await
+    }
+
+    async fn update_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        RemoteAddressBook::update_contact(self, &contact).await
+    }
+
+    async fn delete_contact(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        RemoteAddressBook::delete_contact(self, url).await
+    }
+}
+
+
+/// An address book stored in a local [`Cache`](crate::cache::Cache), whose contacts can be edited offline and are synced by the [`Provider`](crate::provider::Provider)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocalAddressBook {
+    name: String,
+    url: Url,
+    contacts: HashMap<Url, Contact>,
+
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    #[serde(skip)]
+    mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
+}
+
+impl LocalAddressBook {
+    pub fn new(name: String, url: Url) -> Self {
+        Self {
+            name, url,
+            contacts: HashMap::new(),
+            #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+            mock_behaviour: None,
+        }
+    }
+
+    /// Activate the "mocking remote address book" feature (i.e. contacts are stored as synced, since this is what an actual CardDAV sever would do)
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
+        self.mock_behaviour = mock_behaviour;
+    }
+
+    /// Returns every contact of this address book, including the ones that are marked for deletion
+    pub fn get_contacts(&self) -> &HashMap<Url, Contact> {
+        &self.contacts
+    }
+
+    /// Returns a contact
+    pub fn get_contact(&self, url: &Url) -> Option<&Contact> {
+        self.contacts.get(url)
+    }
+
+    /// Returns a contact, so that it can be edited (which updates its sync status, see e.g. [`Contact::set_full_name`])
+    pub fn get_contact_mut(&mut self, url: &Url) -> Option<&mut Contact> {
+        self.contacts.get_mut(url)
+    }
+
+    /// Add a new contact (e.g. created with [`Contact::new`]), that will be uploaded at the next sync
+    pub fn insert_contact(&mut self, contact: Contact) -> Result<(), Box<dyn Error>> {
+        if self.contacts.contains_key(contact.url()) {
+            return Err(format!("Contact {} already exists", redact(contact.url())).into());
+        }
+        self.contacts.insert(contact.url().clone(), contact);
+        Ok(())
+    }
+
+    /// Mark a contact for deletion. It is actually deleted at the next sync (or right now, in case it has never been synced)
+    pub fn mark_for_deletion(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        let contact = self.contacts.get_mut(url).ok_or_else(|| format!("Contact {} does not exist", redact(url)))?;
+        match contact.sync_status().clone() {
+            SyncStatus::NotSynced => { self.contacts.remove(url); },
+            SyncStatus::Synced(tag) | SyncStatus::LocallyModified(tag) => contact.set_sync_status(SyncStatus::LocallyDeleted(tag)),
+            SyncStatus::LocallyDeleted(_) => return Err(format!("Contact {} has been deleted already", redact(url)).into()),
+        }
+        Ok(())
+    }
+
+    /// Store a contact, as servers would: its sync status is set to synced
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    fn store_maybe_mocked(&mut self, mut contact: Contact) -> SyncStatus {
+        if self.mock_behaviour.is_some() && !matches!(contact.sync_status(), SyncStatus::Synced(_)) {
+            contact.set_sync_status(SyncStatus::random_synced());
+        }
+        let ss = contact.sync_status().clone();
+        self.contacts.insert(contact.url().clone(), contact);
+        ss
+    }
+
+    #[cfg(not(feature = "local_calendar_mocks_remote_calendars"))]
+    fn store_maybe_mocked(&mut self, contact: Contact) -> SyncStatus {
+        let ss = contact.sync_status().clone();
+        self.contacts.insert(contact.url().clone(), contact);
+        ss
+    }
+}
+
+#[async_trait]
+impl AddressBook for LocalAddressBook {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { &self.url }
+
+    async fn get_contact_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        Ok(self.contacts.iter()
+            .filter_map(|(url, contact)| match contact.sync_status() {
+                SyncStatus::Synced(tag) => Some((url.clone(), tag.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn get_contact_sync_statuses(&self) -> Result<HashMap<Url, SyncStatus>, Box<dyn Error>> {
+        Ok(self.contacts.iter()
+            .map(|(url, contact)| (url.clone(), contact.sync_status().clone()))
+            .collect())
+    }
+
+    async fn get_contact_by_url(&self, url: &Url) -> Result<Contact, Box<dyn Error>> {
+        self.contacts.get(url).cloned().ok_or_else(|| format!("Contact {} does not exist", redact(url)).into())
+    }
+
+    async fn add_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        if self.contacts.contains_key(contact.url()) {
+            return Err(format!("Contact {} already exists", redact(contact.url())).into());
+        }
+        Ok(self.store_maybe_mocked(contact))
+    }
+
+    async fn update_contact(&mut self, contact: Contact) -> Result<SyncStatus, Box<dyn Error>> {
+        if !self.contacts.contains_key(contact.url()) {
+            return Err(format!("Contact {} does not exist", redact(contact.url())).into());
+        }
+        Ok(self.store_maybe_mocked(contact))
+    }
+
+    async fn delete_contact(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        match self.contacts.remove(url) {
+            None => Err(format!("Contact {} does not exist", redact(url)).into()),
+            Some(_) => Ok(()),
+        }
+    }
+}
@@ -3,75 +3,283 @@
 use std::path::PathBuf;
 use std::path::Path;
 use std::error::Error;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+#[cfg(feature = "local_calendar_mocks_remote_calendars")]
+use std::sync::Mutex;
 use std::ffi::OsStr;
+use std::io::{Read, Write};
 
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
+use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::sync::broadcast;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
 use url::Url;
 
 use crate::traits::CalDavSource;
 use crate::traits::BaseCalendar;
 use crate::traits::CompleteCalendar;
+use crate::item::{Item, ItemVersion, StableItemId, SyncStatus};
 use crate::calendar::cached_calendar::CachedCalendar;
-use crate::calendar::SupportedComponents;
+use crate::calendar::{ArchivePolicy, SupportedComponents};
+use crate::cache::integrity::CacheProblem;
+use crate::clock::Clock;
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
 use crate::mock_behaviour::MockBehaviour;
+#[cfg(feature = "addressbook")]
+use crate::addressbook::{AddressBook, AddressBookSource, LocalAddressBook, SharedAddressBook};
+
+pub mod integrity;
+pub mod backup;
+mod migrations;
 
 const MAIN_FILE: &str = "data.json";
+/// The extension of the files address books are saved in
+#[cfg(feature = "addressbook")]
+const ADDRESS_BOOK_EXTENSION: &str = "abk";
 
-/// A CalDAV source that stores its items in a local folder.
+/// How many events can be queued for a [`Cache::subscribe`]r before the oldest ones are discarded
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A change that happened in a [`Cache`]. See [`Cache::subscribe`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheEvent {
+    /// An item has been added to a calendar
+    ItemAdded{ calendar: Url, item: Url },
+    /// An item has been replaced by a newer version, or has been marked for deletion
+    ItemUpdated{ calendar: Url, item: Url },
+    /// An item has been removed from a calendar
+    ItemDeleted{ calendar: Url, item: Url },
+    /// A calendar has been created, or several of its items have been changed at once (see e.g. [`CompleteCalendar::add_items`])
+    CalendarChanged{ calendar: Url },
+}
+
+/// The error returned by [`Cache::update_item_if_unchanged`] when the item has been changed (or deleted) since the expected version.
 ///
-/// It automatically updates the content of the folder when dropped (see its `Drop` implementation), but you can also manually call [`Cache::save_to_folder`]
+/// Apps can tell it from other errors using `err.downcast_ref::<EditConflict>()`, e.g. to ask the user whether their edit should overwrite the new version
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditConflict {
+    pub item: Url,
+    /// The current version of the item, or `None` if it does not exist anymore
+    pub current: Option<ItemVersion>,
+}
+
+impl std::fmt::Display for EditConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.current {
+            Some(_) => write!(f, "item {} has been changed in the meantime", crate::utils::redact(&self.item)),
+            None => write!(f, "item {} does not exist anymore", crate::utils::redact(&self.item)),
+        }
+    }
+}
+
+impl Error for EditConflict {}
+
+/// What [`Cache::import_ics`] should do with an imported item whose UID already exists in the calendar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupStrategy {
+    /// Keep the existing item, and ignore the imported one
+    Skip,
+    /// Overwrite the existing item with the content of the imported one
+    Replace,
+    /// Keep both items. The imported one is given a new UID
+    KeepBoth,
+}
+
+/// How the content of a [`Cache`] is serialized in its files (see [`CacheBuilder::format`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CacheFormat {
+    /// Human-readable files
+    #[default]
+    Json,
+    /// A binary format, that is quicker to save and load for caches with lots of items
+    Cbor,
+}
+
+/// How the files of a [`Cache`] are compressed (see [`CacheBuilder::compression`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CacheCompression {
+    #[default]
+    None,
+    Gzip,
+    /// Requires the `zstd` feature
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// The header of files in a binary format. It is followed by a byte that tells the format, and a byte for its version
+const BINARY_MAGIC: &[u8] = b"KFCACHE";
+const CBOR_FORMAT: u8 = b'C';
+const CBOR_FORMAT_VERSION: u8 = 1;
+
+/// Serialize a value to a file, in the given format and compression
+fn write_file<T: Serialize>(path: &Path, value: &T, format: CacheFormat, compression: CacheCompression) -> Result<(), std::io::Error> {
+    let content = match format {
+        CacheFormat::Json => serde_json::to_vec(value)?,
+        CacheFormat::Cbor => {
+            let mut content = BINARY_MAGIC.to_vec();
+            content.extend_from_slice(&[CBOR_FORMAT, CBOR_FORMAT_VERSION]);
+            serde_cbor::to_writer(&mut content, value)
+                .map_err(std::io::Error::other)?;
+            content
+        },
+    };
+
+    let mut file = std::fs::File::create(path)?;
+    match compression {
+        CacheCompression::None => file.write_all(&content)?,
+        CacheCompression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            encoder.write_all(&content)?;
+            encoder.finish()?;
+        },
+        #[cfg(feature = "zstd")]
+        CacheCompression::Zstd => {
+            let mut encoder = zstd::Encoder::new(file, 0)?;
+            encoder.write_all(&content)?;
+            encoder.finish()?;
+        },
+    }
+    Ok(())
+}
+
+/// Deserialize a value from a file.
 ///
-/// Most of its functionality is provided by the `CalDavSource` async trait it implements.
-/// However, since these functions do not _need_ to be actually async, non-async versions of them are also provided for better convenience. See [`Cache::get_calendar_sync`] for example
-#[derive(Debug)]
-pub struct Cache {
-    backing_folder: PathBuf,
-    data: CachedData,
+/// Its format and compression are detected from its content, so that caches can be read whatever settings they were saved with
+/// (e.g. caches in JSON are migrated to a binary format the next time they are saved).
+fn read_file<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let mut content = std::fs::read(path)?;
+    if content.starts_with(GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(content.as_slice()).read_to_end(&mut decompressed)?;
+        content = decompressed;
+    } else if content.starts_with(ZSTD_MAGIC) {
+        #[cfg(feature = "zstd")]
+        {
+            content = zstd::decode_all(content.as_slice())?;
+        }
+        #[cfg(not(feature = "zstd"))]
+        return Err(format!("File {:?} is compressed with zstd, which requires the `zstd` feature", path).into());
+    }
 
-    /// In tests, we may add forced errors to this object
-    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-    mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
+    match content.strip_prefix(BINARY_MAGIC) {
+        None => Ok(serde_json::from_slice(&content)?),
+        Some([CBOR_FORMAT, version, data @ ..]) => {
+            if *version > CBOR_FORMAT_VERSION {
+                return Err(format!("File {:?} has been written by a newer version of this crate (CBOR format version {})", path, version).into());
+            }
+            Ok(serde_cbor::from_slice(data)?)
+        },
+        Some(_) => Err(format!("File {:?} has an unknown binary format", path).into()),
+    }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-struct CachedData {
-    #[serde(skip)]
-    calendars: HashMap<Url, Arc<Mutex<CachedCalendar>>>,
+/// A builder for a [`Cache`] with non-default settings (e.g. storage settings)
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use kitchen_fridge::cache::{Cache, CacheCompression, CacheFormat};
+/// let cache = Cache::builder(Path::new("/home/user/.cache/my-tasks"))
+///     .format(CacheFormat::Cbor)
+///     .compression(CacheCompression::Gzip)
+///     .load()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CacheBuilder {
+    folder: PathBuf,
+    format: CacheFormat,
+    compression: CacheCompression,
+    clock: Arc<dyn Clock>,
+    exclude_confidential: bool,
 }
 
-impl Cache {
-    /// Activate the "mocking remote source" features (i.e. tell its children calendars that they are mocked remote calendars)
-    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-    pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
-        self.mock_behaviour = mock_behaviour;
+impl CacheBuilder {
+    /// The format the cache is saved in. It is JSON by default.
+    ///
+    /// Caches are loaded regardless of their format, so that this setting can be changed at any time.
+    pub fn format(mut self, format: CacheFormat) -> Self {
+        self.format = format;
+        self
     }
 
+    /// How the files are compressed when the cache is saved. They are not compressed by default.
+    ///
+    /// Caches are loaded regardless of how they were compressed, so that this setting can be changed at any time.
+    pub fn compression(mut self, compression: CacheCompression) -> Self {
+        self.compression = compression;
+        self
+    }
 
-    /// Get the path to the cache folder
-    pub fn cache_folder() -> PathBuf {
-        return PathBuf::from(String::from("~/.config/my-tasks/cache/"))
+    /// The clock the changes made by the cache are dated with (e.g. when tasks are completed with [`CompleteCalendar::mark_completed`](crate::traits::CompleteCalendar::mark_completed)).
+    /// By default, this is the clock of [`config::CLOCK`](crate::config::CLOCK).
+    ///
+    /// Tasks that are modified directly (e.g. with [`Task::set_name`](crate::task::Task::set_name)) are always dated with the clock of [`config::CLOCK`](crate::config::CLOCK), see [`crate::clock`]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    /// Initialize a cache from the content of a valid backing folder if it exists.
-    /// Returns an error otherwise
-    pub fn from_folder(folder: &Path) -> Result<Self, Box<dyn Error>> {
+    /// Keep the items whose `CLASS` is `CONFIDENTIAL` out of the search index (see [`Cache::search`]) and out of iCal exports
+    /// (see [`CompleteCalendar::export_ics`](crate::traits::CompleteCalendar::export_ics)). They are still stored and synced as usual.
+    ///
+    /// This is off by default.
+    pub fn exclude_confidential(mut self, exclude_confidential: bool) -> Self {
+        self.exclude_confidential = exclude_confidential;
+        self
+    }
+
+    /// Initialize a cache with the default contents (see [`Cache::new`])
+    pub fn build(self) -> Cache {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Cache{
+            backing_folder: self.folder,
+            format: self.format,
+            compression: self.compression,
+            data: CachedData::default(),
+            events,
+            clock: self.clock,
+            exclude_confidential: self.exclude_confidential,
+
+            #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+            mock_behaviour: None,
+        }
+    }
+
+    /// Load a calendar, and upgrade it in case it has been written with an older layout
+    fn load_calendar(path: &Path, version: u32) -> Result<CachedCalendar, Box<dyn Error>> {
+        if version == migrations::CURRENT_VERSION {
+            return read_file(path);
+        }
+        let mut content: serde_json::Value = read_file(path)?;
+        migrations::migrate_calendar(&mut content, version)?;
+        Ok(serde_json::from_value(content)?)
+    }
+
+    /// Initialize a cache from the content of a valid backing folder (see [`Cache::from_folder`])
+    pub fn load(self) -> Result<Cache, Box<dyn Error>> {
         // Load shared data...
-        let main_file = folder.join(MAIN_FILE);
-        let mut data: CachedData = match std::fs::File::open(&main_file) {
+        let main_file = self.folder.join(MAIN_FILE);
+        let mut data: CachedData = match read_file(&main_file) {
             Err(err) => {
                 return Err(format!("Unable to open file {:?}: {}", main_file, err).into());
             },
-            Ok(file) => serde_json::from_reader(file)?,
+            Ok(data) => data,
         };
+        if data.version > migrations::CURRENT_VERSION {
+            return Err(format!("This cache has been written by a newer version of this crate (cache version {}, this crate supports up to version {})",
+                data.version, migrations::CURRENT_VERSION).into());
+        }
+        let version = data.version;
 
         // ...and every calendar
-        for entry in std::fs::read_dir(folder)? {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        for entry in std::fs::read_dir(&self.folder)? {
             match entry {
                 Err(err) => {
                     log::error!("Unable to read dir: {:?}", err);
@@ -81,63 +289,192 @@ impl Cache {
                     let cal_path = entry.path();
                     log::debug!("Considering {:?}", cal_path);
                     if cal_path.extension() == Some(OsStr::new("cal")) {
-                        match Self::load_calendar(&cal_path) {
+                        match Self::load_calendar(&cal_path, version) {
+                            // The whole cache would be saved with the new layout, including the calendars that are still in the old layout
+                            Err(err) if version != migrations::CURRENT_VERSION => {
+                                return Err(format!("Unable to upgrade calendar {:?} from cache version {}: {}. The cache has been left unchanged", cal_path, version, err).into());
+                            },
                             Err(err) => {
                                 log::error!("Unable to load calendar {:?} from cache: {:?}", cal_path, err);
                                 continue;
                             },
-                            Ok(cal) =>
-                                data.calendars.insert(cal.url().clone(), Arc::new(Mutex::new(cal))),
+                            Ok(mut cal) => {
+                                cal.set_event_sender(Some(events.clone()));
+                                cal.set_clock(self.clock.clone());
+                                cal.set_exclude_confidential(self.exclude_confidential);
+                                data.calendars.insert(cal.url().clone(), Arc::new(RwLock::new(cal)))
+                            },
                         };
                     }
+                    #[cfg(feature = "addressbook")]
+                    if cal_path.extension() == Some(OsStr::new(ADDRESS_BOOK_EXTENSION)) {
+                        match read_file::<LocalAddressBook>(&cal_path) {
+                            Err(err) => log::error!("Unable to load address book {:?} from cache: {:?}", cal_path, err),
+                            Ok(address_book) => { data.address_books.insert(address_book.url().clone(), Arc::new(RwLock::new(address_book))); },
+                        }
+                    }
                 },
             }
         }
+        data.version = migrations::CURRENT_VERSION;
 
-        Ok(Self{
-            backing_folder: PathBuf::from(folder),
+        Ok(Cache{
+            backing_folder: self.folder,
+            format: self.format,
+            compression: self.compression,
             data,
+            events,
+            clock: self.clock,
+            exclude_confidential: self.exclude_confidential,
 
             #[cfg(feature = "local_calendar_mocks_remote_calendars")]
             mock_behaviour: None,
         })
     }
+}
+
+/// A CalDAV source that stores its items in a local folder.
+///
+/// It automatically updates the content of the folder when dropped (see its `Drop` implementation), but you can also manually call [`Cache::save_to_folder`]
+///
+/// Most of its functionality is provided by the `CalDavSource` async trait it implements.
+/// However, since these functions do not _need_ to be actually async, non-async versions of them are also provided for better convenience. See [`Cache::get_calendar_sync`] for example
+#[derive(Debug)]
+pub struct Cache {
+    backing_folder: PathBuf,
+    format: CacheFormat,
+    compression: CacheCompression,
+    data: CachedData,
+    events: broadcast::Sender<CacheEvent>,
+    /// The clock of its calendars
+    clock: Arc<dyn Clock>,
+    /// Whether its calendars keep confidential items out of their search indexes and exports
+    exclude_confidential: bool,
 
-    fn load_calendar(path: &Path) -> Result<CachedCalendar, Box<dyn Error>> {
-        let file = std::fs::File::open(&path)?;
-        Ok(serde_json::from_reader(file)?)
+    /// In tests, we may add forced errors to this object
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
+}
+
+/// The calendars of a [`Cache`], by URL
+type CachedCalendars = HashMap<Url, Arc<RwLock<CachedCalendar>>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedData {
+    /// The version of the layout of the cache (see the [`migrations`] module). Caches that have no version are version 0
+    #[serde(default)]
+    version: u32,
+    #[serde(skip)]
+    calendars: CachedCalendars,
+    #[cfg(feature = "addressbook")]
+    #[serde(skip)]
+    address_books: HashMap<Url, Arc<RwLock<LocalAddressBook>>>,
+}
+
+impl Default for CachedData {
+    fn default() -> Self {
+        Self {
+            version: migrations::CURRENT_VERSION,
+            calendars: HashMap::new(),
+            #[cfg(feature = "addressbook")]
+            address_books: HashMap::new(),
+        }
     }
+}
 
-    /// Initialize a cache with the default contents
-    pub fn new(folder_path: &Path) -> Self {
-        Self{
-            backing_folder: PathBuf::from(folder_path),
-            data: CachedData::default(),
+impl Cache {
+    /// Activate the "mocking remote source" features (i.e. tell its children calendars that they are mocked remote calendars)
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
+        self.mock_behaviour = mock_behaviour;
+    }
 
-            #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-            mock_behaviour: None,
+
+    /// Returns a receiver that is notified whenever this cache is mutated (including during a sync).
+    ///
+    /// Note that changes made directly to an item (e.g. through [`CompleteCalendar::get_item_by_url_mut`]) are not notified.
+    pub fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get the path to the cache folder
+    pub fn cache_folder() -> PathBuf {
+        PathBuf::from(String::from("~/.config/my-tasks/cache/"))
+    }
+
+    /// Returns a builder for a cache stored in `folder`, with non-default storage settings (e.g. format or compression)
+    pub fn builder(folder: &Path) -> CacheBuilder {
+        CacheBuilder {
+            folder: PathBuf::from(folder),
+            format: CacheFormat::default(),
+            compression: CacheCompression::default(),
+            clock: crate::clock::default_clock(),
+            exclude_confidential: false,
         }
     }
 
+    /// Initialize a cache from the content of a valid backing folder if it exists.
+    /// Returns an error otherwise
+    pub fn from_folder(folder: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::builder(folder).load()
+    }
+
+    /// Initialize a cache with the default contents
+    pub fn new(folder_path: &Path) -> Self {
+        Self::builder(folder_path).build()
+    }
+
     /// Store the current Cache to its backing folder
     ///
-    /// Note that this is automatically called when `self` is `drop`ped
+    /// Note that this is automatically called when `self` is `drop`ped.
+    /// This fails in case a calendar is currently write-locked. See [`CalDavSource::save`] to wait until it is available instead
     pub fn save_to_folder(&self) -> Result<(), std::io::Error> {
+        let locked = |url: &Url| std::io::Error::new(std::io::ErrorKind::WouldBlock, format!("{} is currently locked", url));
+        let content = FolderContent {
+            calendars: self.data.calendars.iter()
+                .map(|(url, cal)| cal.try_read().map(|cal| (url, cal)).map_err(|_| locked(url)))
+                .collect::<Result<_, _>>()?,
+            #[cfg(feature = "addressbook")]
+            address_books: self.data.address_books.iter()
+                .map(|(url, book)| book.try_read().map(|book| (url, book)).map_err(|_| locked(url)))
+                .collect::<Result<_, _>>()?,
+        };
+        self.write_to_folder(content)
+    }
+
+    /// Like [`Self::save_to_folder`], but waits for the calendars that are currently write-locked
+    async fn save_to_folder_when_unlocked(&self) -> Result<(), std::io::Error> {
+        let mut content = FolderContent::default();
+        for (url, cal) in &self.data.calendars {
+            content.calendars.push((url, cal.read().await));
+        }
+        #[cfg(feature = "addressbook")]
+        for (url, book) in &self.data.address_books {
+            content.address_books.push((url, book.read().await));
+        }
+        self.write_to_folder(content)
+    }
+
+    fn write_to_folder(&self, content: FolderContent) -> Result<(), std::io::Error> {
         let folder = &self.backing_folder;
         std::fs::create_dir_all(folder)?;
 
         // Save the general data
         let main_file_path = folder.join(MAIN_FILE);
-        let file = std::fs::File::create(&main_file_path)?;
-        serde_json::to_writer(file, &self.data)?;
+        write_file(&main_file_path, &self.data, self.format, self.compression)?;
 
         // Save each calendar
-        for (cal_url, cal_mutex) in &self.data.calendars {
+        for (cal_url, cal) in &content.calendars {
             let file_name = sanitize_filename::sanitize(cal_url.as_str()) + ".cal";
             let cal_file = folder.join(file_name);
-            let file = std::fs::File::create(&cal_file)?;
-            let cal = cal_mutex.lock().unwrap();
-            serde_json::to_writer(file, &*cal)?;
+            write_file(&cal_file, &**cal, self.format, self.compression)?;
+        }
+
+        // Save each address book
+        #[cfg(feature = "addressbook")]
+        for (book_url, book) in &content.address_books {
+            let file_name = sanitize_filename::sanitize(book_url.as_str()) + "." + ADDRESS_BOOK_EXTENSION;
+            write_file(&folder.join(file_name), &**book, self.format, self.compression)?;
         }
 
         Ok(())
@@ -159,9 +496,9 @@ impl Cache {
 
         for (calendar_url, cal_l) in calendars_l {
             log::debug!("Comparing calendars {}", calendar_url);
-            let cal_l = cal_l.lock().unwrap();
+            let cal_l = cal_l.read().await;
             let cal_r = match calendars_r.get(&calendar_url) {
-                Some(c) => c.lock().unwrap(),
+                Some(c) => c.read().await,
                 None => return Err("should not happen, we've just tested keys are the same".into()),
             };
 
@@ -176,6 +513,14 @@ impl Cache {
     }
 }
 
+/// The read-locked calendars and address books of a cache, while they are written to its folder
+#[derive(Default)]
+struct FolderContent<'a> {
+    calendars: Vec<(&'a Url, RwLockReadGuard<'a, CachedCalendar>)>,
+    #[cfg(feature = "addressbook")]
+    address_books: Vec<(&'a Url, RwLockReadGuard<'a, LocalAddressBook>)>,
+}
+
 impl Drop for Cache {
     fn drop(&mut self) {
         if let Err(err) = self.save_to_folder() {
@@ -186,7 +531,7 @@ impl Drop for Cache {
 
 impl Cache {
     /// The non-async version of [`crate::traits::CalDavSource::get_calendars`]
-    pub fn get_calendars_sync(&self) -> Result<HashMap<Url, Arc<Mutex<CachedCalendar>>>, Box<dyn Error>> {
+    pub fn get_calendars_sync(&self) -> Result<CachedCalendars, Box<dyn Error>> {
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_calendars())?;
 
@@ -197,39 +542,380 @@ impl Cache {
     }
 
     /// The non-async version of [`crate::traits::CalDavSource::get_calendar`]
-    pub fn get_calendar_sync(&self, url: &Url) -> Option<Arc<Mutex<CachedCalendar>>> {
+    pub fn get_calendar_sync(&self, url: &Url) -> Option<Arc<RwLock<CachedCalendar>>> {
         self.data.calendars.get(url).map(|arc| arc.clone())
     }
+
+    /// Returns every address book of this cache (see [`crate::addressbook`])
+    #[cfg(feature = "addressbook")]
+    pub fn get_address_books_sync(&self) -> HashMap<Url, Arc<RwLock<LocalAddressBook>>> {
+        self.data.address_books.clone()
+    }
+
+    /// Returns an address book of this cache, e.g. to read or edit its contacts
+    #[cfg(feature = "addressbook")]
+    pub fn get_address_book_sync(&self, url: &Url) -> Option<Arc<RwLock<LocalAddressBook>>> {
+        self.data.address_books.get(url).cloned()
+    }
+
+    /// The non-async version of [`AddressBookSource::create_address_book`]
+    #[cfg(feature = "addressbook")]
+    pub fn create_address_book_sync(&mut self, url: Url, name: String) -> Result<Arc<RwLock<LocalAddressBook>>, Box<dyn Error>> {
+        if self.data.address_books.contains_key(&url) {
+            return Err(format!("Address book {} already exists", url).into());
+        }
+        #[allow(unused_mut)]
+        let mut address_book = LocalAddressBook::new(name, url.clone());
+        #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+        if let Some(behaviour) = &self.mock_behaviour {
+            address_book.set_mock_behaviour(Some(Arc::clone(behaviour)));
+        }
+        let arc = Arc::new(RwLock::new(address_book));
+        self.data.address_books.insert(url, arc.clone());
+        Ok(arc)
+    }
+
+    /// Returns an owned copy of a calendar.
+    ///
+    /// This copy is not kept in sync with the cache, but it can be read (e.g. by a UI thread) without holding any lock.
+    pub async fn get_calendar_snapshot(&self, url: &Url) -> Option<CachedCalendar> {
+        match self.data.calendars.get(url) {
+            None => None,
+            Some(cal) => Some(cal.read().await.detached_clone()),
+        }
+    }
+
+    /// Returns the URLs of the items whose name, categories or description contain every word of the query, the most relevant first.
+    ///
+    /// Matches in names are worth more than matches in categories, which are worth more than matches in descriptions.
+    /// Items that are marked for deletion are never returned.
+    pub async fn search(&self, query: &str) -> Vec<Url> {
+        let mut results = Vec::new();
+        for cal in self.data.calendars.values() {
+            results.extend(cal.read().await.search(query));
+        }
+        results.sort_by(|(url_a, score_a), (url_b, score_b)| {
+            score_b.cmp(score_a).then_with(|| url_a.as_str().cmp(url_b.as_str()))
+        });
+        results.into_iter().map(|(url, _score)| url).collect()
+    }
+
+    /// Returns the URLs of the items of every calendar that overlap the `start..end` time range, sorted by their start date.
+    ///
+    /// See [`CachedCalendar::get_items_between`]. Items that are marked for deletion are never returned.
+    pub async fn get_items_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Url> {
+        let mut results = Vec::new();
+        for cal in self.data.calendars.values() {
+            results.extend(cal.read().await.get_item_urls_between(start, end));
+        }
+        crate::time_index::sort_by_start(&mut results);
+        results.into_iter().map(|(_start, url)| url).collect()
+    }
+
+    /// Returns the URLs of the events of every calendar that overlap the `start..end` time range, sorted by their start date.
+    ///
+    /// See [`CachedCalendar::get_events_between`]. Events that are marked for deletion are never returned.
+    pub async fn get_events_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Url> {
+        let mut results = Vec::new();
+        for cal in self.data.calendars.values() {
+            let cal = cal.read().await;
+            results.extend(cal.get_item_urls_between(start, end).into_iter()
+                .filter(|(_start, url)| cal.get_item_by_url_sync(url).map(|item| item.is_event()) == Some(true)));
+        }
+        crate::time_index::sort_by_start(&mut results);
+        results.into_iter().map(|(_start, url)| url).collect()
+    }
+
+    /// Move the items of every calendar that match `policy` into the archive of their calendar, and return their URLs, by calendar.
+    ///
+    /// See [`CompleteCalendar::archive_items`]. Apps would typically call this from time to time (e.g. once a day), so that long-lived calendars stay fast to use.
+    pub async fn archive_items(&self, policy: &ArchivePolicy) -> Result<HashMap<Url, Vec<Url>>, Box<dyn Error>> {
+        let mut archived = HashMap::new();
+        for (cal_url, cal) in &self.data.calendars {
+            let urls = cal.write().await.archive_items_sync(policy)?;
+            if !urls.is_empty() {
+                archived.insert(cal_url.clone(), urls);
+            }
+        }
+        Ok(archived)
+    }
+
+    /// Check that this cache is consistent, and returns the problems that have been found (see [`CacheProblem`]).
+    ///
+    /// This loads every item of the cache.
+    pub async fn check(&self) -> Vec<CacheProblem> {
+        let mut problems = Vec::new();
+        for (url, cal) in &self.data.calendars {
+            let cal = cal.read().await;
+            if cal.url() != url {
+                problems.push(CacheProblem::CalendarUrlMismatch{ key: url.clone(), calendar_url: cal.url().clone() });
+            }
+            problems.extend(cal.check_integrity());
+        }
+        problems
+    }
+
+    /// Fix the problems [`Self::check`] would find, and returns them.
+    ///
+    /// Every kind of [`CacheProblem`] documents how it is repaired. The repaired cache is not saved to disk.
+    pub async fn repair(&mut self) -> Result<Vec<CacheProblem>, Box<dyn Error>> {
+        let mut problems = Vec::new();
+
+        let mismatched: Vec<(Url, Url)> = {
+            let mut mismatched = Vec::new();
+            for (url, cal) in &self.data.calendars {
+                let cal_url = cal.read().await.url().clone();
+                if &cal_url != url {
+                    mismatched.push((url.clone(), cal_url));
+                }
+            }
+            mismatched
+        };
+        for (key, calendar_url) in mismatched {
+            if self.data.calendars.contains_key(&calendar_url) {
+                log::warn!("Unable to register calendar {} under its own URL, another calendar already uses it", crate::utils::redact(&calendar_url));
+            } else if let Some(cal) = self.data.calendars.remove(&key) {
+                self.data.calendars.insert(calendar_url.clone(), cal);
+            }
+            problems.push(CacheProblem::CalendarUrlMismatch{ key, calendar_url });
+        }
+
+        for cal in self.data.calendars.values() {
+            let fixed = cal.write().await.repair_integrity()?;
+            for problem in &fixed {
+                log::info!("Repaired the cache: {}", problem);
+            }
+            problems.extend(fixed);
+        }
+        Ok(problems)
+    }
+
+    /// Import every to-do and event of an iCal (`.ics`) file into a calendar of this cache.
+    ///
+    /// Imported items are locally created, so that they will be uploaded to the server at the next sync. \
+    /// Items whose UID already exists in the calendar (or earlier in the imported file) are handled according to `dedup`.
+    /// Files that are not valid UTF-8 are read as Latin-1 (see [`crate::ical::encoding`]).
+    /// Returns the URLs of the items that have been added or updated.
+    pub async fn import_ics<R: std::io::Read>(&self, mut reader: R, calendar_url: &Url, dedup: DedupStrategy) -> Result<Vec<Url>, Box<dyn Error>> {
+        let mut raw_content = Vec::new();
+        reader.read_to_end(&mut raw_content)?;
+        let content = crate::ical::encoding::decode_bytes(&raw_content);
+        let imported_items = crate::ical::parse_all(&content, calendar_url)?;
+
+        let cal = self.get_calendar(calendar_url).await
+            .ok_or_else(|| format!("No such calendar {}", calendar_url))?;
+        let mut cal = cal.write().await;
+
+        let mut url_by_uid: HashMap<String, Url> = cal.get_items().await?
+            .into_iter()
+            .map(|(url, item)| (item.uid().to_string(), url))
+            .collect();
+
+        let mut imported_urls = Vec::new();
+        for item in imported_items {
+            let existing = url_by_uid.get(item.uid()).cloned();
+            match (existing, dedup) {
+                (None, _) => {
+                    url_by_uid.insert(item.uid().to_string(), item.url().clone());
+                    imported_urls.push(item.url().clone());
+                    cal.add_item(item).await?;
+                },
+                (Some(_), DedupStrategy::Skip) => {
+                    log::info!("Not importing item {} that already exists", crate::utils::redact(item.uid()));
+                },
+                (Some(existing_url), DedupStrategy::Replace) => {
+                    let sync_status = match cal.get_item_by_url(&existing_url).await.map(|item| item.sync_status()) {
+                        Some(SyncStatus::Synced(tag)) | Some(SyncStatus::LocallyModified(tag)) | Some(SyncStatus::LocallyDeleted(tag)) => SyncStatus::LocallyModified(tag.clone()),
+                        Some(SyncStatus::NotSynced) | None => SyncStatus::NotSynced,
+                    };
+                    let replacement = item.clone_with_identity(item.uid().to_string(), existing_url.clone(), sync_status);
+                    imported_urls.push(existing_url);
+                    cal.update_item(replacement).await?;
+                },
+                (Some(_), DedupStrategy::KeepBoth) => {
+                    let new_uid = crate::item::ItemUid::generate();
+                    let copy = item.clone_with_identity(new_uid.clone(), item.url().clone(), SyncStatus::NotSynced);
+                    url_by_uid.insert(new_uid, copy.url().clone());
+                    imported_urls.push(copy.url().clone());
+                    cal.add_item(copy).await?;
+                },
+            }
+        }
+
+        Ok(imported_urls)
+    }
+
+    /// Returns the current URL of an item
+    pub async fn resolve(&self, id: &StableItemId) -> Option<Url> {
+        let cal = self.data.calendars.get(&id.calendar)?;
+        let cal = cal.read().await;
+        cal.get_item_by_uid_sync(&id.uid)
+            .map(|item| item.url().clone())
+    }
+
+    /// Returns the version of an item that both the cache and the server agreed on at the latest sync (see [`CompleteCalendar::get_synced_snapshot`]).
+    ///
+    /// Comparing it with the current version tells what has been locally modified since then.
+    pub async fn base_version(&self, item_url: &Url) -> Option<Item> {
+        for cal in self.data.calendars.values() {
+            let cal = cal.read().await;
+            if cal.get_item_by_url_sync(item_url).is_some() {
+                return cal.get_synced_snapshot_sync(item_url).cloned();
+            }
+        }
+        None
+    }
+
+    /// Edit an item, unless it has been changed since `expected_version` (see [`Item::version`]), and return its new version.
+    ///
+    /// This prevents apps from overwriting changes made in the meantime, e.g. by a sync that ran while the user was filling an edit form.
+    /// In this case, `edit` is not called, and an [`EditConflict`] is returned. `edit` is given a copy of the item, that replaces it once it has been edited.
+    pub async fn update_item_if_unchanged<F>(&self, item_url: &Url, expected_version: &ItemVersion, edit: F) -> Result<ItemVersion, Box<dyn Error>>
+    where
+        F: FnOnce(&mut Item),
+    {
+        for cal in self.data.calendars.values() {
+            let mut cal = cal.write().await;
+            let mut item = match cal.get_item_by_url_sync(item_url) {
+                None => continue,
+                Some(item) => item.clone(),
+            };
+            let current = item.version();
+            if &current != expected_version {
+                return Err(EditConflict{ item: item_url.clone(), current: Some(current) }.into());
+            }
+            edit(&mut item);
+            if item.url() != item_url {
+                return Err(format!("The URL of item {} cannot be changed this way", item_url).into());
+            }
+            let version = item.version();
+            cal.update_item_sync(item)?;
+            return Ok(version);
+        }
+        Err(EditConflict{ item: item_url.clone(), current: None }.into())
+    }
+
+    /// Returns the stable identifier of an item
+    pub async fn stable_id_of(&self, calendar_url: &Url, item_url: &Url) -> Option<StableItemId> {
+        let cal = self.data.calendars.get(calendar_url)?;
+        let cal = cal.read().await;
+        cal.get_item_by_url_sync(item_url)
+            .map(|item| StableItemId::new(calendar_url.clone(), item.uid().to_string()))
+    }
+
+    /// Move an item to another calendar, keeping its UID.
+    ///
+    /// The item is marked for deletion in its current calendar, and a copy of it is created in `to_calendar`, so that the next sync
+    /// will move it on the server as well. Returns the identifier of the moved item.
+    pub async fn move_item(&self, item_url: &Url, from_calendar: &Url, to_calendar: &Url) -> Result<StableItemId, Box<dyn Error>> {
+        if from_calendar == to_calendar {
+            return Err("Cannot move an item to the calendar it already belongs to".into());
+        }
+        let from = self.get_calendar(from_calendar).await.ok_or_else(|| format!("No such calendar {}", from_calendar))?;
+        let to = self.get_calendar(to_calendar).await.ok_or_else(|| format!("No such calendar {}", to_calendar))?;
+        let mut from = from.write().await;
+        let mut to = to.write().await;
+
+        let item = from.get_item_by_url_sync(item_url)
+            .ok_or_else(|| format!("No item {} in calendar {}", item_url, from_calendar))?;
+        if to.get_item_by_uid_sync(item.uid()).is_some() {
+            return Err(format!("Calendar {} already contains an item with UID {}", to_calendar, item.uid()).into());
+        }
+        let moved = item.clone_with_identity(item.uid().to_string(), crate::utils::random_url(to_calendar), SyncStatus::NotSynced);
+        let id = StableItemId::new(to_calendar.clone(), moved.uid().to_string());
+
+        to.add_item(moved).await?;
+        from.mark_for_deletion(item_url).await?;
+        if let Some(app_data) = from.take_app_data(&id.uid) {
+            to.insert_app_data(&id.uid, app_data);
+        }
+        Ok(id)
+    }
+
+    /// The non-async version of [`Self::get_calendar_snapshot`].
+    ///
+    /// Since it cannot wait, this returns `None` as well in case the calendar is currently write-locked (e.g. because a sync is in progress)
+    pub fn get_calendar_snapshot_sync(&self, url: &Url) -> Option<CachedCalendar> {
+        self.data.calendars.get(url)
+            .and_then(|cal| cal.try_read().ok())
+            .map(|cal| cal.detached_clone())
+    }
 }
 
 #[async_trait]
 impl CalDavSource<CachedCalendar> for Cache {
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<CachedCalendar>>>, Box<dyn Error>> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<CachedCalendar>>>, Box<dyn Error>> {
         self.get_calendars_sync()
     }
 
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<CachedCalendar>>> {
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<CachedCalendar>>> {
+        self.get_calendar_sync(url)
+    }
+
+    async fn get_calendar_ids(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+        #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+        self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_calendars())?;
+
+        Ok(self.data.calendars.keys().cloned().collect())
+    }
+
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<CachedCalendar>>> {
         self.get_calendar_sync(url)
     }
 
-    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<Mutex<CachedCalendar>>, Box<dyn Error>> {
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<CachedCalendar>>, Box<dyn Error>> {
         log::debug!("Inserting local calendar {}", url);
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_create_calendar())?;
 
-        let new_calendar = CachedCalendar::new(name, url.clone(), supported_components, color);
-        let arc = Arc::new(Mutex::new(new_calendar));
+        let mut new_calendar = CachedCalendar::new(name, url.clone(), supported_components, color);
+        new_calendar.set_event_sender(Some(self.events.clone()));
+        new_calendar.set_clock(self.clock.clone());
+        new_calendar.set_exclude_confidential(self.exclude_confidential);
 
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         if let Some(behaviour) = &self.mock_behaviour {
-            arc.lock().unwrap().set_mock_behaviour(Some(Arc::clone(behaviour)));
+            new_calendar.set_mock_behaviour(Some(Arc::clone(behaviour)));
         };
 
-        match self.data.calendars.insert(url, arc.clone()) {
+        let arc = Arc::new(RwLock::new(new_calendar));
+
+        match self.data.calendars.insert(url.clone(), arc.clone()) {
             Some(_) => Err("Attempt to insert calendar failed: there is alredy such a calendar.".into()),
-            None => Ok(arc),
+            None => {
+                let _ = self.events.send(CacheEvent::CalendarChanged{ calendar: url });
+                Ok(arc)
+            },
         }
     }
+
+    async fn save(&self) -> Result<(), Box<dyn Error>> {
+        Ok(self.save_to_folder_when_unlocked().await?)
+    }
+
+    #[cfg(feature = "addressbook")]
+    fn address_book_source(&self) -> Option<&(dyn AddressBookSource + Send + Sync)> {
+        Some(self)
+    }
+
+    #[cfg(feature = "addressbook")]
+    fn address_book_source_mut(&mut self) -> Option<&mut (dyn AddressBookSource + Send + Sync)> {
+        Some(self)
+    }
+}
+
+#[cfg(feature = "addressbook")]
+#[async_trait]
+impl AddressBookSource for Cache {
+    async fn get_address_books(&self) -> Result<HashMap<Url, SharedAddressBook>, Box<dyn Error>> {
+        Ok(self.data.address_books.iter()
+            .map(|(url, address_book)| (url.clone(), address_book.clone() as SharedAddressBook))
+            .collect())
+    }
+
+    async fn create_address_book(&mut self, url: Url, name: String) -> Result<SharedAddressBook, Box<dyn Error>> {
+        Ok(self.create_address_book_sync(url, name)?)
+    }
 }
 
 #[cfg(test)]
@@ -238,7 +924,8 @@ mod tests {
 
     use url::Url;
     use crate::calendar::SupportedComponents;
-    use crate::item::Item;
+    use crate::item::{Classification, Item, UnparsedItem, VersionTag};
+    use crate::calendar::cached_calendar::VERSION_HISTORY_LENGTH;
     use crate::task::Task;
 
     async fn populate_cache(cache_path: &Path) -> Cache {
@@ -259,7 +946,7 @@ mod tests {
         ).await.unwrap();
 
         {
-            let mut bucket_list = bucket_list.lock().unwrap();
+            let mut bucket_list = bucket_list.write().await;
             let cal_url = bucket_list.url().clone();
             bucket_list.add_item(Item::Task(Task::new(
                 String::from("Attend a concert of JS Bach"), false, &cal_url
@@ -288,6 +975,61 @@ mod tests {
         assert_eq!(test.unwrap(), true);
     }
 
+    #[tokio::test]
+    async fn cache_failed_migration() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/failed_migration"));
+        let cache = populate_cache(&cache_path).await;
+        cache.save_to_folder().unwrap();
+        drop(cache);
+
+        // A cache written by an older version of this crate, with a calendar that cannot be upgraded
+        let main_file = cache_path.join(MAIN_FILE);
+        let mut main: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&main_file).unwrap()).unwrap();
+        main["version"] = serde_json::json!(0);
+        std::fs::write(&main_file, main.to_string()).unwrap();
+        std::fs::write(cache_path.join("broken.cal"), r#"{"not": "a calendar"}"#).unwrap();
+
+        assert!(Cache::from_folder(&cache_path).is_err());
+        let main: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&main_file).unwrap()).unwrap();
+        assert_eq!(main["version"], 0);
+    }
+
+    #[tokio::test]
+    async fn cache_formats() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/compression"));
+        let mut cache = populate_cache(&cache_path).await;
+        cache.compression = CacheCompression::Gzip;
+        cache.save_to_folder().unwrap();
+
+        let main_file = std::fs::read(cache_path.join(MAIN_FILE)).unwrap();
+        assert!(main_file.starts_with(GZIP_MAGIC));
+
+        // Compressed caches can be loaded whatever the compression setting
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.compression, CacheCompression::None);
+        assert!(cache.has_same_observable_content_as(&retrieved_cache).await.unwrap());
+
+        // JSON caches are migrated to CBOR when they are saved again
+        let mut cbor_cache = Cache::from_folder(&cache_path).unwrap();
+        cbor_cache.format = CacheFormat::Cbor;
+        cbor_cache.compression = CacheCompression::None;
+        cbor_cache.save_to_folder().unwrap();
+        let main_file = std::fs::read(cache_path.join(MAIN_FILE)).unwrap();
+        assert!(main_file.starts_with(BINARY_MAGIC));
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert!(cache.has_same_observable_content_as(&retrieved_cache).await.unwrap());
+    }
+
+    #[test]
+    fn cache_from_newer_version() {
+        let cache_path = PathBuf::from(String::from("test_cache/newer_version"));
+        std::fs::create_dir_all(&cache_path).unwrap();
+        std::fs::write(cache_path.join(MAIN_FILE), format!(r#"{{"version": {}}}"#, migrations::CURRENT_VERSION + 1)).unwrap();
+        assert!(Cache::from_folder(&cache_path).is_err());
+    }
+
     #[tokio::test]
     async fn cache_sanity_checks() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -303,4 +1045,633 @@ mod tests {
         ).await;
         assert!(second_addition_same_calendar.is_err());
     }
+
+    #[tokio::test]
+    async fn cache_notifications() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/notifications"));
+        let mut cache = Cache::new(&cache_path);
+        let mut receiver = cache.subscribe();
+
+        let cal_url = Url::parse("https://caldav.com/notified").unwrap();
+        let calendar = cache.create_calendar(cal_url.clone(), "Notified".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::CalendarChanged{ calendar: cal_url.clone() });
+
+        let task = Task::new(String::from("A task"), false, &cal_url);
+        let task_url = task.url().clone();
+        calendar.write().await.add_item(Item::Task(task)).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::ItemAdded{ calendar: cal_url.clone(), item: task_url.clone() });
+
+        calendar.write().await.mark_for_deletion(&task_url).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::ItemDeleted{ calendar: cal_url.clone(), item: task_url.clone() });
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn cache_duplicate_item() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/duplicate"));
+        let cache = populate_cache(&cache_path).await;
+
+        let bucket_list = cache.get_calendar(&Url::parse("https://caldav.com/bucket-list").unwrap()).await.unwrap();
+        let mut bucket_list = bucket_list.write().await;
+        let original = bucket_list.get_items_sync().unwrap().into_values()
+            .find(|item| item.name() == "Attend a concert of JS Bach")
+            .cloned()
+            .unwrap();
+
+        let copy_url = bucket_list.duplicate_item(original.url()).await.unwrap();
+        assert_eq!(bucket_list.get_items_sync().unwrap().len(), 3);
+        let copy = bucket_list.get_item_by_url_sync(&copy_url).unwrap();
+        assert_eq!(copy.name(), original.name());
+        assert_ne!(copy.uid(), original.uid());
+        assert_eq!(copy.sync_status(), &SyncStatus::NotSynced);
+    }
+
+    #[tokio::test]
+    async fn cache_bulk_operations() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/bulk"));
+        let mut cache = Cache::new(&cache_path);
+        let cal_url = Url::parse("https://caldav.com/bulk").unwrap();
+        let calendar = cache.create_calendar(cal_url.clone(), "Bulk".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut receiver = cache.subscribe();
+
+        let tasks: Vec<Item> = (0..5)
+            .map(|i| Item::Task(Task::new(format!("Task {}", i), false, &cal_url)))
+            .collect();
+        let urls: Vec<Url> = tasks.iter().map(|item| item.url().clone()).collect();
+        let mut calendar = calendar.write().await;
+        calendar.add_items(tasks).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::CalendarChanged{ calendar: cal_url.clone() });
+        assert!(receiver.try_recv().is_err());
+
+        // Failing batches do not change anything
+        let duplicate = calendar.get_item_by_url_sync(&urls[0]).unwrap().clone();
+        assert!(calendar.add_items(vec![duplicate]).await.is_err());
+        let missing = Url::parse("https://caldav.com/bulk/missing.ics").unwrap();
+        assert!(calendar.mark_completed(&[urls[0].clone(), missing]).await.is_err());
+        assert!(receiver.try_recv().is_err());
+        #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+        {
+            let mut behaviour = crate::mock_behaviour::MockBehaviour::new();
+            behaviour.add_item_behaviour = (1, 1);
+            calendar.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(behaviour))));
+            let more_tasks = vec![Item::Task(Task::new("Added".to_string(), false, &cal_url)), Item::Task(Task::new("Rejected".to_string(), false, &cal_url))];
+            assert!(calendar.add_items(more_tasks).await.is_err());
+            calendar.set_mock_behaviour(None);
+            assert_eq!(calendar.get_items_sync().unwrap().len(), 5);
+            assert!(receiver.try_recv().is_err());
+        }
+
+        calendar.mark_completed(&urls[..3]).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::CalendarChanged{ calendar: cal_url.clone() });
+        let completed = calendar.get_items_sync().unwrap().values().filter(|item| item.unwrap_task().completed()).count();
+        assert_eq!(completed, 3);
+
+        calendar.delete_items(&urls[1..]).await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), CacheEvent::CalendarChanged{ calendar: cal_url.clone() });
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(calendar.get_item_urls_sync().unwrap(), vec![urls[0].clone()].into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn cache_unparsed_items() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/unparsed"));
+        let cache = populate_cache(&cache_path).await;
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let item_url = Url::parse("https://caldav.com/shopping/broken.ics").unwrap();
+
+        let unparsed = UnparsedItem{
+            url: item_url.clone(),
+            version_tag: VersionTag::from(String::from("some-tag")),
+            error: String::from("Missing UID"),
+            raw: String::from("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"),
+        };
+        let calendar = cache.get_calendar(&cal_url).await.unwrap();
+        calendar.write().await.set_unparsed_items(vec![unparsed.clone()]).await.unwrap();
+
+        // Unparsed items are persisted
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        let retrieved_calendar = retrieved_cache.get_calendar(&cal_url).await.unwrap();
+        assert_eq!(retrieved_calendar.read().await.get_unparsed_items_sync(), vec![&unparsed]);
+
+        // ...until a valid version of the item is stored
+        let mut task = Task::new(String::from("Fixed"), false, &cal_url);
+        task = task.clone_with_identity(task.uid().to_string(), item_url, SyncStatus::Synced(unparsed.version_tag.clone()));
+        calendar.write().await.add_item(Item::Task(task)).await.unwrap();
+        assert!(calendar.read().await.get_unparsed_items_sync().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_base_version() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/base_version"));
+        let cache = populate_cache(&cache_path).await;
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+
+        let task = Task::new(String::from("Synced name"), false, &cal_url);
+        let item_url = task.url().clone();
+        let task = task.clone_with_identity(task.uid().to_string(), item_url.clone(), SyncStatus::Synced(VersionTag::from(String::from("tag"))));
+        let calendar = cache.get_calendar(&cal_url).await.unwrap();
+        calendar.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        // The synced version is kept when the item is locally modified
+        calendar.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name(String::from("Local name"));
+        assert_eq!(cache.base_version(&item_url).await.unwrap().name(), "Synced name");
+
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.base_version(&item_url).await.unwrap().name(), "Synced name");
+        assert!(retrieved_cache.base_version(&Url::parse("https://caldav.com/shopping/unknown.ics").unwrap()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn cache_search() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/search"));
+        let cache = populate_cache(&cache_path).await;
+
+        assert_eq!(cache.search("bach").await.len(), 1);
+        assert_eq!(cache.search("lighthouse alex").await.len(), 1);
+        assert!(cache.search("bach lighthouse").await.is_empty());
+
+        // The index is rebuilt when the cache is loaded
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.search("concert").await, cache.search("concert").await);
+        assert_eq!(retrieved_cache.search("concert").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cache_export_ics() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/export"));
+        let cache = populate_cache(&cache_path).await;
+
+        let bucket_list = cache.get_calendar(&Url::parse("https://caldav.com/bucket-list").unwrap()).await.unwrap();
+        let mut exported = Vec::new();
+        bucket_list.read().await.export_ics(&mut exported).await.unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert_eq!(exported.matches("BEGIN:VTODO").count(), 2);
+        assert!(exported.contains("SUMMARY:Attend a concert of JS Bach"));
+
+        // Items that are pending a deletion on the server are not exported
+        let concert_url = cache.search("bach").await.remove(0);
+        {
+            let mut bucket_list = bucket_list.write().await;
+            let concert = bucket_list.get_item_by_url_mut(&concert_url).await.unwrap();
+            concert.set_sync_status(SyncStatus::Synced(VersionTag::from(String::from("some-tag"))));
+            bucket_list.mark_for_deletion(&concert_url).await.unwrap();
+        }
+        let mut exported = Vec::new();
+        bucket_list.read().await.export_ics(&mut exported).await.unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert_eq!(exported.matches("BEGIN:VTODO").count(), 1);
+        assert!(!exported.contains("Bach"));
+    }
+
+    #[tokio::test]
+    async fn cache_exclude_confidential() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/confidential"));
+        let cache = populate_cache(&cache_path).await;
+        let bucket_list_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+
+        let concert_url = cache.search("bach").await.remove(0);
+        {
+            let bucket_list = cache.get_calendar(&bucket_list_url).await.unwrap();
+            let mut bucket_list = bucket_list.write().await;
+            let concert = bucket_list.get_item_by_url_mut(&concert_url).await.unwrap().unwrap_task_mut();
+            concert.set_classification(Classification::Confidential);
+            assert_eq!(concert.classification(), Classification::Confidential);
+        }
+
+        // Confidential items are not excluded by default...
+        assert_eq!(cache.search("bach").await, vec![concert_url.clone()]);
+        cache.save_to_folder().unwrap();
+
+        // ...but they can be
+        let cache = Cache::builder(&cache_path).exclude_confidential(true).load().unwrap();
+        assert!(cache.search("bach").await.is_empty());
+        assert_eq!(cache.search("lighthouse").await.len(), 1);
+
+        let bucket_list = cache.get_calendar(&bucket_list_url).await.unwrap();
+        let mut exported = Vec::new();
+        bucket_list.read().await.export_ics(&mut exported).await.unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert_eq!(exported.matches("BEGIN:VTODO").count(), 1);
+        assert!(!exported.contains("Bach"));
+
+        // They are still part of the calendar
+        let concert = bucket_list.read().await.get_item_by_url(&concert_url).await.unwrap().unwrap_task().clone();
+        assert_eq!(concert.classification(), Classification::Confidential);
+        assert_eq!(concert.extra_parameter("CLASS"), Some("CONFIDENTIAL"));
+    }
+
+    #[tokio::test]
+    async fn cache_archive() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/archive"));
+        let cache = populate_cache(&cache_path).await;
+        let bucket_list_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let lighthouse_url = cache.search("lighthouse").await.remove(0);
+
+        // Recently completed tasks, and tasks that have never been synced, are not archived
+        assert!(cache.archive_items(&ArchivePolicy::completed_for(chrono::Duration::days(30))).await.unwrap().is_empty());
+        assert!(cache.archive_items(&ArchivePolicy::completed_for(chrono::Duration::zero())).await.unwrap().is_empty());
+
+        let policy = ArchivePolicy{ completed_for: chrono::Duration::zero(), exclude_from_sync: true };
+        let archived = cache.archive_items(&policy).await.unwrap();
+        assert_eq!(archived, HashMap::from([(bucket_list_url.clone(), vec![lighthouse_url.clone()])]));
+        assert!(cache.search("lighthouse").await.is_empty());
+        cache.save_to_folder().unwrap();
+
+        let cache = Cache::from_folder(&cache_path).unwrap();
+        let bucket_list = cache.get_calendar(&bucket_list_url).await.unwrap();
+        {
+            let bucket_list = bucket_list.read().await;
+            assert_eq!(bucket_list.get_items().await.unwrap().len(), 1);
+            assert!(bucket_list.get_item_by_url(&lighthouse_url).await.is_none());
+            let archived = bucket_list.get_archived_items_matching(&crate::calendar::ItemFilter{ completed: Some(true), ..Default::default() }).await.unwrap();
+            assert_eq!(archived.keys().collect::<Vec<_>>(), vec![&lighthouse_url]);
+            assert_eq!(bucket_list.sync_exclusion(&lighthouse_url), Some(crate::calendar::SyncExclusion::LocalOnly));
+        }
+
+        bucket_list.write().await.unarchive_item(&lighthouse_url).await.unwrap();
+        assert!(bucket_list.write().await.unarchive_item(&lighthouse_url).await.is_err());
+        assert_eq!(cache.search("lighthouse").await, vec![lighthouse_url.clone()]);
+        assert!(!bucket_list.read().await.is_archived(&lighthouse_url));
+        assert_eq!(bucket_list.read().await.sync_exclusion(&lighthouse_url), None);
+    }
+
+    #[tokio::test]
+    async fn cache_item_counts() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache = populate_cache(&PathBuf::from(String::from("test_cache/item_counts"))).await;
+        let concert_url = cache.search("bach").await.remove(0);
+        let lighthouse_url = cache.search("lighthouse").await.remove(0);
+        let bucket_list = cache.get_calendar(&Url::parse("https://caldav.com/bucket-list").unwrap()).await.unwrap();
+        let mut bucket_list = bucket_list.write().await;
+        let in_2024: DateTime<Utc> = "2024-06-01T00:00:00Z".parse().unwrap();
+        assert_eq!(bucket_list.count_items().await.unwrap(), 2);
+        assert_eq!(bucket_list.count_completed().await.unwrap(), 1);
+        assert_eq!(bucket_list.count_due_before(in_2024).await.unwrap(), 0);
+
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Some//Other app//EN\r\nBEGIN:VTODO\r\nUID:aurora\r\nDTSTAMP:20240101T000000Z\r\n\
+            SUMMARY:See an aurora\r\nDUE:20240315T093000Z\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let aurora_url = Url::parse("https://caldav.com/bucket-list/aurora.ics").unwrap();
+        bucket_list.add_item(crate::ical::parse(ics, aurora_url.clone(), SyncStatus::NotSynced).unwrap()).await.unwrap();
+        assert_eq!(bucket_list.count_items().await.unwrap(), 3);
+        assert_eq!(bucket_list.count_due_before(in_2024).await.unwrap(), 1);
+        assert_eq!(bucket_list.count_due_before("2024-03-01T00:00:00Z".parse().unwrap()).await.unwrap(), 0);
+
+        // Counts follow the changes made through mutable references...
+        bucket_list.get_item_by_url_mut(&aurora_url).await.unwrap().unwrap_task_mut().complete(in_2024);
+        assert_eq!(bucket_list.count_completed().await.unwrap(), 2);
+        assert_eq!(bucket_list.count_due_before(in_2024).await.unwrap(), 0);
+
+        // ...the trash...
+        bucket_list.trash_item(&lighthouse_url).await.unwrap();
+        assert_eq!((bucket_list.count_items().await.unwrap(), bucket_list.count_completed().await.unwrap()), (2, 1));
+        bucket_list.restore_item(&lighthouse_url).await.unwrap();
+        assert_eq!((bucket_list.count_items().await.unwrap(), bucket_list.count_completed().await.unwrap()), (3, 2));
+
+        // ...and deletions
+        bucket_list.mark_for_deletion(&concert_url).await.unwrap();
+        bucket_list.mark_for_deletion(&aurora_url).await.unwrap();
+        assert_eq!((bucket_list.count_items().await.unwrap(), bucket_list.count_completed().await.unwrap()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn cache_import_ics() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/import"));
+        let cache = populate_cache(&cache_path).await;
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let ics = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Some//Other app//EN\r\n\
+            BEGIN:VTODO\r\n\
+            UID:imported-uid\r\n\
+            DTSTAMP:20211103T214742\r\n\
+            SUMMARY:Buy milk\r\n\
+            STATUS:NEEDS-ACTION\r\n\
+            END:VTODO\r\n\
+            BEGIN:VTODO\r\n\
+            UID:imported-uid\r\n\
+            DTSTAMP:20211104T214742\r\n\
+            SUMMARY:Buy more milk\r\n\
+            STATUS:NEEDS-ACTION\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+        let cal = cache.get_calendar(&cal_url).await.unwrap();
+
+        let imported = cache.import_ics(ics.as_bytes(), &cal_url, DedupStrategy::Skip).await.unwrap();
+        assert_eq!(imported.len(), 1);
+        {
+            let cal = cal.read().await;
+            let item = cal.get_item_by_url(&imported[0]).await.unwrap();
+            assert_eq!(item.name(), "Buy milk");
+            assert_eq!(item.sync_status(), &SyncStatus::NotSynced);
+        }
+
+        let replaced = cache.import_ics(ics.as_bytes(), &cal_url, DedupStrategy::Replace).await.unwrap();
+        assert_eq!(replaced, vec![imported[0].clone(), imported[0].clone()]);
+        assert_eq!(cal.read().await.get_items().await.unwrap().len(), 1);
+        assert_eq!(cal.read().await.get_item_by_url(&imported[0]).await.unwrap().name(), "Buy more milk");
+
+        let copies = cache.import_ics(ics.as_bytes(), &cal_url, DedupStrategy::KeepBoth).await.unwrap();
+        assert_eq!(copies.len(), 2);
+        let cal = cal.read().await;
+        let uids: std::collections::HashSet<String> = cal.get_items().await.unwrap().values().map(|item| item.uid().to_string()).collect();
+        assert_eq!(uids.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_get_events_between() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/events_between"));
+        let cache = populate_cache(&cache_path).await;
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let ics = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Some//Other app//EN\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:market\r\n\
+            DTSTAMP:20211103T214742\r\n\
+            SUMMARY:Farmers market\r\n\
+            DTSTART:20211106T080000Z\r\n\
+            DURATION:PT4H\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VTODO\r\n\
+            UID:groceries\r\n\
+            DTSTAMP:20211103T214742\r\n\
+            SUMMARY:Buy groceries\r\n\
+            DUE:20211105T180000Z\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+        cache.import_ics(ics.as_bytes(), &cal_url, DedupStrategy::Skip).await.unwrap();
+
+        let week_start: DateTime<Utc> = "2021-11-01T00:00:00Z".parse().unwrap();
+        let week_end: DateTime<Utc> = "2021-11-08T00:00:00Z".parse().unwrap();
+        assert_eq!(cache.get_items_between(week_start, week_end).await.len(), 2);
+        let events = cache.get_events_between(week_start, week_end).await;
+        assert_eq!(events.len(), 1);
+
+        let cal = cache.get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().await;
+        let found = cal.get_events_between(week_start, week_end);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].url(), &events[0]);
+        assert_eq!(found[0].name(), "Farmers market");
+        // The event ends at noon
+        assert!(cal.get_events_between("2021-11-06T12:00:00Z".parse().unwrap(), week_end).is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_move_item() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/move"));
+        let cache = populate_cache(&cache_path).await;
+        let shopping_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let bucket_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+
+        let item_url = cache.get_calendar(&bucket_url).await.unwrap()
+            .read().await.get_item_urls().await.unwrap()
+            .into_iter().next().unwrap();
+        let id = cache.stable_id_of(&bucket_url, &item_url).await.unwrap();
+        assert_eq!(cache.resolve(&id).await, Some(item_url.clone()));
+
+        let moved_id = cache.move_item(&item_url, &bucket_url, &shopping_url).await.unwrap();
+        assert_eq!(moved_id.uid, id.uid);
+        assert_eq!(moved_id.calendar, shopping_url);
+        let new_url = cache.resolve(&moved_id).await.unwrap();
+        assert_ne!(new_url, item_url);
+
+        // The item was never synced, so it has been removed from the original calendar
+        assert_eq!(cache.resolve(&id).await, None);
+    }
+
+    #[tokio::test]
+    async fn cache_app_data() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/app_data"));
+        let cache = populate_cache(&cache_path).await;
+        let shopping_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let bucket_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+
+        let bucket_list = cache.get_calendar(&bucket_url).await.unwrap();
+        let (item_url, uid) = {
+            let mut bucket_list = bucket_list.write().await;
+            let item = bucket_list.get_items().await.unwrap().into_values().next().unwrap().clone();
+            let uid = item.uid().to_string();
+            bucket_list.set_app_data(&uid, "position".to_string(), serde_json::json!(3)).unwrap();
+            assert!(bucket_list.set_app_data("unknown-uid", "position".to_string(), serde_json::json!(4)).is_err());
+
+            // App data is local-only
+            assert!(!crate::ical::build_from(&item).unwrap().contains("position"));
+
+            // App data is kept when the item is updated (e.g. by a sync)
+            bucket_list.update_item(item.clone()).await.unwrap();
+            (item.url().clone(), uid)
+        };
+
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        let retrieved = retrieved_cache.get_calendar(&bucket_url).await.unwrap();
+        assert_eq!(retrieved.read().await.app_data(&uid).unwrap()["position"], serde_json::json!(3));
+
+        // App data follows moved items
+        let moved_id = cache.move_item(&item_url, &bucket_url, &shopping_url).await.unwrap();
+        assert!(bucket_list.read().await.app_data(&uid).is_none());
+        let new_url = cache.resolve(&moved_id).await.unwrap();
+        let shopping_list = cache.get_calendar(&shopping_url).await.unwrap();
+        let mut shopping_list = shopping_list.write().await;
+        assert_eq!(shopping_list.app_data(&uid).unwrap()["position"], serde_json::json!(3));
+
+        // ...and is dropped with them
+        shopping_list.immediately_delete_item(&new_url).await.unwrap();
+        assert!(shopping_list.app_data(&uid).is_none());
+    }
+
+    #[tokio::test]
+    async fn cache_version_history() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/version_history"));
+        let cache = populate_cache(&cache_path).await;
+        let shopping_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let shopping_list = cache.get_calendar(&shopping_url).await.unwrap();
+
+        let mut item = Item::Task(Task::new("Versioned task".to_string(), false, &shopping_url));
+        let item_url = item.url().clone();
+        assert_eq!(item.version_tag(), None);
+        {
+            let mut shopping_list = shopping_list.write().await;
+            shopping_list.add_item(item.clone()).await.unwrap();
+            assert!(shopping_list.version_history(&item_url).is_empty());
+
+            for version in 1..=12 {
+                item.set_sync_status(SyncStatus::Synced(VersionTag::from(format!("v{}", version))));
+                shopping_list.update_item(item.clone()).await.unwrap();
+            }
+            // Local changes keep the version tag of the latest sync
+            item.set_sync_status(SyncStatus::LocallyModified(VersionTag::from("v12".to_string())));
+            shopping_list.update_item(item.clone()).await.unwrap();
+            assert_eq!(item.version_tag(), Some(&VersionTag::from("v12".to_string())));
+        }
+
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        let retrieved = retrieved_cache.get_calendar(&shopping_url).await.unwrap();
+        let history = retrieved.read().await.version_history(&item_url);
+        assert_eq!(history.len(), VERSION_HISTORY_LENGTH);
+        assert_eq!(history.first(), Some(&VersionTag::from("v3".to_string())));
+        assert_eq!(history.last(), Some(&VersionTag::from("v12".to_string())));
+
+        let mut shopping_list = shopping_list.write().await;
+        shopping_list.immediately_delete_item(&item_url).await.unwrap();
+        assert!(shopping_list.version_history(&item_url).is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_update_item_if_unchanged() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/update_if_unchanged"));
+        let cache = populate_cache(&cache_path).await;
+        let bucket_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let bucket_list = cache.get_calendar(&bucket_url).await.unwrap();
+        let displayed = bucket_list.read().await.get_items().await.unwrap().into_values().next().unwrap().clone();
+        let item_url = displayed.url().clone();
+
+        // An edit form is opened, and the item is changed in the background
+        let expected = displayed.version();
+        let mut newer = displayed.clone();
+        newer.set_sync_status(SyncStatus::Synced(VersionTag::from("from-the-server".to_string())));
+        bucket_list.write().await.update_item(newer.clone()).await.unwrap();
+
+        let err = cache.update_item_if_unchanged(&item_url, &expected, |item| {
+            item.unwrap_task_mut().set_name("Overwritten".to_string());
+        }).await.unwrap_err();
+        let conflict = err.downcast_ref::<EditConflict>().unwrap();
+        assert_eq!(conflict.current, Some(newer.version()));
+        assert_eq!(bucket_list.read().await.get_item_by_url(&item_url).await.unwrap().name(), displayed.name());
+
+        // Once the form has been refreshed, the edit goes through
+        let new_version = cache.update_item_if_unchanged(&item_url, &newer.version(), |item| {
+            item.unwrap_task_mut().set_name("Edited".to_string());
+        }).await.unwrap();
+        let edited = bucket_list.read().await.get_item_by_url(&item_url).await.unwrap().clone();
+        assert_eq!(edited.name(), "Edited");
+        assert_eq!(edited.version(), new_version);
+        assert!(matches!(edited.sync_status(), SyncStatus::LocallyModified(_)));
+
+        // Deleted items cannot be edited either
+        bucket_list.write().await.immediately_delete_item(&item_url).await.unwrap();
+        let err = cache.update_item_if_unchanged(&item_url, &new_version, |_item| ()).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<EditConflict>().unwrap().current, None);
+    }
+
+    #[tokio::test]
+    async fn cache_backup() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/backup/original"));
+        let restored_path = PathBuf::from(String::from("test_cache/backup/restored"));
+        let backup_path = PathBuf::from(String::from("test_cache/backup/cache.backup"));
+        let _ = std::fs::remove_dir_all(&restored_path);
+        std::fs::create_dir_all(backup_path.parent().unwrap()).unwrap();
+        let cache = populate_cache(&cache_path).await;
+        let bucket_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let bucket_list = cache.get_calendar(&bucket_url).await.unwrap();
+        let uid = {
+            let mut bucket_list = bucket_list.write().await;
+            let uid = bucket_list.get_items().await.unwrap().into_values().next().unwrap().uid().to_string();
+            bucket_list.set_app_data(&uid, "position".to_string(), serde_json::json!(1)).unwrap();
+            uid
+        };
+
+        cache.backup_to(&backup_path).unwrap();
+        let restored = Cache::restore_from(&backup_path, &restored_path).unwrap();
+        assert!(cache.has_same_observable_content_as(&restored).await.unwrap());
+        let restored_bucket_list = restored.get_calendar(&bucket_url).await.unwrap();
+        assert_eq!(restored_bucket_list.read().await.app_data(&uid).unwrap()["position"], serde_json::json!(1));
+
+        // The restored cache has been saved
+        let reloaded = Cache::from_folder(&restored_path).unwrap();
+        assert!(cache.has_same_observable_content_as(&reloaded).await.unwrap());
+
+        // Existing caches are not overwritten
+        assert!(Cache::restore_from(&backup_path, &restored_path).is_err());
+    }
+
+    #[tokio::test]
+    async fn cache_iterate_calendars() {
+        use futures_util::StreamExt;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/iterate"));
+        let cache = populate_cache(&cache_path).await;
+        let shopping_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let bucket_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+
+        let ids = cache.get_calendar_ids().await.unwrap();
+        assert_eq!(ids, HashSet::from([shopping_url.clone(), bucket_url.clone()]));
+
+        let names: HashSet<String> = cache.calendars().await.unwrap()
+            .then(|(_url, cal)| async move { cal.read().await.name().to_string() })
+            .collect().await;
+        assert_eq!(names, HashSet::from(["My shopping list".to_string(), "My bucket list".to_string()]));
+
+        // This does not wait, even if the calendar is locked
+        let shopping = cache.get_calendar(&shopping_url).await.unwrap();
+        let _lock = shopping.write().await;
+        assert!(cache.try_get_calendar(&shopping_url).is_some());
+        assert!(cache.try_get_calendar(&Url::parse("https://caldav.com/unknown").unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    async fn cache_check_and_repair() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/check"));
+        let mut cache = populate_cache(&cache_path).await;
+        assert!(cache.check().await.is_empty());
+
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let calendar = cache.get_calendar(&cal_url).await.unwrap();
+        let task = Task::new(String::from("Buy milk"), false, &cal_url);
+        let older_url = task.url().clone();
+        let newer_url = cal_url.join("duplicate.ics").unwrap();
+        let older = task.clone_with_identity(task.uid().to_string(), older_url.clone(), SyncStatus::Synced(VersionTag::from(String::from("tag1"))));
+        let newer = task.clone_with_identity(task.uid().to_string(), newer_url.clone(), SyncStatus::Synced(VersionTag::from(String::from("tag2"))));
+        let tombstone = Task::new(String::from("Deleted"), false, &cal_url);
+        let tombstone_url = tombstone.url().clone();
+        let tombstone = tombstone.clone_with_identity(tombstone.uid().to_string(), tombstone_url.clone(), SyncStatus::LocallyDeleted(VersionTag::from(String::new())));
+        let unsynced = Task::new(String::from("Unsynced"), false, &cal_url);
+        let unsynced_url = unsynced.url().clone();
+        {
+            let mut calendar = calendar.write().await;
+            calendar.add_items_sync(vec![Item::Task(older), Item::Task(newer), Item::Task(tombstone), Item::Task(unsynced)]).unwrap();
+            // A local change bumps the SEQUENCE
+            calendar.get_item_by_url_mut_sync(&newer_url).unwrap().unwrap_task_mut().set_name(String::from("Buy oat milk"));
+            calendar.save_synced_snapshot_sync(&unsynced_url).unwrap();
+        }
+
+        let problems = cache.check().await;
+        assert_eq!(problems.len(), 3);
+        let mut duplicates = vec![older_url.clone(), newer_url.clone()];
+        duplicates.sort();
+        assert!(problems.contains(&CacheProblem::DuplicateUid{ calendar: cal_url.clone(), uid: task.uid().to_string(), items: duplicates }));
+        assert!(problems.contains(&CacheProblem::MalformedTombstone{ calendar: cal_url.clone(), item: tombstone_url.clone() }));
+        assert!(problems.contains(&CacheProblem::UnsyncedWithSnapshot{ calendar: cal_url.clone(), item: unsynced_url.clone() }));
+
+        assert_eq!(cache.repair().await.unwrap().len(), 3);
+        assert!(cache.check().await.is_empty());
+        let calendar = calendar.read().await;
+        assert!(matches!(calendar.get_item_by_url_sync(&older_url).unwrap().sync_status(), SyncStatus::LocallyDeleted(_)));
+        assert_eq!(calendar.get_item_by_url_sync(&newer_url).unwrap().name(), "Buy oat milk");
+        assert!(calendar.get_item_by_url_sync(&tombstone_url).is_none());
+        assert!(calendar.get_synced_snapshot_sync(&unsynced_url).is_none());
+    }
 }
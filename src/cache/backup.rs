@@ -0,0 +1,101 @@
+//! Backups of a whole [`Cache`](crate::cache::Cache)
+//!
+//! [`Cache::backup_to`] writes every calendar of a cache (with its items, and what the next syncs need, e.g. the version tags of the items
+//! and their synced snapshots) into a single compressed file. [`Cache::restore_from`] creates a cache from it, e.g. on another device.
+//! Since the restored cache knows what has already been synced, the next sync only transfers what has changed since the backup,
+//! and items keep their URLs and UIDs.
+//!
+//! Backups are gzip-compressed JSON documents. They record the layout version of the cache they have been taken from, so that backups of older
+//! versions of this crate are migrated when they are restored (see [`migrations`](super::migrations)), and backups of newer versions are refused.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{migrations, read_file, write_file, Cache, CacheBuilder, CacheCompression, CacheFormat, CachedData, MAIN_FILE};
+use crate::calendar::cached_calendar::CachedCalendar;
+use crate::traits::BaseCalendar;
+
+/// The version of the layout of backup files
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    format_version: u32,
+    /// The layout version of the calendars
+    cache_version: u32,
+    /// When this backup has been taken
+    created: DateTime<Utc>,
+    calendars: Vec<serde_json::Value>,
+}
+
+impl Cache {
+    /// Write the whole content of this cache into a single compressed file (see the [module documentation](self)).
+    ///
+    /// Like [`Self::save_to_folder`], this fails in case a calendar is currently write-locked (e.g. because a sync is in progress)
+    pub fn backup_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut calendars = Vec::with_capacity(self.data.calendars.len());
+        for (cal_url, cal_lock) in &self.data.calendars {
+            let cal = cal_lock.try_read()
+                .map_err(|_| format!("Calendar {} is currently locked", cal_url))?;
+            calendars.push(serde_json::to_value(&*cal)?);
+        }
+        let backup = Backup {
+            format_version: BACKUP_FORMAT_VERSION,
+            cache_version: migrations::CURRENT_VERSION,
+            created: self.clock.now(),
+            calendars,
+        };
+        write_file(path, &backup, CacheFormat::Json, CacheCompression::Gzip)
+            .map_err(|err| format!("Unable to write backup {:?}: {}", path, err))?;
+        Ok(())
+    }
+
+    /// Create a cache in `folder` from a backup written by [`Self::backup_to`].
+    ///
+    /// See [`CacheBuilder::restore_from`] to restore it with non-default storage settings
+    pub fn restore_from(backup_path: &Path, folder: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::builder(folder).restore_from(backup_path)
+    }
+}
+
+impl CacheBuilder {
+    /// Create a cache from a backup written by [`Cache::backup_to`], and save it into the folder of this builder.
+    ///
+    /// This refuses to overwrite an existing cache, so that the folder should not contain a cache yet
+    pub fn restore_from(self, backup_path: &Path) -> Result<Cache, Box<dyn Error>> {
+        if self.folder.join(MAIN_FILE).exists() {
+            return Err(format!("Folder {:?} already contains a cache", self.folder).into());
+        }
+        let backup: Backup = read_file(backup_path)
+            .map_err(|err| format!("Unable to read backup {:?}: {}", backup_path, err))?;
+        if backup.format_version > BACKUP_FORMAT_VERSION || backup.cache_version > migrations::CURRENT_VERSION {
+            return Err(format!("Backup {:?} has been written by a newer version of this crate (backup version {}, cache version {})",
+                backup_path, backup.format_version, backup.cache_version).into());
+        }
+        log::info!("Restoring {} calendars from a backup taken on {}", backup.calendars.len(), backup.created);
+
+        let mut calendars = Vec::with_capacity(backup.calendars.len());
+        for mut content in backup.calendars {
+            migrations::migrate_calendar(&mut content, backup.cache_version)?;
+            calendars.push(serde_json::from_value::<CachedCalendar>(content)?);
+        }
+
+        // The cache is only created once the backup has been read entirely, since it is saved when it is dropped
+        let mut cache = self.build();
+        let mut data = CachedData::default();
+        for mut cal in calendars {
+            cal.set_event_sender(Some(cache.events.clone()));
+            cal.set_clock(cache.clock.clone());
+            cal.set_exclude_confidential(cache.exclude_confidential);
+            data.calendars.insert(cal.url().clone(), Arc::new(RwLock::new(cal)));
+        }
+        cache.data = data;
+        cache.save_to_folder()?;
+        Ok(cache)
+    }
+}
@@ -0,0 +1,56 @@
+//! Consistency checks of a [`Cache`](crate::cache::Cache)
+//!
+//! A cache can become inconsistent, e.g. because it has been edited by hand, written by a buggy version of this crate, or partially written when the app crashed.
+//! Such a cache usually does not fail right away, but causes weird syncs (items uploaded again and again, items that never disappear...).
+//!
+//! [`Cache::check`](crate::cache::Cache::check) lists every [`CacheProblem`], and [`Cache::repair`](crate::cache::Cache::repair) fixes them.
+//! Every kind of problem documents how it is repaired. Repairs never delete anything from the server by themselves, but they may mark items for deletion,
+//! that will be deleted at the next sync.
+
+use std::fmt::{Display, Formatter};
+
+use url::Url;
+
+use crate::utils::redact;
+
+/// A broken invariant of a cache
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheProblem {
+    /// A calendar is registered under another URL than its own.
+    ///
+    /// Repair: the calendar is registered under its own URL (unless another calendar already uses it, in which case nothing is changed).
+    CalendarUrlMismatch{ key: Url, calendar_url: Url },
+    /// An item is stored under another URL than its own.
+    ///
+    /// Repair: the item is stored under its own URL (unless another item already uses it, in which case the misplaced item is discarded).
+    ItemUrlMismatch{ calendar: Url, key: Url, item_url: Url },
+    /// Several items of the same calendar have the same UID (items that are marked for deletion are not considered).
+    ///
+    /// Repair: the most recent item (according to its `SEQUENCE`, then its last modification date) is kept, the other ones are marked for deletion.
+    DuplicateUid{ calendar: Url, uid: String, items: Vec<Url> },
+    /// An item that has never been synced has a synced snapshot.
+    ///
+    /// Repair: the snapshot is discarded.
+    UnsyncedWithSnapshot{ calendar: Url, item: Url },
+    /// A synced snapshot exists for an item that is not in the calendar.
+    ///
+    /// Repair: the snapshot is discarded.
+    OrphanSnapshot{ calendar: Url, item: Url },
+    /// An item is marked for deletion, but it has no version tag, so that it cannot be deleted from the server.
+    ///
+    /// Repair: the item is removed from the cache right away. If it still exists on the server, it will be downloaded again at the next sync.
+    MalformedTombstone{ calendar: Url, item: Url },
+}
+
+impl Display for CacheProblem {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::CalendarUrlMismatch{ key, calendar_url } => write!(f, "calendar {} is registered as {}", redact(calendar_url), redact(key)),
+            Self::ItemUrlMismatch{ key, item_url, .. } => write!(f, "item {} is stored as {}", redact(item_url), redact(key)),
+            Self::DuplicateUid{ uid, items, .. } => write!(f, "UID {} is used by {} items", redact(uid), items.len()),
+            Self::UnsyncedWithSnapshot{ item, .. } => write!(f, "item {} has never been synced, but has a synced snapshot", redact(item)),
+            Self::OrphanSnapshot{ item, .. } => write!(f, "there is a synced snapshot of missing item {}", redact(item)),
+            Self::MalformedTombstone{ item, .. } => write!(f, "item {} is marked for deletion, but has no version tag", redact(item)),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Upgrades of caches written by older versions of this crate
+//!
+//! The version of the layout of a cache is stored in its main file. When a cache with an older layout is loaded, its calendars go through every
+//! migration between their version and [`CURRENT_VERSION`] (on their serialized content, before they are deserialized), and they are saved with the
+//! current layout the next time the cache is saved. \
+//! Caches written by a newer version of this crate are refused, rather than being loaded with missing data.
+//!
+//! To change the layout of the cache, add a migration at the end of [`CALENDAR_MIGRATIONS`].
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::id::ItemId;
+use crate::item::{Item, ItemMetadata};
+
+/// A function that upgrades the serialized content of a calendar to the next version
+type Migration = fn(&mut Value) -> Result<(), Box<dyn Error>>;
+
+/// `CALENDAR_MIGRATIONS[n]` upgrades a calendar from version `n` to version `n + 1`
+const CALENDAR_MIGRATIONS: &[Migration] = &[
+    split_item_payloads,
+    normalize_item_urls,
+    embed_item_payloads,
+];
+
+/// The version of the layout of caches written by this version of the crate
+pub(crate) const CURRENT_VERSION: u32 = CALENDAR_MIGRATIONS.len() as u32;
+
+/// Upgrade the serialized content of a calendar from version `from` to [`CURRENT_VERSION`]
+pub(crate) fn migrate_calendar(calendar: &mut Value, from: u32) -> Result<(), Box<dyn Error>> {
+    if from > CURRENT_VERSION {
+        return Err(format!("Unable to downgrade a calendar from version {} to version {}", from, CURRENT_VERSION).into());
+    }
+    for (version, migration) in CALENDAR_MIGRATIONS.iter().enumerate().skip(from as usize) {
+        log::info!("Migrating a calendar of the cache from version {} to version {}", version, version + 1);
+        migration(calendar)?;
+    }
+    Ok(())
+}
+
+/// Version 0 to 1: items (and synced snapshots) are stored as their metadata and their payloads, so that they can be lazily loaded
+/// (see [`LazyItems`](crate::calendar::lazy_items::LazyItems)), instead of a single map of items.
+fn split_item_payloads(calendar: &mut Value) -> Result<(), Box<dyn Error>> {
+    for field in ["items", "synced_snapshots"] {
+        let items = match calendar.get_mut(field).and_then(|items| items.as_object_mut()) {
+            None => continue,
+            Some(items) => items,
+        };
+        // Caches written before versions were tracked may already use the new layout
+        if items.contains_key("metadata") || items.contains_key("payloads") {
+            continue;
+        }
+
+        let mut metadata = HashMap::new();
+        let mut payloads = HashMap::new();
+        for (url, item) in std::mem::take(items) {
+            let item: Item = serde_json::from_value(item)?;
+            metadata.insert(url.clone(), ItemMetadata::from(&item));
+            payloads.insert(url, serde_json::to_string(&item)?);
+        }
+        items.insert("metadata".to_string(), serde_json::to_value(metadata)?);
+        items.insert("payloads".to_string(), serde_json::to_value(payloads)?);
+    }
+    Ok(())
+}
+
+/// Version 1 to 2: items are stored at their normalized URL (see [`ItemId`]), since this is how the hrefs of the server are now read.
+/// Otherwise, an item stored with a URL that the server percent-encodes differently (e.g. `%40` instead of `@`) would never match its remote version again
+fn normalize_item_urls(calendar: &mut Value) -> Result<(), Box<dyn Error>> {
+    for field in ["items", "synced_snapshots", "archive"] {
+        let items = match calendar.get_mut(field) {
+            None => continue,
+            Some(items) => items,
+        };
+        if let Some(metadata) = items.get_mut("metadata") {
+            normalize_keys(metadata);
+        }
+        if let Some(payloads) = items.get_mut("payloads") {
+            normalize_keys(payloads);
+            for payload in payloads.as_object_mut().into_iter().flat_map(|payloads| payloads.values_mut()) {
+                // Payloads are JSON strings until version 3
+                let mut item: Value = match payload.as_str() {
+                    None => payload.take(),
+                    Some(text) => serde_json::from_str(text)?,
+                };
+                // Items are serialized as `{"Task": {"url": ..., ...}}`
+                for content in item.as_object_mut().into_iter().flat_map(|variants| variants.values_mut()) {
+                    normalize_url_field(content);
+                }
+                *payload = match payload.is_string() {
+                    true => Value::String(serde_json::to_string(&item)?),
+                    false => item,
+                };
+            }
+        }
+    }
+
+    for field in ["unparsed_items", "version_history", "trash", "sync_exclusions"] {
+        if let Some(map) = calendar.get_mut(field) {
+            normalize_keys(map);
+        }
+    }
+    if let Some(unparsed_items) = calendar.get_mut("unparsed_items").and_then(|items| items.as_object_mut()) {
+        unparsed_items.values_mut().for_each(normalize_url_field);
+    }
+    if let Some(urls) = calendar.get_mut("archived_out_of_sync").and_then(|urls| urls.as_array_mut()) {
+        for url in urls.iter_mut() {
+            if let Some(normalized) = url.as_str().map(normalize_url) {
+                *url = Value::String(normalized);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 2 to 3: the payloads of items are embedded as JSON, instead of JSON strings that contain their JSON.
+/// This avoids escaping them again every time the cache is saved
+fn embed_item_payloads(calendar: &mut Value) -> Result<(), Box<dyn Error>> {
+    for field in ["items", "synced_snapshots", "archive"] {
+        let payloads = match calendar.get_mut(field).and_then(|items| items.get_mut("payloads")).and_then(|payloads| payloads.as_object_mut()) {
+            None => continue,
+            Some(payloads) => payloads,
+        };
+        for payload in payloads.values_mut() {
+            if let Some(text) = payload.as_str() {
+                *payload = serde_json::from_str(text)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Normalize the keys of a map of items by URL.
+/// In case an item is stored under both forms of its URL, the one that has been stored under the non-normalized URL is kept, since it may have local changes
+fn normalize_keys(map: &mut Value) {
+    let map = match map.as_object_mut() {
+        None => return,
+        Some(map) => map,
+    };
+    let mut entries: Vec<(String, String, Value)> = std::mem::take(map).into_iter()
+        .map(|(url, value)| (normalize_url(&url), url, value))
+        .collect();
+    entries.sort_by_key(|(normalized, url, _value)| normalized != url);
+    let mut normalized_map = Map::new();
+    for (normalized, url, value) in entries {
+        if normalized_map.insert(normalized.clone(), value).is_some() {
+            log::warn!("Item {} is stored under two URLs, keeping the one from {}", crate::utils::redact(&normalized), crate::utils::redact(&url));
+        }
+    }
+    *map = normalized_map;
+}
+
+fn normalize_url_field(object: &mut Value) {
+    if let Some(url) = object.get_mut("url") {
+        if let Some(normalized) = url.as_str().map(normalize_url) {
+            *url = Value::String(normalized);
+        }
+    }
+}
+
+fn normalize_url(url: &str) -> String {
+    Url::parse(url).ok()
+        .and_then(|url| ItemId::try_from(url).ok())
+        .map(|id| id.into_url().to_string())
+        .unwrap_or_else(|| url.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::calendar::SupportedComponents;
+    use crate::task::Task;
+    use crate::traits::{BaseCalendar, CompleteCalendar};
+
+    #[test]
+    fn test_migrations() {
+        let cal_url: Url = "https://some.calend.ar/cal/".parse().unwrap();
+        let task = Task::new("A task".to_string(), false, &cal_url);
+        let task_url = task.url().clone();
+
+        // The layout of calendars before they had a version
+        let mut legacy = serde_json::json!({
+            "name": "A calendar",
+            "url": cal_url,
+            "supported_components": SupportedComponents::TODO,
+            "color": null,
+            "items": { task_url.as_str(): Item::Task(task) },
+        });
+        migrate_calendar(&mut legacy, 0).unwrap();
+        let calendar: CachedCalendar = serde_json::from_value(legacy).unwrap();
+        assert_eq!(calendar.name(), "A calendar");
+        assert_eq!(calendar.get_items_metadata()[&task_url].name, "A task");
+        assert!(calendar.get_item_by_url_sync(&task_url).is_some());
+
+        // Calendars that already have the current layout are not changed
+        let mut current = serde_json::to_value(&calendar).unwrap();
+        let before = current.clone();
+        migrate_calendar(&mut current, 0).unwrap();
+        assert_eq!(current, before);
+
+        assert!(migrate_calendar(&mut current, CURRENT_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn test_item_url_normalization() {
+        let cal_url: Url = "https://some.calend.ar/cal/".parse().unwrap();
+        let legacy_url: Url = "https://some.calend.ar/cal/task%40example.com.ics".parse().unwrap();
+        let normalized_url: Url = "https://some.calend.ar/cal/task@example.com.ics".parse().unwrap();
+        let task = Task::new_with_parameters("A task".to_string(), "task@example.com".to_string(), legacy_url.clone(),
+            crate::task::CompletionStatus::Uncompleted, crate::item::SyncStatus::Synced(crate::item::VersionTag::from("tag".to_string())), None, chrono::Utc::now(), "prod_id".to_string(), Vec::new());
+        let mut calendar = CachedCalendar::new("A calendar".to_string(), cal_url, SupportedComponents::TODO, None);
+        calendar.add_item_sync(Item::Task(task)).unwrap();
+
+        let mut serialized = serde_json::to_value(&calendar).unwrap();
+        migrate_calendar(&mut serialized, 1).unwrap();
+        let calendar: CachedCalendar = serde_json::from_value(serialized).unwrap();
+        assert!(calendar.get_item_by_url_sync(&legacy_url).is_none());
+        let item = calendar.get_item_by_url_sync(&normalized_url).unwrap();
+        assert_eq!(item.url(), &normalized_url);
+        assert_eq!(calendar.get_items_metadata()[&normalized_url].name, "A task");
+    }
+
+    #[test]
+    fn test_embed_item_payloads() {
+        let cal_url: Url = "https://some.calend.ar/cal/".parse().unwrap();
+        let mut calendar = CachedCalendar::new("A calendar".to_string(), cal_url.clone(), SupportedComponents::TODO, None);
+        let task = Task::new("A task".to_string(), false, &cal_url);
+        let task_url = task.url().clone();
+        calendar.add_item_sync(Item::Task(task)).unwrap();
+
+        // Payloads used to be JSON strings
+        let mut serialized = serde_json::to_value(&calendar).unwrap();
+        let payload = &mut serialized["items"]["payloads"][task_url.as_str()];
+        *payload = Value::String(payload.to_string());
+        migrate_calendar(&mut serialized, 2).unwrap();
+        assert!(serialized["items"]["payloads"][task_url.as_str()]["Task"].is_object());
+        let calendar: CachedCalendar = serde_json::from_value(serialized).unwrap();
+        assert_eq!(calendar.get_item_by_url_sync(&task_url).unwrap().name(), "A task");
+    }
+}
@@ -1,21 +1,36 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use tokio::sync::broadcast;
 use url::Url;
 
-use crate::item::SyncStatus;
+use crate::item::{ItemMetadata, SyncStatus, UnparsedItem, VanishedItem, VersionTag};
 use crate::traits::{BaseCalendar, CompleteCalendar};
-use crate::calendar::SupportedComponents;
+use crate::calendar::{ArchivePolicy, CalendarMetadata, ItemFilter, ItemPage, Permissions, SortKey, SupportedComponents, SyncExclusion};
+use crate::calendar::lazy_items::LazyItems;
+use crate::cache::CacheEvent;
+use crate::cache::integrity::CacheProblem;
+use crate::clock::Clock;
+use crate::utils::redact;
+use crate::search::SearchIndex;
+use crate::time_index::TimeIndex;
+use crate::item_counts::ItemCounts;
+use crate::Event;
 use crate::Item;
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
 use crate::mock_behaviour::MockBehaviour;
 
+/// How many version tags are kept for every item, see [`CachedCalendar::version_history`]
+pub const VERSION_HISTORY_LENGTH: usize = 10;
 
 /// A calendar used by the [`cache`](crate::cache) module
 ///
@@ -30,8 +45,66 @@ pub struct CachedCalendar {
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     #[serde(skip)]
     mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
+    /// Where changes to this calendar are notified (see [`crate::cache::Cache::subscribe`])
+    #[serde(skip)]
+    event_sender: Option<broadcast::Sender<CacheEvent>>,
+    /// The clock changes are dated with
+    #[serde(skip, default = "crate::clock::default_clock")]
+    clock: Arc<dyn Clock>,
+    /// Whether confidential items are kept out of the search index and exports (see [`crate::cache::CacheBuilder::exclude_confidential`])
+    #[serde(skip)]
+    exclude_confidential: bool,
+    /// A full-text index of the items. It is not persisted, but built the first time it is used
+    #[serde(skip)]
+    search_index: OnceCell<SearchIndex>,
+    /// An index of the items by date. It is not persisted either
+    #[serde(skip)]
+    time_index: OnceCell<TimeIndex>,
+    /// The counts of the items (see [`CompleteCalendar::count_items`]). They are not persisted either
+    #[serde(skip)]
+    item_counts: OnceCell<ItemCounts>,
 
-    items: HashMap<Url, Item>,
+    /// The items of this calendar. They are loaded from the cache only when they are used
+    items: LazyItems,
+    /// Items from the server that could not be parsed
+    #[serde(default)]
+    unparsed_items: HashMap<Url, UnparsedItem>,
+    /// Items with local changes that a sync has deleted, see [`CompleteCalendar::get_vanished_items`]
+    #[serde(default)]
+    vanished_items: Vec<VanishedItem>,
+    /// The version of the items at their latest sync, see [`CompleteCalendar::get_synced_snapshot`]
+    #[serde(default)]
+    synced_snapshots: LazyItems,
+    /// What the current user is allowed to do in the remote calendar
+    #[serde(default)]
+    permissions: Option<Permissions>,
+    /// What the remote calendar tells about itself
+    #[serde(default)]
+    metadata: CalendarMetadata,
+    /// When this calendar has last been synced
+    #[serde(default)]
+    last_sync: Option<DateTime<Utc>>,
+    /// Local-only data attached to items by the app, by UID (see [`Self::set_app_data`])
+    #[serde(default)]
+    app_data: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// The latest version tags of the items, by URL (see [`Self::version_history`])
+    #[serde(default)]
+    version_history: HashMap<Url, VecDeque<VersionTag>>,
+    /// The items that have been moved to the trash, and when (see [`CompleteCalendar::trash_item`])
+    #[serde(default)]
+    trash: HashMap<Url, DateTime<Utc>>,
+    /// The items that are kept out of syncs (see [`CompleteCalendar::set_sync_exclusion`])
+    #[serde(default)]
+    sync_exclusions: HashMap<Url, SyncExclusion>,
+    /// Whether this whole calendar is kept out of syncs (see [`CompleteCalendar::set_calendar_sync_exclusion`])
+    #[serde(default)]
+    sync_exclusion: Option<SyncExclusion>,
+    /// The items that have been moved out of this calendar (see [`CompleteCalendar::archive_items`]). They are loaded from the cache only when they are used
+    #[serde(default)]
+    archive: LazyItems,
+    /// The archived items that are kept out of syncs
+    #[serde(default)]
+    archived_out_of_sync: HashSet<Url>,
 }
 
 impl CachedCalendar {
@@ -42,6 +115,304 @@ impl CachedCalendar {
     }
 
 
+    /// Set the channel where changes to this calendar will be notified
+    pub(crate) fn set_event_sender(&mut self, event_sender: Option<broadcast::Sender<CacheEvent>>) {
+        self.event_sender = event_sender;
+    }
+
+    /// Set the clock changes to this calendar are dated with
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Set whether confidential items are kept out of the search index and exports
+    pub(crate) fn set_exclude_confidential(&mut self, exclude_confidential: bool) {
+        if self.exclude_confidential != exclude_confidential {
+            self.exclude_confidential = exclude_confidential;
+            self.search_index = OnceCell::new();
+        }
+    }
+
+    /// Returns a copy of this calendar, that will not notify its changes to the original cache
+    pub(crate) fn detached_clone(&self) -> Self {
+        let mut clone = self.clone();
+        clone.event_sender = None;
+        clone
+    }
+
+    fn search_index(&self) -> &SearchIndex {
+        self.search_index.get_or_init(|| {
+            let mut index = if self.exclude_confidential { SearchIndex::excluding_confidential() } else { SearchIndex::new() };
+            for item in self.items.values() {
+                index.index_item(item);
+            }
+            index
+        })
+    }
+
+    fn time_index(&self) -> &TimeIndex {
+        self.time_index.get_or_init(|| {
+            let mut index = TimeIndex::new();
+            for item in self.items.values() {
+                index.index_item(item);
+            }
+            index
+        })
+    }
+
+    fn item_counts(&self) -> &ItemCounts {
+        self.item_counts.get_or_init(|| {
+            let mut counts = ItemCounts::new();
+            for (url, item) in self.items.iter() {
+                if !self.trash.contains_key(url) {
+                    counts.index_item(item);
+                }
+            }
+            counts
+        })
+    }
+
+    /// The items that are counted by [`Self::item_counts`], i.e. the ones that are not in the trash
+    fn get_counted_item(&self, url: &Url) -> Option<&Item> {
+        if self.trash.contains_key(url) {
+            return None;
+        }
+        self.items.get(url)
+    }
+
+    /// Re-index the items that have been marked dirty by the indexes (that have been built so far).
+    /// This is called before every change, since the mutable references to the items that have been returned are not alive anymore
+    fn reindex_dirty_items(&mut self) {
+        let items = &self.items;
+        if let Some(index) = self.time_index.get_mut() {
+            index.reindex_dirty(|url| items.get(url));
+        }
+        let trash = &self.trash;
+        if let Some(counts) = self.item_counts.get_mut() {
+            counts.recount_dirty(|url| if trash.contains_key(url) { None } else { items.get(url) });
+        }
+    }
+
+    /// Update the indexes (that have been built so far) after an item has been added or updated
+    fn index_item(&mut self, item: &Item) {
+        self.reindex_dirty_items();
+        if let Some(index) = self.search_index.get_mut() {
+            index.index_item(item);
+        }
+        if let Some(index) = self.time_index.get_mut() {
+            index.index_item(item);
+        }
+        if let Some(counts) = self.item_counts.get_mut() {
+            match self.trash.contains_key(item.url()) {
+                true => counts.remove_item(item.url()),
+                false => counts.index_item(item),
+            }
+        }
+    }
+
+    /// Update the indexes (that have been built so far) after an item has been removed
+    fn unindex_item(&mut self, url: &Url) {
+        self.reindex_dirty_items();
+        if let Some(index) = self.search_index.get_mut() {
+            index.remove_item(url);
+        }
+        if let Some(index) = self.time_index.get_mut() {
+            index.remove_item(url);
+        }
+        if let Some(counts) = self.item_counts.get_mut() {
+            counts.remove_item(url);
+        }
+    }
+
+    /// Tell the indexes (that have been built so far) that an item may be modified without them knowing it
+    fn mark_dirty(&mut self, url: &Url) {
+        self.reindex_dirty_items();
+        if let Some(index) = self.search_index.get_mut() {
+            index.mark_dirty(url);
+        }
+        if let Some(index) = self.time_index.get_mut() {
+            index.mark_dirty(url);
+        }
+        if let Some(counts) = self.item_counts.get_mut() {
+            counts.mark_dirty(url);
+        }
+    }
+
+    /// Returns the items whose name, categories or description contain every word of the query (or words that start with them), with their score
+    pub fn search(&self, query: &str) -> HashMap<Url, u32> {
+        self.search_index().search(query, |url| self.items.get(url))
+    }
+
+    /// Returns the metadata of every item of this calendar.
+    ///
+    /// Unlike [`Self::get_items_sync`], this does not need to load the items that have not been used since the cache has been opened.
+    pub fn get_items_metadata(&self) -> HashMap<Url, ItemMetadata> {
+        self.items.metadata()
+    }
+
+    /// Returns the items that overlap the `start..end` time range, sorted by their start date.
+    ///
+    /// Events span from their `DTSTART` until their `DTEND` (or for their `DURATION`), which is excluded. Tasks span from their `DTSTART` to their `DUE` date,
+    /// and tasks that have neither are never returned.
+    /// This uses an index (see [`crate::time_index`]), so that it does not need to read every item of large calendars.
+    pub fn get_items_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<&Item> {
+        self.get_item_urls_between(start, end)
+            .iter()
+            .filter_map(|(_start, url)| self.items.get(url))
+            .collect()
+    }
+
+    /// Returns the events that overlap the `start..end` time range, sorted by their start date (see [`Self::get_items_between`]).
+    ///
+    /// This is what a month or a week view of a calendar displays.
+    pub fn get_events_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<&Event> {
+        self.get_items_between(start, end)
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Event(event) => Some(event),
+                Item::Task(_) => None,
+            })
+            .collect()
+    }
+
+    /// The URLs of the items returned by [`Self::get_items_between`], with their start date
+    pub(crate) fn get_item_urls_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(DateTime<Utc>, Url)> {
+        self.time_index().items_between(start, end, |url| self.items.get(url))
+    }
+
+    /// The non-async version of [`Self::count_items`]
+    pub fn count_items_sync(&self) -> usize {
+        self.item_counts().count_items(|url| self.get_counted_item(url))
+    }
+
+    /// The non-async version of [`Self::count_completed`]
+    pub fn count_completed_sync(&self) -> usize {
+        self.item_counts().count_completed(|url| self.get_counted_item(url))
+    }
+
+    /// The non-async version of [`Self::count_due_before`]
+    pub fn count_due_before_sync(&self, date: DateTime<Utc>) -> usize {
+        self.item_counts().count_due_before(date, |url| self.get_counted_item(url))
+    }
+
+    /// Set the color of this calendar (or remove it).
+    ///
+    /// This is a local setting: it is not uploaded to the server, but it is kept in the cache, and written into exported iCal files
+    pub fn set_color(&mut self, color: Option<Color>) {
+        self.color = color;
+        self.notify(CacheEvent::CalendarChanged{ calendar: self.url.clone() });
+    }
+
+    /// The latest version tags an item has had in this cache, from the oldest to the current one (at most [`VERSION_HISTORY_LENGTH`] of them).
+    ///
+    /// Apps can compare them with the version of an item they are displaying (see [`Item::version_tag`]), to tell whether it has changed
+    /// (e.g. during a sync) since then. Items that have never been synced have no version tag, and no history
+    pub fn version_history(&self, url: &Url) -> Vec<VersionTag> {
+        self.version_history.get(url)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Append the current version tag of an item to its history, in case it has changed
+    fn record_version(&mut self, url: &Url) {
+        let tag = match self.items.get(url).and_then(|item| item.version_tag().cloned()) {
+            None => return,
+            Some(tag) => tag,
+        };
+        let history = self.version_history.entry(url.clone()).or_default();
+        if history.back() != Some(&tag) {
+            history.push_back(tag);
+            if history.len() > VERSION_HISTORY_LENGTH {
+                history.pop_front();
+            }
+        }
+    }
+
+    /// The data attached by the app to an item, see [`Self::set_app_data`]
+    pub fn app_data(&self, uid: &str) -> Option<&HashMap<String, serde_json::Value>> {
+        self.app_data.get(uid)
+    }
+
+    /// Attach app-specific data to an item (e.g. a local sort order, or a snooze time).
+    ///
+    /// This data is persisted in the cache, but it is never uploaded to the server. It is attached to the UID of the item, so that it is kept when
+    /// the item is changed on the server, or when its URL changes. It is dropped when the item is deleted from this calendar.
+    pub fn set_app_data(&mut self, uid: &str, key: String, value: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let item_url = self.get_item_by_uid_sync(uid)
+            .map(|item| item.url().clone())
+            .ok_or_else(|| format!("No item with UID {} in calendar {}", uid, self.url))?;
+        self.app_data.entry(uid.to_string()).or_default().insert(key, value);
+        self.notify(CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url });
+        Ok(())
+    }
+
+    /// Remove a value attached to an item by [`Self::set_app_data`], and return it
+    pub fn remove_app_data(&mut self, uid: &str, key: &str) -> Option<serde_json::Value> {
+        let data = self.app_data.get_mut(uid)?;
+        let value = data.remove(key);
+        if data.is_empty() {
+            self.app_data.remove(uid);
+        }
+        value
+    }
+
+    /// Remove every value attached to an item by [`Self::set_app_data`], and return them
+    pub(crate) fn take_app_data(&mut self, uid: &str) -> Option<HashMap<String, serde_json::Value>> {
+        self.app_data.remove(uid)
+    }
+
+    /// Attach values that have been removed by [`Self::take_app_data`] (e.g. from another calendar) to an item
+    pub(crate) fn insert_app_data(&mut self, uid: &str, data: HashMap<String, serde_json::Value>) {
+        self.app_data.entry(uid.to_string()).or_default().extend(data);
+    }
+
+    fn notify(&self, event: CacheEvent) {
+        if let Some(sender) = &self.event_sender {
+            // An error only means there is currently no subscriber
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Run a batch of changes, and notify them as a single [`CacheEvent::CalendarChanged`] instead of one event per item
+    fn batch<F, R>(&mut self, changes: F) -> Result<R, Box<dyn Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<R, Box<dyn Error>>
+    {
+        let event_sender = self.event_sender.take();
+        let result = changes(self);
+        self.event_sender = event_sender;
+        if result.is_ok() {
+            self.notify(CacheEvent::CalendarChanged{ calendar: self.url.clone() });
+        }
+        result
+    }
+
+    /// Items that have been synced before snapshots were kept do not have one yet. Their current version is their synced version,
+    /// as long as they have not been modified.
+    fn backfill_synced_snapshot(&mut self, url: &Url) {
+        if let Some(item) = self.items.get(url) {
+            if matches!(item.sync_status(), SyncStatus::Synced(_)) && !self.synced_snapshots.contains_key(url) {
+                self.synced_snapshots.insert(url.clone(), item.clone());
+            }
+        }
+    }
+
+    /// Insert an item, and notify whether it has been added or updated
+    fn insert_and_notify(&mut self, item: Item) {
+        let item_url = item.url().clone();
+        self.backfill_synced_snapshot(&item_url);
+        self.index_item(&item);
+        // This item has eventually been parsed
+        self.unparsed_items.remove(&item_url);
+        let event = if self.items.insert(item_url.clone(), item) {
+            CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() }
+        } else {
+            CacheEvent::ItemAdded{ calendar: self.url.clone(), item: item_url.clone() }
+        };
+        self.record_version(&item_url);
+        self.notify(event);
+    }
+
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     fn add_item_maybe_mocked(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
         if self.mock_behaviour.is_some() {
@@ -66,7 +437,7 @@ impl CachedCalendar {
     fn regular_add_or_update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
         let ss_clone = item.sync_status().clone();
         log::debug!("Adding or updating an item with {:?}", ss_clone);
-        self.items.insert(item.url().clone(), item);
+        self.insert_and_notify(item);
         Ok(ss_clone)
     }
 
@@ -79,7 +450,7 @@ impl CachedCalendar {
             _ => item.set_sync_status(SyncStatus::random_synced()),
         };
         let ss_clone = item.sync_status().clone();
-        self.items.insert(item.url().clone(), item);
+        self.insert_and_notify(item);
         Ok(ss_clone)
     }
 
@@ -109,9 +480,11 @@ impl CachedCalendar {
                 None => return Err("should not happen, we've just tested keys are the same".into()),
             };
             if item_l.has_same_observable_content_as(&item_r) == false {
-                log::debug!("Different items for URL {}:", url_l);
-                log::debug!("{:#?}", item_l);
-                log::debug!("{:#?}", item_r);
+                log::debug!("Different items for URL {}:", crate::utils::redact(&url_l));
+                if *crate::config::LOG_PERSONAL_DATA.lock().unwrap() {
+                    log::debug!("{:#?}", item_l);
+                    log::debug!("{:#?}", item_r);
+                }
                 return Ok(false);
             }
         }
@@ -135,8 +508,43 @@ impl CachedCalendar {
         )
     }
 
+    /// The non-async version of [`Self::get_items_matching`]
+    pub fn get_items_matching_sync(&self, filter: &ItemFilter) -> Result<HashMap<Url, &Item>, Box<dyn Error>> {
+        Ok(self.items.iter()
+            .filter(|(url, _item)| !self.trash.contains_key(url))
+            .filter(|(_url, item)| filter.matches(item))
+            .map(|(url, item)| (url.clone(), item))
+            .collect()
+        )
+    }
+
+    /// Iterate over the items of this calendar (except the ones in the trash), in the order given by `sort`
+    pub fn iter_items_sorted(&self, sort: SortKey) -> impl Iterator<Item = &Item> {
+        let mut items: Vec<&Item> = self.items.iter()
+            .filter(|(url, _item)| !self.trash.contains_key(url))
+            .map(|(_url, item)| item)
+            .collect();
+        items.sort_by(|a, b| sort.compare(a, b));
+        items.into_iter()
+    }
+
+    /// The non-async version of [`Self::get_items_sorted`]
+    pub fn get_items_sorted_sync(&self, sort: SortKey) -> Result<Vec<&Item>, Box<dyn Error>> {
+        Ok(self.iter_items_sorted(sort).collect())
+    }
+
+    /// The non-async version of [`Self::get_items_page`]
+    pub fn get_items_page_sync(&self, offset: usize, limit: usize, sort: SortKey) -> Result<ItemPage<'_>, Box<dyn Error>> {
+        Ok(ItemPage::from_sorted(self.iter_items_sorted(sort), offset, limit))
+    }
+
     /// The non-async version of [`Self::get_items_mut`]
     pub fn get_items_mut_sync(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>> {
+        let urls: Vec<Url> = self.items.keys().cloned().collect();
+        for url in &urls {
+            self.backfill_synced_snapshot(url);
+            self.mark_dirty(url);
+        }
         Ok(self.items.iter_mut()
             .map(|(url, item)| (url.clone(), item))
             .collect()
@@ -148,8 +556,20 @@ impl CachedCalendar {
         self.items.get(url)
     }
 
+    /// The non-async version of [`Self::get_item_by_uid`]
+    pub fn get_item_by_uid_sync<'a>(&'a self, uid: &str) -> Option<&'a Item> {
+        let url = self.items.metadata().into_iter()
+            .find(|(_url, metadata)| metadata.uid == uid)
+            .map(|(url, _metadata)| url)?;
+        self.items.get(&url)
+    }
+
     /// The non-async version of [`Self::get_item_by_url_mut`]
     pub fn get_item_by_url_mut_sync<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item> {
+        if self.items.contains_key(url) {
+            self.backfill_synced_snapshot(url);
+            self.mark_dirty(url);
+        }
         self.items.get_mut(url)
     }
 
@@ -177,42 +597,365 @@ impl CachedCalendar {
         return self.update_item_maybe_mocked(item);
     }
 
+    /// The non-async version of [`Self::add_items`].
+    ///
+    /// Nothing is added if any of the items cannot be added
+    pub fn add_items_sync(&mut self, items: Vec<Item>) -> Result<Vec<SyncStatus>, Box<dyn Error>> {
+        let mut new_urls = HashSet::new();
+        for item in &items {
+            if self.items.contains_key(item.url()) || !new_urls.insert(item.url()) {
+                return Err(format!("Item {:?} cannot be added, it exists already", item.url()).into());
+            }
+        }
+        self.batch(|cal| {
+            let mut statuses = Vec::new();
+            let mut added: Vec<Url> = Vec::new();
+            for item in items {
+                let url = item.url().clone();
+                match cal.add_item_sync(item) {
+                    Ok(status) => {
+                        statuses.push(status);
+                        added.push(url);
+                    },
+                    Err(err) => {
+                        // Roll back, so that the batch is all or nothing
+                        for url in &added {
+                            cal.immediately_delete_item_sync(url)?;
+                        }
+                        return Err(err);
+                    },
+                }
+            }
+            Ok(statuses)
+        })
+    }
+
+    /// The non-async version of [`Self::delete_items`].
+    ///
+    /// Nothing is deleted if any of the items does not exist
+    pub fn delete_items_sync(&mut self, item_urls: &[Url]) -> Result<(), Box<dyn Error>> {
+        if let Some(missing) = item_urls.iter().find(|url| !self.items.contains_key(url)) {
+            return Err(format!("Item {} is absent from this calendar", missing).into());
+        }
+        self.batch(|cal| {
+            item_urls.iter()
+                .try_for_each(|url| cal.mark_for_deletion_sync(url))
+        })
+    }
+
+    /// The non-async version of [`Self::mark_completed`].
+    ///
+    /// Nothing is changed if any of the items does not exist or is not a task
+    pub fn mark_completed_sync(&mut self, item_urls: &[Url]) -> Result<(), Box<dyn Error>> {
+        for url in item_urls {
+            match self.items.get(url) {
+                None => return Err(format!("Item {} is absent from this calendar", url).into()),
+                Some(Item::Event(_)) => return Err(format!("Item {} is an event, it cannot be completed", url).into()),
+                Some(Item::Task(_)) => (),
+            }
+        }
+        let now = self.clock.now();
+        self.batch(|cal| {
+            for url in item_urls {
+                if let Some(Item::Task(task)) = cal.get_item_by_url_mut_sync(url) {
+                    if !task.completed() {
+                        task.complete(now);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The non-async version of [`Self::get_unparsed_items`]
+    pub fn get_unparsed_items_sync(&self) -> Vec<&UnparsedItem> {
+        self.unparsed_items.values().collect()
+    }
+
+    /// The non-async version of [`Self::set_unparsed_items`]
+    pub fn set_unparsed_items_sync(&mut self, items: Vec<UnparsedItem>) {
+        self.unparsed_items = items.into_iter()
+            .map(|item| (item.url.clone(), item))
+            .collect();
+    }
+
+    /// The non-async version of [`Self::get_vanished_items`]
+    pub fn get_vanished_items_sync(&self) -> Vec<&VanishedItem> {
+        self.vanished_items.iter().collect()
+    }
+
+    /// The non-async version of [`Self::set_vanished_items`]
+    pub fn set_vanished_items_sync(&mut self, items: Vec<VanishedItem>) {
+        self.vanished_items = items;
+    }
+
+    /// The non-async version of [`Self::get_synced_snapshot`]
+    pub fn get_synced_snapshot_sync(&self, url: &Url) -> Option<&Item> {
+        self.synced_snapshots.get(url)
+    }
+
+    /// The non-async version of [`Self::save_synced_snapshot`]
+    pub fn save_synced_snapshot_sync(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        let item = self.items.get(url).ok_or_else(|| format!("Item {} is absent from this calendar", url))?;
+        self.synced_snapshots.insert(url.clone(), item.clone());
+        // The provider changes the sync status of synced items in place
+        self.record_version(url);
+        Ok(())
+    }
+
+    /// The non-async version of [`Self::trash_item`]
+    pub fn trash_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        match self.items.get(item_url).map(|item| item.sync_status()) {
+            None => return Err(format!("Item {} is absent from this calendar", item_url).into()),
+            Some(SyncStatus::LocallyDeleted(_)) => return Err(format!("Item {} has been deleted already", item_url).into()),
+            Some(_) => (),
+        }
+        let now = self.clock.now();
+        self.trash.entry(item_url.clone()).or_insert(now);
+        if let Some(counts) = self.item_counts.get_mut() {
+            counts.remove_item(item_url);
+        }
+        self.notify(CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() });
+        Ok(())
+    }
+
+    /// The non-async version of [`Self::restore_item`]
+    pub fn restore_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        if self.trash.remove(item_url).is_none() {
+            return Err(format!("Item {} is not in the trash", item_url).into());
+        }
+        if let (Some(counts), Some(item)) = (self.item_counts.get_mut(), self.items.get(item_url)) {
+            counts.index_item(item);
+        }
+        self.notify(CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() });
+        Ok(())
+    }
+
+    /// The non-async version of [`Self::purge_trash`]
+    pub fn purge_trash_sync(&mut self, trashed_before: DateTime<Utc>) -> Result<Vec<Url>, Box<dyn Error>> {
+        let mut purged: Vec<Url> = self.trash.iter()
+            .filter(|(_url, trashed_at)| **trashed_at <= trashed_before)
+            .map(|(url, _trashed_at)| url.clone())
+            .collect();
+        purged.sort();
+        for url in &purged {
+            self.trash.remove(url);
+            if self.items.contains_key(url) {
+                self.mark_for_deletion_sync(url)?;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// The non-async version of [`Self::archive_items`]
+    pub fn archive_items_sync(&mut self, policy: &ArchivePolicy) -> Result<Vec<Url>, Box<dyn Error>> {
+        let now = self.clock.now();
+        let mut archived: Vec<Url> = self.items.iter()
+            .filter(|(url, item)| !self.trash.contains_key(url) && policy.matches(item, now))
+            .map(|(url, _item)| url.clone())
+            .collect();
+        if archived.is_empty() {
+            return Ok(archived);
+        }
+        archived.sort();
+        self.batch(|cal| {
+            for url in &archived {
+                if let Some(item) = cal.items.get(url).cloned() {
+                    cal.items.remove(url);
+                    cal.unindex_item(url);
+                    cal.archive.insert(url.clone(), item);
+                    if policy.exclude_from_sync {
+                        cal.archived_out_of_sync.insert(url.clone());
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        Ok(archived)
+    }
+
+    /// The non-async version of [`Self::get_archived_items`]
+    pub fn get_archived_items_sync(&self) -> Result<HashMap<Url, &Item>, Box<dyn Error>> {
+        Ok(self.archive.iter()
+            .map(|(url, item)| (url.clone(), item))
+            .collect()
+        )
+    }
+
+    /// Returns an item of the archive of this calendar
+    pub fn get_archived_item_sync<'a>(&'a self, url: &Url) -> Option<&'a Item> {
+        self.archive.get(url)
+    }
+
+    /// Whether an item is in the archive of this calendar
+    pub fn is_archived(&self, url: &Url) -> bool {
+        self.archive.contains_key(url)
+    }
+
+    /// The non-async version of [`Self::unarchive_item`]
+    pub fn unarchive_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        let item = self.archive.get(item_url)
+            .cloned()
+            .ok_or_else(|| format!("Item {} is not in the archive of calendar {}", item_url, self.url))?;
+        self.archive.remove(item_url);
+        self.archived_out_of_sync.remove(item_url);
+        self.insert_and_notify(item);
+        Ok(())
+    }
+
     /// The non-async version of [`Self::mark_for_deletion`]
     pub fn mark_for_deletion_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        match self.items.get_mut(item_url) {
-            None => Err("no item for this key".into()),
+        self.trash.remove(item_url);
+        let event = match self.items.get_mut(item_url) {
+            None => return Err("no item for this key".into()),
             Some(item) => {
                 match item.sync_status() {
                     SyncStatus::Synced(prev_ss) => {
                         let prev_ss = prev_ss.clone();
                         item.set_sync_status( SyncStatus::LocallyDeleted(prev_ss));
+                        CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() }
                     },
                     SyncStatus::LocallyModified(prev_ss) => {
                         let prev_ss = prev_ss.clone();
                         item.set_sync_status( SyncStatus::LocallyDeleted(prev_ss));
+                        CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() }
                     },
                     SyncStatus::LocallyDeleted(prev_ss) => {
                         let prev_ss = prev_ss.clone();
                         item.set_sync_status( SyncStatus::LocallyDeleted(prev_ss));
+                        CacheEvent::ItemUpdated{ calendar: self.url.clone(), item: item_url.clone() }
                     },
                     SyncStatus::NotSynced => {
                         // This was never synced to the server, we can safely delete it as soon as now
                         self.items.remove(item_url);
+                        self.unindex_item(item_url);
+                        CacheEvent::ItemDeleted{ calendar: self.url.clone(), item: item_url.clone() }
                     },
-                };
-                Ok(())
+                }
             }
-        }
+        };
+        self.unindex_item(item_url);
+        self.notify(event);
+        Ok(())
     }
 
     /// The non-async version of [`Self::immediately_delete_item`]
     pub fn immediately_delete_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        match self.items.remove(item_url) {
-            None => Err(format!("Item {} is absent from this calendar", item_url).into()),
-            Some(_) => Ok(())
+        let uid = self.items.metadata_of(item_url)
+            .or_else(|| self.archive.metadata_of(item_url))
+            .map(|metadata| metadata.uid);
+        // Archived items are deleted as well, e.g. when they are deleted from the server
+        if !self.items.remove(item_url) && !self.archive.remove(item_url) {
+            return Err(format!("Item {} is absent from this calendar", item_url).into());
+        }
+        self.archived_out_of_sync.remove(item_url);
+        // The data of the app is kept as long as another copy of the item remains (e.g. when an item is moved within this calendar)
+        if let Some(uid) = uid {
+            if self.get_item_by_uid_sync(&uid).is_none() {
+                self.app_data.remove(&uid);
+            }
+        }
+        self.unindex_item(item_url);
+        self.synced_snapshots.remove(item_url);
+        self.version_history.remove(item_url);
+        self.trash.remove(item_url);
+        self.notify(CacheEvent::ItemDeleted{ calendar: self.url.clone(), item: item_url.clone() });
+        Ok(())
+    }
+
+    /// Check the invariants of this calendar. See [`Cache::check`](crate::cache::Cache::check)
+    pub(crate) fn check_integrity(&self) -> Vec<CacheProblem> {
+        let mut problems = Vec::new();
+        let mut urls_by_uid: HashMap<&str, Vec<Url>> = HashMap::new();
+        for (url, item) in self.items.iter() {
+            if item.url() != url {
+                problems.push(CacheProblem::ItemUrlMismatch{ calendar: self.url.clone(), key: url.clone(), item_url: item.url().clone() });
+            }
+            match item.sync_status() {
+                SyncStatus::LocallyDeleted(tag) => {
+                    if tag.as_str().is_empty() {
+                        problems.push(CacheProblem::MalformedTombstone{ calendar: self.url.clone(), item: url.clone() });
+                    }
+                    continue;
+                },
+                SyncStatus::NotSynced
+                    if self.synced_snapshots.contains_key(url) => {
+                        problems.push(CacheProblem::UnsyncedWithSnapshot{ calendar: self.url.clone(), item: url.clone() });
+                    },
+                _ => (),
+            }
+            urls_by_uid.entry(item.uid()).or_default().push(url.clone());
+        }
+
+        for (uid, mut urls) in urls_by_uid {
+            if urls.len() > 1 {
+                urls.sort();
+                problems.push(CacheProblem::DuplicateUid{ calendar: self.url.clone(), uid: uid.to_string(), items: urls });
+            }
+        }
+        for url in self.synced_snapshots.keys() {
+            if !self.items.contains_key(url) {
+                problems.push(CacheProblem::OrphanSnapshot{ calendar: self.url.clone(), item: url.clone() });
+            }
         }
+        problems
     }
 
+    /// Fix the problems found by [`Self::check_integrity`], and return them. See [`Cache::repair`](crate::cache::Cache::repair)
+    pub(crate) fn repair_integrity(&mut self) -> Result<Vec<CacheProblem>, Box<dyn Error>> {
+        let problems = self.check_integrity();
+        if problems.is_empty() {
+            return Ok(problems);
+        }
+
+        self.batch(|cal| {
+            // Misplaced items first, so that the other repairs use the right URLs
+            for problem in &problems {
+                if let CacheProblem::ItemUrlMismatch{ key, item_url, .. } = problem {
+                    if let Some(item) = cal.items.get(key).cloned() {
+                        cal.items.remove(key);
+                        if cal.items.contains_key(item_url) {
+                            log::warn!("Discarding misplaced item {}, because another item uses its URL", redact(item_url));
+                        } else {
+                            cal.items.insert(item_url.clone(), item);
+                        }
+                    }
+                }
+            }
+
+            for problem in &problems {
+                match problem {
+                    CacheProblem::ItemUrlMismatch{ .. } | CacheProblem::CalendarUrlMismatch{ .. } => (),
+                    CacheProblem::DuplicateUid{ items, .. } => {
+                        let newest = items.iter()
+                            .filter_map(|url| cal.items.get(url))
+                            .max_by(|a, b| a.sequence().cmp(&b.sequence()).then_with(|| a.last_modified().cmp(b.last_modified())))
+                            .map(|item| item.url().clone());
+                        for url in items {
+                            if Some(url) != newest.as_ref() && cal.items.contains_key(url) {
+                                cal.mark_for_deletion_sync(url)?;
+                            }
+                        }
+                    },
+                    CacheProblem::UnsyncedWithSnapshot{ item, .. } | CacheProblem::OrphanSnapshot{ item, .. } => {
+                        cal.synced_snapshots.remove(item);
+                    },
+                    CacheProblem::MalformedTombstone{ item, .. } => {
+                        if cal.items.contains_key(item) {
+                            cal.immediately_delete_item_sync(item)?;
+                        }
+                    },
+                }
+            }
+
+            // Items may have been moved around, the indexes will be rebuilt when they are needed
+            cal.search_index = OnceCell::new();
+            cal.time_index = OnceCell::new();
+            cal.item_counts = OnceCell::new();
+            Ok(())
+        })?;
+        Ok(problems)
+    }
 }
 
 
@@ -234,6 +977,14 @@ impl BaseCalendar for CachedCalendar {
         self.color.as_ref()
     }
 
+    fn permissions(&self) -> Option<Permissions> {
+        self.permissions
+    }
+
+    fn metadata(&self) -> CalendarMetadata {
+        self.metadata.clone()
+    }
+
     async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
         self.add_item_sync(item)
     }
@@ -250,7 +1001,26 @@ impl CompleteCalendar for CachedCalendar {
             name, url, supported_components, color,
             #[cfg(feature = "local_calendar_mocks_remote_calendars")]
             mock_behaviour: None,
-            items: HashMap::new(),
+            event_sender: None,
+            clock: crate::clock::default_clock(),
+            exclude_confidential: false,
+            search_index: OnceCell::new(),
+            time_index: OnceCell::new(),
+            item_counts: OnceCell::new(),
+            items: LazyItems::default(),
+            unparsed_items: HashMap::new(),
+            vanished_items: Vec::new(),
+            synced_snapshots: LazyItems::default(),
+            permissions: None,
+            metadata: CalendarMetadata::default(),
+            last_sync: None,
+            app_data: HashMap::new(),
+            version_history: HashMap::new(),
+            trash: HashMap::new(),
+            sync_exclusions: HashMap::new(),
+            sync_exclusion: None,
+            archive: LazyItems::default(),
+            archived_out_of_sync: HashSet::new(),
         }
     }
 
@@ -262,6 +1032,29 @@ impl CompleteCalendar for CachedCalendar {
         self.get_items_sync()
     }
 
+    async fn get_items_matching<'a>(&'a self, filter: &ItemFilter) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        self.get_items_matching_sync(filter)
+    }
+
+    async fn get_items_sorted<'a>(&'a self, sort: SortKey) -> Result<Vec<&'a Item>, Box<dyn Error>> {
+        self.get_items_sorted_sync(sort)
+    }
+
+    async fn get_items_page<'a>(&'a self, offset: usize, limit: usize, sort: SortKey) -> Result<ItemPage<'a>, Box<dyn Error>> {
+        self.get_items_page_sync(offset, limit, sort)
+    }
+
+    async fn export_ics(&self, writer: &mut (dyn std::io::Write + Send)) -> Result<(), Box<dyn Error>> {
+        let mut items: Vec<&Item> = self.get_items_sync()?.into_values()
+            .filter(|item| !matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)))
+            .filter(|item| !self.exclude_confidential || !item.classification().is_confidential())
+            .collect();
+        items.sort_by(|a, b| SortKey::Url.compare(a, b));
+        let ics = crate::ical::build_calendar(items, &crate::ical::default_prod_id(), self.color())?;
+        writer.write_all(ics.as_bytes())?;
+        Ok(())
+    }
+
     async fn get_items_mut(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>> {
         self.get_items_mut_sync()
     }
@@ -270,14 +1063,137 @@ impl CompleteCalendar for CachedCalendar {
         self.get_item_by_url_sync(url)
     }
 
+    async fn get_item_by_uid<'a>(&'a self, uid: &str) -> Option<&'a Item> {
+        self.get_item_by_uid_sync(uid)
+    }
+
     async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item> {
         self.get_item_by_url_mut_sync(url)
     }
 
+    async fn add_items(&mut self, items: Vec<Item>) -> Result<Vec<SyncStatus>, Box<dyn Error>> {
+        self.add_items_sync(items)
+    }
+
+    async fn delete_items(&mut self, item_ids: &[Url]) -> Result<(), Box<dyn Error>> {
+        self.delete_items_sync(item_ids)
+    }
+
+    async fn mark_completed(&mut self, item_ids: &[Url]) -> Result<(), Box<dyn Error>> {
+        self.mark_completed_sync(item_ids)
+    }
+
+    async fn get_unparsed_items<'a>(&'a self) -> Vec<&'a UnparsedItem> {
+        self.get_unparsed_items_sync()
+    }
+
+    async fn set_unparsed_items(&mut self, items: Vec<UnparsedItem>) -> Result<(), Box<dyn Error>> {
+        self.set_unparsed_items_sync(items);
+        Ok(())
+    }
+
+    async fn get_vanished_items<'a>(&'a self) -> Vec<&'a VanishedItem> {
+        self.get_vanished_items_sync()
+    }
+
+    async fn set_vanished_items(&mut self, items: Vec<VanishedItem>) -> Result<(), Box<dyn Error>> {
+        self.set_vanished_items_sync(items);
+        Ok(())
+    }
+
+    async fn get_synced_snapshot<'a>(&'a self, url: &Url) -> Option<&'a Item> {
+        self.get_synced_snapshot_sync(url)
+    }
+
+    async fn save_synced_snapshot(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        self.save_synced_snapshot_sync(url)
+    }
+
+    fn set_permissions(&mut self, permissions: Option<Permissions>) {
+        self.permissions = permissions;
+    }
+
+    fn set_metadata(&mut self, metadata: CalendarMetadata) {
+        self.metadata = metadata;
+    }
+
+    fn last_sync(&self) -> Option<DateTime<Utc>> {
+        self.last_sync
+    }
+
+    fn set_last_sync(&mut self, last_sync: DateTime<Utc>) {
+        self.last_sync = Some(last_sync);
+    }
+
     async fn mark_for_deletion(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
         self.mark_for_deletion_sync(item_url)
     }
 
+    async fn trash_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        self.trash_item_sync(item_id)
+    }
+
+    async fn restore_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        self.restore_item_sync(item_id)
+    }
+
+    fn trashed_at(&self, item_id: &Url) -> Option<DateTime<Utc>> {
+        self.trash.get(item_id).copied()
+    }
+
+    async fn purge_trash(&mut self, trashed_before: DateTime<Utc>) -> Result<Vec<Url>, Box<dyn Error>> {
+        self.purge_trash_sync(trashed_before)
+    }
+
+    fn sync_exclusion(&self, item_id: &Url) -> Option<SyncExclusion> {
+        match self.sync_exclusions.get(item_id) {
+            Some(exclusion) => Some(*exclusion),
+            None if self.archived_out_of_sync.contains(item_id) => Some(SyncExclusion::LocalOnly),
+            None => None,
+        }
+    }
+
+    fn set_sync_exclusion(&mut self, item_id: &Url, exclusion: Option<SyncExclusion>) -> Result<(), Box<dyn Error>> {
+        match exclusion {
+            None => self.sync_exclusions.remove(item_id),
+            Some(exclusion) => self.sync_exclusions.insert(item_id.clone(), exclusion),
+        };
+        Ok(())
+    }
+
+    fn calendar_sync_exclusion(&self) -> Option<SyncExclusion> {
+        self.sync_exclusion
+    }
+
+    fn set_calendar_sync_exclusion(&mut self, exclusion: Option<SyncExclusion>) -> Result<(), Box<dyn Error>> {
+        self.sync_exclusion = exclusion;
+        Ok(())
+    }
+
+    async fn count_items(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.count_items_sync())
+    }
+
+    async fn count_completed(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.count_completed_sync())
+    }
+
+    async fn count_due_before(&self, date: DateTime<Utc>) -> Result<usize, Box<dyn Error>> {
+        Ok(self.count_due_before_sync(date))
+    }
+
+    async fn archive_items(&mut self, policy: &ArchivePolicy) -> Result<Vec<Url>, Box<dyn Error>> {
+        self.archive_items_sync(policy)
+    }
+
+    async fn get_archived_items<'a>(&'a self) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        self.get_archived_items_sync()
+    }
+
+    async fn unarchive_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        self.unarchive_item_sync(item_id)
+    }
+
     async fn immediately_delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
         self.immediately_delete_item_sync(item_url)
     }
@@ -288,8 +1204,7 @@ impl CompleteCalendar for CachedCalendar {
 // This class can be used to mock a remote calendar for integration tests
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-use crate::{item::VersionTag,
-            traits::DavCalendar,
+use crate::{traits::DavCalendar,
             resource::Resource};
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
@@ -317,6 +1232,33 @@ impl DavCalendar for CachedCalendar {
             result.insert(url.clone(), vt);
         }
 
+        let max_listed_items = self.mock_behaviour.as_ref().and_then(|b| b.lock().unwrap().max_listed_items);
+        if let Some(max) = max_listed_items {
+            let mut urls: Vec<Url> = result.keys().cloned().collect();
+            urls.sort();
+            for url in urls.into_iter().skip(max) {
+                result.remove(&url);
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn get_version_tags_of(&self, urls: &[Url]) -> Result<HashMap<Url, Option<VersionTag>>, Box<dyn Error>> {
+        #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+        self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_item_version_tags())?;
+
+        use crate::item::SyncStatus;
+
+        let mut result = HashMap::new();
+        for url in urls {
+            let vt = match self.items.get(url).map(|item| item.sync_status()) {
+                None => None,
+                Some(SyncStatus::Synced(vt)) => Some(vt.clone()),
+                Some(ss) => panic!("Mock calendars must contain only SyncStatus::Synced. Got {:?}", ss),
+            };
+            result.insert(url.clone(), vt);
+        }
         Ok(result)
     }
 
@@ -0,0 +1,272 @@
+//! The items of a [`CachedCalendar`](crate::calendar::cached_calendar::CachedCalendar), whose content is only deserialized when they are first accessed
+//!
+//! Items are persisted as their [`ItemMetadata`] on one hand, and their serialized content (their "payload") on the other hand.
+//! When a cache is loaded, only the metadata is deserialized, so that opening a cache that contains lots of (mostly old) items is quick.
+//! Payloads are kept as raw JSON (see [`RawValue`]), and are embedded as they are in JSON caches.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{Error as _, MapAccess, Visitor};
+use serde::ser::{Error as _, SerializeMap};
+use serde_json::value::RawValue;
+use url::Url;
+
+use crate::item::{Item, ItemMetadata};
+use crate::utils::redact;
+
+/// A map of items, that are loaded on demand
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LazyItems {
+    items: HashMap<Url, LazyItem>,
+}
+
+#[derive(Clone, Debug)]
+enum LazyItem {
+    Loaded(Item),
+    Unloaded {
+        metadata: ItemMetadata,
+        payload: Payload,
+        /// The item, once it has been deserialized from its payload
+        item: OnceCell<Item>,
+    },
+}
+
+impl LazyItem {
+    fn get(&self, url: &Url) -> Option<&Item> {
+        match self {
+            LazyItem::Loaded(item) => Some(item),
+            LazyItem::Unloaded{ payload, item, .. } => {
+                item.get_or_try_init(|| serde_json::from_str(payload.0.get()))
+                    .map_err(|err| log::error!("Unable to load item {} from the cache: {}", redact(url), err))
+                    .ok()
+            },
+        }
+    }
+
+    fn get_mut(&mut self, url: &Url) -> Option<&mut Item> {
+        if let LazyItem::Unloaded{ payload, item, .. } = self {
+            let loaded = match item.take() {
+                Some(loaded) => loaded,
+                None => match serde_json::from_str(payload.0.get()) {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        log::error!("Unable to load item {} from the cache: {}", redact(url), err);
+                        return None;
+                    },
+                },
+            };
+            *self = LazyItem::Loaded(loaded);
+        }
+        match self {
+            LazyItem::Loaded(item) => Some(item),
+            LazyItem::Unloaded{ .. } => None,
+        }
+    }
+
+    fn metadata(&self) -> ItemMetadata {
+        match self {
+            LazyItem::Loaded(item) => ItemMetadata::from(item),
+            LazyItem::Unloaded{ metadata, .. } => metadata.clone(),
+        }
+    }
+
+    fn payload(&self) -> Result<Cow<'_, Payload>, serde_json::Error> {
+        match self {
+            LazyItem::Unloaded{ payload, .. } => Ok(Cow::Borrowed(payload)),
+            LazyItem::Loaded(item) => Ok(Cow::Owned(Payload(serde_json::value::to_raw_value(item)?))),
+        }
+    }
+}
+
+/// The serialized content of an item, as raw JSON.
+///
+/// Binary formats (see [`CacheFormat`](crate::cache::CacheFormat)) cannot embed raw JSON, so that payloads are stored as strings in these formats
+#[derive(Clone, Debug)]
+struct Payload(Box<RawValue>);
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => self.0.serialize(serializer),
+            false => serializer.serialize_str(self.0.get()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.is_human_readable() {
+            true => Ok(Payload(Box::<RawValue>::deserialize(deserializer)?)),
+            false => RawValue::from_string(String::deserialize(deserializer)?)
+                .map(Payload)
+                .map_err(D::Error::custom),
+        }
+    }
+}
+
+impl LazyItems {
+    pub fn contains_key(&self, url: &Url) -> bool {
+        self.items.contains_key(url)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Url> {
+        self.items.keys()
+    }
+
+    /// Get an item, and load it in case it has not been loaded yet
+    pub fn get(&self, url: &Url) -> Option<&Item> {
+        self.items.get(url).and_then(|entry| entry.get(url))
+    }
+
+    pub fn get_mut(&mut self, url: &Url) -> Option<&mut Item> {
+        self.items.get_mut(url).and_then(|entry| entry.get_mut(url))
+    }
+
+    /// Insert an item, and returns whether it replaced an existing one
+    pub fn insert(&mut self, url: Url, item: Item) -> bool {
+        self.items.insert(url, LazyItem::Loaded(item)).is_some()
+    }
+
+    /// Remove an item, and returns whether it existed
+    pub fn remove(&mut self, url: &Url) -> bool {
+        self.items.remove(url).is_some()
+    }
+
+    /// Iterate over every item. This loads all of them
+    pub fn iter(&self) -> impl Iterator<Item = (&Url, &Item)> {
+        self.items.iter()
+            .filter_map(|(url, entry)| entry.get(url).map(|item| (url, item)))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Item> {
+        self.iter().map(|(_url, item)| item)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Url, &mut Item)> {
+        self.items.iter_mut()
+            .filter_map(|(url, entry)| entry.get_mut(url).map(|item| (url, item)))
+    }
+
+    /// The metadata of an item. This does not load it
+    pub fn metadata_of(&self, url: &Url) -> Option<ItemMetadata> {
+        self.items.get(url).map(|entry| entry.metadata())
+    }
+
+    /// The metadata of every item. This does not load them
+    pub fn metadata(&self) -> HashMap<Url, ItemMetadata> {
+        self.items.iter()
+            .map(|(url, entry)| (url.clone(), entry.metadata()))
+            .collect()
+    }
+
+    /// The number of items that have been loaded
+    #[cfg(test)]
+    fn n_loaded(&self) -> usize {
+        self.items.values()
+            .filter(|entry| match entry {
+                LazyItem::Loaded(_) => true,
+                LazyItem::Unloaded{ item, .. } => item.get().is_some(),
+            })
+            .count()
+    }
+}
+
+impl Serialize for LazyItems {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let payloads = self.items.iter()
+            .map(|(url, entry)| Ok((url, entry.payload()?)))
+            .collect::<Result<HashMap<_, _>, serde_json::Error>>()
+            .map_err(S::Error::custom)?;
+
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("metadata", &self.metadata())?;
+        map.serialize_entry("payloads", &payloads)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LazyItems {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(LazyItemsVisitor)
+    }
+}
+
+struct LazyItemsVisitor;
+
+impl<'de> Visitor<'de> for LazyItemsVisitor {
+    type Value = LazyItems;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a map of items")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<LazyItems, A::Error> {
+        let mut metadata: HashMap<Url, ItemMetadata> = HashMap::new();
+        let mut payloads: HashMap<Url, Payload> = HashMap::new();
+        while let Some(key) = access.next_key::<String>()? {
+            match key.as_str() {
+                "metadata" => metadata = access.next_value()?,
+                "payloads" => payloads = access.next_value()?,
+                other => return Err(A::Error::unknown_field(other, &["metadata", "payloads"])),
+            }
+        }
+
+        let mut items = HashMap::new();
+        for (url, payload) in payloads {
+            let entry = match metadata.remove(&url) {
+                Some(metadata) => LazyItem::Unloaded{ metadata, payload, item: OnceCell::new() },
+                None => LazyItem::Loaded(serde_json::from_str(payload.0.get()).map_err(A::Error::custom)?),
+            };
+            items.insert(url, entry);
+        }
+        Ok(LazyItems{ items })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::SyncStatus;
+    use crate::task::Task;
+
+    #[test]
+    fn test_lazy_items() {
+        let calendar_url: Url = "https://some.calend.ar/cal/".parse().unwrap();
+        let mut items = LazyItems::default();
+        for name in ["first", "second", "third"] {
+            let task = Task::new(name.to_string(), false, &calendar_url);
+            items.insert(task.url().clone(), Item::Task(task));
+        }
+
+        let serialized = serde_json::to_string(&items).unwrap();
+        // Payloads are embedded as JSON, rather than as JSON strings
+        let payload = &serde_json::from_str::<serde_json::Value>(&serialized).unwrap()["payloads"][items.keys().next().unwrap().as_str()];
+        assert!(payload["Task"].is_object());
+        let loaded: LazyItems = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(loaded.n_loaded(), 0);
+        assert_eq!(loaded.metadata(), items.metadata());
+        assert!(loaded.metadata().values().all(|metadata| metadata.sync_status == SyncStatus::NotSynced));
+
+        let url = items.keys().next().unwrap().clone();
+        assert_eq!(loaded.get(&url).unwrap().uid(), items.get(&url).unwrap().uid());
+        assert_eq!(loaded.n_loaded(), 1);
+        assert_eq!(loaded.iter().count(), 3);
+        assert_eq!(loaded.n_loaded(), 3);
+
+        // Caches that are migrated are first read as a `Value` (see `Cache::load_calendar`)
+        let value: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+        let loaded: LazyItems = serde_json::from_value(value).unwrap();
+        assert_eq!(loaded.n_loaded(), 0);
+        assert_eq!(loaded.get(&url).unwrap().uid(), items.get(&url).unwrap().uid());
+
+        let cbor = serde_cbor::to_vec(&items).unwrap();
+        let loaded: LazyItems = serde_cbor::from_slice(&cbor).unwrap();
+        assert_eq!(loaded.n_loaded(), 0);
+        assert_eq!(loaded.get(&url).unwrap().uid(), items.get(&url).unwrap().uid());
+    }
+}
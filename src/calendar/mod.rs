@@ -2,14 +2,23 @@
 
 pub mod cached_calendar;
 pub mod remote_calendar;
+pub mod subscription_calendar;
+pub(crate) mod lazy_items;
 
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::error::Error;
 
 use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use ical::property::Property;
 
 use bitflags::bitflags;
 
+use crate::item::{Item, SyncStatus};
+use crate::task::CompletionStatus;
+use crate::utils::find_elem;
+
 bitflags! {
     #[derive(Serialize, Deserialize)]
     pub struct SupportedComponents: u8 {
@@ -60,6 +69,171 @@ impl TryFrom<minidom::Element> for SupportedComponents {
 }
 
 
+/// What the current user is allowed to do in a calendar, e.g. to disable editing in read-only calendars.
+///
+/// This is a simplification of the WebDAV ACL of the calendar (RFC 3744), as reported by its `current-user-privilege-set`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions {
+    /// Whether items can be read
+    pub read: bool,
+    /// Whether existing items can be modified
+    pub write_content: bool,
+    /// Whether items can be added
+    pub bind: bool,
+    /// Whether items can be deleted
+    pub unbind: bool,
+    /// Whether the calendar can be shared with other users (i.e. the `share` or `write-acl` privilege is granted)
+    pub share: bool,
+}
+
+impl Permissions {
+    /// Permissions that allow everything
+    pub fn all() -> Self {
+        Self { read: true, write_content: true, bind: true, unbind: true, share: true }
+    }
+
+    /// Whether the calendar is read-only
+    pub fn is_read_only(&self) -> bool {
+        !self.write_content && !self.bind && !self.unbind
+    }
+
+    /// Build the permissions from an XML `<current-user-privilege-set>` element
+    pub(crate) fn from_privilege_set(element: &minidom::Element) -> Self {
+        let mut permissions = Self::default();
+        let granted = element.children()
+            .filter(|privilege| privilege.name() == "privilege")
+            .flat_map(|privilege| privilege.children());
+        for privilege in granted {
+            match privilege.name() {
+                "all" => permissions = Self::all(),
+                "read" => permissions.read = true,
+                "write" => {
+                    permissions.write_content = true;
+                    permissions.bind = true;
+                    permissions.unbind = true;
+                },
+                "write-content" => permissions.write_content = true,
+                "bind" => permissions.bind = true,
+                "unbind" => permissions.unbind = true,
+                "share" | "write-acl" => permissions.share = true,
+                _ => (),
+            }
+        }
+        permissions
+    }
+}
+
+
+/// What a calendar tells about itself (RFC 7986), in the properties of its `VCALENDAR` or in its WebDAV properties.
+///
+/// Fields are `None` when the calendar does not tell them. Properties that are not standard, but that are common in published feeds
+/// (`X-WR-CALNAME`, `X-WR-CALDESC` and `X-PUBLISHED-TTL`) are used when the standard ones are missing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarMetadata {
+    /// The name the calendar gives itself (`NAME`). This may differ from its display name (see [`BaseCalendar::name`](crate::traits::BaseCalendar::name))
+    pub name: Option<String>,
+    /// A description of the calendar (`DESCRIPTION`, or the `calendar-description` WebDAV property)
+    pub description: Option<String>,
+    /// How often the calendar should be checked for updates (`REFRESH-INTERVAL`, or the `refreshrate` WebDAV property of subscriptions)
+    pub refresh_interval: Option<std::time::Duration>,
+    /// The URI of an image that represents the calendar (`IMAGE`). Images that are inlined as binary values are ignored
+    pub image: Option<String>,
+}
+
+impl CalendarMetadata {
+    /// Read the metadata from the properties of a `VCALENDAR`
+    pub(crate) fn from_properties(properties: &[Property]) -> Self {
+        let find = |names: &[&str]| -> Option<&Property> {
+            names.iter().find_map(|name| properties.iter().find(|prop| prop.name.eq_ignore_ascii_case(name)))
+        };
+        let text = |names: &[&str]| {
+            find(names)
+                .and_then(|prop| prop.value.as_deref())
+                .map(crate::ical::escape::unescape_text)
+                .filter(|text| !text.is_empty())
+        };
+        let is_binary = |prop: &Property| prop.params.iter().flatten()
+            .any(|(name, values)| values.iter().any(|value| value.eq_ignore_ascii_case("BINARY") || value.eq_ignore_ascii_case("BASE64"))
+                && (name.eq_ignore_ascii_case("VALUE") || name.eq_ignore_ascii_case("ENCODING")));
+
+        Self {
+            name: text(&["NAME", "X-WR-CALNAME"]),
+            description: text(&["DESCRIPTION", "X-WR-CALDESC"]),
+            refresh_interval: find(&["REFRESH-INTERVAL", "X-PUBLISHED-TTL"])
+                .and_then(|prop| prop.value.as_deref())
+                .and_then(crate::ical::parse_duration),
+            image: find(&["IMAGE"])
+                .filter(|prop| !is_binary(prop))
+                .and_then(|prop| prop.value.clone()),
+        }
+    }
+
+    /// Read the metadata from the WebDAV properties of a calendar, as returned by a `PROPFIND`
+    pub(crate) fn from_dav_properties(element: &minidom::Element) -> Self {
+        let text = |name: &str| find_elem(element, name)
+            .map(|el| el.text().trim().to_string())
+            .filter(|text| !text.is_empty());
+
+        Self {
+            description: text("calendar-description"),
+            refresh_interval: text("refreshrate").as_deref().and_then(crate::ical::parse_duration),
+            ..Self::default()
+        }
+    }
+}
+
+
+/// Keeps items or whole calendars out of syncs, in one direction (see [`CompleteCalendar::set_sync_exclusion`](crate::traits::CompleteCalendar::set_sync_exclusion))
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SyncExclusion {
+    /// Never uploaded (e.g. private scratch tasks). The server is left alone, and whatever happens to its copy (if any) is not applied locally either.
+    /// Local-only calendars are not created on the server
+    LocalOnly,
+    /// Never downloaded (e.g. huge archive calendars). Local copies (e.g. the ones downloaded before they were excluded) are removed
+    /// from the local source at the next sync, once their local changes (if any) have been uploaded
+    RemoteOnly,
+}
+
+
+/// Which items are moved to the archive of a calendar (see [`CompleteCalendar::archive_items`](crate::traits::CompleteCalendar::archive_items))
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArchivePolicy {
+    /// Tasks that have been completed for longer than this are archived. Tasks that have no completion date are dated by their last modification
+    pub completed_for: chrono::Duration,
+    /// Whether archived items are kept out of syncs, as if they were [`SyncExclusion::LocalOnly`].
+    /// Otherwise, they are still synced: they are deleted when they are deleted from the server, and taken out of the archive when they are changed on the server
+    pub exclude_from_sync: bool,
+}
+
+impl ArchivePolicy {
+    /// Archive the tasks that have been completed for longer than `completed_for`, and keep syncing them
+    pub fn completed_for(completed_for: chrono::Duration) -> Self {
+        Self { completed_for, exclude_from_sync: false }
+    }
+
+    /// Whether an item should be archived at `now`.
+    ///
+    /// Items that have local changes to sync are not archived (neither are items that have never been synced, unless archived items are kept out of syncs).
+    /// Events are not supported yet, and are never archived
+    pub fn matches(&self, item: &Item, now: DateTime<Utc>) -> bool {
+        let task = match item {
+            Item::Task(task) => task,
+            Item::Event(_) => return false,
+        };
+        let can_be_archived = match task.sync_status() {
+            SyncStatus::Synced(_) => true,
+            SyncStatus::NotSynced => self.exclude_from_sync,
+            SyncStatus::LocallyModified(_) | SyncStatus::LocallyDeleted(_) => false,
+        };
+        let completed_at = match task.completion_status() {
+            CompletionStatus::Uncompleted => return false,
+            CompletionStatus::Completed(date) => date.unwrap_or(*task.last_modified()),
+        };
+        can_be_archived && completed_at + self.completed_for <= now
+    }
+}
+
+
 /// Flags to tell which events should be retrieved
 pub enum SearchFilter {
     /// Return all items
@@ -77,3 +251,323 @@ impl Default for SearchFilter {
         SearchFilter::All
     }
 }
+
+
+/// The kind of an [`Item`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+    Event,
+    Task,
+}
+
+/// Criteria to select items of a calendar (see [`crate::traits::CompleteCalendar::get_items_matching`])
+///
+/// Every criterion that is set must match. The default filter matches every item.
+/// Criteria that only make sense for tasks (completion, due date, categories) never match events.
+#[derive(Clone, Debug, Default)]
+pub struct ItemFilter {
+    /// Only keep completed (`Some(true)`) or uncompleted (`Some(false)`) tasks
+    pub completed: Option<bool>,
+    /// Only keep tasks that are due strictly before this date
+    pub due_before: Option<DateTime<Utc>>,
+    /// Only keep tasks that have this category (case-insensitive)
+    pub category: Option<String>,
+    /// Only keep items whose name or description contains this text (case-insensitive)
+    pub text: Option<String>,
+    /// Only keep items of this kind
+    pub kind: Option<ItemKind>,
+}
+
+impl ItemFilter {
+    /// Returns whether an item matches every criterion of this filter
+    pub fn matches(&self, item: &Item) -> bool {
+        if let Some(kind) = self.kind {
+            let item_kind = match item {
+                Item::Event(_) => ItemKind::Event,
+                Item::Task(_) => ItemKind::Task,
+            };
+            if item_kind != kind {
+                return false;
+            }
+        }
+
+        if let Some(text) = &self.text {
+            let text = text.to_lowercase();
+            let in_description = match item {
+                Item::Task(t) => t.extra_parameter("DESCRIPTION").map(|d| d.to_lowercase().contains(&text)) == Some(true),
+                Item::Event(_) => false,
+            };
+            if !item.name().to_lowercase().contains(&text) && !in_description {
+                return false;
+            }
+        }
+
+        if self.completed.is_none() && self.due_before.is_none() && self.category.is_none() {
+            return true;
+        }
+        let task = match item {
+            Item::Task(t) => t,
+            Item::Event(_) => return false,
+        };
+
+        if let Some(completed) = self.completed {
+            if task.completed() != completed {
+                return false;
+            }
+        }
+
+        if let Some(due_before) = &self.due_before {
+            match task.due() {
+                Some(due) if &due < due_before => (),
+                _ => return false,
+            }
+        }
+
+        if let Some(category) = &self.category {
+            if !task.categories().iter().any(|c| c.eq_ignore_ascii_case(category)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+
+
+/// How to sort items (see [`crate::traits::CompleteCalendar::get_items_page`])
+///
+/// Items that compare equal are always sorted by URL, so that the order is stable across calls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Sort by URL only
+    #[default]
+    Url,
+    /// Alphabetical order of the names (case-insensitive)
+    Name,
+    /// Oldest items first. Items without a creation date come last
+    CreationDate,
+    /// Least recently modified items first
+    LastModified,
+    /// Tasks that are due first come first. Items without a due date come last
+    Due,
+    /// Tasks with the highest priority (1) first, and the lowest (9) last. Items without a priority come last
+    Priority,
+    /// The order the user has set by hand, i.e. the `X-APPLE-SORT-ORDER` property of tasks, that Apple Reminders and Nextcloud Tasks use as well.
+    ///
+    /// Like these clients, tasks without such a property are ordered by their creation date (see [`crate::task::Task::effective_sort_order`]).
+    /// Items that have neither come last
+    Manual,
+}
+
+impl SortKey {
+    /// Compare two items according to this key
+    pub fn compare(&self, a: &Item, b: &Item) -> Ordering {
+        let ordering = match self {
+            SortKey::Url => Ordering::Equal,
+            SortKey::Name => a.name().to_lowercase().cmp(&b.name().to_lowercase()),
+            SortKey::CreationDate => compare_options_last(a.creation_date(), b.creation_date()),
+            SortKey::LastModified => a.last_modified().cmp(b.last_modified()),
+            SortKey::Due => compare_options_last(due_date(a).as_ref(), due_date(b).as_ref()),
+            SortKey::Priority => compare_options_last(priority(a), priority(b)),
+            SortKey::Manual => compare_options_last(manual_order(a), manual_order(b)),
+        };
+        ordering.then_with(|| a.url().as_str().cmp(b.url().as_str()))
+    }
+}
+
+fn due_date(item: &Item) -> Option<DateTime<Utc>> {
+    match item {
+        Item::Task(t) => t.due(),
+        Item::Event(_) => None,
+    }
+}
+
+fn priority(item: &Item) -> Option<u8> {
+    match item {
+        Item::Task(t) => t.priority(),
+        Item::Event(_) => None,
+    }
+}
+
+/// The position of an item in the manual order (see [`SortKey::Manual`])
+fn manual_order(item: &Item) -> Option<i64> {
+    match item {
+        Item::Task(t) => t.effective_sort_order(),
+        Item::Event(_) => None,
+    }
+}
+
+/// Compare two options, `None` being greater than any `Some`
+fn compare_options_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// A page of items (see [`crate::traits::CompleteCalendar::get_items_page`])
+#[derive(Clone, Debug)]
+pub struct ItemPage<'a> {
+    /// The items of this page
+    pub items: Vec<&'a Item>,
+    /// The offset to request the next page, or `None` if this is the last page
+    pub next_offset: Option<usize>,
+}
+
+impl<'a> ItemPage<'a> {
+    /// Build a page out of items that are already sorted
+    pub fn from_sorted<I: Iterator<Item = &'a Item>>(sorted_items: I, offset: usize, limit: usize) -> Self {
+        let mut items: Vec<&Item> = sorted_items.skip(offset).take(limit.saturating_add(1)).collect();
+        let next_offset = match items.len() > limit {
+            true => {
+                items.truncate(limit);
+                Some(offset + limit)
+            },
+            false => None,
+        };
+        Self { items, next_offset }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::item::SyncStatus;
+    use crate::task::{CompletionStatus, Task};
+    use crate::test_utils::task_with;
+
+    #[test]
+    fn test_calendar_metadata() {
+        let feed = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Some//Feed//EN\r\n\
+            NAME:Bin collection\r\n\
+            X-WR-CALNAME:Ignored\r\n\
+            X-WR-CALDESC:Collection days\\, by street\r\n\
+            REFRESH-INTERVAL;VALUE=DURATION:P1DT12H\r\n\
+            IMAGE;VALUE=URI;DISPLAY=BADGE;FMTTYPE=image/png:https://example.com/bin.png\r\n\
+            END:VCALENDAR\r\n";
+        let metadata = crate::ical::parse_calendar_metadata(feed).unwrap();
+        assert_eq!(metadata, CalendarMetadata {
+            name: Some("Bin collection".to_string()),
+            description: Some("Collection days, by street".to_string()),
+            refresh_interval: Some(std::time::Duration::from_secs(36 * 3600)),
+            image: Some("https://example.com/bin.png".to_string()),
+        });
+
+        let binary_image = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nIMAGE;VALUE=BINARY;ENCODING=BASE64:iVBORw0KGgo=\r\nX-PUBLISHED-TTL:PT30M\r\nEND:VCALENDAR\r\n";
+        let metadata = crate::ical::parse_calendar_metadata(binary_image).unwrap();
+        assert_eq!(metadata.image, None);
+        assert_eq!(metadata.refresh_interval, Some(std::time::Duration::from_secs(30 * 60)));
+
+        assert_eq!(crate::ical::parse_duration("P2W"), Some(std::time::Duration::from_secs(14 * 24 * 3600)));
+        assert_eq!(crate::ical::parse_duration("-PT1H"), None);
+        assert_eq!(crate::ical::parse_duration("P1H"), None);
+        assert_eq!(crate::ical::parse_duration("PT"), None);
+
+        let propfind: minidom::Element = r#"<d:prop xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:a="http://apple.com/ns/ical/">
+                <c:calendar-description>Shared with the whole team</c:calendar-description>
+                <a:refreshrate>P1W</a:refreshrate>
+            </d:prop>"#.parse().unwrap();
+        let metadata = CalendarMetadata::from_dav_properties(&propfind);
+        assert_eq!(metadata.description.as_deref(), Some("Shared with the whole team"));
+        assert_eq!(metadata.refresh_interval, Some(std::time::Duration::from_secs(7 * 24 * 3600)));
+        assert_eq!(metadata.name, None);
+    }
+
+    #[test]
+    fn test_item_filter() {
+        let groceries = task_with("Buy groceries", false, &[("DUE", "20211103T220000"), ("CATEGORIES", "Home,Errands")]);
+        let report = task_with("Write report", true, &[("DUE", "20211110"), ("DESCRIPTION", "Quarterly numbers")]);
+        let nothing = task_with("Do nothing", false, &[]);
+
+        assert!(ItemFilter::default().matches(&groceries));
+        assert!(ItemFilter { kind: Some(ItemKind::Task), ..Default::default() }.matches(&groceries));
+        assert!(!ItemFilter { kind: Some(ItemKind::Event), ..Default::default() }.matches(&groceries));
+
+        let uncompleted = ItemFilter { completed: Some(false), ..Default::default() };
+        assert!(uncompleted.matches(&groceries));
+        assert!(!uncompleted.matches(&report));
+
+        let due_soon = ItemFilter { due_before: Some(Utc.ymd(2021, 11, 5).and_hms(0, 0, 0)), ..Default::default() };
+        assert!(due_soon.matches(&groceries));
+        assert!(!due_soon.matches(&report));
+        assert!(!due_soon.matches(&nothing));
+
+        let errands = ItemFilter { category: Some("errands".to_string()), ..Default::default() };
+        assert!(errands.matches(&groceries));
+        assert!(!errands.matches(&report));
+
+        let quarterly = ItemFilter { text: Some("QUARTERLY".to_string()), ..Default::default() };
+        assert!(quarterly.matches(&report));
+        assert!(!quarterly.matches(&groceries));
+        let by_name = ItemFilter { text: Some("groceries".to_string()), completed: Some(false), ..Default::default() };
+        assert!(by_name.matches(&groceries));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let privileges: minidom::Element = r#"<d:current-user-privilege-set xmlns:d="DAV:">
+            <d:privilege><d:read/></d:privilege>
+            <d:privilege><d:write-content/></d:privilege>
+            <d:privilege><d:read-current-user-privilege-set/></d:privilege>
+        </d:current-user-privilege-set>"#.parse().unwrap();
+        let permissions = Permissions::from_privilege_set(&privileges);
+        assert_eq!(permissions, Permissions{ read: true, write_content: true, ..Permissions::default() });
+        assert!(!permissions.is_read_only());
+
+        let privileges: minidom::Element = r#"<d:current-user-privilege-set xmlns:d="DAV:">
+            <d:privilege><d:all/></d:privilege>
+        </d:current-user-privilege-set>"#.parse().unwrap();
+        assert_eq!(Permissions::from_privilege_set(&privileges), Permissions::all());
+    }
+
+    #[test]
+    fn test_item_pages() {
+        let groceries = task_with("Buy groceries", false, &[("DUE", "20211103T220000")]);
+        let report = task_with("Write report", true, &[("DUE", "20211110")]);
+        let nothing = task_with("Do nothing", false, &[]);
+        let mut items = [&nothing, &report, &groceries];
+
+        items.sort_by(|a, b| SortKey::Due.compare(a, b));
+        let names: Vec<&str> = items.iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["Buy groceries", "Write report", "Do nothing"]);
+
+        items.sort_by(|a, b| SortKey::Name.compare(a, b));
+        let first_page = ItemPage::from_sorted(items.iter().cloned(), 0, 2);
+        assert_eq!(first_page.items.iter().map(|i| i.name()).collect::<Vec<_>>(), vec!["Buy groceries", "Do nothing"]);
+        assert_eq!(first_page.next_offset, Some(2));
+
+        let last_page = ItemPage::from_sorted(items.iter().cloned(), 2, 2);
+        assert_eq!(last_page.items.iter().map(|i| i.name()).collect::<Vec<_>>(), vec!["Write report"]);
+        assert_eq!(last_page.next_offset, None);
+
+        let exact_page = ItemPage::from_sorted(items.iter().cloned(), 0, 3);
+        assert_eq!(exact_page.items.len(), 3);
+        assert_eq!(exact_page.next_offset, None);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let urgent = task_with("Urgent", false, &[("PRIORITY", "1"), ("X-APPLE-SORT-ORDER", "700000000")]);
+        let low = task_with("Low", false, &[("PRIORITY", "9"), ("X-APPLE-SORT-ORDER", "-5")]);
+        let undefined = task_with("Undefined", false, &[("PRIORITY", "0")]);
+        // A task that has never been reordered is where it has been created, i.e. 2021-11-03 is 657590400 seconds after 2001-01-01
+        let created = Item::Task(Task::new_with_parameters("Created".to_string(), "created".to_string(), "https://some.calend.ar/cal/Created.ics".parse().unwrap(),
+            CompletionStatus::Uncompleted, SyncStatus::NotSynced, Some(Utc.ymd(2021, 11, 3).and_hms(0, 0, 0)), Utc::now(), "prod_id".to_string(), Vec::new()));
+        let mut items = [&undefined, &created, &low, &urgent];
+
+        items.sort_by(|a, b| SortKey::Priority.compare(a, b));
+        let names: Vec<&str> = items.iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["Urgent", "Low", "Created", "Undefined"]);
+
+        items.sort_by(|a, b| SortKey::Manual.compare(a, b));
+        let names: Vec<&str> = items.iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["Low", "Created", "Urgent", "Undefined"]);
+    }
+}
@@ -1,20 +1,28 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::sync::Mutex;
 
 use async_trait::async_trait;
-use reqwest::{header::CONTENT_TYPE, header::CONTENT_LENGTH};
+use reqwest::{Method, StatusCode, header::CONTENT_TYPE, header::CONTENT_LENGTH, header::ETAG, header::LOCATION};
 use csscolorparser::Color;
 use url::Url;
+use minidom::Element;
 
 use crate::traits::BaseCalendar;
 use crate::traits::DavCalendar;
-use crate::calendar::SupportedComponents;
+use crate::calendar::{CalendarMetadata, Permissions, SupportedComponents};
 use crate::item::Item;
 use crate::item::VersionTag;
 use crate::item::SyncStatus;
-use crate::resource::Resource;
-use crate::utils::find_elem;
+use crate::item::UnparsedItem;
+use crate::id::ItemId;
+use crate::resource::{Resource, redact_url};
+use crate::client::ServerFlavour;
+use crate::client::QuotaExceeded;
+use crate::capabilities::ServerCapabilities;
+use crate::sharing::CalendarSharing;
+use crate::utils::{find_elem, redact};
 
 static TASKS_BODY: &str = r#"
     <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
@@ -29,16 +37,39 @@ static TASKS_BODY: &str = r#"
     </c:calendar-query>
 "#;
 
+static ETAGS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+        <d:prop>
+            <d:resourcetype />
+            <d:getetag />
+        </d:prop>
+    </d:propfind>
+"#;
+
 static MULTIGET_BODY_PREFIX: &str = r#"
     <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
         <d:prop>
             <c:calendar-data />
         </d:prop>
 "#;
+static MULTIGET_ETAGS_BODY_PREFIX: &str = r#"
+    <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+        <d:prop>
+            <d:getetag />
+        </d:prop>
+"#;
 static MULTIGET_BODY_SUFFIX: &str = r#"
     </c:calendar-multiget>
 "#;
 
+static ADD_MEMBER_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+        <d:prop>
+            <d:add-member />
+        </d:prop>
+    </d:propfind>
+"#;
+
 
 
 /// A CalDAV calendar created by a [`Client`](crate::client::Client).
@@ -50,6 +81,16 @@ pub struct RemoteCalendar {
     color: Option<Color>,
 
     cached_version_tags: Mutex<Option<HashMap<Url, VersionTag>>>,
+    /// Items that have been skipped by `get_items_by_url` because they could not be parsed
+    unparsed_items: Mutex<Vec<UnparsedItem>>,
+    /// The URL new items can be `POST`ed to (RFC 5995), once it has been discovered (`Some(None)` if the server does not support it)
+    add_member_url: Mutex<Option<Option<Url>>>,
+    sharing: CalendarSharing,
+    permissions: Option<Permissions>,
+    metadata: CalendarMetadata,
+    flavour: ServerFlavour,
+    /// What the server supports, which tells what requests can be sent. Everything is assumed to be supported until they are known
+    capabilities: ServerCapabilities,
 }
 
 #[async_trait]
@@ -62,35 +103,22 @@ impl BaseCalendar for RemoteCalendar {
     fn color(&self) -> Option<&Color> {
         self.color.as_ref()
     }
+    fn permissions(&self) -> Option<Permissions> {
+        self.permissions
+    }
+    fn metadata(&self) -> CalendarMetadata {
+        self.metadata.clone()
+    }
 
     async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
-        let ical_text = crate::ical::build_from(&item)?;
-
-        let response = reqwest::Client::new()
-            .put(item.url().clone())
-            .header("If-None-Match", "*")
-            .header(CONTENT_TYPE, "text/calendar")
-            .header(CONTENT_LENGTH, ical_text.len())
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(ical_text)
-            .send()
-            .await?;
-
-        if response.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
-        }
-
-        let reply_hdrs = response.headers();
-        match reply_hdrs.get("ETag") {
-            None => Err(format!("No ETag in these response headers: {:?} (request was {:?})", reply_hdrs, item.url()).into()),
-            Some(etag) => {
-                let vtag_str = etag.to_str()?;
-                let vtag = VersionTag::from(String::from(vtag_str));
-                Ok(SyncStatus::Synced(vtag))
-            }
+        let stored = self.add_item_and_fetch(item.clone()).await?;
+        if stored.url() != item.url() {
+            log::warn!("Item {} has been stored at {} by the server", redact(item.url()), redact(stored.url()));
         }
+        Ok(stored.sync_status().clone())
     }
 
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(item = %redact(redact_url(item.url()))))]
     async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
         let old_etag = match item.sync_status() {
             SyncStatus::NotSynced => return Err("Cannot update an item that has not been synced already".into()),
@@ -98,31 +126,39 @@ impl BaseCalendar for RemoteCalendar {
             SyncStatus::LocallyModified(etag) => etag,
             SyncStatus::LocallyDeleted(etag) => etag,
         };
-        let ical_text = crate::ical::build_from(&item)?;
+        let ical_text = upload_text(&item)?;
 
-        let request = reqwest::Client::new()
-            .put(item.url().clone())
+        let request = prefer_minimal_reply(self.resource.http_client().put(item.url().clone()))
             .header("If-Match", old_etag.as_str())
             .header(CONTENT_TYPE, "text/calendar")
             .header(CONTENT_LENGTH, ical_text.len())
             .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(ical_text)
-            .send()
-            .await?;
+            .body(ical_text);
+        let response = self.resource.send(request).await?;
 
-        if request.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", request.status()).into());
+        if response.status() == StatusCode::INSUFFICIENT_STORAGE {
+            return Err(QuotaExceeded.into());
         }
-
-        let reply_hdrs = request.headers();
-        match reply_hdrs.get("ETag") {
-            None => Err(format!("No ETag in these response headers: {:?} (request was {:?})", reply_hdrs, item.url()).into()),
-            Some(etag) => {
-                let vtag_str = etag.to_str()?;
-                let vtag = VersionTag::from(String::from(vtag_str));
-                Ok(SyncStatus::Synced(vtag))
-            }
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
         }
+
+        // Servers are not required to return the new ETag (e.g. when they modify the item they have received)
+        let vtag = match response.headers().get(ETAG) {
+            Some(etag) => Some(VersionTag::from(String::from(etag.to_str()?))),
+            None => self.fetch_version_tag(item.url()).await.unwrap_or_else(|err| {
+                // The item has been uploaded anyway, this must not be reported as a failure
+                log::warn!("Unable to get the ETag of updated item {}: {}", redact(item.url()), err);
+                None
+            }),
+        };
+        let vtag = vtag.unwrap_or_else(|| {
+            // An unknown version tag will make the next sync download this item again, which is harmless
+            log::warn!("No ETag for updated item {}", redact(item.url()));
+            VersionTag::from(String::new())
+        });
+        self.cache_version_tag(item.url(), &vtag);
+        Ok(SyncStatus::Synced(vtag))
     }
 }
 
@@ -132,63 +168,53 @@ impl DavCalendar for RemoteCalendar {
         Self {
             name, resource, supported_components, color,
             cached_version_tags: Mutex::new(None),
+            unparsed_items: Mutex::new(Vec::new()),
+            add_member_url: Mutex::new(None),
+            sharing: CalendarSharing::default(),
+            permissions: None,
+            metadata: CalendarMetadata::default(),
+            flavour: ServerFlavour::Standard,
+            capabilities: ServerCapabilities::default(),
         }
     }
 
 
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(calendar = %redact(redact_url(self.resource.url()))))]
     async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
         if let Some(map) = &*self.cached_version_tags.lock().unwrap() {
             log::debug!("Version tags are already cached.");
             return Ok(map.clone());
         };
 
-        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", TASKS_BODY.to_string(), "response").await?;
-
-        let mut items = HashMap::new();
-        for response in responses {
-            let item_url = crate::utils::find_elem(&response, "href")
-                .map(|elem| self.resource.combine(&elem.text()));
-            let item_url = match item_url {
-                None => {
-                    log::warn!("Unable to extract HREF");
-                    continue;
-                },
-                Some(resource) => {
-                    resource.url().clone()
-                },
-            };
-
-            let version_tag = match crate::utils::find_elem(&response, "getetag") {
-                None => {
-                    log::warn!("Unable to extract ETAG for item {}, ignoring it", item_url);
-                    continue;
-                },
-                Some(etag) => {
-                    VersionTag::from(etag.text())
-                }
-            };
-
-            items.insert(item_url.clone(), version_tag);
-        }
+        // iCloud rejects some calendar queries. Its calendars are either to-do lists or event calendars, so that the items of to-do lists can simply be listed.
+        // This is also the only way to list the items of servers that do not support reports
+        let icloud_todos = self.flavour == ServerFlavour::ICloud && self.supported_components == SupportedComponents::TODO;
+        let responses = if icloud_todos || !self.capabilities.supports_multiget() {
+            crate::client::sub_request_and_extract_elems(&self.resource, "PROPFIND", ETAGS_BODY.to_string(), "response").await?
+        } else {
+            crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", TASKS_BODY.to_string(), "response").await?
+        };
+        let items = version_tags_from_responses(&self.resource, &responses);
 
         // Note: the mutex cannot be locked during this whole async function, but it can safely be re-entrant (this will just waste an unnecessary request)
         *self.cached_version_tags.lock().unwrap() = Some(items.clone());
         Ok(items)
     }
 
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(item = %redact(redact_url(url))))]
     async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
-        let res = reqwest::Client::new()
+        let request = self.resource.http_client()
             .get(url.clone())
             .header(CONTENT_TYPE, "text/calendar")
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .send()
-            .await?;
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let res = self.resource.send(request).await?;
 
         if res.status().is_success() == false {
             return Err(format!("Unexpected HTTP status code {:?}", res.status()).into());
         }
 
         let text = res.text().await?;
+        self.resource.counters().record_received(text.len());
 
         // This is supposed to be cached
         let version_tags = self.get_item_version_tags().await?;
@@ -201,6 +227,29 @@ impl DavCalendar for RemoteCalendar {
         Ok(Some(item))
     }
 
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(calendar = %redact(redact_url(self.resource.url())), count = urls.len()))]
+    async fn get_version_tags_of(&self, urls: &[Url]) -> Result<HashMap<Url, Option<VersionTag>>, Box<dyn Error>> {
+        if !self.capabilities.supports_multiget() {
+            return self.fetch_version_tags_one_by_one(urls).await;
+        }
+        let mut hrefs = String::new();
+        for url in urls {
+            hrefs.push_str(&format!("        <d:href>{}</d:href>\n", url.path()));
+        }
+        let body = format!("{}{}{}", MULTIGET_ETAGS_BODY_PREFIX, hrefs, MULTIGET_BODY_SUFFIX);
+        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", body, "response").await?;
+
+        let tags = confirmed_version_tags(&self.resource, &responses);
+        for (url, tag) in tags.iter() {
+            if let Some(tag) = tag {
+                // So that items that were missing from the listing can be downloaded
+                self.cache_version_tag(url, tag);
+            }
+        }
+        Ok(tags)
+    }
+
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(calendar = %redact(redact_url(self.resource.url())), count = urls.len()))]
     async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
         // Build the request body
         let mut hrefs = String::new();
@@ -219,8 +268,7 @@ impl DavCalendar for RemoteCalendar {
         let mut results = Vec::new();
         for xml_reply in xml_replies {
             let href = find_elem(&xml_reply, "href").ok_or("Missing HREF")?.text();
-            let mut url = self.resource.url().clone();
-            url.set_path(&href);
+            let url = normalize_item_url(self.resource.resolve_href(&href).url().clone());
             let ical_data = find_elem(&xml_reply, "calendar-data").ok_or("Missing calendar-data")?.text();
 
             let vt = match version_tags.get(&url) {
@@ -228,19 +276,113 @@ impl DavCalendar for RemoteCalendar {
                 Some(vt) => vt,
             };
 
-            let item = crate::ical::parse(&ical_data, url.clone(), SyncStatus::Synced(vt.clone()))?;
-            results.push(Some(item));
+            match crate::ical::parse(&ical_data, url.clone(), SyncStatus::Synced(vt.clone())) {
+                Ok(item) => results.push(Some(item)),
+                Err(err) => {
+                    // A malformed item should not prevent the other ones from being synced
+                    log::warn!("Unable to parse item {}: {}. Skipping it", redact(&url), err);
+                    self.unparsed_items.lock().unwrap().push(UnparsedItem{
+                        url, version_tag: vt.clone(), error: err.to_string(), raw: ical_data,
+                    });
+                },
+            }
         }
 
         Ok(results)
     }
 
+    fn take_unparsed_items(&self) -> Vec<UnparsedItem> {
+        std::mem::take(&mut *self.unparsed_items.lock().unwrap())
+    }
+
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(item = %redact(redact_url(item_url)), destination = %redact(redact_url(destination))))]
+    async fn move_item(&mut self, item_url: &Url, destination: &Url) -> Result<SyncStatus, Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .request(Method::from_bytes(b"MOVE").unwrap(), item_url.clone())
+            .header("Destination", destination.as_str())
+            .header("Overwrite", "F")
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let move_response = self.resource.send(request).await?;
+
+        if !move_response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", move_response.status()).into());
+        }
+        if let Some(cached_tags) = &mut *self.cached_version_tags.lock().unwrap() {
+            cached_tags.remove(item_url);
+        }
+
+        // MOVE responses usually do not include the ETag of the moved resource
+        // The destination belongs to another calendar, so that its version tag is not cached by this one
+        match self.fetch_version_tag(destination).await? {
+            Some(vtag) => Ok(SyncStatus::Synced(vtag)),
+            None => {
+                // An unknown version tag will make the next sync download this item again, which is harmless
+                log::warn!("No ETag for moved item {}", redact(destination));
+                Ok(SyncStatus::Synced(VersionTag::from(String::new())))
+            },
+        }
+    }
+
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(item = %redact(redact_url(item.url()))))]
+    async fn add_item_and_fetch(&mut self, item: Item) -> Result<Item, Box<dyn Error>> {
+        let ical_text = upload_text(&item)?;
+
+        let mut response = self.put_new_item(item.url(), &ical_text).await?;
+        if response.url() != item.url() && response.status() != StatusCode::CREATED && response.status() != StatusCode::NO_CONTENT {
+            // 301 and 302 redirections are followed with a GET request, so the item has to be sent again to its new location
+            let redirected_url = response.url().clone();
+            log::debug!("Item {} has been redirected to {}", redact(item.url()), redact(&redirected_url));
+            response = self.put_new_item(&redirected_url, &ical_text).await?;
+        }
+
+        if response.status() == StatusCode::INSUFFICIENT_STORAGE {
+            return Err(QuotaExceeded.into());
+        }
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let stored_url = created_item_url(&response)?;
+        let etag = response.headers().get(ETAG).map(|etag| etag.to_str().map(String::from)).transpose()?;
+        self.stored_item(item, stored_url, etag).await
+    }
+
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(calendar = %redact(redact_url(self.resource.url()))))]
+    async fn add_item_at_server_url(&mut self, item: Item) -> Result<Option<Item>, Box<dyn Error>> {
+        let add_member_url = match self.get_add_member_url().await? {
+            None => return Ok(None),
+            Some(url) => url,
+        };
+        let ical_text = upload_text(&item)?;
+
+        let request = prefer_minimal_reply(self.resource.http_client().post(add_member_url))
+            .header(CONTENT_TYPE, "text/calendar")
+            .header(CONTENT_LENGTH, ical_text.len())
+            .basic_auth(self.resource.username(), Some(self.resource.password()))
+            .body(ical_text);
+        let response = self.resource.send(request).await?;
+
+        if response.status() == StatusCode::INSUFFICIENT_STORAGE {
+            return Err(QuotaExceeded.into());
+        }
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        if !response.headers().contains_key(LOCATION) {
+            return Err(format!("No Location in these response headers: {:?}", response.headers()).into());
+        }
+        let stored_url = created_item_url(&response)?;
+        // The item is always downloaded again, since its new URL is different from the one it has been sent with
+        self.stored_item(item, stored_url, None).await.map(Some)
+    }
+
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(item = %redact(redact_url(item_url))))]
     async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        let del_response = reqwest::Client::new()
+        let request = self.resource.http_client()
             .delete(item_url.clone())
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .send()
-            .await?;
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let del_response = self.resource.send(request).await?;
 
         if del_response.status().is_success() == false {
             return Err(format!("Unexpected HTTP status code {:?}", del_response.status()).into());
@@ -250,3 +392,352 @@ impl DavCalendar for RemoteCalendar {
     }
 }
 
+impl RemoteCalendar {
+    /// Who shared this calendar, and who it is shared with, as it was when this calendar has been discovered (see [`Client::sharees`](crate::client::Client::sharees) to refresh it)
+    pub fn sharing(&self) -> &CalendarSharing {
+        &self.sharing
+    }
+
+    pub(crate) fn set_sharing(&mut self, sharing: CalendarSharing) {
+        self.sharing = sharing;
+    }
+
+    pub(crate) fn set_permissions(&mut self, permissions: Option<Permissions>) {
+        self.permissions = permissions;
+    }
+
+    pub(crate) fn set_metadata(&mut self, metadata: CalendarMetadata) {
+        self.metadata = metadata;
+    }
+
+    pub(crate) fn set_server_flavour(&mut self, flavour: ServerFlavour) {
+        self.flavour = flavour;
+    }
+
+    pub(crate) fn set_capabilities(&mut self, capabilities: ServerCapabilities) {
+        self.capabilities = capabilities;
+    }
+
+    async fn put_new_item(&self, url: &Url, ical_text: &str) -> Result<reqwest::Response, Box<dyn Error>> {
+        let request = prefer_minimal_reply(self.resource.http_client().put(url.clone()))
+            .header("If-None-Match", "*")
+            .header(CONTENT_TYPE, "text/calendar")
+            .header(CONTENT_LENGTH, ical_text.len())
+            .basic_auth(self.resource.username(), Some(self.resource.password()))
+            .body(ical_text.to_string());
+        Ok(self.resource.send(request).await?)
+    }
+
+    /// Returns the version of a newly created item that is stored on the server.
+    ///
+    /// Servers may store the item at another URL than the requested one, or modify it (e.g. change its UID), in which case they do not return any ETag (RFC 4791 section 5.3.4).
+    /// The item is then downloaded again, so that the local cache matches the server.
+    async fn stored_item(&self, mut item: Item, stored_url: Url, etag: Option<String>) -> Result<Item, Box<dyn Error>> {
+        if let (true, Some(etag)) = (&stored_url == item.url(), &etag) {
+            let version_tag = VersionTag::from(etag.clone());
+            self.cache_version_tag(&stored_url, &version_tag);
+            item.set_sync_status(SyncStatus::Synced(version_tag));
+            return Ok(item);
+        }
+
+        log::debug!("Downloading the version of item {} that is stored on the server", redact(&stored_url));
+        let request = self.resource.http_client()
+            .get(stored_url.clone())
+            .header(CONTENT_TYPE, "text/calendar")
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let etag = match response.headers().get(ETAG) {
+            Some(etag) => String::from(etag.to_str()?),
+            None => {
+                // An unknown version tag will make the next sync download this item again, which is harmless
+                log::warn!("No ETag for created item {}", redact(&stored_url));
+                String::new()
+            },
+        };
+        let text = response.text().await?;
+        self.resource.counters().record_received(text.len());
+
+        let version_tag = VersionTag::from(etag);
+        self.cache_version_tag(&stored_url, &version_tag);
+        crate::ical::parse(&text, stored_url, SyncStatus::Synced(version_tag))
+    }
+
+    /// Returns the current version tag of an item, using a `HEAD` request, or a `GET` request for servers that do not return it to `HEAD` requests
+    /// The version tags of some items, for servers that do not support `calendar-multiget` reports (see [`DavCalendar::get_version_tags_of`]).
+    /// Every item is requested on its own
+    async fn fetch_version_tags_one_by_one(&self, urls: &[Url]) -> Result<HashMap<Url, Option<VersionTag>>, Box<dyn Error>> {
+        let mut tags = HashMap::new();
+        for url in urls {
+            let request = self.resource.http_client()
+                .head(url.clone())
+                .basic_auth(self.resource.username(), Some(self.resource.password()));
+            let response = self.resource.send(request).await?;
+            if response.status() == StatusCode::NOT_FOUND || response.status() == StatusCode::GONE {
+                tags.insert(url.clone(), None);
+                continue;
+            }
+            let etag = match response.headers().get(ETAG) {
+                Some(etag) if response.status().is_success() => Some(VersionTag::from(String::from(etag.to_str()?))),
+                _ => self.fetch_version_tag(url).await?,
+            };
+            match etag {
+                Some(etag) => {
+                    // So that items that were missing from the listing can be downloaded
+                    self.cache_version_tag(url, &etag);
+                    tags.insert(url.clone(), Some(etag));
+                },
+                None => log::warn!("No ETag for item {}, ignoring it", redact(url)),
+            }
+        }
+        Ok(tags)
+    }
+
+    async fn fetch_version_tag(&self, url: &Url) -> Result<Option<VersionTag>, Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .head(url.clone())
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let head_response = self.resource.send(request).await?;
+        if head_response.status().is_success() {
+            if let Some(etag) = head_response.headers().get(ETAG) {
+                return Ok(Some(VersionTag::from(String::from(etag.to_str()?))));
+            }
+        }
+
+        log::debug!("No ETag in the HEAD response for {}, trying a GET request", redact(url));
+        let request = self.resource.http_client()
+            .get(url.clone())
+            .header(CONTENT_TYPE, "text/calendar")
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let get_response = self.resource.send(request).await?;
+        if !get_response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", get_response.status()).into());
+        }
+        match get_response.headers().get(ETAG) {
+            None => Ok(None),
+            Some(etag) => Ok(Some(VersionTag::from(String::from(etag.to_str()?)))),
+        }
+    }
+
+    /// Remember the version tag of an item that has just been uploaded, so that the next [`DavCalendar::get_item_version_tags`] does not return a stale one
+    fn cache_version_tag(&self, url: &Url, version_tag: &VersionTag) {
+        if let Some(cached_tags) = &mut *self.cached_version_tags.lock().unwrap() {
+            cached_tags.insert(url.clone(), version_tag.clone());
+        }
+    }
+
+    /// Returns the URL new items can be `POST`ed to, if the server supports it (RFC 5995)
+    async fn get_add_member_url(&self) -> Result<Option<Url>, Box<dyn Error>> {
+        if let Some(cached) = &*self.add_member_url.lock().unwrap() {
+            return Ok(cached.clone());
+        }
+
+        let text = crate::client::sub_request(&self.resource, "PROPFIND", ADD_MEMBER_BODY.to_string(), 0).await?;
+        let root: Element = text.parse()?;
+        let href = find_elem(&root, "add-member")
+            .and_then(|add_member| find_elem(add_member, "href"))
+            .map(|href| href.text())
+            .filter(|href| !href.is_empty());
+        let add_member_url = match href {
+            Some(href) => Some(self.resource.url().join(&href)?),
+            None => {
+                log::debug!("Calendar {} does not support server-assigned URLs", redact(self.resource.url()));
+                None
+            },
+        };
+        *self.add_member_url.lock().unwrap() = Some(add_member_url.clone());
+        Ok(add_member_url)
+    }
+}
+
+/// The URLs and version tags of the items listed in the replies to a request on a calendar (either a `calendar-query` `REPORT`, or a `PROPFIND`)
+fn version_tags_from_responses(calendar: &Resource, responses: &[Element]) -> HashMap<Url, VersionTag> {
+    let mut items = HashMap::new();
+    for response in responses {
+        // A PROPFIND also lists the collection itself
+        let is_collection = find_elem(response, "resourcetype")
+            .map(|rt| rt.children().any(|child| child.name() == "collection"))
+            .unwrap_or(false);
+        if is_collection {
+            continue;
+        }
+
+        let item_url = crate::utils::find_elem(response, "href")
+            .map(|elem| calendar.resolve_href(&elem.text()));
+        let item_url = match item_url {
+            None => {
+                log::warn!("Unable to extract HREF");
+                continue;
+            },
+            Some(resource) => {
+                normalize_item_url(resource.url().clone())
+            },
+        };
+
+        let version_tag = match crate::utils::find_elem(response, "getetag") {
+            None => {
+                log::warn!("Unable to extract ETAG for item {}, ignoring it", redact(&item_url));
+                continue;
+            },
+            Some(etag) => {
+                VersionTag::from(etag.text())
+            }
+        };
+
+        items.insert(item_url.clone(), version_tag);
+    }
+    items
+}
+
+/// Extract the version tags of the items of a multiget reply, and `None` for the items the server replied do not exist.
+/// Items the reply says nothing conclusive about are left out
+fn confirmed_version_tags(calendar: &Resource, responses: &[Element]) -> HashMap<Url, Option<VersionTag>> {
+    let mut tags = HashMap::new();
+    for response in responses {
+        let item_url = match find_elem(response, "href") {
+            None => {
+                log::warn!("Unable to extract HREF");
+                continue;
+            },
+            Some(href) => normalize_item_url(calendar.resolve_href(&href.text()).url().clone()),
+        };
+
+        let etag = find_elem(response, "getetag")
+            .map(|etag| etag.text())
+            .filter(|etag| !etag.trim().is_empty());
+        if let Some(etag) = etag {
+            tags.insert(item_url, Some(VersionTag::from(etag)));
+            continue;
+        }
+
+        let status = find_elem(response, "status").map(|status| status.text()).unwrap_or_default();
+        if status.contains(" 404 ") || status.contains(" 410 ") {
+            tags.insert(item_url, None);
+        } else {
+            log::warn!("Unexpected reply about item {} ({:?}), ignoring it", redact(&item_url), status);
+        }
+    }
+    tags
+}
+
+/// Servers may percent-encode hrefs differently than the URLs items have been created with. Normalizing them prevents the same item
+/// from being seen under two URLs
+fn normalize_item_url(url: Url) -> Url {
+    match ItemId::try_from(url.clone()) {
+        Ok(id) => id.into_url(),
+        Err(_) => url,
+    }
+}
+
+/// Ask the server not to send anything but a status and headers in reply to a write, with a `Prefer: return=minimal` header (RFC 7240),
+/// and the older `Brief` header, that some servers (e.g. SabreDAV) only understand.
+///
+/// Servers that do not support them just ignore them. Replies to writes are never read anyway, only their status and `ETag` and `Location` headers are used
+/// (a `204 No Content` reply, with a `Preference-Applied: return=minimal` header, is a success like the others)
+fn prefer_minimal_reply(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    request
+        .header("Prefer", "return=minimal")
+        .header("Brief", "t")
+}
+
+/// The iCal text an item is uploaded as.
+/// Items that have not been modified since they have been downloaded (e.g. that are moved to another calendar) are sent exactly as the server sent them,
+/// so that nothing this crate does not parse is lost or reformatted
+fn upload_text(item: &Item) -> Result<String, Box<dyn Error>> {
+    match item.raw_ical() {
+        Some(raw_ical) => Ok(raw_ical.to_string()),
+        None => crate::ical::build_from(item),
+    }
+}
+
+/// The URL a new item has actually been stored at: servers may tell it in a `Location` header, or redirect the request
+fn created_item_url(response: &reqwest::Response) -> Result<Url, Box<dyn Error>> {
+    let url = match response.headers().get(LOCATION) {
+        None => response.url().clone(),
+        Some(location) => response.url().join(location.to_str()?)?,
+    };
+    Ok(normalize_item_url(url))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::find_elems;
+
+    #[test]
+    fn test_version_tags_from_icloud_propfind() {
+        let calendar = Resource::new("https://p42-caldav.icloud.com/1234567890/calendars/A1B2C3D4-E5F6-4711-9A2E-0B5B5D0E1F11/".parse().unwrap(),
+            "john@icloud.com".to_string(), "app-specific-password".to_string());
+        let reply: Element = include_str!("../../tests/assets/icloud/reminders-etags.xml").parse().unwrap();
+        let responses: Vec<Element> = find_elems(&reply, "response").into_iter().cloned().collect();
+
+        let version_tags = version_tags_from_responses(&calendar, &responses);
+        assert_eq!(version_tags.len(), 2);
+        let task_url = calendar.url().join("task@example.com.ics").unwrap();
+        assert_eq!(version_tags[&task_url].as_str(), "\"C=1043@U=8e1f7a32-57d0-4c2a-a0f4-7c9e0a4d3b21\"");
+        assert!(version_tags.contains_key(&calendar.url().join("9F0E2E9C-5D4A-4C4B-9A2E-0B5B5D0E1F11.ics").unwrap()));
+    }
+
+    #[test]
+    fn test_minimal_replies() {
+        use reqwest::ResponseBuilderExt;
+
+        let item_url: Url = "https://my.server.com/dav/calendars/john/work/task.ics".parse().unwrap();
+        let request = prefer_minimal_reply(reqwest::Client::new().put(item_url.clone())).build().unwrap();
+        assert_eq!(request.headers()["Prefer"], "return=minimal");
+        assert_eq!(request.headers()["Brief"], "t");
+
+        let reply = reqwest::Response::from(http::Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .url(item_url.clone())
+            .header("Preference-Applied", "return=minimal")
+            .header(ETAG, "\"42\"")
+            .body("")
+            .unwrap());
+        assert_eq!(created_item_url(&reply).unwrap(), item_url);
+    }
+
+    #[test]
+    fn test_confirmed_version_tags() {
+        let calendar = Resource::new("https://my.server.com/dav/calendars/john/work/".parse().unwrap(), "john".to_string(), "password".to_string());
+        let reply: Element = r#"<d:multistatus xmlns:d="DAV:">
+            <d:response>
+                <d:href>/dav/calendars/john/work/existing.ics</d:href>
+                <d:propstat><d:prop><d:getetag>"42"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+            </d:response>
+            <d:response>
+                <d:href>/dav/calendars/john/work/deleted.ics</d:href>
+                <d:status>HTTP/1.1 404 Not Found</d:status>
+            </d:response>
+            <d:response>
+                <d:href>/dav/calendars/john/work/unknown.ics</d:href>
+                <d:status>HTTP/1.1 403 Forbidden</d:status>
+            </d:response>
+        </d:multistatus>"#.parse().unwrap();
+        let responses: Vec<Element> = find_elems(&reply, "response").into_iter().cloned().collect();
+
+        let tags = confirmed_version_tags(&calendar, &responses);
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[&calendar.url().join("existing.ics").unwrap()], Some(VersionTag::from(String::from("\"42\""))));
+        assert_eq!(tags[&calendar.url().join("deleted.ics").unwrap()], None);
+    }
+
+    #[test]
+    fn test_upload_text() {
+        let raw = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Other client//EN\r\nBEGIN:VTODO\r\nUID:abc\r\nDTSTAMP:20240315T093000Z\r\n\
+                   SUMMARY:Water the plants\r\nX-OTHER-CLIENT;X-PARAM=1:kept as is\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let url: Url = "https://my.server.com/dav/calendars/john/work/abc.ics".parse().unwrap();
+        let mut item = crate::ical::parse(raw, url, SyncStatus::Synced(VersionTag::from(String::from("test-tag")))).unwrap();
+        assert_eq!(upload_text(&item).unwrap(), raw);
+
+        item.unwrap_task_mut().set_name("Water the garden".to_string());
+        let rebuilt = upload_text(&item).unwrap();
+        assert!(rebuilt.contains("SUMMARY:Water the garden"));
+        assert!(!rebuilt.contains("SUMMARY:Water the plants"));
+    }
+}
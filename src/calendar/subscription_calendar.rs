@@ -0,0 +1,234 @@
+//! Read-only calendars backed by a plain iCal feed (e.g. `webcal://` subscriptions)
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use csscolorparser::Color;
+use url::Url;
+
+use crate::traits::BaseCalendar;
+use crate::traits::DavCalendar;
+use crate::calendar::{CalendarMetadata, SupportedComponents};
+use crate::item::Item;
+use crate::item::VersionTag;
+use crate::item::SyncStatus;
+use crate::resource::Resource;
+
+/// How often the feed is downloaded again, unless the feed tells otherwise (see [`CalendarMetadata::refresh_interval`]),
+/// or unless it is changed with [`SubscriptionCalendar::set_refresh_interval`]
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A read-only calendar that is downloaded as a single `.ics` file (such as public holidays or sports schedules).
+///
+/// `webcal://` URLs are fetched over HTTPS. The feed is downloaded again once its refresh interval has elapsed,
+/// and its `ETag` and `Last-Modified` headers are used so that an unchanged feed is not downloaded again. \
+/// Items are given URLs made of the feed URL and their UID as a fragment.
+///
+/// Every function that would modify this calendar returns an error.
+#[derive(Debug)]
+pub struct SubscriptionCalendar {
+    name: String,
+    resource: Resource,
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+    refresh_interval: Option<Duration>,
+
+    feed: Mutex<FeedState>,
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: Option<Instant>,
+    items: HashMap<Url, Item>,
+    metadata: CalendarMetadata,
+}
+
+impl SubscriptionCalendar {
+    /// Change how often the feed is downloaded again. This overrides the refresh interval the feed may tell
+    pub fn set_refresh_interval(&mut self, refresh_interval: Duration) {
+        self.refresh_interval = Some(refresh_interval);
+    }
+
+    /// How often the feed is downloaded again
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+            .or(self.feed.lock().unwrap().metadata.refresh_interval)
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+    }
+
+    /// Download the feed, even if the refresh interval has not elapsed yet
+    pub async fn refresh(&self) -> Result<(), Box<dyn Error>> {
+        let (etag, last_modified) = {
+            let feed = self.feed.lock().unwrap();
+            (feed.etag.clone(), feed.last_modified.clone())
+        };
+
+        let mut request = self.resource.http_client().get(http_url(self.resource.url())?);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        if !self.resource.username().is_empty() {
+            request = request.basic_auth(self.resource.username(), Some(self.resource.password()));
+        }
+        let response = self.resource.send(request).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            log::debug!("Feed {} has not changed", self.resource.url());
+            self.feed.lock().unwrap().fetched_at = Some(Instant::now());
+            return Ok(());
+        }
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_string());
+        let new_etag = header(ETAG);
+        let new_last_modified = header(LAST_MODIFIED);
+        let bytes = response.bytes().await?;
+        self.resource.counters().record_received(bytes.len());
+        let content = crate::ical::encoding::decode_bytes(&bytes);
+        let items = self.parse_feed(&content)?;
+        let metadata = crate::ical::parse_calendar_metadata(&content)?;
+
+        // Note: the mutex cannot be locked during this whole async function, but it can safely be re-entrant (this will just waste an unnecessary request)
+        *self.feed.lock().unwrap() = FeedState {
+            etag: new_etag,
+            last_modified: new_last_modified,
+            fetched_at: Some(Instant::now()),
+            items,
+            metadata,
+        };
+        Ok(())
+    }
+
+    async fn refresh_if_needed(&self) -> Result<(), Box<dyn Error>> {
+        let fetched_at = self.feed.lock().unwrap().fetched_at;
+        match fetched_at {
+            Some(instant) if instant.elapsed() < self.refresh_interval() => Ok(()),
+            _ => self.refresh().await,
+        }
+    }
+
+    fn parse_feed(&self, content: &str) -> Result<HashMap<Url, Item>, Box<dyn Error>> {
+        let mut items = HashMap::new();
+        for item in crate::ical::parse_all(content, self.url())? {
+            let mut item_url = self.url().clone();
+            item_url.set_fragment(Some(item.uid()));
+            let version_tag = VersionTag::from(item.last_modified().to_rfc3339());
+            let item = item.clone_with_identity(item.uid().to_string(), item_url.clone(), SyncStatus::Synced(version_tag));
+            items.insert(item_url, item);
+        }
+        Ok(items)
+    }
+}
+
+/// `webcal://` is just a way to tell "this is a calendar feed available over HTTP(S)"
+fn http_url(url: &Url) -> Result<Url, Box<dyn Error>> {
+    match url.scheme() {
+        "webcal" => Ok(Url::parse(&format!("https{}", &url.as_str()["webcal".len()..]))?),
+        _ => Ok(url.clone()),
+    }
+}
+
+#[async_trait]
+impl BaseCalendar for SubscriptionCalendar {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> crate::calendar::SupportedComponents {
+        self.supported_components
+    }
+    fn color(&self) -> Option<&Color> {
+        self.color.as_ref()
+    }
+    /// The metadata of the feed, once it has been downloaded
+    fn metadata(&self) -> CalendarMetadata {
+        self.feed.lock().unwrap().metadata.clone()
+    }
+
+    async fn add_item(&mut self, _item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        Err(format!("Calendar {} is a read-only subscription", self.url()).into())
+    }
+
+    async fn update_item(&mut self, _item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        Err(format!("Calendar {} is a read-only subscription", self.url()).into())
+    }
+}
+
+#[async_trait]
+impl DavCalendar for SubscriptionCalendar {
+    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self {
+            name, resource, supported_components, color,
+            refresh_interval: None,
+            feed: Mutex::new(FeedState::default()),
+        }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        self.refresh_if_needed().await?;
+        Ok(self.feed.lock().unwrap().items.iter()
+            .filter_map(|(url, item)| match item.sync_status() {
+                SyncStatus::Synced(vt) => Some((url.clone(), vt.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        self.refresh_if_needed().await?;
+        Ok(self.feed.lock().unwrap().items.get(url).cloned())
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+        self.refresh_if_needed().await?;
+        let feed = self.feed.lock().unwrap();
+        Ok(urls.iter()
+            .map(|url| feed.items.get(url).cloned())
+            .collect())
+    }
+
+    async fn delete_item(&mut self, _item_url: &Url) -> Result<(), Box<dyn Error>> {
+        Err(format!("Calendar {} is a read-only subscription", self.url()).into())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_feed() {
+        let feed_url: Url = "webcal://example.com/feeds/schedule.ics".parse().unwrap();
+        assert_eq!(http_url(&feed_url).unwrap().as_str(), "https://example.com/feeds/schedule.ics");
+
+        let resource = Resource::new(feed_url.clone(), String::new(), String::new());
+        let cal = SubscriptionCalendar::new("Schedule".to_string(), resource, SupportedComponents::TODO, None);
+        let feed = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Some//Feed//EN\r\n\
+            BEGIN:VTODO\r\n\
+            UID:first@example.com\r\n\
+            DTSTAMP:20211103T214742\r\n\
+            SUMMARY:Renew the licence\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+
+        let items = cal.parse_feed(feed).unwrap();
+        assert_eq!(items.len(), 1);
+        let (url, item) = items.iter().next().unwrap();
+        assert_eq!(url.fragment(), Some("first@example.com"));
+        assert_eq!(item.url(), url);
+        assert!(matches!(item.sync_status(), SyncStatus::Synced(_)));
+    }
+}
@@ -0,0 +1,118 @@
+//! What a CalDAV server supports
+//!
+//! Servers advertise the WebDAV extensions they implement in the `DAV` header of their replies to `OPTIONS` requests, and the methods they accept
+//! in the `Allow` header. Some quirks cannot be told from these headers (e.g. servers that advertise `MOVE` requests, but reject them), so that well-known servers
+//! are also recognized by the product name in their `Server` header, and looked up in a registry of their quirks.
+//!
+//! The [`Client`](crate::client::Client) fetches the [`ServerCapabilities`] of its server when it lists its calendars. Its calendars use them to choose
+//! which requests they send (e.g. `PROPFIND` instead of `REPORT` requests), and the [`Provider`](crate::provider::Provider) uses them to choose how to sync
+//! (e.g. whether items can be downloaded in batches, or moved with a `MOVE` request).
+
+use reqwest::header::{HeaderMap, ALLOW, SERVER};
+
+/// The capabilities of a server, as advertised in the headers of its reply to an `OPTIONS` request.
+///
+/// Features that the server did not tell anything about are assumed to be supported
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerCapabilities {
+    /// The compliance classes of the `DAV` header (e.g. `1`, `calendar-access`, `calendar-auto-schedule`), in lowercase
+    pub dav_classes: Vec<String>,
+    /// The methods of the `Allow` header, in uppercase
+    pub allowed_methods: Vec<String>,
+    /// The content of the `Server` header
+    pub product: Option<String>,
+}
+
+/// Features of well-known servers that cannot be told from their headers
+struct KnownServer {
+    /// A part of the `Server` header of this server, in lowercase
+    product: &'static str,
+    move_method: bool,
+}
+
+const KNOWN_SERVERS: &[KnownServer] = &[
+    KnownServer{ product: "google", move_method: false },
+];
+
+impl ServerCapabilities {
+    /// Read the capabilities of a server from the headers of its reply to an `OPTIONS` request
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let list = |name: &str, uppercase: bool| -> Vec<String> {
+            headers.get_all(name).iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(|token| token.trim())
+                .filter(|token| !token.is_empty())
+                .map(|token| if uppercase { token.to_uppercase() } else { token.to_lowercase() })
+                .collect()
+        };
+
+        Self {
+            dav_classes: list("DAV", false),
+            allowed_methods: list(ALLOW.as_str(), true),
+            product: headers.get(SERVER).and_then(|value| value.to_str().ok()).map(String::from),
+        }
+    }
+
+    fn has_class(&self, class: &str) -> bool {
+        self.dav_classes.iter().any(|c| c == class)
+    }
+
+    /// Whether a method is allowed. Every method is, in case the server did not send an `Allow` header
+    fn allows(&self, method: &str) -> bool {
+        self.allowed_methods.is_empty() || self.allowed_methods.iter().any(|m| m == method)
+    }
+
+    fn known_server(&self) -> Option<&'static KnownServer> {
+        let product = self.product.as_ref()?.to_lowercase();
+        KNOWN_SERVERS.iter().find(|known| product.contains(known.product))
+    }
+
+    /// Whether `REPORT` requests of RFC 4791 are supported, i.e. whether items can be listed with `calendar-query` reports,
+    /// and downloaded in batches with `calendar-multiget` reports
+    pub fn supports_multiget(&self) -> bool {
+        (self.dav_classes.is_empty() || self.has_class("calendar-access")) && self.allows("REPORT")
+    }
+
+    /// Whether items can be moved with a `MOVE` request
+    pub fn supports_move(&self) -> bool {
+        self.allows("MOVE") && self.known_server().map(|known| known.move_method).unwrap_or(true)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_capabilities() {
+        // Headers of a Nextcloud server
+        let mut headers = HeaderMap::new();
+        headers.insert("DAV", HeaderValue::from_static("1, 3, extended-mkcol, access-control, calendar-access, calendar-auto-schedule, calendar-availability"));
+        headers.append("DAV", HeaderValue::from_static("nc-calendar-trashbin, oc-resource-sharing"));
+        headers.insert(ALLOW, HeaderValue::from_static("OPTIONS, GET, HEAD, DELETE, PROPFIND, PUT, PROPPATCH, COPY, MOVE, REPORT"));
+        headers.insert(SERVER, HeaderValue::from_static("Sabre/DAV 4.4.0"));
+        let nextcloud = ServerCapabilities::from_headers(&headers);
+        assert!(nextcloud.dav_classes.contains(&"nc-calendar-trashbin".to_string()));
+        assert!(nextcloud.supports_multiget() && nextcloud.supports_move());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("DAV", HeaderValue::from_static("1, 2"));
+        headers.insert(ALLOW, HeaderValue::from_static("options, get, put, delete, propfind"));
+        let plain_webdav = ServerCapabilities::from_headers(&headers);
+        assert!(!plain_webdav.supports_multiget());
+        assert!(!plain_webdav.supports_move());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ALLOW, HeaderValue::from_static("OPTIONS, GET, PUT, DELETE, PROPFIND, MOVE, REPORT"));
+        headers.insert(SERVER, HeaderValue::from_static("Google Calendar"));
+        let google = ServerCapabilities::from_headers(&headers);
+        assert!(google.supports_multiget() && !google.supports_move());
+
+        // Servers that do not tell anything are assumed to support the basics
+        let unknown = ServerCapabilities::default();
+        assert!(unknown.supports_multiget() && unknown.supports_move());
+    }
+}
@@ -2,23 +2,38 @@
 
 use std::error::Error;
 use std::convert::TryFrom;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream, StreamExt};
 use reqwest::{Method, StatusCode};
 use reqwest::header::CONTENT_TYPE;
 use minidom::Element;
+use tokio::sync::RwLock;
 use url::Url;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
 
-use crate::resource::Resource;
-use crate::utils::{find_elem, find_elems};
+use crate::resource::{Resource, redact_url, DEFAULT_MAX_RETRIES};
+use crate::id::CalendarId;
+use crate::capabilities::ServerCapabilities;
+use crate::trace::SyncTrace;
+use crate::replay::TraceReplay;
+use crate::utils::{find_elem, find_elems, redact};
 use crate::calendar::remote_calendar::RemoteCalendar;
-use crate::calendar::SupportedComponents;
+use crate::calendar::{CalendarMetadata, Permissions, SupportedComponents};
 use crate::traits::CalDavSource;
+use crate::metrics::RequestStats;
+use crate::sharing::{CalendarSharing, Invitation, ShareAccess};
+use crate::trash::TrashedItem;
+use crate::occurrence::Occurrence;
 use crate::traits::BaseCalendar;
 use crate::traits::DavCalendar;
+#[cfg(feature = "addressbook")]
+use crate::addressbook::{AddressBookSource, RemoteAddressBook, SharedAddressBook};
 
 
 static DAVCLIENT_BODY: &str = r#"
@@ -43,32 +58,101 @@ static CAL_BODY: &str = r#"
        <d:prop>
          <d:displayname />
          <E:calendar-color xmlns:E="http://apple.com/ns/ical/"/>
+         <E:refreshrate xmlns:E="http://apple.com/ns/ical/"/>
+         <c:calendar-description />
          <d:resourcetype />
          <c:supported-calendar-component-set />
+         <CS:invite xmlns:CS="http://calendarserver.org/ns/"/>
+         <d:current-user-privilege-set />
        </d:prop>
     </d:propfind>
 "#;
 
+static SHARING_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:CS="http://calendarserver.org/ns/" >
+       <d:prop>
+         <CS:invite />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static NOTIFICATION_URL_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:CS="http://calendarserver.org/ns/" >
+       <d:prop>
+         <CS:notification-URL />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static NOTIFICATIONS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:CS="http://calendarserver.org/ns/" >
+       <d:prop>
+         <CS:notificationtype />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static QUOTA_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" >
+       <d:prop>
+         <d:quota-used-bytes />
+         <d:quota-available-bytes />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static CAL_STUBS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" >
+       <d:prop>
+         <d:displayname />
+         <d:resourcetype />
+       </d:prop>
+    </d:propfind>
+"#;
+
+
+#[cfg(feature = "addressbook")]
+static ADDRESSBOOK_HOMESET_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav" >
+      <d:self/>
+      <d:prop>
+        <card:addressbook-home-set />
+      </d:prop>
+    </d:propfind>
+"#;
+
+#[cfg(feature = "addressbook")]
+static ADDRESSBOOK_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" >
+       <d:prop>
+         <d:displayname />
+         <d:resourcetype />
+       </d:prop>
+    </d:propfind>
+"#;
 
 
+#[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all,
+    fields(method = %method, url = %redact(redact_url(resource.url())), depth, status = tracing::field::Empty))]
 pub(crate) async fn sub_request(resource: &Resource, method: &str, body: String, depth: u32) -> Result<String, Box<dyn Error>> {
     let method = method.parse()
         .expect("invalid method name");
 
-    let res = reqwest::Client::new()
+    let request = resource.http_client()
         .request(method, resource.url().clone())
         .header("Depth", depth)
         .header(CONTENT_TYPE, "application/xml")
         .basic_auth(resource.username(), Some(resource.password()))
-        .body(body)
-        .send()
-        .await?;
+        .body(body);
+    let res = resource.send(request).await?;
 
+    tracing::Span::current().record("status", &res.status().as_u16());
     if res.status().is_success() == false {
         return Err(format!("Unexpected HTTP status code {:?}", res.status()).into());
     }
 
     let text = res.text().await?;
+    resource.counters().record_received(text.len());
     Ok(text)
 }
 
@@ -97,10 +181,40 @@ pub(crate) async fn sub_request_and_extract_elems(resource: &Resource, method: &
 }
 
 
+/// Servers that need a special handling, because of their quirks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerFlavour {
+    /// A server that follows the CalDAV RFCs (e.g. Nextcloud, Radicale, Baïkal...)
+    Standard,
+    /// iCloud, whose quirks are:
+    /// * the calendars of a user are not served by the host clients connect to (`caldav.icloud.com`), but by another host (e.g. `p42-caldav.icloud.com`),
+    ///   that is only known from the calendar home set of the user,
+    /// * it rejects some `calendar-query` `REPORT`s, so that the items of to-do lists are listed with a `PROPFIND` instead.
+    ICloud,
+}
+
+impl ServerFlavour {
+    /// Guess the flavour of a server from its URL
+    pub fn detect(url: &Url) -> Self {
+        match url.host_str() {
+            Some(host) if is_icloud_host(host) => Self::ICloud,
+            _ => Self::Standard,
+        }
+    }
+}
+
+fn is_icloud_host(host: &str) -> bool {
+    host == "icloud.com" || host.ends_with(".icloud.com")
+}
+
 /// A CalDAV data source that fetches its data from a CalDAV server
+///
+/// Every request of a client (and of the calendars it returns) goes through the same HTTP client, so that connections to the server
+/// are kept open and reused, across syncs as well. See [`ClientBuilder::pool_idle_timeout`] to tune this.
 #[derive(Debug)]
 pub struct Client {
     resource: Resource,
+    flavour: ServerFlavour,
 
     /// The interior mutable part of a Client.
     /// This data may be retrieved once and then cached
@@ -112,18 +226,373 @@ pub struct Client {
 struct CachedReplies {
     principal: Option<Resource>,
     calendar_home_set: Option<Resource>,
-    calendars: Option<HashMap<Url, Arc<Mutex<RemoteCalendar>>>>,
+    calendars: Option<HashMap<Url, Arc<RwLock<RemoteCalendar>>>>,
+    calendar_stubs: Option<Vec<CalendarStub>>,
+    capabilities: Option<ServerCapabilities>,
+}
+
+/// A calendar that has been listed by [`Client::list_calendars`], but whose details have not been fetched yet.
+///
+/// Use [`Client::open_calendar`] to get the actual calendar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalendarStub {
+    url: Url,
+    name: String,
+}
+
+impl CalendarStub {
+    pub fn url(&self) -> &Url { &self.url }
+    pub fn name(&self) -> &str { &self.name }
+}
+
+/// The storage quota of a collection (RFC 4331). Servers may not report both values
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The number of bytes used by the collection
+    pub used_bytes: Option<u64>,
+    /// The number of bytes that can still be stored
+    pub available_bytes: Option<u64>,
+}
+
+/// The error returned when the server refuses to store an item because the quota of the user is exceeded (HTTP 507).
+///
+/// Apps can tell it from other errors using `err.downcast_ref::<QuotaExceeded>()`. See also [`SyncReport::is_quota_exceeded`](crate::provider::sync_progress::SyncReport::is_quota_exceeded)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded;
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "insufficient storage on the server (quota exceeded)")
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+/// A builder for a [`Client`] with non-default network settings
+///
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use kitchen_fridge::client::Client;
+/// let ca_certificate = std::fs::read("my-home-server-ca.pem").unwrap();
+/// let client = Client::builder("https://my.server.com/remote.php/dav/", "username", "secret")
+///     .connect_timeout(Duration::from_secs(10))
+///     .request_timeout(Duration::from_secs(60))
+///     .add_root_certificate(ca_certificate)
+///     .app("my-todo-app", "1.0")
+///     .build()
+///     .unwrap();
+/// ```
+pub struct ClientBuilder {
+    url: String,
+    username: String,
+    password: String,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    tcp_keepalive: Option<Duration>,
+    root_certificates: Vec<Vec<u8>>,
+    client_certificate: Option<(Vec<u8>, String)>,
+    accept_invalid_certs: bool,
+    proxy: Option<String>,
+    proxy_auth: Option<(String, String)>,
+    user_agent: Option<String>,
+    app: Option<(String, String)>,
+    client_identifier: Option<String>,
+    http_client: Option<reqwest::Client>,
+    compression: bool,
+    max_retries: u32,
+    flavour: Option<ServerFlavour>,
+}
+
+/// Credentials are not printed, so that they do not end up in logs
+impl std::fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("pool_max_idle_per_host", &self.pool_max_idle_per_host)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("client_certificate", &self.client_certificate.is_some())
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("proxy", &self.proxy)
+            .field("user_agent", &self.user_agent)
+            .field("app", &self.app)
+            .field("client_identifier", &self.client_identifier)
+            .field("compression", &self.compression)
+            .field("max_retries", &self.max_retries)
+            .field("flavour", &self.flavour)
+            .finish()
+    }
+}
+
+impl ClientBuilder {
+    /// The maximum time to establish a connection to the server. There is no limit by default
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// The maximum time for a whole request, from connecting until the response has been read. There is no limit by default
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// The maximum number of idle connections that are kept open to the server, to be reused by the next requests. There is no limit by default.
+    ///
+    /// Use 0 to close connections after every request, e.g. for servers that do not handle persistent connections well
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept open (90 seconds by default).
+    ///
+    /// Apps that sync periodically may set it above their sync interval, so that every sync does not start with new TLS handshakes.
+    /// Use `None` to keep idle connections open until the server closes them
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Send TCP keep-alive probes on idle connections at this interval, so that routers and firewalls do not silently drop them. They are not sent by default
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Trust an additional root certificate (in PEM format), e.g. the CA of a self-hosted server
+    pub fn add_root_certificate(mut self, pem: Vec<u8>) -> Self {
+        self.root_certificates.push(pem);
+        self
+    }
+
+    /// Authenticate to the server with a client certificate, given as a DER-encoded PKCS #12 archive and its password
+    pub fn client_certificate(mut self, pkcs12_der: Vec<u8>, password: String) -> Self {
+        self.client_certificate = Some((pkcs12_der, password));
+        self
+    }
+
+    /// Accept any certificate, even invalid or self-signed ones.
+    ///
+    /// This makes the connection vulnerable to man-in-the-middle attacks. Adding the server certificate with [`Self::add_root_certificate`] should be preferred.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Send every request through this proxy, instead of the proxy set in the environment variables (if any).
+    ///
+    /// HTTP(S) proxies are supported (e.g. `http://proxy.example.com:8080`), as well as SOCKS5 proxies when the `socks` feature is enabled
+    /// (e.g. `socks5h://127.0.0.1:9050` for Tor, `socks5h` meaning that host names are resolved by the proxy)
+    pub fn proxy<S: ToString>(mut self, proxy_url: S) -> Self {
+        self.proxy = Some(proxy_url.to_string());
+        self
+    }
+
+    /// The credentials to authenticate to the proxy (see [`Self::proxy`])
+    pub fn proxy_auth<T: ToString, U: ToString>(mut self, username: T, password: U) -> Self {
+        self.proxy_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// The User-Agent header sent with every request, instead of the default one (e.g. `kitchen-fridge/0.4.0`).
+    ///
+    /// See [`Self::app`] to identify your app while keeping the default User-Agent
+    pub fn user_agent<S: ToString>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Name your app in the User-Agent header, before the default one (e.g. `my-todo-app/1.0 kitchen-fridge/0.4.0`).
+    ///
+    /// Server administrators can then tell which app their users sync with, and some servers throttle unknown clients.
+    /// This cannot be used together with [`Self::user_agent`]
+    pub fn app<S: ToString, T: ToString>(mut self, name: S, version: T) -> Self {
+        self.app = Some((name.to_string(), version.to_string()));
+        self
+    }
+
+    /// Send an `X-Client` header with every request, e.g. to tell the devices of a user apart in the logs of the server
+    pub fn client_identifier<S: ToString>(mut self, identifier: S) -> Self {
+        self.client_identifier = Some(identifier.to_string());
+        self
+    }
+
+    /// Use an already configured HTTP client, e.g. to share its connection pool with the rest of your app, or to set options this builder does not expose.
+    ///
+    /// The network options of this builder (timeouts, connection pool, certificates, proxy, User-Agent and client identifier) cannot be used together with a custom HTTP client, they must be set on the HTTP client itself.
+    /// Note that `reqwest` clients only connect over TCP, so this cannot be used to reach a server through a Unix socket.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Ask the server to compress its replies with gzip or deflate. This is enabled by default, and greatly reduces the size of the replies
+    /// that list the items of large calendars. It can be disabled e.g. to read the traffic in a network capture.
+    ///
+    /// Replies are not compressed while a trace is being recorded (see [`crate::trace`]) anyway.
+    /// The size of compressed replies is counted in [`RequestStats::compressed_bytes_received`]
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
+    /// How many times a request is sent again when it could not reach the server, or when the server replied that it is overloaded
+    /// (`429 Too Many Requests` or `503 Service Unavailable`). This is 2 by default, and 0 disables retries.
+    ///
+    /// The delay before a retry doubles every time, starting at 1 second, unless the server tells how long to wait in a `Retry-After` header.
+    /// Retries are counted in [`RequestStats::retries`]
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Handle the quirks of a given kind of server. By default, this is guessed from the URL of the server (see [`ServerFlavour::detect`])
+    pub fn server_flavour(mut self, flavour: ServerFlavour) -> Self {
+        self.flavour = Some(flavour);
+        self
+    }
+
+    /// Create the client. This does not start a connection
+    pub fn build(mut self) -> Result<Client, Box<dyn Error>> {
+        let url = Url::parse(&self.url)?;
+
+        let http_client = match self.http_client.take() {
+            None => self.build_http_client(&url)?,
+            Some(_) if self.has_network_options() => {
+                return Err("Network options cannot be set when a custom HTTP client is used".into());
+            },
+            Some(http_client) => http_client,
+        };
+
+        let flavour = self.flavour.unwrap_or_else(|| ServerFlavour::detect(&url));
+        Ok(Client{
+            resource: Resource::new_with_http_client(url, self.username, self.password, http_client).with_compression(self.compression).with_max_retries(self.max_retries),
+            flavour,
+            cached_replies: Mutex::new(CachedReplies::default()),
+        })
+    }
+
+    fn has_network_options(&self) -> bool {
+        self.connect_timeout.is_some() || self.request_timeout.is_some()
+            || self.pool_max_idle_per_host.is_some() || self.pool_idle_timeout.is_some() || self.tcp_keepalive.is_some()
+            || !self.root_certificates.is_empty() || self.client_certificate.is_some() || self.accept_invalid_certs
+            || self.proxy.is_some() || self.proxy_auth.is_some() || self.user_agent.is_some() || self.app.is_some() || self.client_identifier.is_some()
+    }
+
+    fn build_http_client(&self, url: &Url) -> Result<reqwest::Client, Box<dyn Error>> {
+        let mut http_client = reqwest::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            http_client = http_client.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            http_client = http_client.timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            http_client = http_client.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            http_client = http_client.pool_idle_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            http_client = http_client.tcp_keepalive(interval);
+        }
+        for pem in &self.root_certificates {
+            let certificate = reqwest::Certificate::from_pem(pem)
+                .map_err(|err| format!("Invalid root certificate: {}", err))?;
+            http_client = http_client.add_root_certificate(certificate);
+        }
+        if let Some((der, password)) = &self.client_certificate {
+            let identity = reqwest::Identity::from_pkcs12_der(der, password)
+                .map_err(|err| format!("Invalid client certificate: {}", err))?;
+            http_client = http_client.identity(identity);
+        }
+        if self.accept_invalid_certs {
+            log::warn!("TLS certificates of {} will not be checked", url);
+            http_client = http_client.danger_accept_invalid_certs(true);
+        }
+        match (&self.proxy, &self.proxy_auth) {
+            (Some(proxy_url), auth) => {
+                let mut proxy = reqwest::Proxy::all(proxy_url.as_str())
+                    .map_err(|err| format!("Invalid proxy {}: {}", proxy_url, err))?;
+                if let Some((username, password)) = auth {
+                    proxy = proxy.basic_auth(username, password);
+                }
+                http_client = http_client.proxy(proxy);
+            },
+            (None, Some(_)) => return Err("Proxy credentials are set, but no proxy is".into()),
+            (None, None) => (),
+        }
+        http_client = http_client.user_agent(self.full_user_agent()?);
+        if let Some(identifier) = &self.client_identifier {
+            let mut headers = reqwest::header::HeaderMap::new();
+            let value = reqwest::header::HeaderValue::from_str(identifier)
+                .map_err(|err| format!("Invalid client identifier {:?}: {}", identifier, err))?;
+            headers.insert("X-Client", value);
+            http_client = http_client.default_headers(headers);
+        }
+
+        Ok(http_client.build()?)
+    }
+
+    fn full_user_agent(&self) -> Result<String, Box<dyn Error>> {
+        let default = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+        match (&self.user_agent, &self.app) {
+            (Some(_), Some(_)) => Err("A User-Agent and an app name cannot be both set".into()),
+            (Some(user_agent), None) => Ok(user_agent.clone()),
+            (None, Some((name, version))) => Ok(format!("{}/{} {}", name, version, default)),
+            (None, None) => Ok(default.to_string()),
+        }
+    }
 }
 
 impl Client {
     /// Create a client. This does not start a connection
     pub fn new<S: AsRef<str>, T: ToString, U: ToString>(url: S, username: T, password: U) -> Result<Self, Box<dyn Error>> {
-        let url = Url::parse(url.as_ref())?;
+        Self::builder(url, username, password).build()
+    }
 
-        Ok(Self{
-            resource: Resource::new(url, username.to_string(), password.to_string()),
-            cached_replies: Mutex::new(CachedReplies::default()),
-        })
+    /// Start building a client, in case non-default settings (e.g. timeouts, TLS certificates or a proxy) are needed
+    pub fn builder<S: AsRef<str>, T: ToString, U: ToString>(url: S, username: T, password: U) -> ClientBuilder {
+        ClientBuilder {
+            url: url.as_ref().to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            connect_timeout: None,
+            request_timeout: None,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
+            root_certificates: Vec::new(),
+            client_certificate: None,
+            accept_invalid_certs: false,
+            proxy: None,
+            proxy_auth: None,
+            user_agent: None,
+            app: None,
+            client_identifier: None,
+            http_client: None,
+            compression: true,
+            max_retries: DEFAULT_MAX_RETRIES,
+            flavour: None,
+        }
+    }
+
+    /// The kind of server this client talks to
+    pub fn server_flavour(&self) -> ServerFlavour {
+        self.flavour
+    }
+
+    /// Answer the requests of this client with the replies of a trace, see [`crate::replay`]
+    pub(crate) fn with_replay(mut self, replay: Arc<TraceReplay>) -> Self {
+        self.resource = self.resource.with_replay(replay);
+        self
     }
 
     /// Return the Principal URL, or fetch it from server if not known yet
@@ -133,7 +602,7 @@ impl Client {
         }
 
         let href = sub_request_and_extract_elem(&self.resource, DAVCLIENT_BODY.into(), &["current-user-principal", "href"]).await?;
-        let principal_url = self.resource.combine(&href);
+        let principal_url = self.resource.resolve_href(&href);
         self.cached_replies.lock().unwrap().principal = Some(principal_url.clone());
         log::debug!("Principal URL is {}", href);
 
@@ -148,86 +617,382 @@ impl Client {
         let principal_url = self.get_principal().await?;
 
         let href = sub_request_and_extract_elem(&principal_url, HOMESET_BODY.into(), &["calendar-home-set", "href"]).await?;
-        let chs_url = self.resource.combine(&href);
+        let chs_url = self.home_set_resource(&principal_url, &href);
         self.cached_replies.lock().unwrap().calendar_home_set = Some(chs_url.clone());
         log::debug!("Calendar home set URL is {:?}", href);
 
         Ok(chs_url)
     }
 
+    #[tracing::instrument(target = "kitchen_fridge::client", level = "debug", skip_all, fields(url = %redact(redact_url(self.resource.url()))))]
     async fn populate_calendars(&self) -> Result<(), Box<dyn Error>> {
         let cal_home_set = self.get_cal_home_set().await?;
+        if let Err(err) = self.fetch_capabilities().await {
+            log::warn!("Unable to fetch the capabilities of the server: {}", err);
+        }
 
         let reps = sub_request_and_extract_elems(&cal_home_set, "PROPFIND", CAL_BODY.to_string(), "response").await?;
         let mut calendars = HashMap::new();
         for rep in reps {
-            let display_name = find_elem(&rep, "displayname").map(|e| e.text()).unwrap_or("<no name>".to_string());
-            log::debug!("Considering calendar {}", display_name);
+            if let Some(this_calendar) = self.calendar_from_response(&cal_home_set, &rep) {
+                log::info!("Found calendar {}", this_calendar.name());
+                calendars.insert(this_calendar.url().clone(), Arc::new(RwLock::new(this_calendar)));
+            }
+        }
+
+        let mut replies = self.cached_replies.lock().unwrap();
+        replies.calendars = Some(calendars);
+        Ok(())
+    }
+
+    /// The resource of a home set, from its href in a reply to a request on the principal
+    fn home_set_resource(&self, principal: &Resource, href: &str) -> Resource {
+        match (self.flavour, Url::parse(href.trim())) {
+            // The calendars of iCloud users are served by another host
+            (ServerFlavour::ICloud, Ok(url)) if url.host_str().map(is_icloud_host).unwrap_or(false) => principal.follow_href(href),
+            _ => principal.resolve_href(href),
+        }
+    }
+
+    /// Return the capabilities of the server (see [`crate::capabilities`]), or fetch them if they are not known yet
+    pub async fn fetch_capabilities(&self) -> Result<ServerCapabilities, Box<dyn Error>> {
+        if let Some(capabilities) = &self.cached_replies.lock().unwrap().capabilities {
+            return Ok(capabilities.clone());
+        }
+        let cal_home_set = self.get_cal_home_set().await?;
+
+        let request = cal_home_set.http_client()
+            .request(Method::OPTIONS, cal_home_set.url().clone())
+            .basic_auth(cal_home_set.username(), Some(cal_home_set.password()));
+        let response = cal_home_set.send(request).await?;
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+
+        let capabilities = ServerCapabilities::from_headers(response.headers());
+        log::debug!("Server capabilities: {:?}", capabilities);
+        self.cached_replies.lock().unwrap().capabilities = Some(capabilities.clone());
+        Ok(capabilities)
+    }
+
+    /// Build a calendar from the reply of a `PROPFIND` request on `base`. Returns `None` if this reply is not about a calendar
+    fn calendar_from_response(&self, base: &Resource, rep: &Element) -> Option<RemoteCalendar> {
+        let display_name = find_elem(rep, "displayname").map(|e| e.text()).unwrap_or("<no name>".to_string());
+        log::debug!("Considering calendar {}", display_name);
 
-            // We filter out non-calendar items
-            let resource_types = match find_elem(&rep, "resourcetype") {
-                None => continue,
-                Some(rt) => rt,
+        // We filter out non-calendar items
+        if !is_calendar(rep) {
+            return None;
+        }
+
+        // We filter out the root calendar collection, that has an empty supported-calendar-component-set
+        let el_supported_comps = find_elem(rep, "supported-calendar-component-set")?;
+        if el_supported_comps.children().count() == 0 {
+            return None;
+        }
+
+        let calendar_href = match find_elem(rep, "href") {
+            None => {
+                log::warn!("Calendar {} has no URL! Ignoring it.", display_name);
+                return None;
+            },
+            Some(h) => h.text(),
+        };
+
+        let this_calendar_url = calendar_resource(base, &calendar_href);
+
+        let supported_components = match crate::calendar::SupportedComponents::try_from(el_supported_comps.clone()) {
+            Err(err) => {
+                log::warn!("Calendar {} has invalid supported components ({})! Ignoring it.", display_name, err);
+                return None;
+            },
+            Ok(sc) => sc,
+        };
+
+        let this_calendar_color = find_elem(rep, "calendar-color")
+            .and_then(|col| {
+                col.texts().next()
+                    .and_then(|t| csscolorparser::parse(t).ok())
+            });
+
+        let mut this_calendar = RemoteCalendar::new(display_name, this_calendar_url, supported_components, this_calendar_color);
+        this_calendar.set_sharing(crate::sharing::parse_sharing(rep));
+        this_calendar.set_permissions(find_elem(rep, "current-user-privilege-set").map(Permissions::from_privilege_set));
+        this_calendar.set_metadata(CalendarMetadata::from_dav_properties(rep));
+        this_calendar.set_server_flavour(self.flavour);
+        if let Some(capabilities) = &self.cached_replies.lock().unwrap().capabilities {
+            this_calendar.set_capabilities(capabilities.clone());
+        }
+        Some(this_calendar)
+    }
+
+    /// List the calendars of the current user, without fetching their details.
+    ///
+    /// This is much faster than [`CalDavSource::get_calendars`] for accounts that have many calendars: only their names are fetched,
+    /// and the calendars the app actually needs can then be opened with [`Client::open_calendar`].
+    /// Calendars are sorted by URL, and only `limit` of them are returned, starting at `offset`. The list is fetched once, and then cached.
+    ///
+    /// Paging happens on the client: WebDAV has no way to page the reply of a `PROPFIND` (RFC 4918). Result limits (`<d:limit>`) only exist for some `REPORT`s,
+    /// such as `sync-collection` (RFC 6578), that servers (e.g. Nextcloud or Radicale) do not support on calendar home sets, and that would not return calendars
+    /// in a stable order anyway. The listing is still kept small: it is a `Depth: 1` `PROPFIND` that only asks for the name and type of each calendar.
+    pub async fn list_calendars(&self, offset: usize, limit: usize) -> Result<Vec<CalendarStub>, Box<dyn Error>> {
+        let cached_stubs = self.cached_replies.lock().unwrap().calendar_stubs.clone();
+        let stubs = match cached_stubs {
+            Some(stubs) => stubs,
+            None => {
+                let cal_home_set = self.get_cal_home_set().await?;
+                let reps = sub_request_and_extract_elems(&cal_home_set, "PROPFIND", CAL_STUBS_BODY.to_string(), "response").await?;
+                let stubs = stubs_from_responses(&cal_home_set, &reps);
+                self.cached_replies.lock().unwrap().calendar_stubs = Some(stubs.clone());
+                stubs
+            },
+        };
+
+        Ok(stubs.into_iter().skip(offset).take(limit).collect())
+    }
+
+    /// Fetch the details of a calendar that has been listed by [`Client::list_calendars`]
+    pub async fn open_calendar(&self, stub: &CalendarStub) -> Result<Arc<RwLock<RemoteCalendar>>, Box<dyn Error>> {
+        let cached = self.cached_replies.lock().unwrap()
+            .calendars
+            .as_ref()
+            .and_then(|cals| cals.get(stub.url())).cloned();
+        if let Some(calendar) = cached {
+            return Ok(calendar);
+        }
+
+        let resource = self.resource.combine(stub.url().path());
+        let text = sub_request(&resource, "PROPFIND", CAL_BODY.to_string(), 0).await?;
+        let root: Element = text.parse()?;
+        let calendar = find_elem(&root, "response")
+            .and_then(|rep| self.calendar_from_response(&resource, rep))
+            .ok_or_else(|| format!("{} is not a calendar", stub.url()))?;
+
+        let calendar = Arc::new(RwLock::new(calendar));
+        self.cached_replies.lock().unwrap()
+            .calendars
+            .get_or_insert_with(HashMap::new)
+            .insert(stub.url().clone(), calendar.clone());
+        Ok(calendar)
+    }
+
+    /// Fetch the storage quota of a calendar (or of any other collection of the server), e.g. to warn the user before it is exceeded
+    pub async fn quota(&self, calendar_url: &Url) -> Result<Quota, Box<dyn Error>> {
+        let resource = self.resource.combine(calendar_url.path());
+        let text = sub_request(&resource, "PROPFIND", QUOTA_BODY.to_string(), 0).await?;
+        parse_quota(&text)
+    }
+
+    /// Fetch every occurrence of the events of a calendar that happen within a time range, sorted by start date.
+    ///
+    /// Recurring events are expanded by the server (see the [`occurrence`](crate::occurrence) module), so that apps do not have to implement `RRULE`s themselves.
+    pub async fn expanded_occurrences(&self, calendar_url: &Url, range: Range<DateTime<Utc>>) -> Result<Vec<Occurrence>, Box<dyn Error>> {
+        let resource = self.resource.combine(calendar_url.path());
+        let reps = sub_request_and_extract_elems(&resource, "REPORT", crate::occurrence::expand_body(&range), "response").await?;
+
+        let mut occurrences = Vec::new();
+        for rep in &reps {
+            occurrences.extend(crate::occurrence::parse_occurrences(rep, &resource)?);
+        }
+        occurrences.sort_by_key(|occurrence| occurrence.start);
+        Ok(occurrences)
+    }
+
+    /// Fetch who shared a calendar, and who it is shared with (see the [`sharing`](crate::sharing) module)
+    pub async fn sharees(&self, calendar_url: &Url) -> Result<CalendarSharing, Box<dyn Error>> {
+        let resource = self.resource.combine(calendar_url.path());
+        let text = sub_request(&resource, "PROPFIND", SHARING_BODY.to_string(), 0).await?;
+        let root: Element = text.parse()?;
+        Ok(crate::sharing::parse_sharing(&root))
+    }
+
+    /// Share a calendar with a user (usually given as a `mailto:` URI), who will receive an [`Invitation`].
+    ///
+    /// This can also be used to change the access of a user the calendar is already shared with.
+    pub async fn share_calendar(&self, calendar_url: &Url, sharee: &str, access: ShareAccess, summary: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let body = crate::sharing::share_body(sharee, access, summary);
+        self.post_sharing_request(calendar_url, body).await
+    }
+
+    /// Stop sharing a calendar with a user
+    pub async fn unshare_calendar(&self, calendar_url: &Url, sharee: &str) -> Result<(), Box<dyn Error>> {
+        let body = crate::sharing::unshare_body(sharee);
+        self.post_sharing_request(calendar_url, body).await
+    }
+
+    /// Fetch the invitations to calendars that have been shared with the current user
+    pub async fn invitations(&self) -> Result<Vec<Invitation>, Box<dyn Error>> {
+        let principal = self.get_principal().await?;
+        let href = sub_request_and_extract_elem(&principal, NOTIFICATION_URL_BODY.into(), &["notification-URL", "href"]).await?;
+        let notifications = principal.resolve_href(&href);
+
+        let reps = sub_request_and_extract_elems(&notifications, "PROPFIND", NOTIFICATIONS_BODY.to_string(), "response").await?;
+        let mut invitations = Vec::new();
+        for rep in reps {
+            let is_invitation = find_elem(&rep, "notificationtype")
+                .map(|notification_type| find_elem(notification_type, "invite-notification").is_some())
+                .unwrap_or(false);
+            let href = match find_elem(&rep, "href") {
+                Some(href) if is_invitation => href.text(),
+                _ => continue,
             };
-            let mut found_calendar_type = false;
-            for resource_type in resource_types.children() {
-                if resource_type.name() == "calendar" {
-                    found_calendar_type = true;
-                    break;
-                }
-            }
-            if found_calendar_type == false {
+
+            let notification_url = notifications.resolve_href(&href);
+            let request = self.resource.http_client()
+                .get(notification_url.url().clone())
+                .basic_auth(self.resource.username(), Some(self.resource.password()));
+            let response = self.resource.send(request).await?;
+            if !response.status().is_success() {
+                log::warn!("Unable to fetch notification {}: HTTP status code {:?}", redact(redact_url(notification_url.url())), response.status());
                 continue;
             }
+            let text = response.text().await?;
+            self.resource.counters().record_received(text.len());
 
-            // We filter out the root calendar collection, that has an empty supported-calendar-component-set
-            let el_supported_comps = match find_elem(&rep, "supported-calendar-component-set") {
-                None => continue,
-                Some(comps) => comps,
-            };
-            if el_supported_comps.children().count() == 0 {
+            match crate::sharing::parse_invitation(&text, self.resource.url()) {
+                Ok(Some(invitation)) => invitations.push(invitation),
+                Ok(None) => (),
+                Err(err) => log::warn!("Unable to parse notification {}: {}", redact(redact_url(notification_url.url())), err),
+            }
+        }
+        Ok(invitations)
+    }
+
+    /// Accept or decline an invitation to a shared calendar
+    pub async fn reply_to_invitation(&self, invitation: &Invitation, accept: bool) -> Result<(), Box<dyn Error>> {
+        let cal_home_set = self.get_cal_home_set().await?;
+        let body = crate::sharing::invite_reply_body(invitation, accept);
+        self.post_sharing_request(cal_home_set.url(), body).await?;
+
+        // The calendars of the current user have changed
+        self.cached_replies.lock().unwrap().calendar_stubs = None;
+        Ok(())
+    }
+
+    async fn post_sharing_request(&self, url: &Url, body: String) -> Result<(), Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .post(url.clone())
+            .header(CONTENT_TYPE, "application/xml")
+            .basic_auth(self.resource.username(), Some(self.resource.password()))
+            .body(body);
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+        Ok(())
+    }
+
+    /// List the items of every calendar that have been deleted, but that are still in the trash bin of the server (see the [`trash`](crate::trash) module).
+    ///
+    /// Servers that have no trash bin return an error.
+    pub async fn trashed_items(&self) -> Result<Vec<TrashedItem>, Box<dyn Error>> {
+        let cal_home_set = self.get_cal_home_set().await?;
+        let trash = self.trash_resource(&cal_home_set, "objects/")?;
+        let reps = sub_request_and_extract_elems(&trash, "PROPFIND", crate::trash::TRASHED_ITEMS_BODY.to_string(), "response").await?;
+        Ok(reps.iter()
+            .filter_map(|rep| crate::trash::parse_trashed_item(rep, &trash, cal_home_set.url()))
+            .collect())
+    }
+
+    /// Restore an item from the trash bin to the calendar it has been deleted from.
+    ///
+    /// The restored item is then downloaded by the next sync of this calendar (see [`Provider::sync_calendar`](crate::provider::Provider::sync_calendar)), just like an item that has been added on the server.
+    pub async fn restore_trashed_item(&self, trashed: &TrashedItem) -> Result<(), Box<dyn Error>> {
+        let cal_home_set = self.get_cal_home_set().await?;
+        let file_name = trashed.url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .ok_or_else(|| format!("Invalid trashed item URL {}", trashed.url))?;
+        let destination = self.trash_resource(&cal_home_set, &format!("restore/{}", file_name))?;
+
+        let request = self.resource.http_client()
+            .request(Method::from_bytes(b"MOVE").unwrap(), trashed.url.clone())
+            .header("Destination", destination.url().as_str())
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+        Ok(())
+    }
+
+    /// Permanently delete an item from the trash bin
+    pub async fn delete_trashed_item(&self, trashed: &TrashedItem) -> Result<(), Box<dyn Error>> {
+        let request = self.resource.http_client()
+            .delete(trashed.url.clone())
+            .basic_auth(self.resource.username(), Some(self.resource.password()));
+        let response = self.resource.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+        }
+        Ok(())
+    }
+
+    /// Permanently delete every item of the trash bin
+    pub async fn empty_trash(&self) -> Result<(), Box<dyn Error>> {
+        for trashed in self.trashed_items().await? {
+            self.delete_trashed_item(&trashed).await?;
+        }
+        Ok(())
+    }
+
+    fn trash_resource(&self, cal_home_set: &Resource, path: &str) -> Result<Resource, Box<dyn Error>> {
+        let url = cal_home_set.url().join("trashbin/")?.join(path)?;
+        Ok(self.resource.combine(url.path()))
+    }
+
+    /// Fetch the CardDAV address books of the current user. Servers that do not support CardDAV have none
+    #[cfg(feature = "addressbook")]
+    pub async fn get_address_books(&self) -> Result<HashMap<Url, RemoteAddressBook>, Box<dyn Error>> {
+        let principal_url = self.get_principal().await?;
+        let text = sub_request(&principal_url, "PROPFIND", ADDRESSBOOK_HOMESET_BODY.into(), 0).await?;
+        let root: Element = text.parse()?;
+        let href = match find_elem(&root, "addressbook-home-set").and_then(|home_set| find_elem(home_set, "href")) {
+            None => {
+                log::info!("The server has no address book home set, it does not support CardDAV");
+                return Ok(HashMap::new());
+            },
+            Some(href) => href.text(),
+        };
+        let home_set = self.home_set_resource(&principal_url, &href);
+        log::debug!("Address book home set URL is {:?}", href);
+
+        let reps = sub_request_and_extract_elems(&home_set, "PROPFIND", ADDRESSBOOK_BODY.to_string(), "response").await?;
+        let mut address_books = HashMap::new();
+        for rep in reps {
+            let display_name = find_elem(&rep, "displayname").map(|e| e.text()).unwrap_or("<no name>".to_string());
+
+            // We filter out collections that are not address books
+            let is_address_book = find_elem(&rep, "resourcetype")
+                .map(|rt| rt.children().any(|child| child.name() == "addressbook"))
+                .unwrap_or(false);
+            if !is_address_book {
                 continue;
             }
 
-            let calendar_href = match find_elem(&rep, "href") {
+            let address_book_href = match find_elem(&rep, "href") {
                 None => {
-                    log::warn!("Calendar {} has no URL! Ignoring it.", display_name);
+                    log::warn!("Address book {} has no URL! Ignoring it.", display_name);
                     continue;
                 },
                 Some(h) => h.text(),
             };
 
-            let this_calendar_url = self.resource.combine(&calendar_href);
-
-            let supported_components = match crate::calendar::SupportedComponents::try_from(el_supported_comps.clone()) {
-                Err(err) => {
-                    log::warn!("Calendar {} has invalid supported components ({})! Ignoring it.", display_name, err);
-                    continue;
-                },
-                Ok(sc) => sc,
-            };
-
-            let this_calendar_color = find_elem(&rep, "calendar-color")
-                .and_then(|col| {
-                    col.texts().next()
-                        .and_then(|t| csscolorparser::parse(t).ok())
-                });
-
-            let this_calendar = RemoteCalendar::new(display_name, this_calendar_url, supported_components, this_calendar_color);
-            log::info!("Found calendar {}", this_calendar.name());
-            calendars.insert(this_calendar.url().clone(), Arc::new(Mutex::new(this_calendar)));
+            let address_book = RemoteAddressBook::new(display_name, home_set.resolve_href(&address_book_href));
+            log::info!("Found address book {}", address_book.name());
+            address_books.insert(address_book.url().clone(), address_book);
         }
 
-        let mut replies = self.cached_replies.lock().unwrap();
-        replies.calendars = Some(calendars);
-        Ok(())
+        Ok(address_books)
     }
 
 }
 
 #[async_trait]
 impl CalDavSource<RemoteCalendar> for Client {
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<RemoteCalendar>>>, Box<dyn Error>> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<RemoteCalendar>>>, Box<dyn Error>> {
         self.populate_calendars().await?;
 
         match &self.cached_replies.lock().unwrap().calendars {
@@ -238,7 +1003,7 @@ impl CalDavSource<RemoteCalendar> for Client {
         };
     }
 
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<RemoteCalendar>>> {
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<RemoteCalendar>>> {
         if let Err(err) = self.populate_calendars().await {
             log::warn!("Unable to fetch calendars: {}", err);
             return None;
@@ -251,7 +1016,34 @@ impl CalDavSource<RemoteCalendar> for Client {
             .map(|cal| cal.clone())
     }
 
-    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<Mutex<RemoteCalendar>>, Box<dyn Error>> {
+    async fn get_calendar_ids(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+        self.populate_calendars().await?;
+
+        match &self.cached_replies.lock().unwrap().calendars {
+            Some(cals) => Ok(cals.keys().cloned().collect()),
+            None => Err("No calendars available".into()),
+        }
+    }
+
+    /// Only returns the calendars that have already been fetched, e.g. by a previous call to [`CalDavSource::get_calendars`]
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<RemoteCalendar>>> {
+        self.cached_replies.try_lock().ok()?
+            .calendars
+            .as_ref()
+            .and_then(|cals| cals.get(url))
+            .cloned()
+    }
+
+    async fn calendars<'a>(&'a self) -> Result<BoxStream<'a, (Url, Arc<RwLock<RemoteCalendar>>)>, Box<dyn Error>>
+    where
+        RemoteCalendar: 'a,
+    {
+        // Calendars are listed with a single request, there is no need to issue one more per calendar
+        let calendars = self.get_calendars().await?;
+        Ok(stream::iter(calendars).boxed())
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<RemoteCalendar>>, Box<dyn Error>> {
         self.populate_calendars().await?;
 
         match self.cached_replies.lock().unwrap().calendars.as_ref() {
@@ -265,13 +1057,12 @@ impl CalDavSource<RemoteCalendar> for Client {
 
         let creation_body = calendar_body(name, supported_components, color);
 
-        let response = reqwest::Client::new()
+        let request = self.resource.http_client()
             .request(Method::from_bytes(b"MKCALENDAR").unwrap(), url.clone())
             .header(CONTENT_TYPE, "application/xml")
             .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(creation_body)
-            .send()
-            .await?;
+            .body(creation_body);
+        let response = self.resource.send(request).await?;
 
         let status = response.status();
         if status != StatusCode::CREATED {
@@ -280,6 +1071,77 @@ impl CalDavSource<RemoteCalendar> for Client {
 
         self.get_calendar(&url).await.ok_or(format!("Unable to insert calendar {:?}", url).into())
     }
+
+    fn capabilities(&self) -> Option<ServerCapabilities> {
+        self.cached_replies.lock().unwrap().capabilities.clone()
+    }
+
+    fn request_stats(&self) -> RequestStats {
+        self.resource.counters().snapshot()
+    }
+
+    fn set_trace(&self, trace: Option<Arc<SyncTrace>>) {
+        self.resource.set_trace(trace)
+    }
+
+    #[cfg(feature = "addressbook")]
+    fn address_book_source(&self) -> Option<&(dyn AddressBookSource + Send + Sync)> {
+        Some(self)
+    }
+}
+
+#[cfg(feature = "addressbook")]
+#[async_trait]
+impl AddressBookSource for Client {
+    async fn get_address_books(&self) -> Result<HashMap<Url, SharedAddressBook>, Box<dyn Error>> {
+        Ok(Client::get_address_books(self).await?.into_iter()
+            .map(|(url, address_book)| (url, Arc::new(RwLock::new(address_book)) as SharedAddressBook))
+            .collect())
+    }
+}
+
+fn parse_quota(text: &str) -> Result<Quota, Box<dyn Error>> {
+    let root: Element = text.parse()?;
+    let value = |name: &str| {
+        find_elem(&root, name).and_then(|elem| elem.text().trim().parse::<u64>().ok())
+    };
+    Ok(Quota {
+        used_bytes: value("quota-used-bytes"),
+        available_bytes: value("quota-available-bytes"),
+    })
+}
+
+fn is_calendar(rep: &Element) -> bool {
+    match find_elem(rep, "resourcetype") {
+        None => false,
+        Some(resource_types) => resource_types.children().any(|resource_type| resource_type.name() == "calendar"),
+    }
+}
+
+/// The resource of a calendar, from its href in a reply to a request on `base`.
+/// Servers do not always agree with themselves on trailing slashes and percent-encoding, so that calendar URLs are normalized
+fn calendar_resource(base: &Resource, href: &str) -> Resource {
+    let resource = base.resolve_href(href);
+    match CalendarId::try_from(resource.url().clone()) {
+        Ok(id) => resource.combine(id.as_url().path()),
+        Err(err) => {
+            log::warn!("Calendar {} has an unusual URL ({}), using it as is", redact(redact_url(resource.url())), err);
+            resource
+        },
+    }
+}
+
+fn stubs_from_responses(base: &Resource, reps: &[Element]) -> Vec<CalendarStub> {
+    let mut stubs: Vec<CalendarStub> = reps.iter()
+        .filter(|rep| is_calendar(rep))
+        .filter_map(|rep| {
+            let href = find_elem(rep, "href")?.text();
+            let name = find_elem(rep, "displayname").map(|e| e.text()).unwrap_or("<no name>".to_string());
+            Some(CalendarStub{ url: calendar_resource(base, &href).url().clone(), name })
+        })
+        .collect();
+    stubs.sort_by(|a, b| a.url.cmp(&b.url));
+    stubs
 }
 
 fn calendar_body(name: String, supported_components: SupportedComponents, color: Option<Color>) -> String {
@@ -305,3 +1167,101 @@ fn calendar_body(name: String, supported_components: SupportedComponents, color:
         supported_components.to_xml_string(),
     )
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icloud_quirks() {
+        let client = Client::new("https://caldav.icloud.com/", "john@icloud.com", "app-specific-password").unwrap();
+        assert_eq!(client.server_flavour(), ServerFlavour::ICloud);
+        let standard = Client::builder("https://caldav.icloud.com/", "john@icloud.com", "app-specific-password")
+            .server_flavour(ServerFlavour::Standard).build().unwrap();
+        assert_eq!(Client::new("https://my.server.com/dav/", "user", "pass").unwrap().server_flavour(), ServerFlavour::Standard);
+
+        // A recorded reply of iCloud: the calendars are on another host
+        let reply: Element = include_str!("../tests/assets/icloud/calendar-home-set.xml").parse().unwrap();
+        let href = find_elem(find_elem(&reply, "calendar-home-set").unwrap(), "href").unwrap().text();
+        let principal = client.resource.resolve_href("/1234567890/principal/");
+        assert_eq!(client.home_set_resource(&principal, &href).url().as_str(), "https://p42-caldav.icloud.com/1234567890/calendars/");
+        assert_eq!(client.home_set_resource(&principal, &href).username(), "john@icloud.com");
+        assert_eq!(standard.home_set_resource(&principal, &href).url().as_str(), "https://caldav.icloud.com/1234567890/calendars/");
+        // Credentials are never sent to other servers
+        assert_eq!(client.home_set_resource(&principal, "https://evil.com/1234567890/calendars/").url().as_str(), "https://caldav.icloud.com/1234567890/calendars/");
+    }
+
+    #[test]
+    fn test_client_builder() {
+        let builder = || Client::builder("https://my.server.com/remote.php/dav/", "user", "pass");
+        assert!(builder().build().is_ok());
+        assert!(builder().connect_timeout(Duration::from_secs(5)).user_agent("tests/1.0").danger_accept_invalid_certs(true).build().is_ok());
+        assert!(builder().pool_max_idle_per_host(8).pool_idle_timeout(Some(Duration::from_secs(600))).tcp_keepalive(Duration::from_secs(30)).build().is_ok());
+        assert!(builder().pool_idle_timeout(None).build().is_ok());
+        assert!(builder().http_client(reqwest::Client::new()).compression(false).build().is_ok());
+        assert_eq!(builder().full_user_agent().unwrap(), format!("kitchen-fridge/{}", env!("CARGO_PKG_VERSION")));
+        assert_eq!(builder().app("my-app", "2.1").full_user_agent().unwrap(), format!("my-app/2.1 kitchen-fridge/{}", env!("CARGO_PKG_VERSION")));
+        assert!(builder().app("my-app", "2.1").client_identifier("phone-of-john").build().is_ok());
+        assert!(builder().app("my-app", "2.1").user_agent("tests/1.0").build().is_err());
+        assert!(builder().client_identifier("line\nbreak").build().is_err());
+        assert!(builder().proxy("http://proxy.example.com:8080").proxy_auth("me", "p@ss:word").build().is_ok());
+        assert!(builder().proxy_auth("me", "p@ss:word").build().is_err());
+        assert!(builder().proxy("ftp://proxy.example.com").build().is_err());
+        assert!(builder().add_root_certificate(b"not a certificate".to_vec()).build().is_err());
+        assert!(Client::builder("not a URL", "user", "pass").build().is_err());
+
+        let shared = reqwest::Client::new();
+        assert!(builder().http_client(shared.clone()).build().is_ok());
+        assert!(builder().http_client(shared.clone()).user_agent("tests/1.0").build().is_err());
+        assert!(builder().http_client(shared).pool_max_idle_per_host(0).build().is_err());
+    }
+
+    #[test]
+    fn test_quota() {
+        let multistatus = r#"<d:multistatus xmlns:d="DAV:">
+            <d:response>
+                <d:href>/dav/calendars/john/work/</d:href>
+                <d:propstat>
+                    <d:prop><d:quota-used-bytes>1048576</d:quota-used-bytes></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat>
+                <d:propstat>
+                    <d:prop><d:quota-available-bytes/></d:prop>
+                    <d:status>HTTP/1.1 404 Not Found</d:status>
+                </d:propstat>
+            </d:response>
+        </d:multistatus>"#;
+        assert_eq!(parse_quota(multistatus).unwrap(), Quota{ used_bytes: Some(1048576), available_bytes: None });
+
+        let err: Box<dyn Error> = QuotaExceeded.into();
+        assert!(err.downcast_ref::<QuotaExceeded>().is_some());
+    }
+
+    #[test]
+    fn test_calendar_stubs() {
+        let multistatus = r#"<d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:response>
+                <d:href>/dav/calendars/john/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+            </d:response>
+            <d:response>
+                <d:href>http://10.0.0.3:8080/dav/calendars/john/work/</d:href>
+                <d:propstat><d:prop><d:displayname>Work</d:displayname><d:resourcetype><d:collection/><c:calendar/></d:resourcetype></d:prop></d:propstat>
+            </d:response>
+            <d:response>
+                <d:href>calendars/john/home</d:href>
+                <d:propstat><d:prop><d:displayname>Home</d:displayname><d:resourcetype><d:collection/><c:calendar/></d:resourcetype></d:prop></d:propstat>
+            </d:response>
+        </d:multistatus>"#;
+        let root: Element = multistatus.parse().unwrap();
+        let reps: Vec<Element> = find_elems(&root, "response").into_iter().cloned().collect();
+        let base = Resource::new("https://my.server.com/dav/".parse().unwrap(), "user".to_string(), "pass".to_string());
+
+        let stubs = stubs_from_responses(&base, &reps);
+        assert_eq!(stubs.iter().map(|stub| stub.name()).collect::<Vec<_>>(), vec!["Home", "Work"]);
+        // Hrefs may be relative, or use the address of a server behind a proxy
+        assert_eq!(stubs[0].url().as_str(), "https://my.server.com/dav/calendars/john/home/");
+        assert_eq!(stubs[1].url().as_str(), "https://my.server.com/dav/calendars/john/work/");
+    }
+}
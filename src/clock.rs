@@ -0,0 +1,105 @@
+//! The source of the current time
+//!
+//! Changes to items (e.g. their `LAST-MODIFIED` date) and syncs are dated with a [`Clock`]. \
+//! The default clock is set in [`config::CLOCK`](crate::config::CLOCK), and it can be overridden for a single [`Cache`](crate::cache::Cache)
+//! or [`Provider`](crate::provider::Provider) (e.g. with a [`MockClock`] in tests, to simulate clock skews or time travel).
+//!
+//! Note that a [`Task`](crate::task::Task) does not know which cache it belongs to: its setters (e.g. [`Task::set_name`](crate::task::Task::set_name))
+//! always date it with the default clock. The clock of a cache only dates the changes the cache makes itself (e.g. [`CompleteCalendar::mark_completed`](crate::traits::CompleteCalendar::mark_completed),
+//! or items moved to the trash).
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The clock of the system. This is the default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// The clock of the system, shifted by an offset (e.g. to compensate a device whose clock is known to be wrong)
+#[derive(Clone, Copy, Debug)]
+pub struct OffsetClock {
+    offset: Duration,
+}
+
+impl OffsetClock {
+    pub fn new(offset: Duration) -> Self {
+        Self { offset }
+    }
+}
+
+impl Clock for OffsetClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset
+    }
+}
+
+/// A clock that only moves when it is told to. Clones of a mock clock share the same time
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Arc::new(Mutex::new(now)) }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Move the clock forward (or backward, for a negative duration)
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = *now + duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The default clock (see [`config::CLOCK`](crate::config::CLOCK))
+pub fn default_clock() -> Arc<dyn Clock> {
+    crate::config::CLOCK.lock().unwrap().clone()
+}
+
+/// The current time, according to the default clock
+pub(crate) fn now() -> DateTime<Utc> {
+    default_clock().now()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(Utc.ymd(2021, 3, 1).and_hms(12, 0, 0));
+        let shared = clock.clone();
+        clock.advance(Duration::days(1));
+        assert_eq!(shared.now(), Utc.ymd(2021, 3, 2).and_hms(12, 0, 0));
+        shared.set(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0));
+        assert_eq!(clock.now(), Utc.ymd(2020, 1, 1).and_hms(0, 0, 0));
+
+        let offset = OffsetClock::new(Duration::hours(-2));
+        assert!(offset.now() < SystemClock.now() - Duration::minutes(119));
+    }
+}
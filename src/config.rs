@@ -3,6 +3,9 @@
 use std::sync::{Arc, Mutex};
 use once_cell::sync::Lazy;
 
+use crate::clock::{Clock, SystemClock};
+use crate::task::FlagMapping;
+
 /// Part of the ProdID string that describes the organization (example of a ProdID string: `-//ABC Corporation//My Product//EN`).
 /// Feel free to override it when initing this library.
 pub static ORG_NAME: Lazy<Arc<Mutex<String>>> = Lazy::new(|| Arc::new(Mutex::new("My organization".to_string())));
@@ -10,3 +13,27 @@ pub static ORG_NAME: Lazy<Arc<Mutex<String>>> = Lazy::new(|| Arc::new(Mutex::new
 /// Part of the ProdID string that describes the product name (example of a ProdID string: `-//ABC Corporation//My Product//EN`).
 /// Feel free to override it when initing this library.
 pub static PRODUCT_NAME: Lazy<Arc<Mutex<String>>> = Lazy::new(|| Arc::new(Mutex::new("KitchenFridge".to_string())));
+
+/// Whether log messages may contain personal data, such as item URLs or UIDs.
+/// By default, these are replaced by a short hash (so that an item can still be followed across log lines, see [`crate::utils::redact`]).
+/// Feel free to enable it when debugging.
+pub static LOG_PERSONAL_DATA: Lazy<Arc<Mutex<bool>>> = Lazy::new(|| Arc::new(Mutex::new(false)));
+
+/// The domain appended to the UIDs of new items (e.g. `<random UUID>@example.com`), see [`crate::item::ItemUid`].
+/// By default, UIDs are bare UUIDs.
+pub static UID_DOMAIN: Lazy<Arc<Mutex<Option<String>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// A function that generates the UIDs of new items
+pub type UidGenerator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// A custom generator for the UIDs of new items, that replaces the default one (see [`crate::item::ItemUid`]).
+/// The returned UIDs must be globally unique.
+pub static UID_GENERATOR: Lazy<Arc<Mutex<Option<UidGenerator>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// The clock changes to items (e.g. their `LAST-MODIFIED` date) and syncs are dated with, see [`crate::clock`].
+/// Apps that run on devices whose clock is known to be wrong can compensate it with an [`OffsetClock`](crate::clock::OffsetClock).
+pub static CLOCK: Lazy<Arc<Mutex<Arc<dyn Clock>>>> = Lazy::new(|| Arc::new(Mutex::new(Arc::new(SystemClock))));
+
+/// How tasks are flagged (see [`Task::flagged`](crate::Task::flagged)).
+/// By default, this follows the convention of Nextcloud Tasks, i.e. flagged tasks are the ones with a high priority. Apps that store flags in their own `X-` property can change it here
+pub static FLAG_MAPPING: Lazy<Arc<Mutex<FlagMapping>>> = Lazy::new(|| Arc::new(Mutex::new(FlagMapping::default())));
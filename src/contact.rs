@@ -0,0 +1,226 @@
+//! Contacts (vCard items), available with the `addressbook` feature
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+use ical::property::Property;
+use url::Url;
+
+use crate::ical::escape::fold_line;
+use crate::item::SyncStatus;
+use crate::utils::random_url;
+
+/// The vCard version of contacts created by this crate. 3.0 is the most widely supported one
+const DEFAULT_VCARD_VERSION: &str = "3.0";
+
+/// A contact, as stored in a CardDAV address book
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Contact {
+    /// The contact URL
+    url: Url,
+    /// Persistent, globally unique identifier of this contact
+    uid: String,
+    /// The sync status of this contact
+    sync_status: SyncStatus,
+    /// The formatted name of this contact (`FN`), that is required by every vCard version
+    full_name: String,
+    /// The vCard version (e.g. "3.0" or "4.0")
+    version: String,
+
+    /// Every other property (emails, phone numbers, addresses...), that are kept as-is
+    /// so that they can be serialized into an equivalent vCard
+    properties: Vec<Property>,
+}
+
+impl Contact {
+    /// Create a brand new Contact that is not on a server yet.
+    /// This will pick a new (random) contact ID.
+    pub fn new(full_name: String, parent_address_book_url: &Url) -> Self {
+        let url = random_url(parent_address_book_url);
+        let uid = crate::item::ItemUid::generate();
+        Self::new_with_parameters(full_name, uid, url, SyncStatus::NotSynced, DEFAULT_VCARD_VERSION.to_string(), Vec::new())
+    }
+
+    /// Create a new Contact instance, that may be synced on the server already
+    pub fn new_with_parameters(full_name: String, uid: String, url: Url, sync_status: SyncStatus, version: String, properties: Vec<Property>) -> Self {
+        Self { url, uid, sync_status, full_name, version, properties }
+    }
+
+    pub fn url(&self) -> &Url       { &self.url         }
+    pub fn uid(&self) -> &str       { &self.uid         }
+    pub fn full_name(&self) -> &str { &self.full_name   }
+    pub fn version(&self) -> &str   { &self.version     }
+    pub fn sync_status(&self) -> &SyncStatus  { &self.sync_status }
+    pub fn properties(&self) -> &[Property]   { &self.properties  }
+
+    /// The email addresses of this contact
+    pub fn emails(&self) -> Vec<&str> {
+        self.property_values("EMAIL")
+    }
+
+    /// The phone numbers of this contact
+    pub fn phone_numbers(&self) -> Vec<&str> {
+        self.property_values("TEL")
+    }
+
+    fn property_values(&self, name: &str) -> Vec<&str> {
+        self.properties.iter()
+            .filter(|prop| prop.name == name)
+            .filter_map(|prop| prop.value.as_deref())
+            .collect()
+    }
+
+    pub fn set_sync_status(&mut self, new_status: SyncStatus) {
+        self.sync_status = new_status;
+    }
+
+    fn update_sync_status(&mut self) {
+        match &self.sync_status {
+            SyncStatus::NotSynced => (),
+            SyncStatus::LocallyModified(_) => (),
+            SyncStatus::Synced(prev_vt) => {
+                self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+            }
+            SyncStatus::LocallyDeleted(_) => {
+                log::warn!("Trying to update a contact that has previously been deleted. These changes will probably be ignored at next sync.");
+            },
+        }
+    }
+
+    /// Rename a contact.
+    /// This updates its "sync status"
+    pub fn set_full_name(&mut self, new_name: String) {
+        self.update_sync_status();
+        self.full_name = new_name;
+    }
+
+    /// Add an email address.
+    /// This updates its "sync status"
+    pub fn add_email(&mut self, email: String) {
+        self.update_sync_status();
+        self.properties.push(Property { name: "EMAIL".to_string(), params: None, value: Some(email) });
+    }
+
+    /// Add a phone number.
+    /// This updates its "sync status"
+    pub fn add_phone_number(&mut self, phone_number: String) {
+        self.update_sync_status();
+        self.properties.push(Property { name: "TEL".to_string(), params: None, value: Some(phone_number) });
+    }
+}
+
+
+/// Parse a vCard file into a [`Contact`]
+pub fn parse(content: &str, contact_url: Url, sync_status: SyncStatus) -> Result<Contact, Box<dyn Error>> {
+    let mut reader = ical::VcardParser::new(content.as_bytes());
+    let parsed_contact = match reader.next() {
+        None => return Err(format!("Invalid vCard data to parse for contact {}", contact_url).into()),
+        Some(contact) => match contact {
+            Err(err) => return Err(format!("Unable to parse vCard data for contact {}: {}", contact_url, err).into()),
+            Ok(contact) => contact,
+        }
+    };
+
+    let mut full_name = None;
+    let mut uid = None;
+    let mut version = None;
+    let mut properties = Vec::new();
+    for prop in parsed_contact.properties {
+        match prop.name.as_str() {
+            "FN" => { full_name = prop.value },
+            "UID" => { uid = prop.value },
+            "VERSION" => { version = prop.value },
+            _ => properties.push(prop),
+        }
+    }
+
+    let full_name = match full_name {
+        Some(name) => name,
+        None => return Err(format!("Missing FN for contact {}", contact_url).into()),
+    };
+    let uid = match uid {
+        Some(uid) => uid,
+        None => return Err(format!("Missing UID for contact {}", contact_url).into()),
+    };
+    let version = version.unwrap_or_else(|| DEFAULT_VCARD_VERSION.to_string());
+
+    if reader.next().map(|r| r.is_ok()) == Some(true) {
+        return Err("Parsing multiple contacts are not supported".into());
+    }
+
+    Ok(Contact::new_with_parameters(full_name, uid, contact_url, sync_status, version, properties))
+}
+
+/// Create a vCard file from a [`Contact`]
+pub fn build_vcard(contact: &Contact) -> String {
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        format!("VERSION:{}", contact.version()),
+        format!("UID:{}", contact.uid()),
+        format!("FN:{}", contact.full_name()),
+    ];
+    for prop in contact.properties() {
+        let mut line = prop.name.clone();
+        if let Some(params) = &prop.params {
+            for (key, values) in params {
+                line.push_str(&format!(";{}={}", key, values.join(",")));
+            }
+        }
+        line.push(':');
+        line.push_str(prop.value.as_deref().unwrap_or(""));
+        lines.push(line);
+    }
+    lines.push("END:VCARD".to_string());
+
+    lines.iter()
+        // Folding is the same as in iCal files (RFC 6350 section 3.2)
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::VersionTag;
+
+    const EXAMPLE_VCARD: &str = "BEGIN:VCARD\r\n\
+        VERSION:3.0\r\n\
+        UID:4b2e3a4e-8b2b-4d45-a1a4-1c2ae58d6b2f\r\n\
+        FN:Jane Doe\r\n\
+        EMAIL;TYPE=work:jane@example.com\r\n\
+        TEL:+1-555-0100\r\n\
+        NOTE:This is a rather long note\\, that will need to be folded because vCard\r\n  lines cannot exceed 75 octets\r\n\
+        END:VCARD\r\n";
+
+    #[test]
+    fn test_vcard_parsing() {
+        let url: Url = "https://some.server/addressbooks/jane.vcf".parse().unwrap();
+        let sync_status = SyncStatus::Synced(VersionTag::from(String::from("test-tag")));
+        let contact = parse(EXAMPLE_VCARD, url.clone(), sync_status.clone()).unwrap();
+
+        assert_eq!(contact.full_name(), "Jane Doe");
+        assert_eq!(contact.uid(), "4b2e3a4e-8b2b-4d45-a1a4-1c2ae58d6b2f");
+        assert_eq!(contact.version(), "3.0");
+        assert_eq!(contact.emails(), vec!["jane@example.com"]);
+        assert_eq!(contact.phone_numbers(), vec!["+1-555-0100"]);
+        assert_eq!(contact.sync_status(), &sync_status);
+
+        assert_eq!(build_vcard(&contact), EXAMPLE_VCARD);
+    }
+
+    #[test]
+    fn test_new_contact() {
+        let address_book_url: Url = "https://some.server/addressbooks/".parse().unwrap();
+        let mut contact = Contact::new("John Doe".to_string(), &address_book_url);
+        contact.add_email("john@example.com".to_string());
+        assert_eq!(contact.sync_status(), &SyncStatus::NotSynced);
+
+        let vcard = build_vcard(&contact);
+        let parsed = parse(&vcard, contact.url().clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(parsed.uid(), contact.uid());
+        assert_eq!(parsed.full_name(), "John Doe");
+        assert_eq!(parsed.emails(), vec!["john@example.com"]);
+    }
+}
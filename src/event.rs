@@ -2,26 +2,162 @@
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use ical::property::Property;
 use url::Url;
 
-use crate::item::SyncStatus;
+use crate::event_time::EventTime;
+use crate::item::{Classification, SyncStatus};
+use crate::utils::random_url;
+use crate::validation::Violation;
 
-/// TODO: implement `Event` one day.
-/// This crate currently only supports tasks, not calendar events.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// The `STATUS` of an event (RFC 5545 section 3.8.1.11)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    Cancelled,
+}
+
+impl EventStatus {
+    /// Parse the value of a `STATUS` property (e.g. `TENTATIVE`)
+    pub fn from_ical(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "TENTATIVE" => Some(Self::Tentative),
+            "CONFIRMED" => Some(Self::Confirmed),
+            "CANCELLED" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// The value of the `STATUS` property for this status
+    pub fn as_ical(&self) -> &'static str {
+        match self {
+            Self::Tentative => "TENTATIVE",
+            Self::Confirmed => "CONFIRMED",
+            Self::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+/// Whether an event takes time on the calendar of its attendees (its `TRANSP` property, RFC 5545 section 3.8.2.7)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Transparency {
+    /// The event blocks time, e.g. in free/busy lookups. This is the default
+    #[default]
+    Opaque,
+    /// The event does not block time (e.g. a reminder, or a whole-day event such as a birthday)
+    Transparent,
+}
+
+impl Transparency {
+    /// Parse the value of a `TRANSP` property
+    pub fn from_ical(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "OPAQUE" => Some(Self::Opaque),
+            "TRANSPARENT" => Some(Self::Transparent),
+            _ => None,
+        }
+    }
+
+    /// The value of the `TRANSP` property for this transparency
+    pub fn as_ical(&self) -> &'static str {
+        match self {
+            Self::Opaque => "OPAQUE",
+            Self::Transparent => "TRANSPARENT",
+        }
+    }
+}
+
+/// A calendar event
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event {
+    /// The event URL
+    url: Url,
+
+    /// Persistent, globally unique identifier for the calendar component (see [`crate::Task`])
     uid: String,
-    name: String,
+
+    /// The sync status of this item
     sync_status: SyncStatus,
+    /// The time this item was created, if known
+    creation_date: Option<DateTime<Utc>>,
+    /// The last time this item was modified
+    last_modified: DateTime<Utc>,
+
+    /// The display name of the event (its `SUMMARY`, that may be empty)
+    name: String,
+
+    /// The PRODID, as defined in iCal files
+    ical_prod_id: String,
+
+    /// Extra parameters that have not been parsed from the iCal file (e.g. its `DTSTART`, `DTEND` or `DURATION`).
+    /// They are needed to serialize this item into an equivalent iCal file
+    extra_parameters: Vec<Property>,
+
+    /// The iCal text this event has been downloaded as, as long as it has not been modified since (see [`Self::raw_ical`])
+    #[serde(default)]
+    raw_ical: Option<Box<str>>,
+
+    /// The definitions of the time zones (`VTIMEZONE` components, as iCal text) this event has been downloaded with, that its properties may refer to
+    #[serde(default)]
+    time_zones: Vec<String>,
 }
 
 impl Event {
-    pub fn new() -> Self {
-        unimplemented!();
+    /// Create a brand new event that is not on a server yet, from `start` to `end`.
+    /// This will pick a new (random) event ID.
+    pub fn new(name: String, start: DateTime<Utc>, end: DateTime<Utc>, parent_calendar_url: &Url) -> Self {
+        let now = crate::clock::now();
+        let date_time = |name: &str, dt: DateTime<Utc>| Property { name: name.to_string(), params: None, value: Some(dt.format("%Y%m%dT%H%M%SZ").to_string()) };
+        let extra_parameters = vec![date_time("DTSTART", start), date_time("DTEND", end)];
+        Self::new_with_parameters(name, crate::item::ItemUid::generate(), random_url(parent_calendar_url), SyncStatus::NotSynced, now, crate::ical::default_prod_id(), extra_parameters)
+            .with_creation_date(Some(now))
+    }
+
+    /// Create a new Event instance, that may be synced on the server already
+    pub fn new_with_parameters(name: String, uid: String, new_url: Url, sync_status: SyncStatus, last_modified: DateTime<Utc>,
+                               ical_prod_id: String, extra_parameters: Vec<Property>,
+                            ) -> Self
+    {
+        Self {
+            url: new_url,
+            uid,
+            name,
+            sync_status,
+            creation_date: None,
+            last_modified,
+            ical_prod_id,
+            extra_parameters,
+            raw_ical: None,
+            time_zones: Vec::new(),
+        }
+    }
+
+    /// Set the time this event was created (its `CREATED` property)
+    pub fn with_creation_date(mut self, creation_date: Option<DateTime<Utc>>) -> Self {
+        self.creation_date = creation_date;
+        self
+    }
+
+    /// Set the iCal text an event has been parsed from
+    pub(crate) fn with_raw_ical(mut self, raw_ical: String) -> Self {
+        self.raw_ical = Some(raw_ical.into_boxed_str());
+        self
+    }
+
+    /// Set the time zone definitions an event has been parsed with
+    pub(crate) fn with_time_zones(mut self, time_zones: Vec<String>) -> Self {
+        self.time_zones = time_zones;
+        self
+    }
+
+    /// The definitions of the time zones (`VTIMEZONE` components) this event has been parsed with, so that they are written along with it
+    pub(crate) fn time_zones(&self) -> &[String] {
+        &self.time_zones
     }
 
     pub fn url(&self) -> &Url {
-        unimplemented!();
+        &self.url
     }
 
     pub fn uid(&self) -> &str {
@@ -33,26 +169,191 @@ impl Event {
     }
 
     pub fn ical_prod_id(&self) -> &str {
-        unimplemented!()
+        &self.ical_prod_id
     }
 
     pub fn creation_date(&self) -> Option<&DateTime<Utc>> {
-        unimplemented!()
+        self.creation_date.as_ref()
     }
 
     pub fn last_modified(&self) -> &DateTime<Utc> {
-        unimplemented!()
+        &self.last_modified
+    }
+
+    pub fn extra_parameters(&self) -> &[Property] {
+        &self.extra_parameters
+    }
+
+    /// Returns the value of the first extra parameter with the given name (e.g. `LOCATION`)
+    pub fn extra_parameter(&self, name: &str) -> Option<&str> {
+        self.extra_parameters.iter()
+            .find(|prop| prop.name == name)
+            .and_then(|prop| prop.value.as_deref())
+    }
+
+    /// The revision number of this event (its `SEQUENCE` property), that is incremented every time it is locally modified after a sync
+    pub fn sequence(&self) -> u32 {
+        self.extra_parameter("SEQUENCE")
+            .and_then(|seq| seq.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// The start date of this event (its `DTSTART` property), if any. Whole days are at midnight UTC, see [`Self::start_time`]
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.extra_parameter("DTSTART")
+            .and_then(|start| crate::ical::parse_date_or_date_time(start).ok())
+    }
+
+    /// The end date of this event (exclusive), if it has a start date. Whole days are at midnight UTC, see [`Self::end_time`].
+    ///
+    /// This is its `DTEND` property, or its start date plus its `DURATION`. Events that have neither last one day when they start on a whole day,
+    /// and end when they start otherwise (RFC 5545 section 3.6.1)
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        let start = self.start()?;
+        match self.extra_parameter("DTEND") {
+            Some(end) => crate::ical::parse_date_or_date_time(end).ok(),
+            None => start.checked_add_signed(self.duration()?),
+        }
+    }
+
+    /// How long this event lasts: its `DURATION` property, or the time between its start and end dates (RFC 5545 allows either of them), if it has a start date
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        let start = self.start()?;
+        if let Some(duration) = self.extra_parameter("DURATION") {
+            return chrono::Duration::from_std(crate::ical::parse_duration(duration)?).ok();
+        }
+        match self.extra_parameter("DTEND") {
+            Some(end) => Some(crate::ical::parse_date_or_date_time(end).ok()? - start),
+            None if self.start_time()?.is_all_day() => Some(chrono::Duration::days(1)),
+            None => Some(chrono::Duration::zero()),
+        }
+    }
+
+    /// The start date of this event, telling whole days apart from date-times (see [`crate::event_time`])
+    pub fn start_time(&self) -> Option<EventTime> {
+        self.extra_parameter("DTSTART").and_then(EventTime::parse)
+    }
+
+    /// The end date of this event, telling whole days apart from date-times (see [`crate::event_time`])
+    pub fn end_time(&self) -> Option<EventTime> {
+        match self.extra_parameter("DTEND") {
+            Some(end) => EventTime::parse(end),
+            None => match self.start_time()? {
+                EventTime::Date(date) if self.extra_parameter("DURATION").is_none() => date.succ_opt().map(EventTime::Date),
+                _ => self.end().map(EventTime::DateTime),
+            },
+        }
+    }
+
+    /// The `STATUS` of this event, if any
+    pub fn status(&self) -> Option<EventStatus> {
+        self.extra_parameter("STATUS").and_then(EventStatus::from_ical)
+    }
+
+    /// Whether this event blocks time (its `TRANSP` property)
+    pub fn transparency(&self) -> Transparency {
+        self.extra_parameter("TRANSP").and_then(Transparency::from_ical).unwrap_or_default()
     }
 
     pub fn sync_status(&self) -> &SyncStatus {
         &self.sync_status
     }
+
+    /// The color of this event (its `COLOR` property, or the color properties of Apple or Outlook), if any
+    pub fn color(&self) -> Option<csscolorparser::Color> {
+        crate::ical::parse_color(&self.extra_parameters)
+    }
+
+    /// The access classification of this event (its `CLASS` property). Events without one are public, and events with an unknown one are private
+    pub fn classification(&self) -> Classification {
+        match self.extra_parameter("CLASS") {
+            None => Classification::Public,
+            Some(value) => Classification::from_ical(value).unwrap_or(Classification::Private),
+        }
+    }
+
+    /// The exact iCal text this event has been downloaded as, as long as it has not been modified since (see [`crate::Task::raw_ical`])
+    pub fn raw_ical(&self) -> Option<&str> {
+        self.raw_ical.as_deref()
+    }
+
+    /// Check whether this event complies with RFC 5545, and returns every issue otherwise
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        if self.uid.is_empty() {
+            violations.push(Violation::MissingUid);
+        }
+        crate::validation::check_event_properties(&self.extra_parameters, &mut violations);
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
     pub fn set_sync_status(&mut self, new_status: SyncStatus) {
         self.sync_status = new_status;
     }
 
+    fn update_sync_status(&mut self) {
+        self.raw_ical = None;
+        match &self.sync_status {
+            SyncStatus::NotSynced | SyncStatus::LocallyModified(_) => (),
+            SyncStatus::Synced(prev_vt) => {
+                self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+                let new_sequence = (self.sequence() + 1).to_string();
+                match self.extra_parameters.iter_mut().find(|prop| prop.name == "SEQUENCE") {
+                    Some(prop) => prop.value = Some(new_sequence),
+                    None => self.extra_parameters.push(Property { name: "SEQUENCE".to_string(), params: None, value: Some(new_sequence) }),
+                }
+            },
+            SyncStatus::LocallyDeleted(_) => {
+                log::warn!("Trying to update an item that has previously been deleted. These changes will probably be ignored at next sync.");
+            },
+        }
+    }
+
+    /// Rename an event.
+    /// This updates its "last modified" field
+    pub fn set_name(&mut self, new_name: String) {
+        self.update_sync_status();
+        self.last_modified = crate::clock::now();
+        self.name = new_name;
+    }
+
+    /// Move an event to `start`, keeping its duration.
+    /// This updates its "last modified" field
+    pub fn set_start(&mut self, start: DateTime<Utc>) {
+        let duration = self.duration();
+        self.update_sync_status();
+        self.last_modified = crate::clock::now();
+        self.extra_parameters.retain(|prop| prop.name != "DTSTART" && prop.name != "DTEND" && prop.name != "DURATION");
+        let date_time = |name: &str, dt: DateTime<Utc>| Property { name: name.to_string(), params: None, value: Some(dt.format("%Y%m%dT%H%M%SZ").to_string()) };
+        self.extra_parameters.push(date_time("DTSTART", start));
+        if let Some(end) = duration.and_then(|duration| start.checked_add_signed(duration)) {
+            self.extra_parameters.push(date_time("DTEND", end));
+        }
+    }
+
+    /// Returns a copy of this event, with a different UID, URL and sync status.
+    /// Its raw iCal text is kept only if it has the same UID, since this is the only identity the iCal text contains
+    pub(crate) fn clone_with_identity(&self, uid: String, url: Url, sync_status: SyncStatus) -> Self {
+        let raw_ical = if uid == self.uid { self.raw_ical.clone() } else { None };
+        Self {
+            uid, url, sync_status, raw_ical,
+            ..self.clone()
+        }
+    }
+
     #[cfg(any(test, feature = "integration_tests"))]
-    pub fn has_same_observable_content_as(&self, _other: &Event) -> bool {
-        unimplemented!();
+    pub fn has_same_observable_content_as(&self, other: &Event) -> bool {
+           self.url == other.url
+        && self.uid == other.uid
+        && self.name == other.name
+        // sync status must be the same variant, but we ignore its embedded version tag
+        && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
+        && self.start() == other.start()
+        && self.end() == other.end()
+        // last modified dates are ignored (they are not totally mocked in integration tests)
     }
 }
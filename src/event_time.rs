@@ -0,0 +1,104 @@
+//! Dates of items, that are either a date and time, or a whole day
+//!
+//! iCal dates (e.g. `DUE;VALUE=DATE:20240315`) have no time and no timezone: they name a whole day of whoever reads them.
+//! [`Task::start`](crate::Task::start) and [`Task::due`](crate::Task::due) return them at midnight UTC, which is the previous or the next day
+//! for users far from UTC, so that queries such as "what is due today" miss them near midnight.
+//!
+//! An [`EventTime`] keeps dates apart from date-times, and [`EventTime::overlaps`] checks them against a time range in the timezone of the user.
+
+use std::ops::Range;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+/// The date of an item (e.g. its start or due date)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventTime {
+    /// A whole day (an iCal `DATE`), in the timezone of whoever reads it
+    Date(NaiveDate),
+    /// An instant (an iCal `DATE-TIME`). Floating times (that have no timezone) are considered to be UTC, like everywhere in this crate
+    DateTime(DateTime<Utc>),
+}
+
+impl EventTime {
+    /// Parse an iCal `DATE` (e.g. `20240315`) or `DATE-TIME` (e.g. `20240315T093000Z`)
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        match NaiveDate::parse_from_str(value, "%Y%m%d") {
+            Ok(date) => Some(Self::Date(date)),
+            Err(_) => crate::ical::parse_date_or_date_time(value).ok().map(Self::DateTime),
+        }
+    }
+
+    /// Whether this is a whole day rather than an instant
+    pub fn is_all_day(&self) -> bool {
+        matches!(self, Self::Date(_))
+    }
+
+    /// The time span this covers in the timezone `tz`: a whole day lasts from its midnight until the next one, and an instant is an empty range.
+    /// (The last day chrono can represent is an empty range as well)
+    pub fn span<Tz: TimeZone>(&self, tz: &Tz) -> Range<DateTime<Utc>> {
+        match self {
+            Self::DateTime(dt) => *dt..*dt,
+            Self::Date(date) => start_of_day(*date, tz)..start_of_day(date.succ_opt().unwrap_or(*date), tz),
+        }
+    }
+
+    /// Whether this overlaps a time range, whole days being considered in the timezone `tz` (e.g. [`chrono::Local`]).
+    ///
+    /// Instants overlap the ranges that contain them (the start of a range included, its end excluded).
+    /// Whole days overlap the ranges that share any time with them, so that a task due on a day is part of "today" during all that day
+    pub fn overlaps<Tz: TimeZone>(&self, range: &Range<DateTime<Utc>>, tz: &Tz) -> bool {
+        match self {
+            Self::DateTime(dt) => range.start <= *dt && *dt < range.end,
+            Self::Date(_) => {
+                let span = self.span(tz);
+                span.start < range.end && range.start < span.end
+            },
+        }
+    }
+}
+
+/// The first instant of a day in a timezone.
+/// In case midnight does not exist that day (e.g. because of a DST change at midnight), this is midnight UTC, which is close enough
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms(0, 0, 0);
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_all_day_overlaps() {
+        let due = EventTime::parse("20240315").unwrap();
+        assert!(due.is_all_day());
+        assert_eq!(EventTime::parse("20240315T093000Z"), Some(EventTime::DateTime(Utc.ymd(2024, 3, 15).and_hms(9, 30, 0))));
+        assert_eq!(EventTime::parse("not a date"), None);
+
+        // "Today" in New York (UTC-4), asked at 22:00 local time, i.e. 02:00 UTC the next day
+        let new_york = FixedOffset::west(4 * 3600);
+        let today = Utc.ymd(2024, 3, 15).and_hms(4, 0, 0)..Utc.ymd(2024, 3, 16).and_hms(4, 0, 0);
+        assert!(due.overlaps(&today, &new_york));
+        assert_eq!(due.span(&new_york), today);
+        // The same day in UTC ends before the New York day does
+        assert!(due.overlaps(&(Utc.ymd(2024, 3, 16).and_hms(2, 0, 0)..Utc.ymd(2024, 3, 16).and_hms(3, 0, 0)), &new_york));
+        assert!(!due.overlaps(&(Utc.ymd(2024, 3, 16).and_hms(2, 0, 0)..Utc.ymd(2024, 3, 16).and_hms(3, 0, 0)), &Utc));
+
+        // "Today" in Tokyo (UTC+9) starts the day before in UTC
+        let tokyo = FixedOffset::east(9 * 3600);
+        let tokyo_morning = Utc.ymd(2024, 3, 14).and_hms(16, 0, 0)..Utc.ymd(2024, 3, 14).and_hms(17, 0, 0);
+        assert!(due.overlaps(&tokyo_morning, &tokyo));
+        assert!(!due.overlaps(&tokyo_morning, &Utc));
+
+        let meeting = EventTime::parse("20240315T093000Z").unwrap();
+        assert!(meeting.overlaps(&today, &new_york));
+        assert!(meeting.overlaps(&(Utc.ymd(2024, 3, 15).and_hms(9, 30, 0)..Utc.ymd(2024, 3, 15).and_hms(10, 0, 0)), &tokyo));
+        assert!(!meeting.overlaps(&(Utc.ymd(2024, 3, 15).and_hms(9, 0, 0)..Utc.ymd(2024, 3, 15).and_hms(9, 30, 0)), &tokyo));
+    }
+}
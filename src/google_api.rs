@@ -0,0 +1,74 @@
+//! Requests to the REST APIs of Google, that the `google_tasks` and `google_calendar` sources share
+//!
+//! Both APIs authenticate requests with an OAuth2 access token (which is the password of the [`Resource`]s), exchange JSON objects,
+//! and split their listings into pages.
+
+use std::error::Error;
+
+use reqwest::{Method, StatusCode};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::resource::{Resource, redact_url};
+use crate::utils::redact;
+
+/// One page of a listing of the API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ApiPage<T> {
+    #[serde(default = "Vec::new")]
+    pub(crate) items: Vec<T>,
+    pub(crate) next_page_token: Option<String>,
+}
+
+/// Send a request to the API, and check it has been successful.
+/// `404 Not Found` and `410 Gone` replies are returned as `None`
+pub(crate) async fn send<B: Serialize>(resource: &Resource, method: Method, query: &[(&str, &str)], body: Option<&B>) -> Result<Option<reqwest::Response>, Box<dyn Error>> {
+    let mut request = resource.http_client().request(method.clone(), resource.url().clone())
+        .bearer_auth(resource.password())
+        .query(query);
+    if let Some(body) = body {
+        request = request
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(body)?);
+    }
+    let response = resource.send(request).await?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND | StatusCode::GONE => Ok(None),
+        status if status.is_success() => Ok(Some(response)),
+        status => Err(format!("Unexpected HTTP status code {:?} for {} {}", status, method, redact(redact_url(resource.url()))).into()),
+    }
+}
+
+/// Send a request to the API, and parse its JSON reply
+pub(crate) async fn send_json<T: DeserializeOwned, B: Serialize>(resource: &Resource, method: Method, query: &[(&str, &str)], body: Option<&B>) -> Result<Option<T>, Box<dyn Error>> {
+    let response = match send(resource, method, query, body).await? {
+        None => return Ok(None),
+        Some(response) => response,
+    };
+    let text = response.text().await?;
+    resource.counters().record_received(text.len());
+    Ok(Some(serde_json::from_str(&text)?))
+}
+
+/// Fetch every page of a listing of the API, `page_size` items at a time
+pub(crate) async fn get_all_pages<T: DeserializeOwned>(resource: &Resource, query: &[(&str, &str)], page_size: &str) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut page_query = query.to_vec();
+        page_query.push(("maxResults", page_size));
+        if let Some(token) = &page_token {
+            page_query.push(("pageToken", token));
+        }
+        let page: ApiPage<T> = send_json(resource, Method::GET, &page_query, None::<&()>).await?
+            .ok_or_else(|| format!("{} does not exist", redact(redact_url(resource.url()))))?;
+        items.extend(page.items);
+        match page.next_page_token {
+            None => return Ok(items),
+            Some(token) => page_token = Some(token),
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! A source that syncs with the [Google Calendar API](https://developers.google.com/calendar/api/v3/reference), available with the `google_calendar` feature
+//!
+//! Google's CalDAV endpoint is limited (e.g. it does not list every calendar of an account), so this source talks to its REST API instead. It can be plugged into a
+//! [`Provider`](crate::provider::Provider), that syncs it into the same local cache as CalDAV sources:
+//! * every calendar of the calendar list of the account is a calendar (that only supports events), whose URL is the one of its events in the API
+//!   (`https://www.googleapis.com/calendar/v3/calendars/<calendar>/events`),
+//! * every event is an [`Event`], whose URL is the one of the event in the API, and whose UID is its Google identifier,
+//! * the `etag` of an event is its version tag.
+//!
+//! Only the fields Google Calendar knows about are synced: the title, the description, the location, the start and end dates, the status, the transparency,
+//! and the recurrence rules (`RRULE`, `EXDATE`, etc.). Date-times are stored (and uploaded) in UTC. Other properties of the local events are not uploaded,
+//! and are lost once an event is downloaded again. \
+//! Modified occurrences of recurring events are separate events for Google: they are synced as such, with a `RECURRENCE-ID` that tells which occurrence they replace.
+//!
+//! Getting an OAuth2 access token (with the `https://www.googleapis.com/auth/calendar` scope) is up to the app, this source only sends it along with its requests.
+//! Google assigns the identifiers of new events and calendars: new events are stored at the URL Google has chosen (see [`DavCalendar::add_item_and_fetch`]),
+//! and calendars cannot be created by this source.
+//! Tasks are available through the Google Tasks API instead (see the `google_tasks` feature).
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use csscolorparser::Color;
+use ical::property::Property;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::calendar::SupportedComponents;
+use crate::event::Event;
+use crate::event_time::EventTime;
+use crate::google_api::{get_all_pages, send, send_json};
+use crate::ical::escape::{escape_text, unescape_text};
+use crate::item::{Item, SyncStatus, VersionTag};
+use crate::metrics::RequestStats;
+use crate::resource::{Resource, redact_url};
+use crate::trace::SyncTrace;
+use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
+use crate::utils::redact;
+
+/// The root of the Google Calendar API
+pub const API_URL: &str = "https://www.googleapis.com/calendar/v3/";
+
+/// How many events (or calendars) are requested at once (this is the maximum the API allows for events)
+const PAGE_SIZE: &str = "2500";
+
+/// The properties that describe the recurrence of an event, which Google keeps as iCal lines
+const RECURRENCE_PROPERTIES: [&str; 4] = ["RRULE", "RDATE", "EXRULE", "EXDATE"];
+
+/// An entry of the calendar list of an account, as returned by the API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiCalendarListEntry {
+    id: String,
+    #[serde(default)]
+    summary: String,
+    summary_override: Option<String>,
+    background_color: Option<String>,
+}
+
+/// The start or end of an event, as exchanged with the API. Whole-day events have a `date`, the other ones a `dateTime`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiEventTime {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_zone: Option<String>,
+}
+
+/// An event, as exchanged with the API
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing)]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    start: Option<ApiEventTime>,
+    end: Option<ApiEventTime>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recurrence: Vec<String>,
+    #[serde(skip_serializing)]
+    original_start_time: Option<ApiEventTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transparency: Option<String>,
+    #[serde(skip_serializing)]
+    created: Option<String>,
+    #[serde(skip_serializing)]
+    updated: Option<String>,
+}
+
+
+/// The URL of an event of a calendar
+fn event_url(events_url: &Url, event_id: &str) -> Result<Url, Box<dyn Error>> {
+    Ok(Url::parse(&format!("{}/{}", events_url.as_str().trim_end_matches('/'), event_id))?)
+}
+
+/// Convert the start or end of an event returned by the API into an iCal property
+fn property_from_api_time(name: &str, time: &ApiEventTime) -> Option<Property> {
+    if let Some(date) = time.date.as_deref().and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()) {
+        return Some(Property {
+            name: name.to_string(),
+            params: Some(vec![("VALUE".to_string(), vec!["DATE".to_string()])]),
+            value: Some(date.format("%Y%m%d").to_string()),
+        });
+    }
+    let date_time = DateTime::parse_from_rfc3339(time.date_time.as_deref()?).ok()?;
+    Some(Property { name: name.to_string(), params: None, value: Some(date_time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()) })
+}
+
+/// Convert the start or end of an event into what the API expects
+fn event_time_to_api(time: EventTime) -> ApiEventTime {
+    match time {
+        EventTime::Date(day) => ApiEventTime { date: Some(day.format("%Y-%m-%d").to_string()), ..ApiEventTime::default() },
+        // Recurring events need a time zone, so that Google knows how to repeat them
+        EventTime::DateTime(time) => ApiEventTime {
+            date_time: Some(time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            time_zone: Some("UTC".to_string()),
+            ..ApiEventTime::default()
+        },
+    }
+}
+
+/// Parse a recurrence line of the API (e.g. `EXDATE;VALUE=DATE:20240315`) into an iCal property
+fn property_from_recurrence_line(line: &str) -> Option<Property> {
+    let (header, value) = line.split_once(':')?;
+    let mut parts = header.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params: Vec<(String, Vec<String>)> = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, values)| (key.to_string(), values.split(',').map(|value| value.trim_matches('"').to_string()).collect()))
+        .collect();
+    Some(Property { name, params: if params.is_empty() { None } else { Some(params) }, value: Some(value.to_string()) })
+}
+
+/// Write an iCal property as a recurrence line of the API
+fn recurrence_line(prop: &Property) -> String {
+    let mut line = prop.name.clone();
+    for (key, values) in prop.params.iter().flatten() {
+        line.push_str(&format!(";{}={}", key, values.join(",")));
+    }
+    format!("{}:{}", line, prop.value.as_deref().unwrap_or_default())
+}
+
+/// Convert an event returned by the API into an [`Event`]
+fn event_from_api(api_event: &ApiEvent, events_url: &Url) -> Result<Event, Box<dyn Error>> {
+    let id = api_event.id.as_deref().ok_or("Event has no id")?;
+    let url = event_url(events_url, id)?;
+    let etag = api_event.etag.clone().ok_or_else(|| format!("Event {} has no etag", redact(&url)))?;
+
+    let parse_time = |time: &str| DateTime::parse_from_rfc3339(time).map(|time| time.with_timezone(&Utc)).ok();
+    let last_modified = api_event.updated.as_deref().and_then(parse_time).unwrap_or_else(Utc::now);
+    let text = |name: &str, value: &str| Property { name: name.to_string(), params: None, value: Some(value.to_string()) };
+
+    let mut extra_parameters = Vec::new();
+    extra_parameters.extend(api_event.start.as_ref().and_then(|start| property_from_api_time("DTSTART", start)));
+    extra_parameters.extend(api_event.end.as_ref().and_then(|end| property_from_api_time("DTEND", end)));
+    extra_parameters.extend(api_event.original_start_time.as_ref().and_then(|start| property_from_api_time("RECURRENCE-ID", start)));
+    extra_parameters.extend(api_event.recurrence.iter().filter_map(|line| property_from_recurrence_line(line)));
+    if let Some(description) = api_event.description.as_deref().filter(|description| !description.is_empty()) {
+        extra_parameters.push(text("DESCRIPTION", &escape_text(description)));
+    }
+    if let Some(location) = api_event.location.as_deref().filter(|location| !location.is_empty()) {
+        extra_parameters.push(text("LOCATION", &escape_text(location)));
+    }
+    if let Some(status) = &api_event.status {
+        extra_parameters.push(text("STATUS", &status.to_ascii_uppercase()));
+    }
+    // Opaque is the default
+    if api_event.transparency.as_deref() == Some("transparent") {
+        extra_parameters.push(text("TRANSP", "TRANSPARENT"));
+    }
+
+    let event = Event::new_with_parameters(
+        api_event.summary.clone().unwrap_or_default(), id.to_string(), url,
+        SyncStatus::Synced(VersionTag::from(etag)), last_modified, crate::ical::default_prod_id(), extra_parameters,
+    );
+    Ok(event.with_creation_date(api_event.created.as_deref().and_then(parse_time)))
+}
+
+/// Convert an [`Event`] into what the API expects
+fn event_to_api(event: &Event, id: Option<String>) -> Result<ApiEvent, Box<dyn Error>> {
+    let start = event.start_time().ok_or_else(|| format!("Event {} has no start date, which Google Calendar requires", redact(event.url())))?;
+    let end = event.end_time().unwrap_or(start);
+
+    Ok(ApiEvent {
+        id,
+        status: event.status().map(|status| status.as_ical().to_ascii_lowercase()),
+        summary: Some(event.name().to_string()),
+        description: event.extra_parameter("DESCRIPTION").map(unescape_text),
+        location: event.extra_parameter("LOCATION").map(unescape_text),
+        start: Some(event_time_to_api(start)),
+        end: Some(event_time_to_api(end)),
+        recurrence: event.extra_parameters().iter()
+            .filter(|prop| RECURRENCE_PROPERTIES.contains(&prop.name.as_str()))
+            .map(recurrence_line)
+            .collect(),
+        transparency: Some(event.transparency().as_ical().to_ascii_lowercase()),
+        ..ApiEvent::default()
+    })
+}
+
+
+/// A Google calendar
+#[derive(Clone, Debug)]
+pub struct GoogleCalendar {
+    name: String,
+    /// Its URL is the one of its events in the API. Its password is the OAuth2 access token
+    resource: Resource,
+    color: Option<Color>,
+}
+
+impl GoogleCalendar {
+    /// Store an event returned by the API as a [`Item`]
+    fn item_from_api(&self, api_event: &ApiEvent) -> Result<Item, Box<dyn Error>> {
+        Ok(Item::Event(event_from_api(api_event, self.url())?))
+    }
+
+    fn event_resource(&self, url: &Url) -> Resource {
+        self.resource.combine(url.path())
+    }
+
+    /// Create an event, at a URL chosen by Google
+    async fn insert_event(&self, item: &Item) -> Result<Item, Box<dyn Error>> {
+        let event = match item {
+            Item::Event(event) => event,
+            _ => return Err(format!("Item {} cannot be stored in a Google calendar, which only supports events", redact(item.url())).into()),
+        };
+        let api_event = event_to_api(event, None)?;
+        let stored: ApiEvent = send_json(&self.resource, Method::POST, &[], Some(&api_event)).await?
+            .ok_or_else(|| format!("Calendar {} does not exist", redact(redact_url(self.url()))))?;
+        self.item_from_api(&stored)
+    }
+}
+
+#[async_trait]
+impl BaseCalendar for GoogleCalendar {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> SupportedComponents { SupportedComponents::EVENT }
+    fn color(&self) -> Option<&Color> { self.color.as_ref() }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        let stored = self.insert_event(&item).await?;
+        log::debug!("Item {} has been stored at {} by Google", redact(item.url()), redact(stored.url()));
+        Ok(stored.sync_status().clone())
+    }
+
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        let event = match &item {
+            Item::Event(event) => event,
+            _ => return Err(format!("Item {} cannot be stored in a Google calendar, which only supports events", redact(item.url())).into()),
+        };
+        let api_event = event_to_api(event, Some(event.uid().to_string()))?;
+        let stored: ApiEvent = send_json(&self.event_resource(item.url()), Method::PUT, &[], Some(&api_event)).await?
+            .ok_or_else(|| format!("Event {} does not exist", redact(item.url())))?;
+        Ok(self.item_from_api(&stored)?.sync_status().clone())
+    }
+}
+
+#[async_trait]
+impl DavCalendar for GoogleCalendar {
+    /// Create a calendar from the URL of its events in the API. The password of `resource` is used as the OAuth2 access token
+    fn new(name: String, resource: Resource, _supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self { name, resource, color }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        // Deleted events are listed only when they are asked for, and recurring events are listed once (rather than once per occurrence)
+        let query = [("showDeleted", "false"), ("singleEvents", "false")];
+        let api_events: Vec<ApiEvent> = get_all_pages(&self.resource, &query, PAGE_SIZE).await?;
+
+        let mut version_tags = HashMap::new();
+        for api_event in api_events {
+            match (&api_event.id, api_event.etag) {
+                (Some(id), Some(etag)) => { version_tags.insert(event_url(self.url(), id)?, VersionTag::from(etag)); },
+                _ => log::warn!("Ignoring an event of {} that has no id or no etag", redact(self.url())),
+            }
+        }
+        Ok(version_tags)
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        let api_event: Option<ApiEvent> = send_json(&self.event_resource(url), Method::GET, &[], None::<&ApiEvent>).await?;
+        match api_event {
+            // Deleted events may still be returned, as cancelled ones without any date
+            Some(api_event) if api_event.status.as_deref() == Some("cancelled") && api_event.start.is_none() => Ok(None),
+            api_event => api_event.map(|api_event| self.item_from_api(&api_event)).transpose(),
+        }
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+        // The API has no way to fetch several events at once (besides listing the whole calendar)
+        let mut items = Vec::new();
+        for url in urls {
+            items.push(self.get_item_by_url(url).await?);
+        }
+        Ok(items)
+    }
+
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        match send(&self.event_resource(item_url), Method::DELETE, &[], None::<&ApiEvent>).await? {
+            None => Err(format!("Event {} does not exist", redact(item_url)).into()),
+            Some(_) => Ok(()),
+        }
+    }
+
+    async fn add_item_and_fetch(&mut self, item: Item) -> Result<Item, Box<dyn Error>> {
+        self.insert_event(&item).await
+    }
+
+    async fn add_item_at_server_url(&mut self, item: Item) -> Result<Option<Item>, Box<dyn Error>> {
+        Ok(Some(self.insert_event(&item).await?))
+    }
+}
+
+
+/// A source that contains the calendars of the calendar list of a Google account
+pub struct GoogleCalendarSource {
+    /// Its URL is the root of the API. Its password is the OAuth2 access token
+    resource: Resource,
+    cached_calendars: Mutex<Option<HashMap<Url, Arc<RwLock<GoogleCalendar>>>>>,
+}
+
+impl GoogleCalendarSource {
+    /// Create a source that uses an OAuth2 access token
+    pub fn new(access_token: String) -> Self {
+        Self::new_with_http_client(access_token, reqwest::Client::new())
+    }
+
+    /// Create a source that uses an OAuth2 access token, and a specific HTTP client (e.g. with custom timeouts)
+    pub fn new_with_http_client(access_token: String, http_client: reqwest::Client) -> Self {
+        let api_url = Url::parse(API_URL).unwrap(/* this is a valid URL */);
+        Self {
+            resource: Resource::new_with_http_client(api_url, String::new(), access_token, http_client),
+            cached_calendars: Mutex::new(None),
+        }
+    }
+
+    /// Forget the calendars that have been fetched already, so that the next calls fetch them again
+    pub fn invalidate_cache(&self) {
+        *self.cached_calendars.lock().unwrap() = None;
+    }
+
+    async fn fetch_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<GoogleCalendar>>>, Box<dyn Error>> {
+        let list_resource = self.resource.combine(&format!("{}users/me/calendarList", self.resource.url().path()));
+        let entries: Vec<ApiCalendarListEntry> = get_all_pages(&list_resource, &[], PAGE_SIZE).await?;
+
+        let mut calendars = HashMap::new();
+        for entry in entries {
+            // Calendar identifiers may contain characters that are escaped in URLs (e.g. the `#` of shared holiday calendars)
+            let calendar_resource = self.resource.combine(&format!("{}calendars/{}/events", self.resource.url().path(), entry.id));
+            let calendar_url = calendar_resource.url().clone();
+            let color = entry.background_color.as_deref().and_then(|color| csscolorparser::parse(color).ok());
+            let name = entry.summary_override.unwrap_or(entry.summary);
+            let calendar = GoogleCalendar::new(name, calendar_resource, SupportedComponents::EVENT, color);
+            calendars.insert(calendar_url, Arc::new(RwLock::new(calendar)));
+        }
+        Ok(calendars)
+    }
+}
+
+#[async_trait]
+impl CalDavSource<GoogleCalendar> for GoogleCalendarSource {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<GoogleCalendar>>>, Box<dyn Error>> {
+        if let Some(calendars) = &*self.cached_calendars.lock().unwrap() {
+            return Ok(calendars.clone());
+        }
+        let calendars = self.fetch_calendars().await?;
+        *self.cached_calendars.lock().unwrap() = Some(calendars.clone());
+        Ok(calendars)
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<GoogleCalendar>>> {
+        match self.get_calendars().await {
+            Err(err) => {
+                log::warn!("Unable to fetch the Google calendars: {}", err);
+                None
+            },
+            Ok(calendars) => calendars.get(url).cloned(),
+        }
+    }
+
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<GoogleCalendar>>> {
+        self.cached_calendars.lock().unwrap().as_ref()?.get(url).cloned()
+    }
+
+    async fn create_calendar(&mut self, url: Url, _name: String, _supported_components: SupportedComponents, _color: Option<Color>)
+        -> Result<Arc<RwLock<GoogleCalendar>>, Box<dyn Error>>
+    {
+        match self.get_calendar(&url).await {
+            Some(calendar) => Ok(calendar),
+            None => Err(format!("Unable to create {}: Google chooses the URLs of new calendars, that must be created with Google Calendar", redact(redact_url(&url))).into()),
+        }
+    }
+
+    fn request_stats(&self) -> RequestStats {
+        self.resource.counters().snapshot()
+    }
+
+    fn set_trace(&self, trace: Option<Arc<SyncTrace>>) {
+        self.resource.set_trace(trace)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google_api::ApiPage;
+
+    fn events_url() -> Url {
+        format!("{}calendars/primary/events", API_URL).parse().unwrap()
+    }
+
+    #[test]
+    fn test_event_from_api() {
+        let page: ApiPage<ApiEvent> = serde_json::from_str(r#"{
+            "kind": "calendar#events",
+            "etag": "\"page-etag\"",
+            "items": [{
+                "kind": "calendar#event",
+                "id": "ZXZlbnQtMQ",
+                "etag": "\"event-etag\"",
+                "status": "confirmed",
+                "htmlLink": "https://www.google.com/calendar/event?eid=ZXZlbnQtMQ",
+                "created": "2024-03-01T10:00:00.000Z",
+                "updated": "2024-03-14T09:30:00.000Z",
+                "summary": "Weekly review",
+                "description": "Bring the figures, and the charts",
+                "location": "Room 4",
+                "start": { "dateTime": "2024-03-15T10:00:00+01:00", "timeZone": "Europe/Paris" },
+                "end": { "dateTime": "2024-03-15T11:30:00+01:00", "timeZone": "Europe/Paris" },
+                "recurrence": ["RRULE:FREQ=WEEKLY;BYDAY=FR", "EXDATE;VALUE=DATE-TIME:20240322T090000Z"],
+                "transparency": "transparent",
+                "iCalUID": "ZXZlbnQtMQ@google.com"
+            }]
+        }"#).unwrap();
+        assert!(page.next_page_token.is_none());
+
+        let event = event_from_api(&page.items[0], &events_url()).unwrap();
+        assert_eq!(event.url().as_str(), "https://www.googleapis.com/calendar/v3/calendars/primary/events/ZXZlbnQtMQ");
+        assert_eq!(event.uid(), "ZXZlbnQtMQ");
+        assert_eq!(event.name(), "Weekly review");
+        assert_eq!(event.extra_parameter("DESCRIPTION"), Some("Bring the figures\\, and the charts"));
+        assert_eq!(event.start(), Some("2024-03-15T09:00:00Z".parse().unwrap()));
+        assert_eq!(event.duration(), Some(chrono::Duration::minutes(90)));
+        assert_eq!(event.extra_parameter("RRULE"), Some("FREQ=WEEKLY;BYDAY=FR"));
+        assert_eq!(event.status(), Some(crate::event::EventStatus::Confirmed));
+        assert_eq!(event.transparency(), crate::event::Transparency::Transparent);
+        assert_eq!(event.creation_date(), Some(&"2024-03-01T10:00:00Z".parse().unwrap()));
+        assert_eq!(event.sync_status(), &SyncStatus::Synced(VersionTag::from("\"event-etag\"".to_string())));
+        assert!(event.validate().is_ok());
+
+        // Events are uploaded with the same fields (in UTC)
+        let api_event = event_to_api(&event, Some(event.uid().to_string())).unwrap();
+        assert_eq!(api_event.start.as_ref().unwrap().date_time.as_deref(), Some("2024-03-15T09:00:00Z"));
+        assert_eq!(api_event.recurrence, page.items[0].recurrence);
+        let downloaded_again = event_from_api(&ApiEvent { etag: page.items[0].etag.clone(), ..api_event }, &events_url()).unwrap();
+        assert!(downloaded_again.has_same_observable_content_as(&event));
+        assert_eq!(downloaded_again.extra_parameters().len(), event.extra_parameters().len());
+    }
+
+    #[test]
+    fn test_event_to_api() {
+        let start: DateTime<Utc> = "2024-03-15T09:00:00Z".parse().unwrap();
+        let event = Event::new("Dentist".to_string(), start, start + chrono::Duration::hours(1), &events_url());
+        assert_eq!(serde_json::to_value(event_to_api(&event, None).unwrap()).unwrap(), serde_json::json!({
+            "summary": "Dentist",
+            "description": null,
+            "location": null,
+            "start": { "dateTime": "2024-03-15T09:00:00Z", "timeZone": "UTC" },
+            "end": { "dateTime": "2024-03-15T10:00:00Z", "timeZone": "UTC" },
+            "transparency": "opaque",
+        }));
+
+        // Whole-day events have dates, and Google needs an end date
+        let day = ApiEventTime { date: Some("2024-03-15".to_string()), ..ApiEventTime::default() };
+        let all_day = ApiEvent { id: Some("ZGF5".to_string()), etag: Some("\"etag\"".to_string()), start: Some(day), ..ApiEvent::default() };
+        let all_day = event_from_api(&all_day, &events_url()).unwrap();
+        let api_event = event_to_api(&all_day, None).unwrap();
+        assert_eq!(api_event.end.unwrap().date.as_deref(), Some("2024-03-16"));
+
+        let undated = Event::new_with_parameters("Undated".to_string(), "uid".to_string(), events_url(), SyncStatus::NotSynced, Utc::now(), String::new(), Vec::new());
+        assert!(event_to_api(&undated, None).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! A source that syncs with the [Google Tasks API](https://developers.google.com/tasks/reference/rest), available with the `google_tasks` feature
+//!
+//! Google does not expose tasks through its CalDAV endpoint, so this source talks to its REST API instead. It can be plugged into a
+//! [`Provider`](crate::provider::Provider), that syncs it into the same local cache as CalDAV sources:
+//! * every task list is a calendar (that only supports to-do items), whose URL is the one of its tasks in the API (`https://tasks.googleapis.com/tasks/v1/lists/<list>/tasks`),
+//! * every task is a [`Task`], whose URL is the one of the task in the API, and whose UID is its Google identifier,
+//! * the `etag` of a task is its version tag.
+//!
+//! Only the fields Google Tasks knows about are synced: the title, the notes (as the `DESCRIPTION` of the task), the completion status (and date), and the due date
+//! (which is a whole day, since Google Tasks ignores its time). Other properties of the local tasks are not uploaded, and are lost once a task is downloaded again.
+//!
+//! Getting an OAuth2 access token (with the `https://www.googleapis.com/auth/tasks` scope) is up to the app, this source only sends it along with its requests.
+//! Google assigns the identifiers of new tasks and task lists: new tasks are stored at the URL Google has chosen (see [`DavCalendar::add_item_and_fetch`]),
+//! and task lists cannot be created by this source.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use csscolorparser::Color;
+use ical::property::Property;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::calendar::SupportedComponents;
+use crate::event_time::EventTime;
+use crate::google_api::{get_all_pages, send, send_json};
+use crate::ical::escape::{escape_text, unescape_text};
+use crate::item::{Item, SyncStatus, VersionTag};
+use crate::metrics::RequestStats;
+use crate::resource::{Resource, redact_url};
+use crate::task::{CompletionStatus, Task};
+use crate::trace::SyncTrace;
+use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
+use crate::utils::redact;
+
+/// The root of the Google Tasks API
+pub const API_URL: &str = "https://tasks.googleapis.com/tasks/v1/";
+
+/// How many tasks (or task lists) are requested at once (this is the maximum the API allows)
+const PAGE_SIZE: &str = "100";
+
+/// A task list, as returned by the API
+#[derive(Debug, Deserialize)]
+struct ApiTaskList {
+    id: String,
+    #[serde(default)]
+    title: String,
+}
+
+/// A task, as exchanged with the API
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiTask {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing)]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    notes: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    completed: Option<String>,
+    due: Option<String>,
+    #[serde(skip_serializing)]
+    updated: Option<String>,
+}
+
+/// The URL of a task of a task list
+fn task_url(list_url: &Url, task_id: &str) -> Result<Url, Box<dyn Error>> {
+    Ok(Url::parse(&format!("{}/{}", list_url.as_str().trim_end_matches('/'), task_id))?)
+}
+
+/// Convert a task returned by the API into a [`Task`]
+fn task_from_api(api_task: &ApiTask, list_url: &Url) -> Result<Task, Box<dyn Error>> {
+    let id = api_task.id.as_deref().ok_or("Task has no id")?;
+    let url = task_url(list_url, id)?;
+    let etag = api_task.etag.clone().ok_or_else(|| format!("Task {} has no etag", redact(&url)))?;
+
+    let parse_time = |time: &str| DateTime::parse_from_rfc3339(time).map(|time| time.with_timezone(&Utc)).ok();
+    let completion_status = match api_task.status.as_deref() {
+        Some("completed") => CompletionStatus::Completed(api_task.completed.as_deref().and_then(parse_time)),
+        _ => CompletionStatus::Uncompleted,
+    };
+    let last_modified = api_task.updated.as_deref().and_then(parse_time).unwrap_or_else(Utc::now);
+
+    let mut extra_parameters = Vec::new();
+    if let Some(notes) = api_task.notes.as_deref().filter(|notes| !notes.is_empty()) {
+        extra_parameters.push(Property { name: "DESCRIPTION".to_string(), params: None, value: Some(escape_text(notes)) });
+    }
+    // Google Tasks only keeps the date of due dates
+    if let Some(due) = api_task.due.as_deref().and_then(parse_time) {
+        extra_parameters.push(Property {
+            name: "DUE".to_string(),
+            params: Some(vec![("VALUE".to_string(), vec!["DATE".to_string()])]),
+            value: Some(due.format("%Y%m%d").to_string()),
+        });
+    }
+
+    Ok(Task::new_with_parameters(
+        api_task.title.clone().unwrap_or_default(), id.to_string(), url,
+        completion_status, SyncStatus::Synced(VersionTag::from(etag)),
+        None, last_modified, crate::ical::default_prod_id(), extra_parameters,
+    ))
+}
+
+/// Convert a [`Task`] into what the API expects
+fn task_to_api(task: &Task, id: Option<String>) -> ApiTask {
+    let format_day = |day: NaiveDate| format!("{}T00:00:00.000Z", day.format("%Y-%m-%d"));
+    let (status, completed) = match task.completion_status() {
+        CompletionStatus::Completed(date) => ("completed", Some(date.unwrap_or_else(Utc::now).to_rfc3339_opts(SecondsFormat::Millis, true))),
+        CompletionStatus::Uncompleted => ("needsAction", None),
+    };
+    let due = task.due_time().map(|due| match due {
+        EventTime::Date(day) => format_day(day),
+        EventTime::DateTime(time) => format_day(time.naive_utc().date()),
+    });
+
+    ApiTask {
+        id,
+        etag: None,
+        title: Some(task.name().to_string()),
+        notes: task.extra_parameter("DESCRIPTION").map(unescape_text),
+        status: Some(status.to_string()),
+        completed,
+        due,
+        updated: None,
+    }
+}
+
+
+/// A Google task list
+#[derive(Clone, Debug)]
+pub struct GoogleTaskList {
+    name: String,
+    /// Its URL is the one of its tasks in the API. Its password is the OAuth2 access token
+    resource: Resource,
+}
+
+impl GoogleTaskList {
+    /// Store a task returned by the API as a [`Item`]
+    fn item_from_api(&self, api_task: &ApiTask) -> Result<Item, Box<dyn Error>> {
+        Ok(Item::Task(task_from_api(api_task, self.url())?))
+    }
+
+    fn task_resource(&self, url: &Url) -> Resource {
+        self.resource.combine(url.path())
+    }
+
+    /// Create a task, at a URL chosen by Google
+    async fn insert_task(&self, item: &Item) -> Result<Item, Box<dyn Error>> {
+        let task = match item {
+            Item::Task(task) => task,
+            _ => return Err(format!("Item {} cannot be stored in a Google task list, which only supports tasks", redact(item.url())).into()),
+        };
+        let api_task = task_to_api(task, None);
+        let stored: ApiTask = send_json(&self.resource, Method::POST, &[], Some(&api_task)).await?
+            .ok_or_else(|| format!("Task list {} does not exist", redact(redact_url(self.url()))))?;
+        self.item_from_api(&stored)
+    }
+}
+
+#[async_trait]
+impl BaseCalendar for GoogleTaskList {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> SupportedComponents { SupportedComponents::TODO }
+    fn color(&self) -> Option<&Color> { None }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        let stored = self.insert_task(&item).await?;
+        log::debug!("Item {} has been stored at {} by Google", redact(item.url()), redact(stored.url()));
+        Ok(stored.sync_status().clone())
+    }
+
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        let task = match &item {
+            Item::Task(task) => task,
+            _ => return Err(format!("Item {} cannot be stored in a Google task list, which only supports tasks", redact(item.url())).into()),
+        };
+        let api_task = task_to_api(task, Some(task.uid().to_string()));
+        let stored: ApiTask = send_json(&self.task_resource(item.url()), Method::PUT, &[], Some(&api_task)).await?
+            .ok_or_else(|| format!("Task {} does not exist", redact(item.url())))?;
+        Ok(self.item_from_api(&stored)?.sync_status().clone())
+    }
+}
+
+#[async_trait]
+impl DavCalendar for GoogleTaskList {
+    /// Create a task list from the URL of its tasks in the API. The password of `resource` is used as the OAuth2 access token
+    fn new(name: String, resource: Resource, _supported_components: SupportedComponents, _color: Option<Color>) -> Self {
+        Self { name, resource }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        // Completed tasks that have been cleared from the list are hidden, but they still exist
+        let query = [("showCompleted", "true"), ("showHidden", "true")];
+        let api_tasks: Vec<ApiTask> = get_all_pages(&self.resource, &query, PAGE_SIZE).await?;
+
+        let mut version_tags = HashMap::new();
+        for api_task in api_tasks {
+            match (&api_task.id, api_task.etag) {
+                (Some(id), Some(etag)) => { version_tags.insert(task_url(self.url(), id)?, VersionTag::from(etag)); },
+                _ => log::warn!("Ignoring a task of {} that has no id or no etag", redact(self.url())),
+            }
+        }
+        Ok(version_tags)
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        let api_task: Option<ApiTask> = send_json(&self.task_resource(url), Method::GET, &[], None::<&ApiTask>).await?;
+        api_task.map(|api_task| self.item_from_api(&api_task)).transpose()
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+        // The API has no way to fetch several tasks at once (besides listing the whole task list)
+        let mut items = Vec::new();
+        for url in urls {
+            items.push(self.get_item_by_url(url).await?);
+        }
+        Ok(items)
+    }
+
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        match send(&self.task_resource(item_url), Method::DELETE, &[], None::<&ApiTask>).await? {
+            None => Err(format!("Task {} does not exist", redact(item_url)).into()),
+            Some(_) => Ok(()),
+        }
+    }
+
+    async fn add_item_and_fetch(&mut self, item: Item) -> Result<Item, Box<dyn Error>> {
+        self.insert_task(&item).await
+    }
+
+    async fn add_item_at_server_url(&mut self, item: Item) -> Result<Option<Item>, Box<dyn Error>> {
+        Ok(Some(self.insert_task(&item).await?))
+    }
+}
+
+
+/// A source that contains the task lists of a Google account
+pub struct GoogleTasksSource {
+    /// Its URL is the root of the API. Its password is the OAuth2 access token
+    resource: Resource,
+    cached_task_lists: Mutex<Option<HashMap<Url, Arc<RwLock<GoogleTaskList>>>>>,
+}
+
+impl GoogleTasksSource {
+    /// Create a source that uses an OAuth2 access token
+    pub fn new(access_token: String) -> Self {
+        Self::new_with_http_client(access_token, reqwest::Client::new())
+    }
+
+    /// Create a source that uses an OAuth2 access token, and a specific HTTP client (e.g. with custom timeouts)
+    pub fn new_with_http_client(access_token: String, http_client: reqwest::Client) -> Self {
+        let api_url = Url::parse(API_URL).unwrap(/* this is a valid URL */);
+        Self {
+            resource: Resource::new_with_http_client(api_url, String::new(), access_token, http_client),
+            cached_task_lists: Mutex::new(None),
+        }
+    }
+
+    /// Forget the task lists that have been fetched already, so that the next calls fetch them again
+    pub fn invalidate_cache(&self) {
+        *self.cached_task_lists.lock().unwrap() = None;
+    }
+
+    async fn fetch_task_lists(&self) -> Result<HashMap<Url, Arc<RwLock<GoogleTaskList>>>, Box<dyn Error>> {
+        let lists_resource = self.resource.combine(&format!("{}users/@me/lists", self.resource.url().path()));
+        let api_lists: Vec<ApiTaskList> = get_all_pages(&lists_resource, &[], PAGE_SIZE).await?;
+
+        let mut task_lists = HashMap::new();
+        for api_list in api_lists {
+            let list_resource = self.resource.combine(&format!("{}lists/{}/tasks", self.resource.url().path(), api_list.id));
+            let list_url = list_resource.url().clone();
+            let task_list = GoogleTaskList::new(api_list.title, list_resource, SupportedComponents::TODO, None);
+            task_lists.insert(list_url, Arc::new(RwLock::new(task_list)));
+        }
+        Ok(task_lists)
+    }
+}
+
+#[async_trait]
+impl CalDavSource<GoogleTaskList> for GoogleTasksSource {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<GoogleTaskList>>>, Box<dyn Error>> {
+        if let Some(task_lists) = &*self.cached_task_lists.lock().unwrap() {
+            return Ok(task_lists.clone());
+        }
+        let task_lists = self.fetch_task_lists().await?;
+        *self.cached_task_lists.lock().unwrap() = Some(task_lists.clone());
+        Ok(task_lists)
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<GoogleTaskList>>> {
+        match self.get_calendars().await {
+            Err(err) => {
+                log::warn!("Unable to fetch the Google task lists: {}", err);
+                None
+            },
+            Ok(task_lists) => task_lists.get(url).cloned(),
+        }
+    }
+
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<GoogleTaskList>>> {
+        self.cached_task_lists.lock().unwrap().as_ref()?.get(url).cloned()
+    }
+
+    async fn create_calendar(&mut self, url: Url, _name: String, _supported_components: SupportedComponents, _color: Option<Color>)
+        -> Result<Arc<RwLock<GoogleTaskList>>, Box<dyn Error>>
+    {
+        match self.get_calendar(&url).await {
+            Some(task_list) => Ok(task_list),
+            None => Err(format!("Unable to create {}: Google chooses the URLs of new task lists, that must be created with Google Tasks", redact(redact_url(&url))).into()),
+        }
+    }
+
+    fn request_stats(&self) -> RequestStats {
+        self.resource.counters().snapshot()
+    }
+
+    fn set_trace(&self, trace: Option<Arc<SyncTrace>>) {
+        self.resource.set_trace(trace)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google_api::ApiPage;
+
+    fn list_url() -> Url {
+        format!("{}lists/MTIzNDU/tasks", API_URL).parse().unwrap()
+    }
+
+    #[test]
+    fn test_task_from_api() {
+        let page: ApiPage<ApiTask> = serde_json::from_str(r#"{
+            "kind": "tasks#tasks",
+            "etag": "\"page-etag\"",
+            "items": [{
+                "kind": "tasks#task",
+                "id": "dGFzay0x",
+                "etag": "\"task-etag\"",
+                "title": "Buy milk",
+                "updated": "2024-03-14T09:30:00.000Z",
+                "selfLink": "https://www.googleapis.com/tasks/v1/lists/MTIzNDU/tasks/dGFzay0x",
+                "position": "00000000000000000000",
+                "notes": "Semi-skimmed, 2 bottles",
+                "status": "completed",
+                "due": "2024-03-15T00:00:00.000Z",
+                "completed": "2024-03-14T09:30:00.000Z"
+            }]
+        }"#).unwrap();
+        assert!(page.next_page_token.is_none());
+
+        let task = task_from_api(&page.items[0], &list_url()).unwrap();
+        assert_eq!(task.url().as_str(), "https://tasks.googleapis.com/tasks/v1/lists/MTIzNDU/tasks/dGFzay0x");
+        assert_eq!(task.uid(), "dGFzay0x");
+        assert_eq!(task.name(), "Buy milk");
+        assert_eq!(task.extra_parameter("DESCRIPTION"), Some("Semi-skimmed\\, 2 bottles"));
+        assert_eq!(task.due_time(), Some(EventTime::Date(NaiveDate::from_ymd(2024, 3, 15))));
+        assert_eq!(task.completion_status(), &CompletionStatus::Completed(Some("2024-03-14T09:30:00Z".parse().unwrap())));
+        assert_eq!(task.sync_status(), &SyncStatus::Synced(VersionTag::from("\"task-etag\"".to_string())));
+
+        // Tasks are uploaded with the same fields
+        let api_task = task_to_api(&task, Some(task.uid().to_string()));
+        assert_eq!(api_task, ApiTask { etag: None, updated: None, ..page.items[0].clone() });
+        let downloaded_again = task_from_api(&ApiTask { etag: page.items[0].etag.clone(), updated: page.items[0].updated.clone(), ..api_task }, &list_url()).unwrap();
+        assert!(downloaded_again.has_same_observable_content_as(&task));
+    }
+
+    #[test]
+    fn test_task_to_api() {
+        let mut task = Task::new("Call the plumber".to_string(), false, &list_url());
+        assert_eq!(serde_json::to_value(task_to_api(&task, None)).unwrap(), serde_json::json!({
+            "title": "Call the plumber",
+            "notes": null,
+            "status": "needsAction",
+            "completed": null,
+            "due": null,
+        }));
+
+        task.complete("2024-03-14T09:30:00Z".parse().unwrap());
+        let api_task = task_to_api(&task, None);
+        assert_eq!(api_task.status.as_deref(), Some("completed"));
+        assert_eq!(api_task.completed.as_deref(), Some("2024-03-14T09:30:00.000Z"));
+    }
+}
@@ -3,26 +3,103 @@
 use std::error::Error;
 
 use chrono::{DateTime, Utc};
+use csscolorparser::Color;
 use ics::properties::{Completed, Created, LastModified, PercentComplete, Status, Summary};
-use ics::{ICalendar, ToDo};
+use ics::{Event as IcsEvent, ICalendar, ToDo};
 use ics::components::Parameter as IcsParameter;
 use ics::components::Property as IcsProperty;
 use ical::property::Property as IcalProperty;
 
 use crate::Task;
+use crate::event::Event;
 use crate::item::Item;
-use crate::task::CompletionStatus;
+use crate::task::{CompletionStatus, TaskStatus};
+use super::escape::{escape_text, quote_param_value};
 
 
 /// Create an iCal item from a `crate::item::Item`
 pub fn build_from(item: &Item) -> Result<String, Box<dyn Error>> {
     match item {
         Item::Task(t) => build_from_task(t),
-        _ => unimplemented!(),
+        Item::Event(e) => build_from_event(e),
     }
 }
 
 pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
+    let mut calendar = ICalendar::new("2.0", task.ical_prod_id());
+    calendar.add_todo(build_todo(task));
+
+    Ok(with_time_zones(calendar.to_string(), &[(task.extra_parameters(), task.time_zones())]))
+}
+
+pub fn build_from_event(event: &Event) -> Result<String, Box<dyn Error>> {
+    let mut calendar = ICalendar::new("2.0", event.ical_prod_id());
+    calendar.add_event(build_event(event));
+
+    Ok(with_time_zones(calendar.to_string(), &[(event.extra_parameters(), event.time_zones())]))
+}
+
+/// Create a single iCal file that contains every given item
+///
+/// The color of the calendar (if any) is written both as a `COLOR` (RFC 7986) and as an `X-APPLE-CALENDAR-COLOR`, that more clients understand.
+/// The time zones the items refer to are defined by `VTIMEZONE` components (see [`with_time_zones`]).
+pub fn build_calendar<'a, I: IntoIterator<Item = &'a Item>>(items: I, prod_id: &str, color: Option<&Color>) -> Result<String, Box<dyn Error>> {
+    let mut calendar = ICalendar::new("2.0", prod_id);
+    if let Some(color) = color {
+        calendar.push(IcsProperty::new("COLOR", color.to_hex_string()));
+        calendar.push(IcsProperty::new("X-APPLE-CALENDAR-COLOR", format!("{}FF", color.to_hex_string().to_ascii_uppercase())));
+    }
+    let mut components = Vec::new();
+    for item in items {
+        match item {
+            Item::Task(t) => {
+                calendar.add_todo(build_todo(t));
+                components.push((t.extra_parameters(), t.time_zones()));
+            },
+            Item::Event(e) => {
+                calendar.add_event(build_event(e));
+                components.push((e.extra_parameters(), e.time_zones()));
+            },
+        }
+    }
+
+    Ok(with_time_zones(calendar.to_string(), &components))
+}
+
+/// Add the definitions (`VTIMEZONE` components) of the time zones that `components` refer to, before the first component of an iCal file.
+///
+/// `components` are the properties of every item, along with the definitions they have been downloaded with (date-times set by this crate are in UTC).
+/// A warning is logged for the time zones that are not defined there, that are written as they are.
+fn with_time_zones(mut ical: String, components: &[(&[IcalProperty], &[String])]) -> String {
+    let mut tzids: Vec<&str> = Vec::new();
+    for (properties, _) in components {
+        let params = properties.iter().filter_map(|prop| prop.params.as_ref()).flatten();
+        for (_, values) in params.filter(|(name, _)| name.eq_ignore_ascii_case("TZID")) {
+            for tzid in values {
+                if !tzids.contains(&tzid.as_str()) {
+                    tzids.push(tzid);
+                }
+            }
+        }
+    }
+
+    let mut definitions = String::new();
+    for tzid in tzids {
+        let definition = components.iter()
+            .flat_map(|(_, time_zones)| time_zones.iter())
+            .find(|time_zone| time_zone.lines().any(|line| line.split_once(':').map(|(name, value)| name.eq_ignore_ascii_case("TZID") && value == tzid) == Some(true)));
+        match definition {
+            Some(definition) => definitions.push_str(definition),
+            None => log::warn!("Time zone {} is not defined, it is written without its definition", tzid),
+        }
+    }
+    if let Some(position) = ical.find("\r\nBEGIN:").map(|position| position + 2) {
+        ical.insert_str(position, &definitions);
+    }
+    ical
+}
+
+fn build_todo(task: &Task) -> ToDo<'_> {
     let s_last_modified = format_date_time(task.last_modified());
 
     let mut todo = ToDo::new(
@@ -34,11 +111,15 @@ pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
         todo.push(Created::new(format_date_time(dt)))
     );
     todo.push(LastModified::new(s_last_modified));
-    todo.push(Summary::new(task.name()));
+    todo.push(Summary::new(escape_text(task.name())));
 
     match task.completion_status() {
         CompletionStatus::Uncompleted => {
-            todo.push(Status::needs_action());
+            todo.push(match task.status() {
+                TaskStatus::InProcess => Status::in_process(),
+                TaskStatus::Cancelled => Status::cancelled(),
+                _ => Status::needs_action(),
+            });
         },
         CompletionStatus::Completed(completion_date) => {
             todo.push(PercentComplete::new("100"));
@@ -50,15 +131,45 @@ pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
     }
 
     // Also add fields that we have not handled
+    // A task cannot have both a DUE and a DURATION (RFC 5545 section 3.6.2). The DUE is kept, since this is what other clients show
+    let has_due = task.extra_parameter("DUE").is_some();
     for ical_property in task.extra_parameters() {
+        if has_due && ical_property.name == "DURATION" {
+            continue;
+        }
         let ics_property = ical_to_ics_property(ical_property.clone());
         todo.push(ics_property);
     }
 
-    let mut calendar = ICalendar::new("2.0", task.ical_prod_id());
-    calendar.add_todo(todo);
+    todo
+}
+
+fn build_event(event: &Event) -> IcsEvent<'_> {
+    let s_last_modified = format_date_time(event.last_modified());
 
-    Ok(calendar.to_string())
+    let mut ics_event = IcsEvent::new(
+        event.uid(),
+        s_last_modified.clone(),
+    );
+
+    if let Some(dt) = event.creation_date() {
+        ics_event.push(Created::new(format_date_time(dt)));
+    }
+    ics_event.push(LastModified::new(s_last_modified));
+    if !event.name().is_empty() {
+        ics_event.push(Summary::new(escape_text(event.name())));
+    }
+
+    // An event cannot have both a DTEND and a DURATION (RFC 5545 section 3.6.1). They are equivalent, and the DTEND is kept, like the DUE of tasks
+    let has_end = event.extra_parameter("DTEND").is_some();
+    for ical_property in event.extra_parameters() {
+        if has_end && ical_property.name == "DURATION" {
+            continue;
+        }
+        ics_event.push(ical_to_ics_property(ical_property.clone()));
+    }
+
+    ics_event
 }
 
 fn format_date_time(dt: &DateTime<Utc>) -> String {
@@ -73,7 +184,10 @@ fn ical_to_ics_property(prop: IcalProperty) -> IcsProperty<'static> {
     };
     prop.params.map(|v| {
         for (key, vec_values) in v {
-            let values = vec_values.join(";");
+            let values = vec_values.iter()
+                .map(|value| quote_param_value(value))
+                .collect::<Vec<_>>()
+                .join(",");
             ics_prop.add(IcsParameter::new(key, values));
         }
     });
@@ -85,7 +199,10 @@ fn ical_to_ics_property(prop: IcalProperty) -> IcsProperty<'static> {
 mod tests {
     use super::*;
     use crate::Task;
+    use crate::task::FlagMapping;
+    use crate::item::{SyncStatus, VersionTag};
     use crate::config::{ORG_NAME, PRODUCT_NAME};
+    use chrono::TimeZone;
 
     #[test]
     fn test_ical_from_completed_task() {
@@ -131,7 +248,7 @@ mod tests {
 
     fn build_task(completed: bool) -> (String, String, String) {
         let cal_url = "http://my.calend.ar/id".parse().unwrap();
-        let now = Utc::now();
+        let now = crate::clock::now();
         let s_now = format_date_time(&now);
 
         let task = Item::Task(Task::new(
@@ -142,6 +259,294 @@ mod tests {
         (s_now, task.uid().to_string(), ical)
     }
 
+    #[test]
+    fn test_ical_calendar_from_tasks() {
+        let cal_url = "http://my.calend.ar/id".parse().unwrap();
+        let first = Item::Task(Task::new(String::from("First task"), false, &cal_url));
+        let second = Item::Task(Task::new(String::from("Second task"), true, &cal_url));
+
+        let ical = build_calendar(vec![&first, &second], "-//Some//Product//EN", None).unwrap();
+        assert_eq!(ical.matches("BEGIN:VCALENDAR").count(), 1);
+        assert_eq!(ical.matches("BEGIN:VTODO").count(), 2);
+        assert!(ical.contains("PRODID:-//Some//Product//EN\r\n"));
+        assert!(ical.contains(&format!("UID:{}\r\n", first.uid())));
+        assert!(ical.contains(&format!("UID:{}\r\n", second.uid())));
+    }
+
+    #[test]
+    fn test_ical_calendar_time_zones() {
+        let ical = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Some//Product//EN\r\n\
+            BEGIN:VTIMEZONE\r\n\
+            TZID:Europe/Paris\r\n\
+            BEGIN:STANDARD\r\n\
+            DTSTART:19701025T030000\r\n\
+            TZOFFSETFROM:+0200\r\n\
+            TZOFFSETTO:+0100\r\n\
+            END:STANDARD\r\n\
+            END:VTIMEZONE\r\n\
+            BEGIN:VTODO\r\n\
+            UID:zoned\r\n\
+            DTSTAMP:20211103T212345Z\r\n\
+            SUMMARY:Zoned task\r\n\
+            DUE;TZID=Europe/Paris:20211104T090000\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+        let cal_url = "http://my.calend.ar/id".parse().unwrap();
+        let zoned = crate::ical::parse(ical, "http://my.calend.ar/id/zoned.ics".parse().unwrap(), SyncStatus::NotSynced).unwrap();
+        let other = Item::Task(Task::new(String::from("Task in UTC"), false, &cal_url));
+
+        let built = build_calendar(vec![&zoned, &other], "-//Some//Product//EN", None).unwrap();
+        assert_eq!(built.matches("BEGIN:VTIMEZONE\r\nTZID:Europe/Paris\r\n").count(), 1);
+        assert!(built.find("END:VTIMEZONE").unwrap() < built.find("BEGIN:VTODO").unwrap());
+        assert!(built.contains("DUE;TZID=Europe/Paris:20211104T090000\r\n"));
+
+        // Tasks that refer to no time zone come without definitions
+        let built = build_calendar(vec![&other], "-//Some//Product//EN", None).unwrap();
+        assert!(!built.contains("VTIMEZONE"));
+    }
+
+    #[test]
+    fn test_ical_from_task_completion() {
+        let cal_url = "http://my.calend.ar/id".parse().unwrap();
+        let percent = IcalProperty { name: String::from("PERCENT-COMPLETE"), params: None, value: Some(String::from("40")) };
+        let mut task = Task::new_with_parameters(
+            String::from("A task in progress"), String::from("some-uid"), cal_url, CompletionStatus::Uncompleted,
+            SyncStatus::Synced(VersionTag::from(String::from("some-tag"))), None, Utc::now(),
+            crate::ical::default_prod_id(), vec![percent]);
+
+        let now = Utc::now();
+        task.complete(now);
+        assert!(matches!(task.sync_status(), SyncStatus::LocallyModified(_)));
+        assert_eq!(task.last_modified(), &now);
+        let ical = build_from(&Item::Task(task.clone())).unwrap();
+        assert_eq!(ical.matches("PERCENT-COMPLETE").count(), 1);
+        assert!(ical.contains("PERCENT-COMPLETE:100\r\n"));
+        assert!(ical.contains(&format!("COMPLETED:{}\r\n", format_date_time(&now))));
+        assert!(ical.contains("STATUS:COMPLETED\r\n"));
+
+        task.uncomplete();
+        assert_eq!(task.completion_status(), &CompletionStatus::Uncompleted);
+        let ical = build_from(&Item::Task(task)).unwrap();
+        assert!(!ical.contains("PERCENT-COMPLETE"));
+        assert!(!ical.contains("COMPLETED:"));
+        assert!(ical.contains("STATUS:NEEDS-ACTION\r\n"));
+    }
+
+    #[test]
+    fn test_ical_escaping() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let name = "Buy milk, eggs; and\nsome bread at the bakery that is a bit far away, but which is worth it";
+        let attendee = IcalProperty {
+            name: String::from("ATTENDEE"),
+            params: Some(vec![(String::from("ROLE"), vec![String::from("CHAIR")]), (String::from("DELEGATED-FROM"), vec![String::from("mailto:a@example.com"), String::from("mailto:b@example.com")])]),
+            value: Some(String::from("mailto:c@example.com")),
+        };
+        let task = Task::new_with_parameters(String::from(name), String::from("some-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), vec![attendee]);
+
+        let ical = build_from(&Item::Task(task)).unwrap();
+        assert!(ical.replace("\r\n ", "").contains("SUMMARY:Buy milk\\, eggs\\; and\\nsome bread"));
+        assert!(ical.replace("\r\n ", "").contains("DELEGATED-FROM=\"mailto:a@example.com\",\"mailto:b@example.com\""));
+        assert!(ical.split("\r\n").all(|line| line.len() <= 75));
+
+        let parsed = crate::ical::parse(&ical, cal_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(parsed.name(), name);
+    }
+
+    #[test]
+    fn test_ical_extra_properties() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let mut task = Task::new_with_parameters(String::from("Parent task"), String::from("some-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::Synced(VersionTag::from(String::from("some-tag"))), None, Utc::now(), crate::ical::default_prod_id(), Vec::new());
+
+        assert!(task.set_extra_property("DESCRIPTION", "Not an X- property").is_err());
+        assert!(task.set_extra_property("X-", "No name").is_err());
+        assert!(task.set_extra_property("X-MY APP", "Invalid name").is_err());
+        assert!(matches!(task.sync_status(), SyncStatus::Synced(_)));
+
+        task.set_extra_property("x-oc-hidesubtasks", "0").unwrap();
+        task.set_extra_property("X-OC-HIDESUBTASKS", "1").unwrap();
+        task.set_extra_property("X-MYAPP-NOTE", "milk, eggs").unwrap();
+        assert!(matches!(task.sync_status(), SyncStatus::LocallyModified(_)));
+        assert_eq!(task.sequence(), 1);
+
+        let ical = build_from(&Item::Task(task.clone())).unwrap();
+        assert_eq!(ical.matches("X-OC-HIDESUBTASKS").count(), 1);
+        assert!(ical.contains("X-OC-HIDESUBTASKS:1\r\n"));
+        assert!(ical.contains("X-MYAPP-NOTE:milk\\, eggs\r\n"));
+
+        let parsed = crate::ical::parse(&ical, cal_url, SyncStatus::NotSynced).unwrap();
+        let parsed = match parsed { Item::Task(task) => task, _ => panic!("Expected a task") };
+        assert_eq!(parsed.extra_property("X-MYAPP-NOTE").as_deref(), Some("milk, eggs"));
+
+        assert!(task.remove_extra_property("X-OC-HIDESUBTASKS").unwrap());
+        assert!(!task.remove_extra_property("X-OC-HIDESUBTASKS").unwrap());
+        assert!(!build_from(&Item::Task(task)).unwrap().contains("X-OC-HIDESUBTASKS"));
+    }
+
+    #[test]
+    fn test_ical_durations() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let property = |name: &str, value: &str| IcalProperty { name: String::from(name), params: None, value: Some(String::from(value)) };
+        let task = Task::new_with_parameters(String::from("Task with a duration"), String::from("some-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), vec![property("DTSTART", "20240315T090000Z"), property("DURATION", "PT1H30M")]);
+        assert!(task.validate().is_ok());
+        assert_eq!(task.duration(), Some(chrono::Duration::minutes(90)));
+        assert_eq!(task.due(), Some(Utc.ymd(2024, 3, 15).and_hms(10, 30, 0)));
+        let ical = build_from(&Item::Task(task.clone())).unwrap();
+        assert!(ical.contains("DURATION:PT1H30M\r\n"));
+        assert!(!ical.contains("DUE"));
+
+        // The DUE wins over the DURATION
+        let mut properties = task.extra_parameters().to_vec();
+        properties.push(property("DUE", "20240316T090000Z"));
+        let task = Task::new_with_parameters(String::from("Task with both"), String::from("other-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), properties);
+        assert!(task.validate().is_err());
+        let parsed = crate::ical::parse(&build_from(&Item::Task(task)).unwrap(), cal_url, SyncStatus::NotSynced).unwrap();
+        let parsed = match parsed { Item::Task(task) => task, _ => panic!("Expected a task") };
+        assert!(parsed.validate().is_ok());
+        assert_eq!(parsed.due(), Some(Utc.ymd(2024, 3, 16).and_hms(9, 0, 0)));
+        assert_eq!(parsed.duration(), Some(chrono::Duration::hours(24)));
+    }
+
+    #[test]
+    fn test_ical_events() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let start = Utc.ymd(2024, 3, 15).and_hms(9, 0, 0);
+        let event = Event::new(String::from("Meeting, at last"), start, start + chrono::Duration::hours(2), &cal_url);
+        assert!(event.validate().is_ok());
+        assert_eq!(event.duration(), Some(chrono::Duration::hours(2)));
+
+        let ical = build_from(&Item::Event(event.clone())).unwrap();
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ical.contains("SUMMARY:Meeting\\, at last\r\n"));
+        assert!(ical.contains("DTSTART:20240315T090000Z\r\n"));
+        assert!(ical.contains("DTEND:20240315T110000Z\r\n"));
+        let parsed = crate::ical::parse(&ical, event.url().clone(), SyncStatus::NotSynced).unwrap();
+        assert!(parsed.has_same_observable_content_as(&Item::Event(event.clone())));
+
+        // The DTEND wins over the DURATION, that means the same
+        let property = |name: &str, value: &str| IcalProperty { name: String::from(name), params: None, value: Some(String::from(value)) };
+        let mut properties = event.extra_parameters().to_vec();
+        properties.push(property("DURATION", "PT1H"));
+        let both = Event::new_with_parameters(String::from("Event with both"), String::from("other-uid"), cal_url.clone(),
+            SyncStatus::NotSynced, Utc::now(), crate::ical::default_prod_id(), properties);
+        assert!(both.validate().is_err());
+        let parsed = crate::ical::parse(&build_from(&Item::Event(both)).unwrap(), cal_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(parsed.validate().is_ok());
+        assert_eq!(parsed.unwrap_event().end(), Some(start + chrono::Duration::hours(2)));
+
+        // Moving an event keeps its duration
+        let mut moved = event.clone();
+        moved.set_start(start + chrono::Duration::days(1));
+        assert_eq!(moved.end(), Some(start + chrono::Duration::hours(26)));
+
+        let task = Item::Task(Task::new(String::from("A task"), false, &cal_url));
+        let ical = build_calendar(vec![&Item::Event(event), &task], "-//Some//Product//EN", None).unwrap();
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 1);
+        assert_eq!(ical.matches("BEGIN:VTODO").count(), 1);
+        assert_eq!(crate::ical::parse_all(&ical, &cal_url).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_ical_colors() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let apple_color = IcalProperty { name: String::from("X-APPLE-CALENDAR-COLOR"), params: None, value: Some(String::from("#FF8000FF")) };
+        let mut task = Task::new_with_parameters(String::from("Colored task"), String::from("some-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), vec![apple_color]);
+        assert_eq!(task.color(), Some(csscolorparser::parse("#ff8000").unwrap()));
+
+        task.set_color(Some(&csscolorparser::parse("turquoise").unwrap()));
+        let ical = build_from(&Item::Task(task.clone())).unwrap();
+        assert!(ical.contains("COLOR:#40e0d0\r\n"));
+        assert!(!ical.contains("X-APPLE-CALENDAR-COLOR"));
+        let parsed = crate::ical::parse(&ical, cal_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(parsed.color(), Some(csscolorparser::parse("turquoise").unwrap()));
+
+        task.set_color(None);
+        assert_eq!(task.color(), None);
+
+        let color = csscolorparser::parse("#ff8000").unwrap();
+        let ical = build_calendar(vec![&Item::Task(task)], "-//Some//Product//EN", Some(&color)).unwrap();
+        assert!(ical.contains("COLOR:#ff8000\r\n"));
+        assert!(ical.contains("X-APPLE-CALENDAR-COLOR:#FF8000FF\r\n"));
+    }
+
+    #[test]
+    fn test_ical_sort_order() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let sort_order = |value: &str| IcalProperty { name: String::from("X-APPLE-SORT-ORDER"), params: None, value: Some(String::from(value)) };
+        let first = Task::new_with_parameters(String::from("First"), String::from("first-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), vec![sort_order("100")]);
+        let second = Task::new_with_parameters(String::from("Second"), String::from("second-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, Utc::now(), crate::ical::default_prod_id(), vec![sort_order("101")]);
+        // Never reordered: this is where it has been created
+        let mut moved = Task::new_with_parameters(String::from("Moved"), String::from("moved-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::Synced(VersionTag::from(String::from("some-tag"))), Some(Utc.ymd(2021, 11, 3).and_hms(0, 0, 0)), Utc::now(), crate::ical::default_prod_id(), Vec::new());
+        assert_eq!(moved.sort_order(), None);
+        assert_eq!(moved.effective_sort_order(), Some(657590400));
+
+        assert!(moved.reorder_between(Some(&first), Some(&second)).is_err());
+        assert!(matches!(moved.sync_status(), SyncStatus::Synced(_)));
+        moved.reorder_between(None, Some(&first)).unwrap();
+        assert_eq!(moved.sort_order(), Some(99));
+        moved.reorder_between(Some(&second), None).unwrap();
+        assert_eq!(moved.sort_order(), Some(102));
+        assert!(matches!(moved.sync_status(), SyncStatus::LocallyModified(_)));
+
+        let ical = build_from(&Item::Task(moved.clone())).unwrap();
+        assert_eq!(ical.matches("X-APPLE-SORT-ORDER").count(), 1);
+        assert!(ical.contains("X-APPLE-SORT-ORDER:102\r\n"));
+        let parsed = crate::ical::parse(&ical, cal_url, SyncStatus::NotSynced).unwrap();
+        let parsed = match parsed { Item::Task(task) => task, _ => panic!("Expected a task") };
+        assert_eq!(parsed.sort_order(), Some(102));
+
+        let third = parsed;
+        moved.reorder_between(Some(&first), Some(&third)).unwrap();
+        assert_eq!(moved.sort_order(), Some(101));
+        moved.set_sort_order(None);
+        assert!(!build_from(&Item::Task(moved)).unwrap().contains("X-APPLE-SORT-ORDER"));
+    }
+
+    #[test]
+    fn test_ical_flags() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let priority = IcalProperty { name: String::from("PRIORITY"), params: None, value: Some(String::from("3")) };
+        let mut task = Task::new_with_parameters(String::from("Important task"), String::from("some-uid"), cal_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::Synced(VersionTag::from(String::from("some-tag"))), None, Utc::now(), crate::ical::default_prod_id(), vec![priority]);
+        let nextcloud = FlagMapping::default();
+        let custom = FlagMapping::ExtraProperty(String::from("X-MYAPP-FLAGGED"));
+        assert_eq!(task.priority(), Some(3));
+        assert!(task.flagged_with(&nextcloud));
+        assert!(!task.flagged_with(&FlagMapping::Priority(2)));
+        assert!(!task.flagged_with(&custom));
+
+        // Flagging a task that already is does not modify it
+        task.set_flagged_with(&nextcloud, true).unwrap();
+        assert!(matches!(task.sync_status(), SyncStatus::Synced(_)));
+        task.set_flagged_with(&nextcloud, false).unwrap();
+        assert_eq!(task.priority(), None);
+        assert!(matches!(task.sync_status(), SyncStatus::LocallyModified(_)));
+
+        task.set_flagged_with(&custom, true).unwrap();
+        let ical = build_from(&Item::Task(task.clone())).unwrap();
+        assert!(ical.contains("X-MYAPP-FLAGGED:1\r\n"));
+        assert!(!ical.contains("PRIORITY"));
+        let parsed = crate::ical::parse(&ical, cal_url, SyncStatus::NotSynced).unwrap();
+        let parsed = match parsed { Item::Task(task) => task, _ => panic!("Expected a task") };
+        assert!(parsed.flagged_with(&custom));
+        assert!(!parsed.flagged_with(&nextcloud));
+
+        task.set_flagged_with(&nextcloud, true).unwrap();
+        assert!(build_from(&Item::Task(task.clone())).unwrap().contains("PRIORITY:1\r\n"));
+        task.set_flagged_with(&custom, false).unwrap();
+        assert!(!task.flagged_with(&custom));
+        assert!(task.set_flagged_with(&FlagMapping::ExtraProperty(String::from("FLAGGED")), true).is_err());
+    }
+
     #[test]
     #[ignore]
     fn test_ical_from_event() {
@@ -0,0 +1,161 @@
+//! Decoding of iCal content that is not plain UTF-8
+//!
+//! Older servers and exported files may use `ENCODING=QUOTED-PRINTABLE` properties (from vCalendar 1.0), `CHARSET` parameters, or even whole files in Latin-1.
+//! These are decoded when items are parsed, so that items are always stored (and written back) in UTF-8.
+
+use std::borrow::Cow;
+
+use ical::property::Property;
+
+/// Decode the raw content of an iCal file.
+///
+/// UTF-8 is expected, but content that is not valid UTF-8 is considered as Latin-1 (ISO-8859-1), rather than being rejected.
+pub fn decode_bytes(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            log::info!("iCal content is not valid UTF-8, decoding it as Latin-1");
+            decode_latin1(bytes)
+        },
+    }
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Quoted-printable values end with a `=` when they continue on the next line (a "soft line break"), that is not folded the iCal way.
+/// This joins such lines, so that the iCal parser sees a single property.
+pub(crate) fn unfold_quoted_printable(content: &str) -> Cow<'_, str> {
+    if !content.contains("QUOTED-PRINTABLE") {
+        return Cow::Borrowed(content);
+    }
+
+    let mut unfolded = String::with_capacity(content.len());
+    let mut in_quoted_printable = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        let continued = line.starts_with([' ', '\t']);
+        if !continued {
+            in_quoted_printable = trimmed.split(':').next().map(|name| name.to_uppercase().contains("QUOTED-PRINTABLE")).unwrap_or(false);
+        }
+
+        if in_quoted_printable && trimmed.ends_with('=') {
+            unfolded.push_str(&trimmed[..trimmed.len() - 1]);
+        } else {
+            unfolded.push_str(line);
+        }
+    }
+    Cow::Owned(unfolded)
+}
+
+/// Returns a copy of this property, whose value is decoded according to its `ENCODING` and `CHARSET` parameters (that are then removed)
+pub(crate) fn decode_property(prop: &Property) -> Property {
+    let mut decoded = prop.clone();
+    let params = match &mut decoded.params {
+        None => return decoded,
+        Some(params) => params,
+    };
+    let encoding = take_param(params, "ENCODING");
+    let charset = take_param(params, "CHARSET");
+    if params.is_empty() {
+        decoded.params = None;
+    }
+
+    if let Some(value) = &decoded.value {
+        let raw = match encoding.as_deref() {
+            Some(enc) if enc.eq_ignore_ascii_case("QUOTED-PRINTABLE") => decode_quoted_printable(value),
+            _ => value.as_bytes().to_vec(),
+        };
+        decoded.value = Some(decode_charset(&raw, charset.as_deref()));
+    }
+    decoded
+}
+
+fn take_param(params: &mut Vec<(String, Vec<String>)>, name: &str) -> Option<String> {
+    let index = params.iter().position(|(key, _)| key.eq_ignore_ascii_case(name))?;
+    let (_, values) = params.remove(index);
+    values.into_iter().next()
+}
+
+fn decode_quoted_printable(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'=' {
+            let hex = bytes.get(i+1..i+3).and_then(|h| std::str::from_utf8(h).ok());
+            match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                },
+                None => {
+                    // Invalid sequences are kept as-is
+                    decoded.push(b'=');
+                    i += 1;
+                    continue;
+                }
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}
+
+fn decode_charset(raw: &[u8], charset: Option<&str>) -> String {
+    match charset.map(|c| c.to_uppercase()).as_deref() {
+        None | Some("UTF-8") | Some("UTF8") | Some("US-ASCII") => {
+            match std::str::from_utf8(raw) {
+                Ok(s) => s.to_string(),
+                Err(_) => decode_latin1(raw),
+            }
+        },
+        // Windows-1252 only differs from Latin-1 for rarely used characters
+        Some("ISO-8859-1") | Some("LATIN1") | Some("LATIN-1") | Some("WINDOWS-1252") | Some("CP1252") => decode_latin1(raw),
+        Some(other) => {
+            log::warn!("Unsupported charset {}. Decoding it as UTF-8", other);
+            String::from_utf8_lossy(raw).to_string()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+    use crate::item::SyncStatus;
+
+    const EXAMPLE_QUOTED_PRINTABLE: &str = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        PRODID:-//Some old//Organizer//EN\r\n\
+        BEGIN:VTODO\r\n\
+        UID:quoted-printable-task\r\n\
+        DTSTAMP:20210321T001600\r\n\
+        SUMMARY;ENCODING=QUOTED-PRINTABLE;CHARSET=ISO-8859-1:R=E9server le restaurant pour l'anniversai=\r\n\
+        re de No=EBl\r\n\
+        DESCRIPTION;ENCODING=QUOTED-PRINTABLE;CHARSET=UTF-8:Caf=C3=A9 =3D coffee\r\n\
+        END:VTODO\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_quoted_printable_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let item = crate::ical::parse(EXAMPLE_QUOTED_PRINTABLE, item_url, SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task();
+        assert_eq!(task.name(), "Réserver le restaurant pour l'anniversaire de Noël");
+        assert_eq!(task.extra_parameter("DESCRIPTION"), Some("Café = coffee"));
+        assert!(task.extra_parameters()[0].params.is_none());
+    }
+
+    #[test]
+    fn test_latin1_content() {
+        let latin1: Vec<u8> = "SUMMARY:No".bytes().chain(std::iter::once(0xEB)).chain("l".bytes()).collect();
+        assert_eq!(decode_bytes(&latin1), "SUMMARY:Noël");
+        assert_eq!(decode_bytes("\u{feff}SUMMARY:Noël".as_bytes()), "SUMMARY:Noël");
+    }
+}
@@ -0,0 +1,108 @@
+//! Escaping and line folding of iCal content lines (RFC 5545 section 3.1 and 3.3.11)
+//!
+//! These are applied by the [`builder`](super::build_from), but are also useful to apps that write iCal content by hand.
+
+/// The maximum length of a content line, excluding the line break
+pub const MAX_LINE_OCTETS: usize = 75;
+
+/// Escape a TEXT value: backslashes, commas, semicolons and line breaks
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {
+                // CRLF is a single line break
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                escaped.push_str("\\n");
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The reverse of [`escape_text`]
+pub fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Quote a parameter value if it contains characters that are not allowed unquoted (`:`, `;` or `,`).
+///
+/// Double quotes cannot be represented in a parameter value at all, so they are removed.
+pub fn quote_param_value(value: &str) -> String {
+    let value = value.replace('"', "");
+    if value.contains([':', ';', ',']) {
+        format!("\"{}\"", value)
+    } else {
+        value
+    }
+}
+
+/// Fold a content line so that no line is longer than 75 octets, and terminate it with a CRLF.
+///
+/// Multi-byte UTF-8 characters are never split across lines.
+pub fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut current_len = 0;
+    for c in line.chars() {
+        if current_len + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // The leading space of the continuation line counts
+            current_len = 1;
+        }
+        folded.push(c);
+        current_len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_text() {
+        let text = "Buy milk, eggs; and\r\nsome C:\\ stuff\nÜnïcödé";
+        let escaped = escape_text(text);
+        assert_eq!(escaped, "Buy milk\\, eggs\\; and\\nsome C:\\\\ stuff\\nÜnïcödé");
+        assert_eq!(unescape_text(&escaped), text.replace("\r\n", "\n"));
+        assert_eq!(unescape_text("Upper\\Ncase"), "Upper\ncase");
+
+        assert_eq!(quote_param_value("Attendee dial-in"), "Attendee dial-in");
+        assert_eq!(quote_param_value("mailto:someone@example.com"), "\"mailto:someone@example.com\"");
+    }
+
+    #[test]
+    fn test_fold_line() {
+        assert_eq!(fold_line("SUMMARY:short"), "SUMMARY:short\r\n");
+
+        let line = format!("SUMMARY:{}", "é".repeat(50));
+        let folded = fold_line(&line);
+        for physical_line in folded.split("\r\n").filter(|l| !l.is_empty()) {
+            assert!(physical_line.len() <= MAX_LINE_OCTETS);
+        }
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
@@ -4,8 +4,18 @@
 
 mod parser;
 pub use parser::parse;
+pub use parser::parse_all;
+pub use parser::parse_calendar_metadata;
+pub(crate) use parser::parse_date_or_date_time;
+pub(crate) use parser::parse_duration;
 mod builder;
 pub use builder::build_from;
+pub use builder::build_calendar;
+pub mod escape;
+pub mod encoding;
+
+use csscolorparser::Color;
+use ical::property::Property;
 
 use crate::config::{ORG_NAME, PRODUCT_NAME};
 
@@ -13,6 +23,17 @@ pub fn default_prod_id() -> String {
     format!("-//{}//{}//EN", ORG_NAME.lock().unwrap(), PRODUCT_NAME.lock().unwrap())
 }
 
+/// The properties a color is read from, by order of preference: `COLOR` (RFC 7986), then the ones of Apple and Outlook
+pub(crate) const COLOR_PROPERTIES: &[&str] = &["COLOR", "X-APPLE-CALENDAR-COLOR", "X-OUTLOOK-COLOR"];
+
+/// Returns the first valid color among `properties` (see [`COLOR_PROPERTIES`])
+pub(crate) fn parse_color(properties: &[Property]) -> Option<Color> {
+    COLOR_PROPERTIES.iter()
+        .flat_map(|name| properties.iter().filter(move |prop| prop.name.eq_ignore_ascii_case(name)))
+        .filter_map(|prop| prop.value.as_deref())
+        .find_map(|value| csscolorparser::parse(value.trim()).ok())
+}
+
 
 
 #[cfg(test)]
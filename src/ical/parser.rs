@@ -7,15 +7,28 @@ use chrono::{DateTime, TimeZone, Utc};
 use url::Url;
 
 use crate::Item;
+use crate::calendar::CalendarMetadata;
+use crate::event::Event;
 use crate::item::SyncStatus;
 use crate::Task;
-use crate::task::CompletionStatus;
-use crate::Event;
-
+use crate::task::{CompletionStatus, TaskStatus};
+use crate::utils::redact;
+use super::escape::unescape_text;
+use super::encoding::{decode_property, unfold_quoted_printable};
+
+/// The maximum length (in bytes) of a content line, once unfolded.
+/// This is plenty for any property, including inline attachments, but prevents absurd content from using huge amounts of memory
+const MAX_LINE_LENGTH: usize = 16 * 1024 * 1024;
+/// The maximum length (in bytes) of the name and parameters of a property (i.e. of a content line, up to its value).
+/// The underlying parser takes a quadratic time to parse parameters, so that a single line with thousands of them would take minutes
+const MAX_PROPERTY_HEADER_LENGTH: usize = 8 * 1024;
 
 /// Parse an iCal file into the internal representation [`crate::Item`]
+#[tracing::instrument(target = "kitchen_fridge::ical", level = "trace", skip_all, fields(item = %redact(crate::resource::redact_url(&item_url))))]
 pub fn parse(content: &str, item_url: Url, sync_status: SyncStatus) -> Result<Item, Box<dyn Error>> {
-    let mut reader = ical::IcalParser::new(content.as_bytes());
+    let unfolded = unfold_quoted_printable(content);
+    check_content_lines(&unfolded).map_err(|err| format!("Invalid iCal data for item {}: {}", item_url, err))?;
+    let mut reader = ical::IcalParser::new(unfolded.as_bytes());
     let parsed_item = match reader.next() {
         None => return Err(format!("Invalid iCal data to parse for item {}", item_url).into()),
         Some(item) => match item {
@@ -29,87 +42,14 @@ pub fn parse(content: &str, item_url: Url, sync_status: SyncStatus) -> Result<It
         .unwrap_or_else(|| super::default_prod_id());
 
     let item = match assert_single_type(&parsed_item)? {
-        CurrentType::Event(_) => {
-            Item::Event(Event::new())
+        CurrentType::Event(event) => {
+            let event = parse_event(event, item_url, sync_status, ical_prod_id)?;
+            Item::Event(event.with_raw_ical(content.to_string()).with_time_zones(extract_time_zones(content)))
         },
 
-        CurrentType::Todo(todo) => {
-            let mut name = None;
-            let mut uid = None;
-            let mut completed = false;
-            let mut last_modified = None;
-            let mut completion_date = None;
-            let mut creation_date = None;
-            let mut extra_parameters = Vec::new();
-
-            for prop in &todo.properties {
-                match prop.name.as_str() {
-                    "SUMMARY" => { name = prop.value.clone() },
-                    "UID" => { uid = prop.value.clone() },
-                    "DTSTAMP" => {
-                        // The property can be specified once, but is not mandatory
-                        // "This property specifies the date and time that the information associated with
-                        //  the calendar component was last revised in the calendar store."
-                        // "In the case of an iCalendar object that doesn't specify a "METHOD"
-                        //  property [e.g.: VTODO and VEVENT], this property is equivalent to the "LAST-MODIFIED" property".
-                        last_modified = parse_date_time_from_property(&prop.value);
-                    },
-                    "LAST-MODIFIED" => {
-                        // The property can be specified once, but is not mandatory
-                        // "This property specifies the date and time that the information associated with
-                        //  the calendar component was last revised in the calendar store."
-                        // In practise, for VEVENT and VTODO, this is generally the same value as DTSTAMP.
-                        last_modified = parse_date_time_from_property(&prop.value);
-                    }
-                    "COMPLETED" => {
-                        // The property can be specified once, but is not mandatory
-                        // "This property defines the date and time that a to-do was
-                        //  actually completed."
-                        completion_date = parse_date_time_from_property(&prop.value)
-                    },
-                    "CREATED" => {
-                        // The property can be specified once, but is not mandatory
-                        creation_date = parse_date_time_from_property(&prop.value)
-                    },
-                    "STATUS" => {
-                        // Possible values:
-                        //   "NEEDS-ACTION" ;Indicates to-do needs action.
-                        //   "COMPLETED"    ;Indicates to-do completed.
-                        //   "IN-PROCESS"   ;Indicates to-do in process of.
-                        //   "CANCELLED"    ;Indicates to-do was cancelled.
-                        if prop.value.as_ref().map(|s| s.as_str()) == Some("COMPLETED") {
-                            completed = true;
-                        }
-                    }
-                    _ => {
-                        // This field is not supported. Let's store it anyway, so that we are able to re-create an identical iCal file
-                        extra_parameters.push(prop.clone());
-                    }
-                }
-            }
-            let name = match name {
-                Some(name) => name,
-                None => return Err(format!("Missing name for item {}", item_url).into()),
-            };
-            let uid = match uid {
-                Some(uid) => uid,
-                None => return Err(format!("Missing UID for item {}", item_url).into()),
-            };
-            let last_modified = match last_modified {
-                Some(dt) => dt,
-                None => return Err(format!("Missing DTSTAMP for item {}, but this is required by RFC5545", item_url).into()),
-            };
-            let completion_status = match completed {
-                false => {
-                    if completion_date.is_some() {
-                        log::warn!("Task {:?} has an inconsistent content: its STATUS is not completed, yet it has a COMPLETED timestamp at {:?}", uid, completion_date);
-                    }
-                    CompletionStatus::Uncompleted
-                },
-                true => CompletionStatus::Completed(completion_date),
-            };
-
-            Item::Task(Task::new_with_parameters(name, uid, item_url, completion_status, sync_status, creation_date, last_modified, ical_prod_id, extra_parameters))
+        CurrentType::Todo(todo) => match parse_todo(todo, item_url, sync_status, ical_prod_id)? {
+            Item::Task(task) => Item::Task(task.with_raw_ical(content.to_string()).with_time_zones(extract_time_zones(content))),
+            item => item,
         },
     };
 
@@ -122,11 +62,282 @@ pub fn parse(content: &str, item_url: Url, sync_status: SyncStatus) -> Result<It
     Ok(item)
 }
 
+/// Parse every to-do and event of an iCal file that may contain several components (and even several `VCALENDAR`s).
+///
+/// Each item is given a new random URL in `calendar_url`, and a [`SyncStatus::NotSynced`] status.
+#[tracing::instrument(target = "kitchen_fridge::ical", level = "trace", skip_all, fields(calendar = %redact(crate::resource::redact_url(calendar_url))))]
+pub fn parse_all(content: &str, calendar_url: &Url) -> Result<Vec<Item>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let content = unfold_quoted_printable(content);
+    check_content_lines(&content).map_err(|err| format!("Invalid iCal data: {}", err))?;
+    let time_zones = extract_time_zones(&content);
+    for parsed_calendar in ical::IcalParser::new(content.as_bytes()) {
+        let parsed_calendar = parsed_calendar.map_err(|err| format!("Unable to parse iCal data: {}", err))?;
+        let ical_prod_id = extract_ical_prod_id(&parsed_calendar)
+            .map(|s| s.to_string())
+            .unwrap_or_else(super::default_prod_id);
+
+        for event in &parsed_calendar.events {
+            let item_url = crate::utils::random_url(calendar_url);
+            let event = parse_event(event, item_url, SyncStatus::NotSynced, ical_prod_id.clone())?;
+            items.push(Item::Event(event.with_time_zones(time_zones.clone())));
+        }
+        for todo in &parsed_calendar.todos {
+            let item_url = crate::utils::random_url(calendar_url);
+            items.push(match parse_todo(todo, item_url, SyncStatus::NotSynced, ical_prod_id.clone())? {
+                Item::Task(task) => Item::Task(task.with_time_zones(time_zones.clone())),
+                item => item,
+            });
+        }
+    }
+    Ok(items)
+}
+
+/// Parse the metadata of a calendar (RFC 7986) from the properties of the first `VCALENDAR` of an iCal file
+pub fn parse_calendar_metadata(content: &str) -> Result<CalendarMetadata, Box<dyn Error>> {
+    let content = unfold_quoted_printable(content);
+    check_content_lines(&content).map_err(|err| format!("Invalid iCal data: {}", err))?;
+    match ical::IcalParser::new(content.as_bytes()).next() {
+        None => Err("Invalid iCal data: no calendar".into()),
+        Some(Err(err)) => Err(format!("Unable to parse iCal data: {}", err).into()),
+        Some(Ok(calendar)) => {
+            let properties: Vec<_> = calendar.properties.iter().map(decode_property).collect();
+            Ok(CalendarMetadata::from_properties(&properties))
+        },
+    }
+}
+
+/// Returns the text of every `VTIMEZONE` component of an iCal file, so that the time zones its items refer to (with `TZID` parameters) can be written along with them.
+///
+/// This crate has no time zone database, so that these definitions are the only way to tell which times these items are at.
+fn extract_time_zones(content: &str) -> Vec<String> {
+    let mut time_zones = Vec::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        if line.eq_ignore_ascii_case("BEGIN:VTIMEZONE") {
+            current = Some(String::new());
+        }
+        if let Some(time_zone) = current.as_mut() {
+            time_zone.push_str(line);
+            time_zone.push_str("\r\n");
+        }
+        if line.eq_ignore_ascii_case("END:VTIMEZONE") {
+            time_zones.extend(current.take());
+        }
+    }
+    time_zones
+}
+
+/// Check that no content line is too long to be parsed in a reasonable time and memory (see [`MAX_LINE_LENGTH`] and [`MAX_PROPERTY_HEADER_LENGTH`]).
+///
+/// Items may come from untrusted sources (e.g. attachments of emails), that should not be able to make the parser hang.
+fn check_content_lines(content: &str) -> Result<(), Box<dyn Error>> {
+    let mut line_number = 0;
+    let mut line_length = 0;
+    let mut header_length = 0;
+    let mut in_header = false;
+    let mut in_quotes = false;
+    for line in content.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // Folded lines continue the previous one, and start with a single whitespace
+        let text = match line.strip_prefix(|c| c == ' ' || c == '\t') {
+            Some(continuation) if line_number > 0 => continuation,
+            _ => {
+                line_number += 1;
+                line_length = 0;
+                header_length = 0;
+                in_header = true;
+                in_quotes = false;
+                line
+            },
+        };
+
+        line_length += text.len();
+        if line_length > MAX_LINE_LENGTH {
+            return Err(format!("line {} is longer than {} bytes", line_number, MAX_LINE_LENGTH).into());
+        }
+        if in_header {
+            // Colons may appear in quoted parameter values (e.g. `ALTREP="http://..."`)
+            for c in text.chars() {
+                match c {
+                    '"' => in_quotes = !in_quotes,
+                    ':' if !in_quotes => {
+                        in_header = false;
+                        break;
+                    },
+                    _ => (),
+                }
+                header_length += c.len_utf8();
+            }
+            if header_length > MAX_PROPERTY_HEADER_LENGTH {
+                return Err(format!("the parameters of line {} are longer than {} bytes", line_number, MAX_PROPERTY_HEADER_LENGTH).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a `DURATION` value (RFC 5545 section 3.3.6), e.g. `P1W` or `PT12H`. Negative durations are not supported
+pub(crate) fn parse_duration(value: &str) -> Option<std::time::Duration> {
+    let value = value.trim();
+    let designators = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut has_value = false;
+    for c in designators.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if !in_time && number.is_empty() => in_time = true,
+            _ => {
+                let unit = match (c, in_time) {
+                    ('W', false) => 7 * 24 * 3600,
+                    ('D', false) => 24 * 3600,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                let n: u64 = number.parse().ok()?;
+                seconds = seconds.saturating_add(n.saturating_mul(unit));
+                number.clear();
+                has_value = true;
+            },
+        }
+    }
+
+    match has_value && number.is_empty() {
+        true => Some(std::time::Duration::from_secs(seconds)),
+        false => None,
+    }
+}
+
+fn parse_todo(todo: &IcalTodo, item_url: Url, sync_status: SyncStatus, ical_prod_id: String) -> Result<Item, Box<dyn Error>> {
+    let mut name = None;
+    let mut uid = None;
+    let mut status = None;
+    let mut last_modified = None;
+    let mut completion_date = None;
+    let mut creation_date = None;
+    let mut extra_parameters = Vec::new();
+
+    for prop in todo.properties.iter().map(decode_property) {
+        match prop.name.as_str() {
+            "SUMMARY" => { name = prop.value.as_deref().map(unescape_text) },
+            "UID" => { uid = prop.value.clone() },
+            "DTSTAMP" => {
+                // The property can be specified once, but is not mandatory
+                // "This property specifies the date and time that the information associated with
+                //  the calendar component was last revised in the calendar store."
+                // "In the case of an iCalendar object that doesn't specify a "METHOD"
+                //  property [e.g.: VTODO and VEVENT], this property is equivalent to the "LAST-MODIFIED" property".
+                last_modified = parse_date_time_from_property(&prop.value);
+            },
+            "LAST-MODIFIED" => {
+                // The property can be specified once, but is not mandatory
+                // "This property specifies the date and time that the information associated with
+                //  the calendar component was last revised in the calendar store."
+                // In practise, for VEVENT and VTODO, this is generally the same value as DTSTAMP.
+                last_modified = parse_date_time_from_property(&prop.value);
+            }
+            "COMPLETED" => {
+                // The property can be specified once, but is not mandatory
+                // "This property defines the date and time that a to-do was
+                //  actually completed."
+                completion_date = parse_date_time_from_property(&prop.value)
+            },
+            "CREATED" => {
+                // The property can be specified once, but is not mandatory
+                creation_date = parse_date_time_from_property(&prop.value)
+            },
+            "STATUS" => {
+                // Possible values:
+                //   "NEEDS-ACTION" ;Indicates to-do needs action.
+                //   "COMPLETED"    ;Indicates to-do completed.
+                //   "IN-PROCESS"   ;Indicates to-do in process of.
+                //   "CANCELLED"    ;Indicates to-do was cancelled.
+                status = prop.value.as_deref().and_then(TaskStatus::from_ical);
+            }
+            _ => {
+                // This field is not supported. Let's store it anyway, so that we are able to re-create an identical iCal file
+                extra_parameters.push(prop.clone());
+            }
+        }
+    }
+    let name = match name {
+        Some(name) => name,
+        None => return Err(format!("Missing name for item {}", item_url).into()),
+    };
+    let uid = match uid {
+        Some(uid) => uid,
+        None => return Err(format!("Missing UID for item {}", item_url).into()),
+    };
+    let last_modified = match last_modified {
+        Some(dt) => dt,
+        None => return Err(format!("Missing DTSTAMP for item {}, but this is required by RFC5545", item_url).into()),
+    };
+    let status = status.unwrap_or_default();
+    let completion_status = match status.is_completed() {
+        false => {
+            if completion_date.is_some() {
+                log::warn!("Task {} has an inconsistent content: its STATUS is not completed, yet it has a COMPLETED timestamp at {:?}", redact(&uid), completion_date);
+            }
+            CompletionStatus::Uncompleted
+        },
+        true => CompletionStatus::Completed(completion_date),
+    };
+
+    let task = Task::new_with_parameters(name, uid, item_url, completion_status, sync_status, creation_date, last_modified, ical_prod_id, extra_parameters);
+    Ok(Item::Task(task.with_status(status)))
+}
+
+fn parse_event(event: &IcalEvent, item_url: Url, sync_status: SyncStatus, ical_prod_id: String) -> Result<Event, Box<dyn Error>> {
+    let mut name = None;
+    let mut uid = None;
+    let mut last_modified = None;
+    let mut creation_date = None;
+    let mut extra_parameters = Vec::new();
+
+    for prop in event.properties.iter().map(decode_property) {
+        match prop.name.as_str() {
+            "SUMMARY" => { name = prop.value.as_deref().map(unescape_text) },
+            "UID" => { uid = prop.value.clone() },
+            // See parse_todo
+            "DTSTAMP" | "LAST-MODIFIED" => { last_modified = parse_date_time_from_property(&prop.value) },
+            "CREATED" => { creation_date = parse_date_time_from_property(&prop.value) },
+            _ => {
+                // DTSTART, DTEND, DURATION, STATUS, etc. are kept as they are, so that we are able to re-create an identical iCal file
+                extra_parameters.push(prop.clone());
+            }
+        }
+    }
+    let uid = match uid {
+        Some(uid) => uid,
+        None => return Err(format!("Missing UID for item {}", item_url).into()),
+    };
+    let last_modified = match last_modified {
+        Some(dt) => dt,
+        None => return Err(format!("Missing DTSTAMP for item {}, but this is required by RFC5545", item_url).into()),
+    };
+
+    // Unlike to-dos, events without a SUMMARY are common (e.g. busy slots shared by other calendars)
+    let name = name.unwrap_or_default();
+    Ok(Event::new_with_parameters(name, uid, item_url, sync_status, last_modified, ical_prod_id, extra_parameters)
+        .with_creation_date(creation_date))
+}
+
 fn parse_date_time(dt: &str) -> Result<DateTime<Utc>, chrono::format::ParseError> {
                     Utc.datetime_from_str(dt, "%Y%m%dT%H%M%SZ")
     .or_else(|_err| Utc.datetime_from_str(dt, "%Y%m%dT%H%M%S") )
 }
 
+/// Parse either a DATE-TIME or a DATE (that is considered to be at midnight UTC)
+pub(crate) fn parse_date_or_date_time(dt: &str) -> Result<DateTime<Utc>, chrono::format::ParseError> {
+    parse_date_time(dt)
+        .or_else(|_err| chrono::NaiveDate::parse_from_str(dt, "%Y%m%d").map(|date| Utc.from_utc_datetime(&date.and_hms(0, 0, 0))))
+}
+
 fn parse_date_time_from_property(value: &Option<String>) -> Option<DateTime<Utc>> {
     value.as_ref()
         .and_then(|s| {
@@ -223,6 +434,21 @@ SUMMARY:Clean up your room or Mom will be angry
 STATUS:COMPLETED
 END:VTODO
 END:VCALENDAR
+"#;
+
+const EXAMPLE_EVENT: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud calendar v2.2.0
+BEGIN:VEVENT
+UID:1f1d0c3d-2e2c-4a4b-9d24-0e3a3e5c2b41
+CREATED:20210321T001600
+DTSTAMP:20210321T001600
+SUMMARY:Team meeting
+DTSTART:20210322T090000Z
+DURATION:PT1H30M
+STATUS:CONFIRMED
+END:VEVENT
+END:VCALENDAR
 "#;
 
     const EXAMPLE_MULTIPLE_ICAL: &str = r#"BEGIN:VCALENDAR
@@ -268,6 +494,61 @@ END:VCALENDAR
         assert_eq!(task.last_modified(), &Utc.ymd(2021, 03, 21).and_hms(0, 16, 0));
     }
 
+    #[test]
+    fn test_raw_ical() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let item = parse(EXAMPLE_ICAL, item_url.clone(), SyncStatus::Synced(VersionTag::from(String::from("test-tag")))).unwrap();
+        assert_eq!(item.raw_ical(), Some(EXAMPLE_ICAL));
+
+        // Copies of an item keep its text, as long as they keep its UID
+        let task = item.unwrap_task();
+        let moved = task.clone_with_identity(task.uid().to_string(), "http://some.other/id".parse().unwrap(), SyncStatus::NotSynced);
+        assert_eq!(moved.raw_ical(), Some(EXAMPLE_ICAL));
+        let copy = task.clone_with_identity("another-uid".to_string(), item_url.clone(), SyncStatus::NotSynced);
+        assert_eq!(copy.raw_ical(), None);
+
+        // Modified items have to be serialized again
+        let mut task = task.clone();
+        task.set_name("Renamed".to_string());
+        assert_eq!(task.raw_ical(), None);
+
+        // Items that are not downloaded have no raw text
+        assert_eq!(parse_all(EXAMPLE_ICAL, &item_url).unwrap()[0].raw_ical(), None);
+    }
+
+    #[test]
+    fn test_event_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let item = parse(EXAMPLE_EVENT, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.raw_ical(), Some(EXAMPLE_EVENT));
+        let event = item.unwrap_event();
+
+        assert_eq!(event.name(), "Team meeting");
+        assert_eq!(event.url(), &item_url);
+        assert_eq!(event.uid(), "1f1d0c3d-2e2c-4a4b-9d24-0e3a3e5c2b41");
+        assert_eq!(event.creation_date(), Some(&Utc.ymd(2021, 3, 21).and_hms(0, 16, 0)));
+        assert_eq!(event.status(), Some(crate::event::EventStatus::Confirmed));
+        assert_eq!(event.start(), Some(Utc.ymd(2021, 3, 22).and_hms(9, 0, 0)));
+        // The end is derived from the DURATION, as if it were a DTEND
+        assert_eq!(event.duration(), Some(chrono::Duration::minutes(90)));
+        assert_eq!(event.end(), Some(Utc.ymd(2021, 3, 22).and_hms(10, 30, 0)));
+        let with_end = EXAMPLE_EVENT.replace("DURATION:PT1H30M", "DTEND:20210322T103000Z");
+        let with_end = parse(&with_end, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(with_end.unwrap_event().duration(), event.duration());
+        assert_eq!(with_end.unwrap_event().end(), event.end());
+
+        // Whole-day events without an end last one day
+        let all_day = EXAMPLE_EVENT.replace("DTSTART:20210322T090000Z\nDURATION:PT1H30M", "DTSTART;VALUE=DATE:20210322");
+        let all_day = parse(&all_day, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let all_day = all_day.unwrap_event();
+        assert_eq!(all_day.duration(), Some(chrono::Duration::days(1)));
+        assert_eq!(all_day.end_time(), Some(crate::event_time::EventTime::Date(chrono::NaiveDate::from_ymd(2021, 3, 23))));
+
+        // ...and events without a SUMMARY have an empty name
+        let unnamed = EXAMPLE_EVENT.replace("SUMMARY:Team meeting\n", "");
+        assert_eq!(parse(&unnamed, item_url.clone(), SyncStatus::NotSynced).unwrap().name(), "");
+    }
+
     #[test]
     fn test_completed_ical_parsing() {
         let version_tag = VersionTag::from(String::from("test-tag"));
@@ -294,6 +575,64 @@ END:VCALENDAR
         assert_eq!(task.completion_status(), &CompletionStatus::Completed(None));
     }
 
+    #[test]
+    fn test_task_status_ical_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let cancelled = EXAMPLE_ICAL_COMPLETED_WITHOUT_A_COMPLETION_DATE.replace("STATUS:COMPLETED", "STATUS:CANCELLED");
+        let mut task = parse(&cancelled, item_url.clone(), SyncStatus::NotSynced).unwrap().unwrap_task().clone();
+        assert!(!task.completed());
+        assert_eq!(task.status(), TaskStatus::Cancelled);
+        assert!(crate::ical::build_from(&Item::Task(task.clone())).unwrap().contains("STATUS:CANCELLED\r\n"));
+
+        task.set_status(TaskStatus::InProcess);
+        let ical = crate::ical::build_from(&Item::Task(task.clone())).unwrap();
+        assert_eq!(parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap().unwrap_task().status(), TaskStatus::InProcess);
+
+        task.set_status(TaskStatus::Completed);
+        assert!(task.completed() && task.status().is_completed());
+        task.uncomplete();
+        assert_eq!(task.status(), TaskStatus::NeedsAction);
+        assert_eq!(TaskStatus::from_ical("in-process"), Some(TaskStatus::InProcess));
+        assert_eq!(TaskStatus::from_ical("TENTATIVE"), None);
+    }
+
+    #[test]
+    fn test_hostile_ical() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let with_property = |prop: &str| EXAMPLE_ICAL.replace("SUMMARY:", &format!("{}\nSUMMARY:", prop));
+
+        // Events may be anything but a single one
+        let event_and_todo = EXAMPLE_ICAL.replace("END:VCALENDAR", "BEGIN:VEVENT\nUID:a\nDTSTAMP:20210321T001600\nEND:VEVENT\nEND:VCALENDAR");
+        assert!(parse(&event_and_todo, item_url.clone(), SyncStatus::NotSynced).is_err());
+        assert_eq!(parse_all(&event_and_todo, &item_url).unwrap().len(), 2);
+
+        // Invalid properties are rejected
+        for prop in &["X-A;P:a", "X-A;=:a", "X-A;P=\":a", "X-A;:a", ":a", "X-A"] {
+            assert!(parse(&with_property(prop), item_url.clone(), SyncStatus::NotSynced).is_err(), "{:?} should be rejected", prop);
+            assert!(parse_all(&with_property(prop), &item_url).is_err(), "{:?} should be rejected", prop);
+        }
+
+        // Lines with too many parameters are rejected before they reach the parser
+        let many_params = with_property(&format!("X-A{}:a", ";P=1".repeat(100_000)));
+        assert!(parse(&many_params, item_url.clone(), SyncStatus::NotSynced).is_err());
+        assert!(parse_all(&many_params, &item_url).is_err());
+        // ...even when they are folded
+        let folded_params = with_property(&format!("X-A{}:a", ";P=1\n ".repeat(100_000)));
+        assert!(parse(&folded_params, item_url.clone(), SyncStatus::NotSynced).is_err());
+        // Long values are fine, colons in quoted parameters too
+        let long_value = with_property(&format!("X-A;ALTREP=\"http://a.b/c\":{}", "a".repeat(100_000)));
+        assert_eq!(parse(&long_value, item_url.clone(), SyncStatus::NotSynced).unwrap().unwrap_task().extra_parameter("X-A").unwrap().len(), 100_000);
+        assert!(check_content_lines(&"a".repeat(MAX_LINE_LENGTH + 1)).is_err());
+
+        // Absurd dates and durations
+        let task = parse(&with_property("DTSTART;VALUE=DATE:+2621431231\nDURATION:P99999999999999999999W"), item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let task = task.unwrap_task();
+        assert_eq!(task.due(), None);
+        if let Some(start) = task.start_time() {
+            let _ = start.span(&Utc);
+        }
+    }
+
     #[test]
     fn test_multiple_items_in_ical() {
         let version_tag = VersionTag::from(String::from("test-tag"));
@@ -303,4 +642,19 @@ END:VCALENDAR
         let item = parse(EXAMPLE_MULTIPLE_ICAL, item_url.clone(), sync_status.clone());
         assert!(item.is_err());
     }
+
+    #[test]
+    fn test_parse_all() {
+        let cal_url: Url = "http://some.id/for/".parse().unwrap();
+
+        let items = parse_all(EXAMPLE_MULTIPLE_ICAL, &cal_url).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].name(), "Call Mom");
+        assert_eq!(items[1].name(), "Buy a gift for Mom");
+        assert_ne!(items[0].url(), items[1].url());
+        for item in &items {
+            assert_eq!(item.sync_status(), &SyncStatus::NotSynced);
+            assert!(item.url().as_str().starts_with(cal_url.as_str()));
+        }
+    }
 }
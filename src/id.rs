@@ -0,0 +1,183 @@
+//! Validated identifiers of calendars and items
+//!
+//! Calendars and items are identified by their URL. However, several URLs can designate the same resource: servers may return hrefs with or without
+//! a trailing slash for collections, or percent-encode characters (e.g. `@` as `%40`) that were sent unencoded. Comparing raw [`Url`]s would then
+//! see an item disappear and another one appear, and a sync would delete and re-create it.
+//!
+//! [`CalendarId`] and [`ItemId`] only accept URLs that can designate a CalDAV resource, and store them in a normalized form, so that equivalent URLs
+//! are equal:
+//! * only `http` and `https` URLs that have a host are accepted,
+//! * fragments are removed,
+//! * percent-encoded characters that do not need to be encoded in a path are decoded, and the remaining escapes are written in uppercase,
+//! * calendar URLs always end with a slash, since calendars are collections.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The URL schemes identifiers can use
+const SUPPORTED_SCHEMES: &[&str] = &["http", "https"];
+
+/// Implements the conversions and comparisons that are common to every kind of identifier
+macro_rules! impl_id {
+    ($id_type:ident, $is_collection:expr) => {
+        impl $id_type {
+            /// Parse and normalize an identifier
+            pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+                Self::try_from(Url::parse(input)?)
+            }
+
+            pub fn as_url(&self) -> &Url {
+                &self.0
+            }
+
+            pub fn into_url(self) -> Url {
+                self.0
+            }
+
+            /// Whether a raw URL designates the same resource as this identifier
+            pub fn matches(&self, url: &Url) -> bool {
+                match Self::try_from(url.clone()) {
+                    Ok(other) => *self == other,
+                    Err(_) => false,
+                }
+            }
+        }
+
+        impl TryFrom<Url> for $id_type {
+            type Error = Box<dyn Error>;
+
+            fn try_from(url: Url) -> Result<Self, Self::Error> {
+                Ok(Self(normalize(url, $is_collection)?))
+            }
+        }
+
+        impl FromStr for $id_type {
+            type Err = Box<dyn Error>;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s)
+            }
+        }
+
+        impl AsRef<Url> for $id_type {
+            fn as_ref(&self) -> &Url {
+                &self.0
+            }
+        }
+
+        impl Display for $id_type {
+            fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    }
+}
+
+/// The identifier of a calendar, i.e. its normalized URL (see the [module documentation](self))
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "Url")]
+pub struct CalendarId(Url);
+
+/// The identifier of an item, i.e. its normalized URL (see the [module documentation](self))
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "Url")]
+pub struct ItemId(Url);
+
+impl_id!(CalendarId, true);
+impl_id!(ItemId, false);
+
+impl ItemId {
+    /// The identifier of the calendar that contains this item
+    pub fn calendar(&self) -> CalendarId {
+        let mut url = self.0.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().pop();
+        }
+        CalendarId::try_from(url).unwrap(/* a URL is still valid once its last segment has been removed */)
+    }
+}
+
+/// Validate and normalize a URL. Collections are given a trailing slash
+fn normalize(mut url: Url, is_collection: bool) -> Result<Url, Box<dyn Error>> {
+    if !SUPPORTED_SCHEMES.contains(&url.scheme()) {
+        return Err(format!("Unsupported URL scheme {:?}", url.scheme()).into());
+    }
+    if url.host().is_none() {
+        return Err(format!("URL {} has no host", crate::utils::redact(&url)).into());
+    }
+
+    url.set_fragment(None);
+    let mut path = normalize_percent_encoding(url.path());
+    if is_collection && !path.ends_with('/') {
+        path.push('/');
+    }
+    url.set_path(&path);
+    Ok(url)
+}
+
+/// Decode the percent-encoded characters that are allowed in a path segment (RFC 3986 section 3.3), and write the other escapes in uppercase
+fn normalize_percent_encoding(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut normalized = String::with_capacity(path.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], bytes.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) if is_path_char(byte) => {
+                normalized.push(byte as char);
+                i += 3;
+            },
+            Some(byte) => {
+                normalized.push_str(&format!("%{:02X}", byte));
+                i += 3;
+            },
+            None => {
+                // Paths of parsed URLs are ASCII
+                normalized.push(bytes[i] as char);
+                i += 1;
+            },
+        }
+    }
+    normalized
+}
+
+/// Whether a byte can appear unencoded in a path segment
+fn is_path_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids() {
+        let cal = CalendarId::parse("https://caldav.com/dav/calendars/john/work").unwrap();
+        assert_eq!(cal.to_string(), "https://caldav.com/dav/calendars/john/work/");
+        assert_eq!(cal, "HTTPS://CalDAV.com:443/dav/calendars/john/work/#frag".parse().unwrap());
+        assert!(cal.matches(&Url::parse("https://caldav.com/dav/calendars/john/work").unwrap()));
+        assert!(!cal.matches(&Url::parse("https://caldav.com/dav/calendars/john/home/").unwrap()));
+
+        let item = ItemId::parse("https://caldav.com/dav/calendars/john/work/task%40example.com.ics").unwrap();
+        assert_eq!(item, ItemId::parse("https://caldav.com/dav/calendars/john/work/task@example.com.ics").unwrap());
+        assert_eq!(item.calendar(), cal);
+        assert_eq!(ItemId::parse("https://caldav.com/a%2fb%20c.ics").unwrap().as_url().path(), "/a%2Fb%20c.ics");
+        assert!(ItemId::parse("https://caldav.com/work/task.ics/").unwrap() != ItemId::parse("https://caldav.com/work/task.ics").unwrap());
+
+        assert!(CalendarId::parse("ftp://caldav.com/work/").is_err());
+        assert!(ItemId::parse("mailto:john@caldav.com").is_err());
+
+        let serialized = serde_json::to_string(&item).unwrap();
+        assert_eq!(serde_json::from_str::<ItemId>(&serialized).unwrap(), item);
+        assert!(serde_json::from_str::<CalendarId>("\"file:///tmp/cal/\"").is_err());
+    }
+}
@@ -30,8 +30,27 @@ impl Item {
     synthetise_common_getter!(name, &str);
     synthetise_common_getter!(creation_date, Option<&DateTime<Utc>>);
     synthetise_common_getter!(last_modified, &DateTime<Utc>);
+    synthetise_common_getter!(sequence, u32);
     synthetise_common_getter!(sync_status, &SyncStatus);
     synthetise_common_getter!(ical_prod_id, &str);
+    synthetise_common_getter!(color, Option<csscolorparser::Color>);
+    synthetise_common_getter!(classification, Classification);
+    synthetise_common_getter!(raw_ical, Option<&str>);
+    synthetise_common_getter!(validate, Result<(), Vec<crate::validation::Violation>>);
+
+    /// The version tag of this item on the remote source, at its latest sync (or `None` if it has never been synced). See [`SyncStatus::version_tag`]
+    pub fn version_tag(&self) -> Option<&VersionTag> {
+        self.sync_status().version_tag()
+    }
+
+    /// The current version of this item, that changes whenever it is modified (see [`ItemVersion`])
+    pub fn version(&self) -> ItemVersion {
+        ItemVersion {
+            sync_status: self.sync_status().clone(),
+            last_modified: *self.last_modified(),
+            sequence: self.sequence(),
+        }
+    }
 
     pub fn set_sync_status(&mut self, new_status: SyncStatus) {
         match self {
@@ -76,6 +95,46 @@ impl Item {
         }
     }
 
+    /// Returns a reference to the inner Event
+    ///
+    /// # Panics
+    /// Panics if the inner item is not an Event
+    pub fn unwrap_event(&self) -> &crate::event::Event {
+        match self {
+            Item::Event(e) => e,
+            _ => panic!("Not an event"),
+        }
+    }
+
+    /// Returns a mutable reference to the inner Event
+    ///
+    /// # Panics
+    /// Panics if the inner item is not an Event
+    pub fn unwrap_event_mut(&mut self) -> &mut crate::event::Event {
+        match self {
+            Item::Event(e) => e,
+            _ => panic!("Not an event"),
+        }
+    }
+
+    /// Returns a copy of this item, with a different UID, URL and sync status (see [`Task::clone_with_identity`](crate::task::Task::clone_with_identity))
+    pub(crate) fn clone_with_identity(&self, uid: String, url: Url, sync_status: SyncStatus) -> Item {
+        match self {
+            Item::Event(e) => Item::Event(e.clone_with_identity(uid, url, sync_status)),
+            Item::Task(t) => Item::Task(t.clone_with_identity(uid, url, sync_status)),
+        }
+    }
+
+    /// Merge the changes made to two versions of an item since their common version `base`, field by field (see [`Task::merge`](crate::task::Task::merge)).
+    ///
+    /// Events cannot be merged yet.
+    pub fn merge(base: &Item, local: &Item, remote: &Item) -> Option<Item> {
+        match (base, local, remote) {
+            (Item::Task(b), Item::Task(l), Item::Task(r)) => crate::task::Task::merge(b, l, r).map(Item::Task),
+            _ => None,
+        }
+    }
+
     #[cfg(any(test, feature = "integration_tests"))]
     pub fn has_same_observable_content_as(&self, other: &Item) -> bool {
         match (self, other) {
@@ -88,6 +147,160 @@ impl Item {
 
 
 
+/// The access classification of an item (its `CLASS` property, RFC 5545 section 3.8.1.3).
+///
+/// This is only a hint to the clients that display it: servers enforce no access restriction based on it.
+/// A cache can be told to keep confidential items out of its search index and exports (see [`crate::cache::CacheBuilder::exclude_confidential`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Classification {
+    #[default]
+    Public,
+    Private,
+    Confidential,
+}
+
+impl Classification {
+    /// Parse the value of a `CLASS` property (e.g. `PRIVATE`).
+    /// Unknown values (e.g. `X-` or IANA tokens) are `None`, and RFC 5545 asks to treat them as `PRIVATE`
+    pub fn from_ical(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "PUBLIC" => Some(Self::Public),
+            "PRIVATE" => Some(Self::Private),
+            "CONFIDENTIAL" => Some(Self::Confidential),
+            _ => None,
+        }
+    }
+
+    /// The value of the `CLASS` property for this classification
+    pub fn as_ical(&self) -> &'static str {
+        match self {
+            Self::Public => "PUBLIC",
+            Self::Private => "PRIVATE",
+            Self::Confidential => "CONFIDENTIAL",
+        }
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        *self == Self::Confidential
+    }
+}
+
+
+/// An identifier of an item that does not depend on its URL.
+///
+/// Item URLs are chosen by their creator (and may change, e.g. when a server path changes, or when an item is moved to another calendar),
+/// whereas the UID of an item is persistent. Apps that keep references to items should rather store this identifier,
+/// and resolve it to the current URL of the item (see [`crate::cache::Cache::resolve`]).
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StableItemId {
+    /// The URL of the calendar that contains this item
+    pub calendar: Url,
+    /// The UID of this item
+    pub uid: String,
+}
+
+impl StableItemId {
+    pub fn new(calendar: Url, uid: String) -> Self {
+        Self { calendar, uid }
+    }
+}
+
+
+
+/// Generation of UIDs for new items.
+///
+/// UIDs must be globally unique (RFC 5545 section 3.8.4.7). By default, a random UUID is used,
+/// followed by `@` and the domain set in [`crate::config::UID_DOMAIN`] (if any).
+/// Apps that need another scheme can set their own generator in [`crate::config::UID_GENERATOR`].
+pub struct ItemUid;
+
+impl ItemUid {
+    /// Generate a UID for a new item, using the strategy set in the [`crate::config`]
+    pub fn generate() -> String {
+        let generator = crate::config::UID_GENERATOR.lock().unwrap().clone();
+        match generator {
+            Some(generator) => generator(),
+            None => {
+                let domain = crate::config::UID_DOMAIN.lock().unwrap().clone();
+                Self::new_random(domain.as_deref())
+            },
+        }
+    }
+
+    /// Generate a random UID, with an optional domain suffix (e.g. `3f1c...@example.com`)
+    pub fn new_random(domain: Option<&str>) -> String {
+        let uuid = uuid::Uuid::new_v4().to_hyphenated().to_string();
+        match domain {
+            Some(domain) if !domain.is_empty() => format!("{}@{}", uuid, domain),
+            _ => uuid,
+        }
+    }
+}
+
+
+
+/// The properties of an item that can be read without loading its whole content (see [`CachedCalendar::get_items_metadata`](crate::calendar::cached_calendar::CachedCalendar::get_items_metadata))
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemMetadata {
+    pub uid: String,
+    pub name: String,
+    pub sync_status: SyncStatus,
+}
+
+impl From<&Item> for ItemMetadata {
+    fn from(item: &Item) -> Self {
+        Self {
+            uid: item.uid().to_string(),
+            name: item.name().to_string(),
+            sync_status: item.sync_status().clone(),
+        }
+    }
+}
+
+
+
+/// An item that has been downloaded, but that this crate is not able to parse.
+///
+/// Its raw content is kept, so that apps can tell the user, or try to handle it themselves.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UnparsedItem {
+    pub url: Url,
+    pub version_tag: VersionTag,
+    /// Why it could not be parsed
+    pub error: String,
+    /// The raw iCal content
+    pub raw: String,
+}
+
+
+
+/// A local item with local changes, that a sync has deleted because it vanished from its remote calendar.
+///
+/// It may have been moved to another calendar on the server. It is kept until a sync finds it there, so that its local changes are applied to its new copy instead of being lost
+/// (see [`CompleteCalendar::get_vanished_items`](crate::traits::CompleteCalendar::get_vanished_items))
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VanishedItem {
+    pub item: Item,
+    /// Its version at its latest sync
+    pub base: Option<Item>,
+    /// When the sync has deleted it
+    pub vanished_at: DateTime<Utc>,
+}
+
+
+
+/// A version of an item, that changes whenever the item is changed, either locally or by a sync.
+///
+/// Unlike a [`VersionTag`], that only changes when the item is changed on the server, this can be used to detect local changes as well
+/// (see [`Cache::update_item_if_unchanged`](crate::cache::Cache::update_item_if_unchanged))
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemVersion {
+    sync_status: SyncStatus,
+    last_modified: DateTime<Utc>,
+    sequence: u32,
+}
+
+
 
 /// A VersionTag is basically a CalDAV `ctag` or `etag`. Whenever it changes, this means the data has changed.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -131,9 +344,32 @@ pub enum SyncStatus {
     LocallyDeleted(VersionTag),
 }
 impl SyncStatus {
+    /// The version tag of the item when it has last been synced, or `None` if it has never been synced
+    pub fn version_tag(&self) -> Option<&VersionTag> {
+        match self {
+            SyncStatus::NotSynced => None,
+            SyncStatus::Synced(tag) | SyncStatus::LocallyModified(tag) | SyncStatus::LocallyDeleted(tag) => Some(tag),
+        }
+    }
+
     /// Generate a random SyncStatus::Synced
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     pub fn random_synced() -> Self {
         Self::Synced(VersionTag::random())
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_uid() {
+        let uid = ItemUid::new_random(Some("example.com"));
+        assert!(uid.ends_with("@example.com"));
+        assert_eq!(uid.len(), 36 + "@example.com".len());
+        assert_eq!(ItemUid::new_random(Some("")).len(), 36);
+        assert_ne!(ItemUid::new_random(None), ItemUid::new_random(None));
+    }
+}
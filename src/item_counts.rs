@@ -0,0 +1,200 @@
+//! Counts of the items of a calendar, e.g. to display badges in list views
+//!
+//! Every [`CachedCalendar`](crate::calendar::cached_calendar::CachedCalendar) keeps such counts up to date whenever its items are added, updated or deleted,
+//! so that [`CompleteCalendar::count_items`](crate::traits::CompleteCalendar::count_items) and the like do not have to go through every item.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::item::{Item, SyncStatus};
+
+/// What an item adds to the counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Counted {
+    completed: bool,
+    /// The due date of uncompleted tasks
+    due: Option<DateTime<Utc>>,
+}
+
+/// The counts of the items of a calendar
+#[derive(Clone, Debug, Default)]
+pub struct ItemCounts {
+    /// What every item has been counted as
+    counted: HashMap<Url, Counted>,
+    /// The number of completed tasks
+    completed: usize,
+    /// For every due date, the number of uncompleted tasks that are due then
+    by_due: BTreeMap<DateTime<Utc>, usize>,
+    /// Items that may have been modified since they were counted (e.g. using a mutable reference)
+    dirty: HashSet<Url>,
+}
+
+impl ItemCounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count (or re-count) an item. Items that are marked for deletion are not counted
+    pub fn index_item(&mut self, item: &Item) {
+        let url = item.url().clone();
+        self.remove_item(&url);
+        let counted = match counted(item) {
+            None => return,
+            Some(counted) => counted,
+        };
+
+        if counted.completed {
+            self.completed += 1;
+        }
+        if let Some(due) = counted.due {
+            *self.by_due.entry(due).or_default() += 1;
+        }
+        self.counted.insert(url, counted);
+    }
+
+    /// Stop counting an item
+    pub fn remove_item(&mut self, url: &Url) {
+        self.dirty.remove(url);
+        let counted = match self.counted.remove(url) {
+            None => return,
+            Some(counted) => counted,
+        };
+
+        if counted.completed {
+            self.completed -= 1;
+        }
+        if let Some(due) = counted.due {
+            if let Some(n_due) = self.by_due.get_mut(&due) {
+                *n_due -= 1;
+                if *n_due == 0 {
+                    self.by_due.remove(&due);
+                }
+            }
+        }
+    }
+
+    /// Tell that an item may have changed without these counts knowing it.
+    /// Until it is counted again, this item will be checked by reading its content
+    pub fn mark_dirty(&mut self, url: &Url) {
+        self.dirty.insert(url.clone());
+    }
+
+    /// Count again the items that have been marked dirty, so that they are not read anymore by [`Self::count_items`] and the like.
+    ///
+    /// `get_item` returns the items the counts have been built from. Items it does not return anymore are not counted anymore.
+    pub fn recount_dirty<'a, F>(&mut self, get_item: F)
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        for url in std::mem::take(&mut self.dirty) {
+            match get_item(&url) {
+                Some(item) => self.index_item(item),
+                None => self.remove_item(&url),
+            }
+        }
+    }
+
+    /// The number of items (except the ones that are marked for deletion).
+    ///
+    /// `get_item` returns the items the counts have been built from. It is only used for items that have been marked dirty.
+    pub fn count_items<'a, F>(&self, get_item: F) -> usize
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        self.count(self.counted.len(), |_counted| true, get_item)
+    }
+
+    /// The number of completed tasks. See [`Self::count_items`]
+    pub fn count_completed<'a, F>(&self, get_item: F) -> usize
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        self.count(self.completed, |counted| counted.completed, get_item)
+    }
+
+    /// The number of uncompleted tasks that are due strictly before `date`. See [`Self::count_items`]
+    pub fn count_due_before<'a, F>(&self, date: DateTime<Utc>, get_item: F) -> usize
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        let indexed = self.by_due.range(..date).map(|(_due, n_due)| n_due).sum();
+        self.count(indexed, |counted| counted.due.map(|due| due < date).unwrap_or(false), get_item)
+    }
+
+    /// Correct a count of the indexed items that match `matches` with the current content of the dirty items
+    fn count<'a, M, F>(&self, indexed: usize, matches: M, get_item: F) -> usize
+    where
+        M: Fn(&Counted) -> bool,
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        let mut count = indexed;
+        for url in &self.dirty {
+            if self.counted.get(url).map(&matches).unwrap_or(false) {
+                count -= 1;
+            }
+            if get_item(url).and_then(counted).map(|counted| matches(&counted)).unwrap_or(false) {
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// What an item adds to the counts, or `None` if it is not counted (because it is marked for deletion)
+fn counted(item: &Item) -> Option<Counted> {
+    if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+        return None;
+    }
+    match item {
+        Item::Event(_) => Some(Counted{ completed: false, due: None }),
+        Item::Task(task) => {
+            let completed = task.completed();
+            let due = if completed { None } else { task.due() };
+            Some(Counted{ completed, due })
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use crate::test_utils::task_with;
+
+    #[test]
+    fn test_item_counts() {
+        let mut items = HashMap::new();
+        let mut counts = ItemCounts::new();
+        for item in [task_with("overdue", false, &[("DUE", "20210301T000000Z")]),
+            task_with("due-later", false, &[("DUE", "20210601T000000Z")]),
+            task_with("done", true, &[("DUE", "20210301T000000Z")]),
+            task_with("undated", false, &[])] {
+            counts.index_item(&item);
+            items.insert(item.url().clone(), item);
+        }
+        let april = Utc.ymd(2021, 4, 1).and_hms(0, 0, 0);
+        assert_eq!(counts.count_items(|url| items.get(url)), 4);
+        assert_eq!(counts.count_completed(|url| items.get(url)), 1);
+        assert_eq!(counts.count_due_before(april, |url| items.get(url)), 1);
+
+        // Items that are modified behind the back of the counts
+        let overdue_url: Url = "https://some.calend.ar/cal/overdue.ics".parse().unwrap();
+        counts.mark_dirty(&overdue_url);
+        items.insert(overdue_url.clone(), task_with("overdue", true, &[("DUE", "20210301T000000Z")]));
+        assert_eq!(counts.count_completed(|url| items.get(url)), 2);
+        assert_eq!(counts.count_due_before(april, |url| items.get(url)), 0);
+
+        counts.recount_dirty(|url| items.get(url));
+        assert!(counts.dirty.is_empty());
+        assert_eq!(counts.count_completed(|url| items.get(url)), 2);
+        assert_eq!(counts.count_due_before(april, |url| items.get(url)), 0);
+        counts.remove_item(&"https://some.calend.ar/cal/done.ics".parse().unwrap());
+        assert_eq!(counts.count_items(|url| items.get(url)), 3);
+        assert_eq!(counts.count_completed(|url| items.get(url)), 1);
+        assert_eq!(counts.count_due_before(Utc.ymd(2022, 1, 1).and_hms(0, 0, 0), |url| items.get(url)), 1);
+    }
+}
@@ -1,9 +1,9 @@
 //! This crate provides a CalDAV client library. \
 //! CalDAV is described as "Calendaring Extensions to WebDAV" in [RFC 4791](https://datatracker.ietf.org/doc/html/rfc4791) and [RFC 7986](https://datatracker.ietf.org/doc/html/rfc7986) and the underlying iCal format is described at least in [RFC 5545](https://datatracker.ietf.org/doc/html/rfc5545). \
-//! This library has been intensivley tested with Nextcloud servers. It should support Owncloud and iCloud as well, since they use the very same CalDAV protocol.
+//! This library has been intensivley tested with Nextcloud servers. It should support Owncloud as well, since it uses the very same CalDAV protocol. iCloud needs some special handling, that is enabled automatically for iCloud URLs (see [`client::ServerFlavour`]).
 //!
-//! This initial implementation only supports TODO events. Thus it can fetch and update a CalDAV-hosted todo-list...just like [sticky notes on a kitchen fridge](https://www.google.com/search?q=kitchen+fridge+todo+list&tbm=isch) would. \
-//! Supporting other items (and especially regular CalDAV calendar events) should be fairly trivial, as it should boil down to adding little logic in iCal files parsing, but any help is appreciated :-)
+//! It has first been written for TODO items. Thus it can fetch and update a CalDAV-hosted todo-list...just like [sticky notes on a kitchen fridge](https://www.google.com/search?q=kitchen+fridge+todo+list&tbm=isch) would. \
+//! Regular calendar events are parsed and written as well (see [`Event`]), e.g. when importing or exporting iCal files, although servers are only queried for the to-dos of their calendars.
 //!
 //! ## Possible uses
 //!
@@ -26,6 +26,29 @@
 //! ## Configuration options
 //!
 //! Have a look at the [`config`] module to see what default options can be overridden.
+//!
+//! Network settings (timeouts, TLS certificates, proxies...) are set with a [`ClientBuilder`](client::ClientBuilder).
+//! SOCKS proxies (e.g. to connect over Tor) require the `socks` feature.
+//!
+//! The files of the [`cache`] can be saved in a binary format, and compressed, with a [`CacheBuilder`](cache::CacheBuilder). Zstandard compression requires the `zstd` feature.
+//!
+//! ## Diagnostics
+//!
+//! Besides the messages logged with the `log` crate, HTTP requests, iCal parsing and syncs are instrumented with [`tracing`](https://docs.rs/tracing) spans,
+//! whose targets are `kitchen_fridge::client`, `kitchen_fridge::ical` and `kitchen_fridge::sync`. Credentials are never recorded.
+//! Statistics about syncs and requests are available in the [`metrics`] module, and are also reported to the [`metrics`](https://docs.rs/metrics) crate with the `metrics` feature.
+//!
+//! ## Contacts
+//!
+//! With the `addressbook` feature, this crate can also fetch and update contacts from CardDAV address books (see the [`addressbook`] and [`contact`] modules).
+//! Contacts are stored in the local [`cache`], and synced by the [`provider`] along with calendars.
+//!
+//! ## Google Tasks and Google Calendar
+//!
+//! Google does not expose tasks through CalDAV. With the `google_tasks` feature, the `google_tasks` module provides a source that syncs task lists
+//! through the Google Tasks API instead, and that can be used in a [`Provider`](provider::Provider) like any CalDAV source. \
+//! Similarly, the `google_calendar` feature provides a source that syncs the events of Google calendars through the Google Calendar API
+//! (see the `google_calendar` module), whose CalDAV endpoint is limited.
 
 #![doc(html_logo_url = "https://raw.githubusercontent.com/daladim/kitchen-fridge/master/resources/kitchen-fridge.svg")]
 
@@ -34,23 +57,57 @@ pub mod traits;
 pub mod calendar;
 pub mod item;
 pub use item::Item;
+pub mod id;
 pub mod task;
 pub use task::Task;
 pub mod event;
 pub use event::Event;
+pub mod event_time;
+pub use event_time::EventTime;
+#[cfg(feature = "addressbook")]
+pub mod contact;
+#[cfg(feature = "addressbook")]
+pub use contact::Contact;
+#[cfg(feature = "addressbook")]
+pub mod addressbook;
+#[cfg(feature = "google_tasks")]
+pub mod google_tasks;
+#[cfg(feature = "google_calendar")]
+pub mod google_calendar;
+#[cfg(any(feature = "google_tasks", feature = "google_calendar"))]
+mod google_api;
 pub mod provider;
 pub mod mock_behaviour;
 
 pub mod client;
+pub mod capabilities;
+pub mod trace;
+pub mod replay;
 pub use client::Client;
 pub mod cache;
 pub use cache::Cache;
 pub mod ical;
+pub mod search;
+pub mod time_index;
+pub mod item_counts;
+pub mod validation;
+pub mod metrics;
+pub mod sharing;
+pub mod trash;
+pub mod occurrence;
+pub mod clock;
 
 pub mod config;
 pub mod utils;
 pub mod resource;
+#[cfg(test)]
+mod test_utils;
 
 /// Unless you want another kind of Provider to write integration tests, you'll probably want this kind of Provider. \
 /// See alse the [`Provider` documentation](crate::provider::Provider)
 pub type CalDavProvider = provider::Provider<cache::Cache, calendar::cached_calendar::CachedCalendar, Client, calendar::remote_calendar::RemoteCalendar>;
+
+/// A Provider whose sources are type-erased, so that it can be stored in non-generic structs (e.g. the state of an app), whatever its sources are. \
+/// By default, the calendars are the ones of a [`CalDavProvider`]. See [`Provider::into_dyn`](crate::provider::Provider::into_dyn)
+pub type DynProvider<T = calendar::cached_calendar::CachedCalendar, U = calendar::remote_calendar::RemoteCalendar> =
+    provider::Provider<traits::DynSource<T>, T, traits::DynSource<U>, U>;
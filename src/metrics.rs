@@ -0,0 +1,137 @@
+//! Statistics about syncs and network usage, e.g. to be exported to a monitoring system by long-running daemons
+//!
+//! See [`Provider::metrics`](crate::provider::Provider::metrics).
+//!
+//! With the `metrics` feature, these statistics are also reported to the [`metrics`](https://docs.rs/metrics) crate as they change,
+//! so that they can be exported by any of its exporters (e.g. to Prometheus). Their names start with `kitchen_fridge_`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::provider::sync_progress::SyncReport;
+
+/// Counters of the HTTP requests made by a [`Client`](crate::client::Client), shared by every calendar it returns
+#[derive(Debug, Default)]
+pub(crate) struct RequestCounters {
+    requests: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    compressed_bytes_received: AtomicU64,
+    retries: AtomicU64,
+}
+
+impl RequestCounters {
+    pub(crate) fn record_request(&self, bytes_sent: usize) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes_sent as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::increment_counter!("kitchen_fridge_requests_total");
+            ::metrics::counter!("kitchen_fridge_bytes_sent_total", bytes_sent as u64);
+        }
+    }
+
+    pub(crate) fn record_received(&self, bytes_received: usize) {
+        self.bytes_received.fetch_add(bytes_received as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("kitchen_fridge_bytes_received_total", bytes_received as u64);
+    }
+
+    pub(crate) fn record_compressed(&self, compressed_bytes: usize) {
+        self.compressed_bytes_received.fetch_add(compressed_bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("kitchen_fridge_compressed_bytes_received_total", compressed_bytes as u64);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        ::metrics::increment_counter!("kitchen_fridge_retries_total");
+    }
+
+    pub(crate) fn snapshot(&self) -> RequestStats {
+        RequestStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            compressed_bytes_received: self.compressed_bytes_received.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Statistics about the HTTP requests made to a server
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestStats {
+    /// The number of requests sent
+    pub requests: u64,
+    /// The size of the bodies of the requests
+    pub bytes_sent: u64,
+    /// The size of the response bodies that have been read
+    pub bytes_received: u64,
+    /// The size of the response bodies that the server has compressed, as they have been transferred.
+    /// Once decompressed, they are counted in `bytes_received` as well
+    pub compressed_bytes_received: u64,
+    /// The number of requests that have been sent again after a transient failure (they are counted in `requests` as well)
+    pub retries: u64,
+}
+
+/// A snapshot of the statistics of a [`Provider`](crate::provider::Provider), since it has been created
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncMetrics {
+    /// The number of syncs that have been run (including syncs of a single calendar)
+    pub syncs: u64,
+    /// The number of syncs that have not completed successfully
+    pub failed_syncs: u64,
+    /// The number of items added or updated on the remote source
+    pub items_uploaded: u64,
+    /// The number of items added or updated in the local source
+    pub items_downloaded: u64,
+    /// The number of items deleted from either source
+    pub items_deleted: u64,
+    /// The number of conflicts that have been resolved
+    pub conflicts: u64,
+    /// The number of errors that happened during syncs
+    pub errors: u64,
+    /// How long the latest sync took
+    pub last_sync_duration: Option<Duration>,
+    /// When the latest successful sync ended
+    pub last_successful_sync: Option<DateTime<Utc>>,
+    /// The requests made to the remote source (only for sources that count them, such as a [`Client`](crate::client::Client))
+    pub requests: RequestStats,
+}
+
+impl SyncMetrics {
+    pub(crate) fn record_sync(&mut self, report: &SyncReport, duration: Duration, now: DateTime<Utc>) {
+        self.syncs += 1;
+        if !report.is_success() {
+            self.failed_syncs += 1;
+        } else {
+            self.last_successful_sync = Some(now);
+        }
+        self.items_uploaded += report.uploaded() as u64;
+        self.items_downloaded += report.downloaded() as u64;
+        self.items_deleted += (report.deleted_locally() + report.deleted_remotely()) as u64;
+        self.conflicts += report.conflicts() as u64;
+        self.errors += report.n_errors() as u64;
+        self.last_sync_duration = Some(duration);
+
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::increment_counter!("kitchen_fridge_syncs_total");
+            if !report.is_success() {
+                ::metrics::increment_counter!("kitchen_fridge_failed_syncs_total");
+            } else {
+                ::metrics::gauge!("kitchen_fridge_last_successful_sync_timestamp_seconds", now.timestamp() as f64);
+            }
+            ::metrics::counter!("kitchen_fridge_items_uploaded_total", report.uploaded() as u64);
+            ::metrics::counter!("kitchen_fridge_items_downloaded_total", report.downloaded() as u64);
+            ::metrics::counter!("kitchen_fridge_items_deleted_total", (report.deleted_locally() + report.deleted_remotely()) as u64);
+            ::metrics::counter!("kitchen_fridge_conflicts_total", report.conflicts() as u64);
+            ::metrics::counter!("kitchen_fridge_sync_errors_total", report.n_errors() as u64);
+            ::metrics::histogram!("kitchen_fridge_sync_duration_seconds", duration);
+        }
+    }
+}
@@ -24,6 +24,9 @@ pub struct MockBehaviour {
     pub get_item_version_tags_behaviour: (u32, u32),
     pub get_item_by_url_behaviour: (u32, u32),
     pub delete_item_behaviour: (u32, u32),
+
+    /// If set, `get_item_version_tags` lists at most this number of items, like servers that truncate their replies
+    pub max_listed_items: Option<usize>,
 }
 
 impl MockBehaviour {
@@ -43,6 +46,7 @@ impl MockBehaviour {
             get_item_version_tags_behaviour: (0, n_fails),
             get_item_by_url_behaviour: (0, n_fails),
             delete_item_behaviour: (0, n_fails),
+            max_listed_items: None,
         }
     }
 
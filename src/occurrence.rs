@@ -0,0 +1,167 @@
+//! Occurrences of recurring events, expanded by the server
+//!
+//! This crate does not expand `RRULE`s locally. CalDAV servers can do it for us, when asked with an `expand` element in a `calendar-query` REPORT (RFC 4791 section 9.6.5).
+//! See [`Client::expanded_occurrences`](crate::client::Client::expanded_occurrences).
+
+use std::error::Error;
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use ical::parser::ical::component::IcalEvent;
+use minidom::Element;
+use url::Url;
+
+use crate::event::{EventStatus, Transparency};
+use crate::resource::Resource;
+use crate::utils::find_elem;
+
+/// The format of the dates used in `time-range` and `expand` elements
+const CALDAV_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A single occurrence of an event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    /// The URL of the event this occurrence belongs to
+    pub url: Url,
+    pub uid: String,
+    pub summary: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    /// The original start of this occurrence in the recurrence set (or `None` for events that do not recur)
+    pub recurrence_id: Option<DateTime<Utc>>,
+    pub status: Option<EventStatus>,
+    pub transparency: Transparency,
+}
+
+impl Occurrence {
+    /// Whether this occurrence makes its attendees busy, e.g. for free/busy lookups or to find a time slot for a meeting.
+    ///
+    /// Transparent and cancelled events do not (RFC 4791 section 7.10)
+    pub fn blocks_time(&self) -> bool {
+        self.transparency == Transparency::Opaque && self.status != Some(EventStatus::Cancelled)
+    }
+}
+
+/// The body of a REPORT that fetches every occurrence of events within a time range
+pub(crate) fn expand_body(range: &Range<DateTime<Utc>>) -> String {
+    let start = range.start.format(CALDAV_DATE_FORMAT);
+    let end = range.end.format(CALDAV_DATE_FORMAT);
+    format!(r#"
+    <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+        <d:prop>
+            <d:getetag />
+            <c:calendar-data>
+                <c:expand start="{start}" end="{end}" />
+            </c:calendar-data>
+        </d:prop>
+        <c:filter>
+            <c:comp-filter name="VCALENDAR">
+                <c:comp-filter name="VEVENT">
+                    <c:time-range start="{start}" end="{end}" />
+                </c:comp-filter>
+            </c:comp-filter>
+        </c:filter>
+    </c:calendar-query>
+    "#, start=start, end=end)
+}
+
+/// Parse a reply to the REPORT built by [`expand_body`].
+///
+/// Each event of the reply contains one `VEVENT` per occurrence.
+pub(crate) fn parse_occurrences(rep: &Element, calendar: &Resource) -> Result<Vec<Occurrence>, Box<dyn Error>> {
+    let href = find_elem(rep, "href").ok_or("Missing href in REPORT reply")?.text();
+    let url = calendar.resolve_href(&href).url().clone();
+    let data = match find_elem(rep, "calendar-data") {
+        None => return Ok(Vec::new()),
+        Some(data) => data.text(),
+    };
+
+    let mut occurrences = Vec::new();
+    for parsed_calendar in ical::IcalParser::new(data.as_bytes()) {
+        let parsed_calendar = parsed_calendar.map_err(|err| format!("Unable to parse iCal data: {}", err))?;
+        for event in &parsed_calendar.events {
+            occurrences.push(parse_occurrence(event, &url)?);
+        }
+    }
+    Ok(occurrences)
+}
+
+fn parse_occurrence(event: &IcalEvent, url: &Url) -> Result<Occurrence, Box<dyn Error>> {
+    let value = |name: &str| {
+        event.properties.iter()
+            .find(|prop| prop.name == name)
+            .and_then(|prop| prop.value.as_deref())
+    };
+    let date = |name: &str| -> Result<Option<DateTime<Utc>>, Box<dyn Error>> {
+        match value(name) {
+            None => Ok(None),
+            Some(dt) => Ok(Some(crate::ical::parse_date_or_date_time(dt).map_err(|err| format!("Invalid {} {}: {}", name, dt, err))?)),
+        }
+    };
+
+    Ok(Occurrence {
+        url: url.clone(),
+        uid: value("UID").ok_or("Missing UID in occurrence")?.to_string(),
+        summary: value("SUMMARY").map(crate::ical::escape::unescape_text),
+        start: date("DTSTART")?.ok_or("Missing DTSTART in occurrence")?,
+        end: date("DTEND")?,
+        recurrence_id: date("RECURRENCE-ID")?,
+        status: value("STATUS").and_then(EventStatus::from_ical),
+        transparency: value("TRANSP").and_then(Transparency::from_ical).unwrap_or_default(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_expanded_occurrences() {
+        let response = r#"<d:response xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+            <d:href>/dav/calendars/john/work/standup.ics</d:href>
+            <d:propstat><d:prop>
+                <d:getetag>"abc"</d:getetag>
+                <cal:calendar-data>BEGIN:VCALENDAR&#13;
+VERSION:2.0&#13;
+PRODID:-//Example Corp.//CalDAV Server//EN&#13;
+BEGIN:VEVENT&#13;
+UID:standup&#13;
+DTSTAMP:20210401T080000Z&#13;
+DTSTART:20210405T090000Z&#13;
+DTEND:20210405T091500Z&#13;
+SUMMARY:Stand-up\, daily&#13;
+RECURRENCE-ID:20210405T090000Z&#13;
+END:VEVENT&#13;
+BEGIN:VEVENT&#13;
+UID:standup&#13;
+DTSTAMP:20210401T080000Z&#13;
+DTSTART:20210406T100000Z&#13;
+DTEND:20210406T101500Z&#13;
+SUMMARY:Stand-up (moved)&#13;
+RECURRENCE-ID:20210406T090000Z&#13;
+TRANSP:TRANSPARENT&#13;
+END:VEVENT&#13;
+END:VCALENDAR&#13;
+</cal:calendar-data>
+            </d:prop></d:propstat>
+        </d:response>"#;
+        let calendar = Resource::new("https://my.server.com/dav/calendars/john/work/".parse().unwrap(), "john".to_string(), "pass".to_string());
+
+        let occurrences = parse_occurrences(&response.parse().unwrap(), &calendar).unwrap();
+        assert_eq!(occurrences.len(), 2);
+        assert_eq!(occurrences[0].url.as_str(), "https://my.server.com/dav/calendars/john/work/standup.ics");
+        assert_eq!(occurrences[0].summary.as_deref(), Some("Stand-up, daily"));
+        assert_eq!(occurrences[0].end, Some(Utc.ymd(2021, 4, 5).and_hms(9, 15, 0)));
+        assert_eq!(occurrences[1].start, Utc.ymd(2021, 4, 6).and_hms(10, 0, 0));
+        assert_eq!(occurrences[1].recurrence_id, Some(Utc.ymd(2021, 4, 6).and_hms(9, 0, 0)));
+        assert!(occurrences[0].blocks_time());
+        assert_eq!(occurrences[1].transparency, Transparency::Transparent);
+        assert!(!occurrences[1].blocks_time());
+        assert!(!Occurrence{ status: Some(EventStatus::Cancelled), ..occurrences[0].clone() }.blocks_time());
+
+        let range = Utc.ymd(2021, 4, 5).and_hms(0, 0, 0)..Utc.ymd(2021, 4, 12).and_hms(0, 0, 0);
+        assert!(expand_body(&range).contains(r#"<c:expand start="20210405T000000Z" end="20210412T000000Z" />"#));
+    }
+}
@@ -0,0 +1,163 @@
+//! Support for several accounts (i.e. several providers, that may use different servers and credentials)
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::traits::{CalDavSource, CompleteCalendar, DavCalendar};
+use super::Provider;
+
+/// Identifies a calendar among every account of an [`AccountManager`]
+///
+/// Two accounts may have calendars with the same URL (e.g. two users of the same server share a calendar), hence the account name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccountCalendarId {
+    pub account: String,
+    pub calendar: Url,
+}
+
+/// Owns several named [`Provider`]s, syncs them together, and merges their local calendars in a single view
+#[derive(Debug)]
+pub struct AccountManager<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    accounts: HashMap<String, Provider<L, T, R, U>>,
+}
+
+impl<L, T, R, U> Default for AccountManager<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    fn default() -> Self {
+        Self { accounts: HashMap::new() }
+    }
+}
+
+impl<L, T, R, U> AccountManager<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an account. Returns an error if an account with the same name already exists
+    pub fn add_account<S: Into<String>>(&mut self, name: S, provider: Provider<L, T, R, U>) -> Result<(), Box<dyn Error>> {
+        let name = name.into();
+        if self.accounts.contains_key(&name) {
+            return Err(format!("Account {} already exists", name).into());
+        }
+        self.accounts.insert(name, provider);
+        Ok(())
+    }
+
+    /// Remove an account, and return its provider
+    pub fn remove_account(&mut self, name: &str) -> Option<Provider<L, T, R, U>> {
+        self.accounts.remove(name)
+    }
+
+    pub fn account(&self, name: &str) -> Option<&Provider<L, T, R, U>> {
+        self.accounts.get(name)
+    }
+
+    pub fn account_mut(&mut self, name: &str) -> Option<&mut Provider<L, T, R, U>> {
+        self.accounts.get_mut(name)
+    }
+
+    /// The names of every account
+    pub fn account_names(&self) -> impl Iterator<Item = &str> {
+        self.accounts.keys().map(|name| name.as_str())
+    }
+
+    /// Sync every account concurrently.
+    ///
+    /// Returns whether the sync of each account was totally successful (see [`Provider::sync`])
+    pub async fn sync_all(&mut self) -> HashMap<String, bool> {
+        let syncs = self.accounts.iter_mut()
+            .map(|(name, provider)| async move {
+                let success = provider.sync().await;
+                if !success {
+                    log::warn!("Sync of account {} did not complete", name);
+                }
+                (name.clone(), success)
+            });
+        join_all(syncs).await.into_iter().collect()
+    }
+
+    /// Returns the local calendars of every account
+    pub async fn get_calendars(&self) -> Result<HashMap<AccountCalendarId, Arc<RwLock<T>>>, Box<dyn Error>> {
+        let mut calendars = HashMap::new();
+        for (name, provider) in &self.accounts {
+            for (url, cal) in provider.local().get_calendars().await? {
+                calendars.insert(AccountCalendarId{ account: name.clone(), calendar: url }, cal);
+            }
+        }
+        Ok(calendars)
+    }
+
+    /// Returns a local calendar of an account
+    pub async fn get_calendar(&self, id: &AccountCalendarId) -> Option<Arc<RwLock<T>>> {
+        match self.accounts.get(&id.account) {
+            None => None,
+            Some(provider) => provider.local().get_calendar(&id.calendar).await,
+        }
+    }
+}
+
+
+#[cfg(all(test, feature = "local_calendar_mocks_remote_calendars"))]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use crate::cache::Cache;
+    use crate::calendar::SupportedComponents;
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::traits::BaseCalendar;
+
+    async fn provider_with_calendar(name: &str, cal_url: &Url) -> Provider<Cache, CachedCalendar, Cache, CachedCalendar> {
+        let local = Cache::new(&PathBuf::from(format!("test_cache/accounts/{}_local", name)));
+        let mut remote = Cache::new(&PathBuf::from(format!("test_cache/accounts/{}_remote", name)));
+        remote.create_calendar(cal_url.clone(), name.to_string(), SupportedComponents::TODO, None).await.unwrap();
+        Provider::new(remote, local)
+    }
+
+    #[tokio::test]
+    async fn test_account_manager() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/shared".parse().unwrap();
+
+        let mut manager = AccountManager::new();
+        manager.add_account("work", provider_with_calendar("work", &cal_url).await).unwrap();
+        manager.add_account("home", provider_with_calendar("home", &cal_url).await).unwrap();
+        assert!(manager.add_account("home", provider_with_calendar("home", &cal_url).await).is_err());
+
+        let results = manager.sync_all().await;
+        assert_eq!(results.len(), 2);
+        assert!(results.values().all(|success| *success));
+
+        // The same calendar URL is kept apart for each account
+        let calendars = manager.get_calendars().await.unwrap();
+        assert_eq!(calendars.len(), 2);
+        let work_id = AccountCalendarId{ account: "work".to_string(), calendar: cal_url.clone() };
+        let work_cal = manager.get_calendar(&work_id).await.unwrap();
+        assert_eq!(work_cal.read().await.name(), "work");
+
+        assert!(manager.remove_account("work").is_some());
+        assert!(manager.get_calendar(&work_id).await.is_none());
+    }
+}
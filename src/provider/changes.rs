@@ -0,0 +1,190 @@
+//! Classification of the differences between a local and a remote calendar
+//!
+//! Every item is compared by its local [`SyncStatus`] and its remote [`VersionTag`] only, so that this is decided without downloading anything.
+//! Items that have changed are sorted into explicit sets: those that have changed on the server only, on the local source only, or on both.
+
+use std::collections::HashMap;
+
+use url::Url;
+
+use crate::item::{SyncStatus, VersionTag};
+
+/// How a single item differs between both sources since their last sync
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    /// Nothing to sync
+    Unchanged,
+    /// Created on the server
+    RemoteAddition,
+    /// Modified on the server
+    RemoteModification,
+    /// Deleted from the server
+    RemoteDeletion,
+    /// Created locally
+    LocalAddition,
+    /// Modified locally
+    LocalModification,
+    /// Deleted locally
+    LocalDeletion,
+    /// Modified on both sources (a conflict)
+    ModifiedOnBoth,
+    /// Deleted locally, but modified on the server (a conflict)
+    LocallyDeletedRemotelyModified,
+    /// Modified locally, but deleted from the server (a conflict)
+    LocallyModifiedRemotelyDeleted,
+    /// Deleted from both sources
+    DeletedOnBoth,
+    /// A local item that has never been synced has the URL of a remote item
+    UrlReuse,
+}
+
+impl Change {
+    /// Tell how an item has changed, from its local sync status and its remote version tag (`None` means that it does not exist on that side)
+    pub fn classify(local: Option<&SyncStatus>, remote: Option<&VersionTag>) -> Self {
+        match (local, remote) {
+            (None, None) => Self::Unchanged,
+            (None, Some(_)) => Self::RemoteAddition,
+
+            (Some(SyncStatus::NotSynced), None) => Self::LocalAddition,
+            (Some(SyncStatus::NotSynced), Some(_)) => Self::UrlReuse,
+
+            (Some(SyncStatus::Synced(_)), None) => Self::RemoteDeletion,
+            (Some(SyncStatus::Synced(local_tag)), Some(remote_tag)) => match local_tag == remote_tag {
+                true => Self::Unchanged,
+                false => Self::RemoteModification,
+            },
+
+            (Some(SyncStatus::LocallyModified(_)), None) => Self::LocallyModifiedRemotelyDeleted,
+            (Some(SyncStatus::LocallyModified(local_tag)), Some(remote_tag)) => match local_tag == remote_tag {
+                true => Self::LocalModification,
+                false => Self::ModifiedOnBoth,
+            },
+
+            (Some(SyncStatus::LocallyDeleted(_)), None) => Self::DeletedOnBoth,
+            (Some(SyncStatus::LocallyDeleted(local_tag)), Some(remote_tag)) => match local_tag == remote_tag {
+                true => Self::LocalDeletion,
+                false => Self::LocallyDeletedRemotelyModified,
+            },
+        }
+    }
+}
+
+/// The items that differ between both sources, by where they have changed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Changes {
+    /// Items that have only changed on the server
+    pub server_only: HashMap<Url, Change>,
+    /// Items that have only changed locally
+    pub local_only: HashMap<Url, Change>,
+    /// Items that have changed on both sources (including the items that have been deleted from both)
+    pub both: HashMap<Url, Change>,
+    /// Local items that have never been synced, but whose URL is used by a remote item. They cannot be synced
+    pub url_reuses: Vec<Url>,
+}
+
+impl Changes {
+    /// Compare the sync statuses of the local items with the version tags of the remote items
+    pub fn between(local: &HashMap<Url, SyncStatus>, remote: &HashMap<Url, VersionTag>) -> Self {
+        let mut changes = Self::default();
+        let urls = local.keys().chain(remote.keys().filter(|url| !local.contains_key(*url)));
+        for url in urls {
+            let change = Change::classify(local.get(url), remote.get(url));
+            let set = match change {
+                Change::Unchanged => continue,
+                Change::UrlReuse => {
+                    changes.url_reuses.push(url.clone());
+                    continue;
+                },
+                Change::RemoteAddition | Change::RemoteModification | Change::RemoteDeletion => &mut changes.server_only,
+                Change::LocalAddition | Change::LocalModification | Change::LocalDeletion => &mut changes.local_only,
+                Change::ModifiedOnBoth | Change::LocallyDeletedRemotelyModified
+                    | Change::LocallyModifiedRemotelyDeleted | Change::DeletedOnBoth => &mut changes.both,
+            };
+            set.insert(url.clone(), change);
+        }
+        changes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let tag = || VersionTag::from(String::from("tag"));
+        let other_tag = || VersionTag::from(String::from("other-tag"));
+
+        let cases = [
+            (None,                                       None,              Change::Unchanged),
+            (None,                                       Some(tag()),       Change::RemoteAddition),
+            (Some(SyncStatus::NotSynced),                None,              Change::LocalAddition),
+            (Some(SyncStatus::NotSynced),                Some(tag()),       Change::UrlReuse),
+            (Some(SyncStatus::Synced(tag())),            None,              Change::RemoteDeletion),
+            (Some(SyncStatus::Synced(tag())),            Some(tag()),       Change::Unchanged),
+            (Some(SyncStatus::Synced(tag())),            Some(other_tag()), Change::RemoteModification),
+            (Some(SyncStatus::LocallyModified(tag())),   None,              Change::LocallyModifiedRemotelyDeleted),
+            (Some(SyncStatus::LocallyModified(tag())),   Some(tag()),       Change::LocalModification),
+            (Some(SyncStatus::LocallyModified(tag())),   Some(other_tag()), Change::ModifiedOnBoth),
+            (Some(SyncStatus::LocallyDeleted(tag())),    None,              Change::DeletedOnBoth),
+            (Some(SyncStatus::LocallyDeleted(tag())),    Some(tag()),       Change::LocalDeletion),
+            (Some(SyncStatus::LocallyDeleted(tag())),    Some(other_tag()), Change::LocallyDeletedRemotelyModified),
+        ];
+        for (local, remote, expected) in cases.iter() {
+            assert_eq!(Change::classify(local.as_ref(), remote.as_ref()), *expected, "local {:?}, remote {:?}", local, remote);
+        }
+    }
+
+    #[test]
+    fn test_changes_between() {
+        let url = |name: &str| Url::parse(&format!("https://caldav.com/cal/{}.ics", name)).unwrap();
+        let tag = |value: &str| VersionTag::from(String::from(value));
+
+        let mut local = HashMap::new();
+        let mut remote = HashMap::new();
+        local.insert(url("unchanged"), SyncStatus::Synced(tag("1")));
+        remote.insert(url("unchanged"), tag("1"));
+        remote.insert(url("remote-addition"), tag("1"));
+        local.insert(url("remote-change"), SyncStatus::Synced(tag("1")));
+        remote.insert(url("remote-change"), tag("2"));
+        local.insert(url("remote-deletion"), SyncStatus::Synced(tag("1")));
+        local.insert(url("local-addition"), SyncStatus::NotSynced);
+        local.insert(url("local-change"), SyncStatus::LocallyModified(tag("1")));
+        remote.insert(url("local-change"), tag("1"));
+        local.insert(url("local-deletion"), SyncStatus::LocallyDeleted(tag("1")));
+        remote.insert(url("local-deletion"), tag("1"));
+        local.insert(url("conflict"), SyncStatus::LocallyModified(tag("1")));
+        remote.insert(url("conflict"), tag("2"));
+        local.insert(url("deleted-on-both"), SyncStatus::LocallyDeleted(tag("1")));
+        local.insert(url("reused"), SyncStatus::NotSynced);
+        remote.insert(url("reused"), tag("1"));
+
+        let changes = Changes::between(&local, &remote);
+        let expected_server_only: HashMap<Url, Change> = vec![
+            (url("remote-addition"), Change::RemoteAddition),
+            (url("remote-change"), Change::RemoteModification),
+            (url("remote-deletion"), Change::RemoteDeletion),
+        ].into_iter().collect();
+        let expected_local_only: HashMap<Url, Change> = vec![
+            (url("local-addition"), Change::LocalAddition),
+            (url("local-change"), Change::LocalModification),
+            (url("local-deletion"), Change::LocalDeletion),
+        ].into_iter().collect();
+        let expected_both: HashMap<Url, Change> = vec![
+            (url("conflict"), Change::ModifiedOnBoth),
+            (url("deleted-on-both"), Change::DeletedOnBoth),
+        ].into_iter().collect();
+        assert_eq!(changes.server_only, expected_server_only);
+        assert_eq!(changes.local_only, expected_local_only);
+        assert_eq!(changes.both, expected_both);
+        assert_eq!(changes.url_reuses, vec![url("reused")]);
+
+        // Once the server is empty, every local item that is not new has been deleted from the server
+        let changes = Changes::between(&local, &HashMap::new());
+        assert_eq!(changes.server_only.values().filter(|change| **change == Change::RemoteDeletion).count(), 3);
+        assert_eq!(changes.local_only.len(), 2);
+        assert_eq!(changes.both.len(), 4);
+        assert_eq!(Changes::between(&HashMap::new(), &remote).server_only.len(), remote.len());
+    }
+}
@@ -0,0 +1,237 @@
+//! Sync of the contacts of address books (see [`crate::addressbook`]), that happens along with the sync of calendars
+//!
+//! Contacts are compared like the items of calendars (see the `changes` module). Conflicts are resolved in favour of the server.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::addressbook::{AddressBook, SharedAddressBook};
+use crate::contact::build_vcard;
+use crate::item::SyncStatus;
+use crate::traits::{CalDavSource, CompleteCalendar, DavCalendar};
+use crate::utils::redact;
+use super::Provider;
+use super::changes::Change;
+use super::sync_progress::SyncProgress;
+
+impl<L, T, R, U> Provider<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    /// Sync every address book of the remote source with its local counterpart, that is created if needed.
+    /// Address books that only exist locally are not created on the server
+    pub(super) async fn sync_address_books(&mut self, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let books_remote = match self.remote.address_book_source() {
+            None => return Ok(()),
+            Some(source) => source.get_address_books().await?,
+        };
+        let books_local = match self.local.address_book_source() {
+            None => {
+                progress.debug("The local source does not store contacts, address books are not synced");
+                return Ok(());
+            },
+            Some(source) => source.get_address_books().await?,
+        };
+
+        for (book_url, book_remote) in books_remote {
+            progress.check_cancelled()?;
+            let book_local = match books_local.get(&book_url) {
+                Some(book_local) => book_local.clone(),
+                None => {
+                    let name = book_remote.read().await.name().to_string();
+                    let created = match self.local.address_book_source_mut() {
+                        None => Err("the local source does not store contacts".into()),
+                        Some(source) => source.create_address_book(book_url.clone(), name).await,
+                    };
+                    match created {
+                        Err(err) => {
+                            progress.warn(&format!("Unable to create a local counterpart for address book {} ({}). Skipping this time", book_url, err));
+                            continue;
+                        },
+                        Ok(book_local) => book_local,
+                    }
+                },
+            };
+            if let Err(err) = Self::sync_address_book_pair(book_local, book_remote, progress).await {
+                progress.check_cancelled()?;
+                progress.warn(&format!("Unable to sync address book {}: {}, skipping this time.", book_url, err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sync an address book with its remote counterpart.
+    ///
+    /// The local address book is only locked for short sections that send no requests, so that it can be used while the sync waits for the server.
+    /// Contacts that are changed locally meanwhile are not overwritten, they will be synced next time
+    async fn sync_address_book_pair(book_local: SharedAddressBook, book_remote: SharedAddressBook, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let mut book_remote = book_remote.write().await;
+        let book_url = book_remote.url().clone();
+        progress.info(&format!("Syncing address book {}", book_remote.name()));
+
+        let remote_tags = book_remote.get_contact_version_tags().await?;
+        let local_statuses = book_local.read().await.get_contact_sync_statuses().await?;
+        let urls: HashSet<&Url> = remote_tags.keys().chain(local_statuses.keys()).collect();
+
+        for url in urls {
+            progress.check_cancelled()?;
+            let local_status = local_statuses.get(url);
+            let change = Change::classify(local_status, remote_tags.get(url));
+            if let Err(err) = Self::sync_contact(url, change, local_status, &*book_local, &mut *book_remote, progress).await {
+                progress.item_error(&book_url, url, &err.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_contact(
+        url: &Url, change: Change, local_status: Option<&SyncStatus>,
+        book_local: &RwLock<dyn AddressBook + Send + Sync>, book_remote: &mut (dyn AddressBook + Send + Sync),
+        progress: &mut SyncProgress,
+    ) -> Result<(), Box<dyn Error>> {
+        match change {
+            Change::Unchanged => {},
+
+            Change::RemoteAddition | Change::RemoteModification
+            | Change::ModifiedOnBoth | Change::LocallyDeletedRemotelyModified | Change::UrlReuse => {
+                if change != Change::RemoteAddition && change != Change::RemoteModification {
+                    progress.debug(&format!("> Conflict for contact {}, the server wins", redact(url)));
+                    progress.report_mut().conflicts += 1;
+                }
+                let contact = book_remote.get_contact_by_url(url).await?;
+                let mut book_local = book_local.write().await;
+                if Self::changed_during_sync(&*book_local, url, local_status).await {
+                    progress.debug(&format!("> Contact {} has been changed locally during the sync, it will be synced next time", redact(url)));
+                    return Ok(());
+                }
+                match local_status.is_some() {
+                    true => book_local.update_contact(contact).await?,
+                    false => book_local.add_contact(contact).await?,
+                };
+                progress.report_mut().downloaded += 1;
+            },
+
+            Change::RemoteDeletion | Change::LocallyModifiedRemotelyDeleted | Change::DeletedOnBoth => {
+                let mut book_local = book_local.write().await;
+                if Self::changed_during_sync(&*book_local, url, local_status).await {
+                    progress.debug(&format!("> Contact {} has been changed locally during the sync, it will be synced next time", redact(url)));
+                    return Ok(());
+                }
+                book_local.delete_contact(url).await?;
+                if change != Change::DeletedOnBoth {
+                    progress.report_mut().deleted_locally += 1;
+                }
+            },
+
+            Change::LocalAddition | Change::LocalModification => {
+                let mut contact = book_local.read().await.get_contact_by_url(url).await?;
+                let new_status = match change {
+                    Change::LocalAddition => book_remote.add_contact(contact.clone()).await?,
+                    _ => book_remote.update_contact(contact.clone()).await?,
+                };
+                let mut book_local = book_local.write().await;
+                // Changes made during the upload are kept, and uploaded by the next sync
+                let contact = match (book_local.get_contact_by_url(url).await, new_status.version_tag()) {
+                    (Ok(mut current), Some(tag)) if build_vcard(&current) != build_vcard(&contact) || current.sync_status() != contact.sync_status() => {
+                        progress.debug(&format!("> Contact {} has been changed during its upload, these changes will be uploaded next time", redact(url)));
+                        let status = match current.sync_status() {
+                            SyncStatus::LocallyDeleted(_) => SyncStatus::LocallyDeleted(tag.clone()),
+                            _ => SyncStatus::LocallyModified(tag.clone()),
+                        };
+                        current.set_sync_status(status);
+                        current
+                    },
+                    _ => {
+                        contact.set_sync_status(new_status);
+                        contact
+                    },
+                };
+                book_local.update_contact(contact).await?;
+                progress.report_mut().uploaded += 1;
+            },
+
+            Change::LocalDeletion => {
+                book_remote.delete_contact(url).await?;
+                book_local.write().await.delete_contact(url).await?;
+                progress.report_mut().deleted_remotely += 1;
+            },
+        }
+        Ok(())
+    }
+
+    /// Whether the sync status of a local contact is not `status_before_sync` anymore
+    async fn changed_during_sync(book_local: &(dyn AddressBook + Send + Sync), url: &Url, status_before_sync: Option<&SyncStatus>) -> bool {
+        let status = book_local.get_contact_by_url(url).await.ok().map(|contact| contact.sync_status().clone());
+        status.as_ref() != status_before_sync
+    }
+}
+
+#[cfg(all(test, feature = "local_calendar_mocks_remote_calendars"))]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use crate::cache::Cache;
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::contact::Contact;
+    use crate::mock_behaviour::MockBehaviour;
+
+    #[tokio::test]
+    async fn test_sync_address_books() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let book_url: Url = "https://carddav.com/contacts/".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/sync_address_books/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sync_address_books/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_book = remote.create_address_book_sync(book_url.clone(), "Contacts".to_string()).unwrap();
+        let mut jane = Contact::new("Jane Doe".to_string(), &book_url);
+        jane.set_sync_status(SyncStatus::random_synced());
+        let jane_url = jane.url().clone();
+        remote_book.write().await.insert_contact(jane).unwrap();
+
+        // Remote address books and contacts are downloaded...
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_book = provider.local().get_address_book_sync(&book_url).unwrap();
+        assert_eq!(local_book.read().await.name(), "Contacts");
+        assert_eq!(local_book.read().await.get_contact(&jane_url).unwrap().full_name(), "Jane Doe");
+        assert_eq!(provider.last_sync_report().downloaded(), 1);
+
+        // ...and local changes are uploaded
+        let john = Contact::new("John Doe".to_string(), &book_url);
+        let john_url = john.url().clone();
+        {
+            let mut local_book = local_book.write().await;
+            local_book.insert_contact(john).unwrap();
+            local_book.get_contact_mut(&jane_url).unwrap().set_full_name("Jane Smith".to_string());
+        }
+        assert!(provider.sync().await);
+        assert_eq!(provider.last_sync_report().uploaded(), 2);
+        {
+            let remote_book = provider.remote().get_address_book_sync(&book_url).unwrap();
+            let remote_book = remote_book.read().await;
+            assert_eq!(remote_book.get_contact(&jane_url).unwrap().full_name(), "Jane Smith");
+            assert_eq!(remote_book.get_contact(&john_url).unwrap().full_name(), "John Doe");
+        }
+        for contact in local_book.read().await.get_contacts().values() {
+            assert!(matches!(contact.sync_status(), SyncStatus::Synced(_)));
+        }
+
+        // Deletions are synced both ways
+        local_book.write().await.mark_for_deletion(&john_url).unwrap();
+        provider.remote().get_address_book_sync(&book_url).unwrap().write().await.delete_contact(&jane_url).await.unwrap();
+        assert!(provider.sync().await);
+        assert_eq!(provider.last_sync_report().deleted_locally(), 1);
+        assert_eq!(provider.last_sync_report().deleted_remotely(), 1);
+        assert!(local_book.read().await.get_contacts().is_empty());
+        assert!(provider.remote().get_address_book_sync(&book_url).unwrap().read().await.get_contacts().is_empty());
+    }
+}
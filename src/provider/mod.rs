@@ -3,21 +3,42 @@
 //! It is also responsible for syncing them together
 
 use std::error::Error;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::marker::PhantomData;
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::sync::Arc;
 use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use url::Url;
 use itertools::Itertools;
+use futures_util::{Stream, StreamExt};
+use tokio::sync::RwLock;
 
-use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
+use crate::traits::{BaseCalendar, CalDavSource, DavCalendar, DynSource};
 use crate::traits::CompleteCalendar;
-use crate::item::SyncStatus;
+use crate::item::{Item, ItemVersion, SyncStatus, UnparsedItem, VanishedItem};
+use crate::calendar::SyncExclusion;
+use crate::metrics::SyncMetrics;
+use crate::trace::SyncTrace;
+use crate::clock::Clock;
+use crate::client::QuotaExceeded;
+use crate::utils::redact;
+use crate::id::CalendarId;
 
 pub mod sync_progress;
+pub mod scheduler;
+pub mod push;
+pub mod accounts;
+mod changes;
+#[cfg(feature = "addressbook")]
+mod contacts;
+use changes::{Change, Changes};
 use sync_progress::SyncProgress;
-use sync_progress::{FeedbackSender, SyncEvent};
+use sync_progress::{CancellationToken, EventQueue, FeedbackSender, SyncEvent, SyncReport};
 
 /// How many items will be batched in a single HTTP request when downloading from the server
 #[cfg(not(test))]
@@ -42,6 +63,137 @@ impl Display for BatchDownloadType {
     }
 }
 
+/// The local changes of a calendar that have not been uploaded to the remote source yet, see [`Provider::pending_changes`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PendingChanges {
+    /// Items that have been created locally
+    pub created: Vec<Url>,
+    /// Items that have been modified locally
+    pub modified: Vec<Url>,
+    /// Items that have been deleted locally
+    pub deleted: Vec<Url>,
+}
+
+impl PendingChanges {
+    /// The number of changes awaiting upload
+    pub fn len(&self) -> usize {
+        self.created.len() + self.modified.len() + self.deleted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Where items that have been created locally are stored on the remote source, see [`Provider::set_href_strategy`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HrefStrategy {
+    /// Keep the URL the item has been created with (a random one, for items created by [`Task::new`](crate::Task::new)). This is the default.
+    #[default]
+    Random,
+    /// Upload items to `<UID>.ics`, which is what most clients do.
+    /// Items whose UID contains characters that are not safe in a URL (or whose UID-based URL is already used, locally or remotely) keep their URL.
+    Uid,
+    /// Let the server choose the URL (using a `POST` request, see RFC 5995).
+    /// Servers that do not support it are handled as with [`HrefStrategy::Uid`].
+    ServerAssigned,
+}
+
+/// What a sync does with the items of a calendar that have the same UID (e.g. because of a server quirk, or an item moved by another client),
+/// see [`Provider::set_uid_dedup`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum UidDedup {
+    /// Keep every item. This is the default.
+    #[default]
+    KeepAll,
+    /// Keep the newest item (the one with the highest `SEQUENCE`, then the latest `LAST-MODIFIED`, see [`Provider::set_clock_skew_tolerance`]), and delete the other ones from both sources.
+    KeepNewest,
+}
+
+/// How the first sync of a calendar handles the items that have been created locally before, see [`Provider::set_bootstrap_strategy`].
+///
+/// These items may already exist on the server under another URL (e.g. when an app is reinstalled, or when a calendar is imported on several devices).
+/// Local items are matched to remote items by their UID.
+///
+/// Calendars that are empty on the server (e.g. because they have just been created there by this sync) are not reconciled:
+/// their local items are simply uploaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BootstrapStrategy {
+    /// Upload every local item, as during any other sync. Items that already exist on the server will be duplicated. This is the default.
+    #[default]
+    PushLocal,
+    /// Local items that exist on the server are merged with their remote counterpart: the newest version (the one with the highest `SEQUENCE`,
+    /// then the latest `LAST-MODIFIED`, see [`Provider::set_clock_skew_tolerance`]) is kept. Local items that do not exist on the server are uploaded.
+    MergeByUid,
+    /// The server is the reference: local items that exist on the server are replaced by their remote version.
+    /// Local items that do not exist on the server are uploaded.
+    AdoptServer,
+    /// Like [`Self::AdoptServer`], but local items that do not exist on the server are discarded as well, although they have never been synced.
+    /// This loses data, and must be explicitly chosen.
+    DiscardLocal,
+}
+
+
+/// How a calendar is synced, see [`Provider::set_sync_mode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// Changes are applied to both sources. This is the default.
+    #[default]
+    TwoWay,
+    /// Only the changes of the server are applied locally. Local changes are not uploaded, but they are kept, so that they are uploaded once the calendar is synced both ways again.
+    PullOnly,
+    /// Only the local changes are uploaded. Changes made on the server are not applied locally (neither are the conflicts that the server wins).
+    PushOnly,
+    /// The local calendar is made to exactly match the server (e.g. for backups or kiosk displays): local changes are discarded, and nothing is uploaded.
+    Mirror,
+}
+
+
+/// The settings of a [`Provider`] that are used by the sync of every calendar
+#[derive(Clone, Debug)]
+struct SyncSettings {
+    /// Where locally created items are uploaded
+    href_strategy: HrefStrategy,
+    /// What to do with items that have the same UID
+    uid_dedup: UidDedup,
+    /// How the first sync of a calendar handles its local items
+    bootstrap_strategy: BootstrapStrategy,
+    /// The clock syncs are dated with
+    clock: Arc<dyn Clock>,
+    /// How far apart `LAST-MODIFIED` dates must be to be considered different
+    skew_tolerance: chrono::Duration,
+    /// Whether items are downloaded in batches. This depends on the capabilities of the server
+    multiget: bool,
+    /// How long items stay in the trash before they are deleted
+    trash_purge_delay: chrono::Duration,
+    /// How calendars are synced, if they are not synced both ways
+    sync_modes: HashMap<Url, SyncMode>,
+    /// How many calendars are synced at once
+    max_concurrent_calendars: usize,
+}
+
+impl SyncSettings {
+    fn sync_mode(&self, cal_url: &Url) -> SyncMode {
+        self.sync_modes.get(cal_url).copied().unwrap_or_default()
+    }
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            href_strategy: HrefStrategy::default(),
+            uid_dedup: UidDedup::default(),
+            bootstrap_strategy: BootstrapStrategy::default(),
+            clock: crate::clock::default_clock(),
+            skew_tolerance: chrono::Duration::zero(),
+            multiget: true,
+            trash_purge_delay: chrono::Duration::days(30),
+            sync_modes: HashMap::new(),
+            max_concurrent_calendars: 4,
+        }
+    }
+}
+
 
 /// A data source that combines two `CalDavSource`s, which is able to sync both sources.
 ///
@@ -62,6 +214,15 @@ where
 
     phantom_t: PhantomData<T>,
     phantom_u: PhantomData<U>,
+
+    /// The report of the latest sync
+    last_report: SyncReport,
+    /// The maximum duration of a sync
+    sync_timeout: Option<Duration>,
+    /// Statistics about every sync
+    metrics: SyncMetrics,
+    /// How calendars are synced
+    settings: SyncSettings,
 }
 
 impl<L, T, R, U> Provider<L, T, R, U>
@@ -74,10 +235,31 @@ where
     /// Create a provider.
     ///
     /// `remote` is usually a [`Client`](crate::client::Client), `local` is usually a [`Cache`](crate::cache::Cache).
-    /// However, both can be interchangeable. The only difference is that `remote` always wins in case of a sync conflict
+    /// However, both can be interchangeable. The only difference is that `remote` wins in case of a sync conflict (unless the `local` version has a higher `SEQUENCE`)
     pub fn new(remote: R, local: L) -> Self {
         Self { remote, local,
             phantom_t: PhantomData, phantom_u: PhantomData,
+            last_report: SyncReport::default(),
+            sync_timeout: None,
+            metrics: SyncMetrics::default(),
+            settings: SyncSettings::default(),
+        }
+    }
+
+    /// Turn this provider into a [`DynProvider`](crate::DynProvider), whose sources are type-erased. Its settings are kept
+    pub fn into_dyn(self) -> Provider<DynSource<T>, T, DynSource<U>, U>
+    where
+        L: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        Provider {
+            remote: Box::new(self.remote),
+            local: Box::new(self.local),
+            phantom_t: PhantomData, phantom_u: PhantomData,
+            last_report: self.last_report,
+            sync_timeout: self.sync_timeout,
+            metrics: self.metrics,
+            settings: self.settings,
         }
     }
 
@@ -92,10 +274,128 @@ where
     /// To be sure `local` accurately mirrors the `remote` source, you can run [`Provider::sync`]
     pub fn remote(&self) -> &R { &self.remote }
 
+    /// Set the maximum duration of every sync (or `None` for no limit, which is the default).
+    ///
+    /// Once this duration has elapsed, syncs stop as if they were cancelled (see [`Self::sync_with_cancellation`]): what has been synced already is kept.
+    /// A sync that is waiting for a request at that time is interrupted as well, the same way as when a [`Self::sync_stream`] is dropped.
+    pub fn set_sync_timeout(&mut self, timeout: Option<Duration>) {
+        self.sync_timeout = timeout;
+    }
+
+    /// Set where the items that have been created locally will be uploaded (see [`HrefStrategy`]).
+    ///
+    /// With a strategy other than [`HrefStrategy::Random`], the URL of a new item may change when it is uploaded.
+    /// Apps that keep references to items should rather use their [`StableItemId`](crate::item::StableItemId).
+    pub fn set_href_strategy(&mut self, strategy: HrefStrategy) {
+        self.settings.href_strategy = strategy;
+    }
+
+    /// Set what syncs do with the items of a calendar that have the same UID (see [`UidDedup`]).
+    ///
+    /// Duplicates are looked for at the end of the sync of every calendar, once both sources contain the same items.
+    pub fn set_uid_dedup(&mut self, dedup: UidDedup) {
+        self.settings.uid_dedup = dedup;
+    }
+
+    /// Set how the first sync of a calendar handles the items that have been created locally before (see [`BootstrapStrategy`]).
+    ///
+    /// A calendar is synced for the first time when it has no [`last_sync`](CompleteCalendar::last_sync), and none of its local items has ever been synced.
+    pub fn set_bootstrap_strategy(&mut self, strategy: BootstrapStrategy) {
+        self.settings.bootstrap_strategy = strategy;
+    }
+
+    /// Set the clock syncs are dated with (see [`CompleteCalendar::last_sync`] and [`SyncMetrics`]).
+    /// By default, this is the clock of [`config::CLOCK`](crate::config::CLOCK)
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.settings.clock = clock;
+    }
+
+    /// Set how far apart the `LAST-MODIFIED` dates of two versions of an item must be for one of them to be considered newer (there is no tolerance by default).
+    ///
+    /// Changes are detected with version tags, and conflicts are resolved with `SEQUENCE` numbers. `LAST-MODIFIED` dates are only used as a last resort
+    /// (see [`UidDedup::KeepNewest`] and [`BootstrapStrategy::MergeByUid`]), but they are written by devices whose clocks may disagree.
+    /// Versions whose dates are closer than this tolerance are handled as if they had the same date, i.e. the remote version wins.
+    pub fn set_clock_skew_tolerance(&mut self, tolerance: Duration) {
+        self.settings.skew_tolerance = chrono::Duration::from_std(tolerance).unwrap_or_else(|_| chrono::Duration::max_value());
+    }
+
+    /// Set how many calendars are synced at once (4 by default). Use 1 to sync them one after the other.
+    ///
+    /// Syncing several calendars at once is faster for accounts with many calendars, since requests are sent while waiting for the replies
+    /// to other ones (using the same connections, see [`ClientBuilder`](crate::client::ClientBuilder)). Note that the remote copy of each calendar is locked while it is being synced (its local copy is only locked for short moments).
+    pub fn set_max_concurrent_calendars(&mut self, limit: usize) {
+        self.settings.max_concurrent_calendars = limit.max(1);
+    }
+
+    /// Set how a calendar is synced (see [`SyncMode`]). Calendars are synced both ways by default.
+    ///
+    /// Except for [`SyncMode::TwoWay`], the first sync of the calendar ignores the [`BootstrapStrategy`], and duplicates are not removed (see [`UidDedup`]).
+    pub fn set_sync_mode(&mut self, calendar: Url, mode: SyncMode) {
+        match mode {
+            SyncMode::TwoWay => self.settings.sync_modes.remove(&calendar),
+            _ => self.settings.sync_modes.insert(calendar, mode),
+        };
+    }
+
+    /// Returns how a calendar is synced, see [`Self::set_sync_mode`]
+    pub fn sync_mode(&self, calendar: &Url) -> SyncMode {
+        self.settings.sync_mode(calendar)
+    }
+
+    /// Set how long items stay in the trash (see [`CompleteCalendar::trash_item`]) before they are deleted, locally and from the server (30 days by default).
+    ///
+    /// The trash of a calendar is purged when it is synced, so that items may stay in it longer than this delay.
+    pub fn set_trash_purge_delay(&mut self, delay: Duration) {
+        self.settings.trash_purge_delay = chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::max_value());
+    }
+
+    /// Returns statistics about the syncs run by this provider, and the requests made to the remote source
+    pub fn metrics(&self) -> SyncMetrics {
+        SyncMetrics {
+            requests: self.remote.request_stats(),
+            ..self.metrics.clone()
+        }
+    }
+
+    /// Returns the local changes that are waiting for the next sync to be uploaded, for every calendar that has some
+    /// (e.g. to tell the user that some changes have only been made offline so far)
+    pub async fn pending_changes(&self) -> Result<HashMap<Url, PendingChanges>, Box<dyn Error>> {
+        let mut pending = HashMap::new();
+        for (cal_url, cal) in self.local.get_calendars().await? {
+            let cal = cal.read().await;
+            if cal.calendar_sync_exclusion() == Some(SyncExclusion::LocalOnly) {
+                continue;
+            }
+            let mut changes = PendingChanges::default();
+            for (url, item) in cal.get_items().await? {
+                if cal.sync_exclusion(&url) == Some(SyncExclusion::LocalOnly) {
+                    continue;
+                }
+                match item.sync_status() {
+                    SyncStatus::NotSynced => changes.created.push(url),
+                    SyncStatus::LocallyModified(_) => changes.modified.push(url),
+                    SyncStatus::LocallyDeleted(_) => changes.deleted.push(url),
+                    SyncStatus::Synced(_) => (),
+                }
+            }
+            if !changes.is_empty() {
+                for urls in [&mut changes.created, &mut changes.modified, &mut changes.deleted] {
+                    urls.sort();
+                }
+                pending.insert(cal_url, changes);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Returns the report of the latest sync (e.g. to tell which items could not be synced)
+    pub fn last_sync_report(&self) -> &SyncReport { &self.last_report }
+
     /// Performs a synchronisation between `local` and `remote`, and provide feeedback to the user about the progress.
     ///
     /// This bidirectional sync applies additions/deletions made on a source to the other source.
-    /// In case of conflicts (the same item has been modified on both ends since the last sync, `remote` always wins).
+    /// In case of conflicts (the same item has been modified on both ends since the last sync), changes to different fields are merged (see [`Item::merge`]).
+    /// Otherwise, the version with the highest `SEQUENCE` wins. If they are equal, `remote` wins.
     ///
     /// It returns whether the sync was totally successful (details about errors are logged using the `log::*` macros).
     /// In case errors happened, the sync might have been partially executed but your data will never be correupted (either locally nor in the server).
@@ -105,6 +405,72 @@ where
         self.run_sync(&mut progress).await
     }
 
+    /// Performs a synchronisation between `local` and `remote`, and returns a summary of what has been synced (and what failed).
+    ///
+    /// See [`Self::sync_with_feedback`]
+    pub async fn sync_with_report(&mut self) -> SyncReport {
+        let mut progress = SyncProgress::new();
+        self.run_sync(&mut progress).await;
+        self.last_report.clone()
+    }
+
+    /// Performs a synchronisation between `local` and `remote`, that stops as soon as `token` is cancelled.
+    ///
+    /// The sync stops between two items, so that both sources are left consistent: the items that have been synced already stay synced,
+    /// and the other ones will be synced by the next sync. See also [`Self::sync_with_report`]
+    pub async fn sync_with_cancellation(&mut self, token: CancellationToken) -> SyncReport {
+        let mut progress = SyncProgress::new().with_cancellation(token);
+        self.run_sync(&mut progress).await;
+        self.last_report.clone()
+    }
+
+    /// Performs a synchronisation between `local` and `remote`, and records the HTTP traffic of `remote` into a trace file (see [`crate::trace`]).
+    ///
+    /// This is meant to be attached to bug reports about servers this crate does not work well with.
+    /// Only remote sources that make HTTP requests (such as a [`Client`](crate::client::Client)) are traced. See also [`Self::sync_with_report`]
+    pub async fn sync_with_trace<P: AsRef<Path>>(&mut self, trace_path: P) -> Result<SyncReport, Box<dyn Error>> {
+        let trace = Arc::new(SyncTrace::create(trace_path)?);
+        self.remote.set_trace(Some(trace));
+        let report = self.sync_with_report().await;
+        self.remote.set_trace(None);
+        Ok(report)
+    }
+
+    /// Performs a synchronisation between `local` and `remote`, as a stream of every [`SyncEvent`] of the sync, that ends after [`SyncEvent::Finished`].
+    ///
+    /// The sync only makes progress while the stream is polled, and it waits for each event to be consumed before going on.
+    /// This lets a UI render the progress between two steps of the sync, or pause the sync by not polling the stream for a while
+    /// (note that the remote copy of the calendar being synced stays locked while the sync is paused, and that servers may close idle connections in the meantime).
+    /// Dropping the stream stops the sync, the same way a [cancellation](Self::sync_with_cancellation) does, except that a request being sent may be interrupted.
+    /// Once the stream has ended, the summary of the sync is available in [`Self::last_sync_report`]. See also [`Self::sync_with_feedback`]
+    pub fn sync_stream(&mut self) -> impl Stream<Item = SyncEvent> + '_ {
+        let events = EventQueue::default();
+        let mut progress = SyncProgress::new().with_event_queue(events.clone());
+        let mut sync = Some(Box::pin(async move {
+            self.run_sync(&mut progress).await;
+        }));
+
+        futures_util::stream::poll_fn(move |cx| {
+            loop {
+                if let Some(event) = events.lock().unwrap().pop_front() {
+                    return Poll::Ready(Some(event));
+                }
+                let future = match sync.as_mut() {
+                    None => return Poll::Ready(None),
+                    Some(future) => future,
+                };
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(()) => sync = None,
+                    Poll::Pending => {
+                        if events.lock().unwrap().is_empty() {
+                            return Poll::Pending;
+                        }
+                    },
+                }
+            }
+        })
+    }
+
     /// Performs a synchronisation between `local` and `remote`, without giving any feedback.
     ///
     /// See [`Self::sync_with_feedback`]
@@ -113,58 +479,196 @@ where
         self.run_sync(&mut progress).await
     }
 
+    /// Move an item to another calendar, keeping its UID, and returns its new URL.
+    ///
+    /// If the item is synced already, it is moved on the remote source right away (e.g. using a WebDAV `MOVE` request). \
+    /// Otherwise (or in case the remote source does not support it), a copy of the item is locally created in the target calendar and the original one
+    /// is marked for deletion, so that the next sync will apply the move on the remote source.
+    pub async fn move_item(&mut self, item_url: &Url, from_calendar: &Url, to_calendar: &Url) -> Result<Url, Box<dyn Error>> {
+        if from_calendar == to_calendar {
+            return Err("Cannot move an item to the calendar it already belongs to".into());
+        }
+        let local_from = self.local.get_calendar(from_calendar).await.ok_or_else(|| format!("No such local calendar {}", from_calendar))?;
+        let local_to = self.local.get_calendar(to_calendar).await.ok_or_else(|| format!("No such local calendar {}", to_calendar))?;
+
+        let item = local_from.read().await.get_item_by_url(item_url).await.cloned()
+            .ok_or_else(|| format!("No item {} in calendar {}", item_url, from_calendar))?;
+        if local_to.read().await.get_item_by_uid(item.uid()).await.is_some() {
+            return Err(format!("Calendar {} already contains an item with UID {}", to_calendar, item.uid()).into());
+        }
+        let new_url = crate::utils::random_url(to_calendar);
+
+        let supports_move = self.remote.capabilities().map(|capabilities| capabilities.supports_move()).unwrap_or(true);
+        if let (SyncStatus::Synced(_), true) = (item.sync_status(), supports_move) {
+            if let Some(remote_from) = self.remote.get_calendar(from_calendar).await {
+                let move_result = remote_from.write().await.move_item(item_url, &new_url).await;
+                match move_result {
+                    Ok(new_ss) => {
+                        let moved = item.clone_with_identity(item.uid().to_string(), new_url.clone(), new_ss);
+                        local_to.write().await.add_item(moved).await?;
+                        local_from.write().await.immediately_delete_item(item_url).await?;
+                        return Ok(new_url);
+                    },
+                    Err(err) => log::info!("Unable to move {} on the remote source ({}). It will be copied and deleted instead", redact(item_url), err),
+                }
+            }
+        }
+
+        let copy = item.clone_with_identity(item.uid().to_string(), new_url.clone(), SyncStatus::NotSynced);
+        local_to.write().await.add_item(copy).await?;
+        local_from.write().await.mark_for_deletion(item_url).await?;
+        Ok(new_url)
+    }
+
+    /// Performs a synchronisation of a single calendar between `local` and `remote`, without giving any feedback.
+    ///
+    /// This is useful when you know only this calendar has changed (e.g. after a push notification from the server, see [`crate::provider::push`]). \
+    /// It returns whether the sync was totally successful. See [`Self::sync_with_feedback`] for more details.
+    pub async fn sync_calendar(&mut self, cal_url: &Url) -> bool {
+        let start = Instant::now();
+        let mut progress = SyncProgress::new();
+        self.apply_sync_timeout(&mut progress);
+        progress.step(SyncEvent::Started).await;
+        let timeout = self.sync_timeout;
+        if let Err(err) = within_timeout(timeout, self.sync_calendar_inner(cal_url, &mut progress)).await {
+            if progress.is_cancelled() {
+                progress.report_mut().cancelled = true;
+                progress.info(&format!("Sync of calendar {} cancelled", cal_url));
+            } else {
+                progress.error(&format!("Sync of calendar {} terminated because of an error: {}", cal_url, err));
+            }
+        }
+        self.last_report = progress.report();
+        self.metrics.record_sync(&self.last_report, start.elapsed(), self.settings.clock.now());
+        progress.feedback(SyncEvent::Finished{ success: progress.is_success() });
+        progress.is_success()
+    }
+
+    async fn sync_calendar_inner(&mut self, cal_url: &Url, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        if let Some(cal_remote) = self.remote.get_calendar(cal_url).await {
+            let settings = self.sync_settings(progress);
+            let counterpart = self.get_or_insert_local_counterpart_calendar(cal_url, cal_remote.clone()).await?;
+            Self::sync_calendar_pair(counterpart, cal_remote, &settings, progress).await?;
+        } else if let Some(cal_local) = self.local.get_calendar(cal_url).await {
+            if cal_local.read().await.calendar_sync_exclusion() == Some(SyncExclusion::LocalOnly) {
+                progress.debug(&format!("Calendar {} is local only, it is not synced", cal_url));
+                return Ok(());
+            }
+            let counterpart = self.get_or_insert_remote_counterpart_calendar(cal_url, cal_local.clone()).await?;
+            let settings = self.sync_settings(progress);
+            Self::sync_calendar_pair(cal_local, counterpart, &settings, progress).await?;
+        } else {
+            return Err(format!("No such calendar {}", cal_url).into());
+        }
+
+        self.restore_moved_items(progress).await;
+        Ok(())
+    }
+
+    /// The settings of a sync, adapted to the capabilities of the server (that are known once its calendars have been fetched)
+    fn sync_settings(&self, progress: &mut SyncProgress) -> SyncSettings {
+        let mut settings = self.settings.clone();
+        if let Some(capabilities) = self.remote.capabilities() {
+            progress.debug(&format!("Server capabilities: {:?}", capabilities));
+            settings.multiget = capabilities.supports_multiget();
+        }
+        settings
+    }
+
+    fn apply_sync_timeout(&self, progress: &mut SyncProgress) {
+        if let Some(timeout) = self.sync_timeout {
+            progress.set_deadline(Instant::now() + timeout);
+        }
+    }
+
     async fn run_sync(&mut self, progress: &mut SyncProgress) -> bool {
-        if let Err(err) = self.run_sync_inner(progress).await {
-            progress.error(&format!("Sync terminated because of an error: {}", err));
+        let start = Instant::now();
+        self.apply_sync_timeout(progress);
+        let timeout = self.sync_timeout;
+        if let Err(err) = within_timeout(timeout, self.run_sync_inner(progress)).await {
+            if progress.is_cancelled() {
+                progress.report_mut().cancelled = true;
+                progress.info("Sync cancelled");
+            } else {
+                progress.error(&format!("Sync terminated because of an error: {}", err));
+            }
         }
+        self.last_report = progress.report();
+        self.metrics.record_sync(&self.last_report, start.elapsed(), self.settings.clock.now());
         progress.feedback(SyncEvent::Finished{ success: progress.is_success() });
         progress.is_success()
     }
 
+    #[tracing::instrument(name = "sync", target = "kitchen_fridge::sync", skip_all)]
     async fn run_sync_inner(&mut self, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
         progress.info("Starting a sync.");
-        progress.feedback(SyncEvent::Started);
+        progress.step(SyncEvent::Started).await;
 
-        let mut handled_calendars = HashSet::new();
+        let mut pairs = Vec::new();
 
-        // Sync every remote calendar
+        // Pair every remote calendar...
         let cals_remote = self.remote.get_calendars().await?;
+        let settings = self.sync_settings(progress);
         for (cal_url, cal_remote) in cals_remote {
-            let counterpart = match self.get_or_insert_local_counterpart_calendar(&cal_url, cal_remote.clone()).await {
-                Err(err) => {
-                    progress.warn(&format!("Unable to get or insert local counterpart calendar for {} ({}). Skipping this time", cal_url, err));
-                    continue;
-                },
-                Ok(arc) => arc,
-            };
-
-            if let Err(err) = Self::sync_calendar_pair(counterpart, cal_remote, progress).await {
-                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
-                continue;
+            progress.check_cancelled()?;
+            match self.get_or_insert_local_counterpart_calendar(&cal_url, cal_remote.clone()).await {
+                Err(err) => progress.warn(&format!("Unable to get or insert local counterpart calendar for {} ({}). Skipping this time", cal_url, err)),
+                Ok(counterpart) => pairs.push((cal_url, counterpart, cal_remote)),
             }
-            handled_calendars.insert(cal_url);
         }
 
-        // Sync every local calendar that would not be in the remote yet
+        // ...and every local calendar that would not be in the remote yet
         let cals_local = self.local.get_calendars().await?;
         for (cal_url, cal_local) in cals_local {
-            if handled_calendars.contains(&cal_url) {
+            progress.check_cancelled()?;
+            if pairs.iter().any(|(url, _, _)| url == &cal_url) {
+                continue;
+            }
+            if cal_local.read().await.calendar_sync_exclusion() == Some(SyncExclusion::LocalOnly) {
+                progress.debug(&format!("Calendar {} is local only, it is not created on the server", cal_url));
                 continue;
             }
+            match self.get_or_insert_remote_counterpart_calendar(&cal_url, cal_local.clone()).await {
+                Err(err) => progress.warn(&format!("Unable to get or insert remote counterpart calendar for {} ({}). Skipping this time", cal_url, err)),
+                Ok(counterpart) => pairs.push((cal_url, cal_local, counterpart)),
+            }
+        }
 
-            let counterpart = match self.get_or_insert_remote_counterpart_calendar(&cal_url, cal_local.clone()).await {
-                Err(err) => {
-                    progress.warn(&format!("Unable to get or insert remote counterpart calendar for {} ({}). Skipping this time", cal_url, err));
-                    continue;
-                },
-                Ok(arc) => arc,
-            };
+        // Calendars are independent, so that several of them are synced at once
+        let settings = &settings;
+        let parent_progress = &*progress;
+        let mut syncs = futures_util::stream::iter(pairs)
+            .map(|(cal_url, cal_local, cal_remote)| async move {
+                let mut cal_progress = parent_progress.child();
+                let result = Self::sync_calendar_pair(cal_local, cal_remote, settings, &mut cal_progress).await
+                    .map_err(|err| err.to_string());
+                (cal_url, result, cal_progress)
+            })
+            .buffer_unordered(settings.max_concurrent_calendars.max(1));
 
-            if let Err(err) = Self::sync_calendar_pair(cal_local, counterpart, progress).await {
-                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
-                continue;
+        let mut results = Vec::new();
+        while let Some(result) = syncs.next().await {
+            results.push(result);
+        }
+        drop(syncs);
+
+        for (cal_url, result, cal_progress) in results {
+            progress.merge(cal_progress);
+            if let Err(err) = result {
+                if !progress.is_cancelled() {
+                    progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
+                }
             }
         }
+        progress.check_cancelled()?;
+
+        self.restore_moved_items(progress).await;
+
+        #[cfg(feature = "addressbook")]
+        if let Err(err) = self.sync_address_books(progress).await {
+            progress.check_cancelled()?;
+            progress.warn(&format!("Unable to sync address books: {}", err));
+        }
 
         progress.info("Sync ended");
 
@@ -172,26 +676,56 @@ where
     }
 
 
-    async fn get_or_insert_local_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<Mutex<U>>) -> Result<Arc<Mutex<T>>, Box<dyn Error>> {
+    async fn get_or_insert_local_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<RwLock<U>>) -> Result<Arc<RwLock<T>>, Box<dyn Error>> {
         get_or_insert_counterpart_calendar("local", &mut self.local, cal_url, needle).await
     }
-    async fn get_or_insert_remote_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<Mutex<T>>) -> Result<Arc<Mutex<U>>, Box<dyn Error>> {
+    async fn get_or_insert_remote_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<RwLock<T>>) -> Result<Arc<RwLock<U>>, Box<dyn Error>> {
         get_or_insert_counterpart_calendar("remote", &mut self.remote, cal_url, needle).await
     }
 
 
-    async fn sync_calendar_pair(cal_local: Arc<Mutex<T>>, cal_remote: Arc<Mutex<U>>, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
-        let mut cal_remote = cal_remote.lock().unwrap();
-        let mut cal_local = cal_local.lock().unwrap();
-        let cal_name = cal_local.name().to_string();
+    /// Sync a calendar, in three steps: find the differences between both sources, push the local changes, then apply the remote changes.
+    ///
+    /// The remote deletion or change of an item is only applied once its local changes have been pushed, so that a failure (e.g. a network error)
+    /// cannot leave the local source without data that never reached the remote source. Failures to push an item do not delay the other items.
+    ///
+    /// The local calendar is only locked for short sections that send no requests, so that it can be used (e.g. read or saved) while the sync waits for the server.
+    #[tracing::instrument(name = "sync_calendar", target = "kitchen_fridge::sync", skip_all, fields(calendar = tracing::field::Empty))]
+    async fn sync_calendar_pair(cal_local: Arc<RwLock<T>>, cal_remote: Arc<RwLock<U>>, settings: &SyncSettings, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let mut cal_remote = cal_remote.write().await;
+        let (cal_name, cal_url) = {
+            let mut cal_local = cal_local.write().await;
+            cal_local.set_permissions(cal_remote.permissions());
+            cal_local.set_metadata(cal_remote.metadata());
+            (cal_local.name().to_string(), cal_local.url().clone())
+        };
+        tracing::Span::current().record("calendar", &tracing::field::display(redact(crate::resource::redact_url(&cal_url))));
 
         progress.info(&format!("Syncing calendar {}", cal_name));
         progress.reset_counter();
-        progress.feedback(SyncEvent::InProgress{
+        progress.step(SyncEvent::InProgress{
             calendar: cal_name.clone(),
             items_done_already: 0,
             details: "started".to_string()
-        });
+        }).await;
+
+        let sync_mode = settings.sync_mode(&cal_url);
+        if sync_mode == SyncMode::TwoWay && settings.bootstrap_strategy != BootstrapStrategy::PushLocal {
+            let first_sync = {
+                let cal_local = cal_local.read().await;
+                cal_local.calendar_sync_exclusion().is_none() && Self::is_first_sync(&*cal_local).await?
+            };
+            if first_sync {
+                Self::bootstrap(&cal_local, &mut *cal_remote, settings, progress).await?;
+            }
+        }
+
+        if let Some(trashed_before) = settings.clock.now().checked_sub_signed(settings.trash_purge_delay) {
+            let purged = cal_local.write().await.purge_trash(trashed_before).await?;
+            if !purged.is_empty() {
+                progress.debug(&format!("{} items have been purged from the trash", purged.len()));
+            }
+        }
 
         // Step 1 - find the differences
         progress.debug("Finding the differences to sync...");
@@ -201,291 +735,1059 @@ where
         let mut remote_changes = HashSet::new();
         let mut local_additions = HashSet::new();
         let mut remote_additions = HashSet::new();
+        let mut local_overrides = Vec::new();
+        let mut local_merges = Vec::new();
 
-        let remote_items = cal_remote.get_item_version_tags().await?;
-        progress.feedback(SyncEvent::InProgress{
+        let mut remote_items = cal_remote.get_item_version_tags().await?;
+        let mut unparsed_items = Vec::new();
+        progress.step(SyncEvent::InProgress{
             calendar: cal_name.clone(),
             items_done_already: 0,
             details: format!("{} remote items", remote_items.len()),
-        });
-
-        let mut local_items_to_handle = cal_local.get_item_urls().await?;
-        for (url, remote_tag) in remote_items {
-            progress.trace(&format!("***** Considering remote item {}...", url));
-            match cal_local.get_item_by_url(&url).await {
-                None => {
-                    // This was created on the remote
-                    progress.debug(&format!("*   {} is a remote addition", url));
-                    remote_additions.insert(url);
-                },
-                Some(local_item) => {
-                    if local_items_to_handle.remove(&url) == false {
-                        progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
-                    }
+        }).await;
 
-                    match local_item.sync_status() {
-                        SyncStatus::NotSynced => {
-                            progress.error(&format!("URL reuse between remote and local sources ({}). Ignoring this item in the sync", url));
-                            continue;
-                        },
-                        SyncStatus::Synced(local_tag) => {
-                            if &remote_tag != local_tag {
-                                // This has been modified on the remote
-                                progress.debug(&format!("*   {} is a remote change", url));
-                                remote_changes.insert(url);
-                            }
-                        },
-                        SyncStatus::LocallyModified(local_tag) => {
-                            if &remote_tag == local_tag {
-                                // This has been changed locally
-                                progress.debug(&format!("*   {} is a local change", url));
-                                local_changes.insert(url);
-                            } else {
-                                progress.info(&format!("Conflict: task {} has been modified in both sources. Using the remote version.", url));
-                                progress.debug(&format!("*   {} is considered a remote change", url));
-                                remote_changes.insert(url);
-                            }
-                        },
-                        SyncStatus::LocallyDeleted(local_tag) => {
-                            if &remote_tag == local_tag {
-                                // This has been locally deleted
-                                progress.debug(&format!("*   {} is a local deletion", url));
-                                local_del.insert(url);
-                            } else {
-                                progress.info(&format!("Conflict: task {} has been locally deleted and remotely modified. Reverting to the remote version.", url));
-                                progress.debug(&format!("*   {} is a considered a remote change", url));
-                                remote_changes.insert(url);
-                            }
-                        },
-                    }
+        let mut local_statuses = HashMap::new();
+        // The local calendar may be changed during the sync. Items that have been changed since they have been compared are synced next time
+        let mut local_versions = HashMap::new();
+        {
+            let cal_local = cal_local.read().await;
+            for url in cal_local.get_item_urls().await? {
+                match cal_local.get_item_by_url(&url).await {
+                    None => progress.error(&format!("Inconsistent state: missing task {} from the local tasks", redact(&url))),
+                    Some(item) => {
+                        local_statuses.insert(url.clone(), item.sync_status().clone());
+                        local_versions.insert(url, item.version());
+                    },
                 }
             }
+            // Archived items are still synced, unless they are kept out of syncs
+            for (url, item) in cal_local.get_archived_items().await? {
+                local_statuses.insert(url.clone(), item.sync_status().clone());
+                local_versions.insert(url, item.version());
+            }
         }
 
-        // Also iterate on the local tasks that are not on the remote
-        for url in local_items_to_handle {
-            progress.trace(&format!("##### Considering local item {}...", url));
-            let local_item = match cal_local.get_item_by_url(&url).await {
-                None => {
-                    progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
-                    continue;
+        // The listing of the server may be incomplete (e.g. truncated), so that items missing from it are only deleted once the server has confirmed they do not exist any more
+        let missing: Vec<Url> = local_statuses.iter()
+            .filter(|(url, status)| **status != SyncStatus::NotSynced && !remote_items.contains_key(*url))
+            .map(|(url, _)| url.clone())
+            .collect();
+        if !missing.is_empty() {
+            match cal_remote.get_version_tags_of(&missing).await {
+                Err(err) => {
+                    progress.warn(&format!("Unable to confirm the deletion of {} items from the server, they will be synced next time: {}", missing.len(), err));
+                    for url in &missing {
+                        local_statuses.remove(url);
+                    }
                 },
-                Some(item) => item,
-            };
+                Ok(tags) => {
+                    for url in missing {
+                        match tags.get(&url) {
+                            Some(None) => {},
+                            Some(Some(tag)) => {
+                                progress.debug(&format!("*   {} is missing from the listing of the server, but still exists", redact(&url)));
+                                remote_items.insert(url, tag.clone());
+                            },
+                            None => {
+                                progress.warn(&format!("The server has not confirmed the deletion of {}, it will be synced next time", redact(&url)));
+                                local_statuses.remove(&url);
+                            },
+                        }
+                    }
+                },
+            }
+        }
+        let remote_urls: HashSet<Url> = remote_items.keys().cloned().collect();
+        let changes = Changes::between(&local_statuses, &remote_items);
+
+        for url in changes.url_reuses {
+            progress.error(&format!("URL reuse between remote and local sources ({}). Ignoring this item in the sync", redact(&url)));
+        }
 
-            match local_item.sync_status() {
-                SyncStatus::Synced(_) => {
-                    // This item has been removed from the remote
-                    progress.debug(&format!("#   {} is a deletion from the server", url));
+        for (url, change) in changes.server_only {
+            match change {
+                Change::RemoteAddition => {
+                    progress.debug(&format!("*   {} is a remote addition", redact(&url)));
+                    remote_additions.insert(url);
+                },
+                Change::RemoteModification => {
+                    progress.debug(&format!("*   {} is a remote change", redact(&url)));
+                    remote_changes.insert(url);
+                },
+                _ => {
+                    progress.debug(&format!("#   {} is a deletion from the server", redact(&url)));
                     remote_del.insert(url);
                 },
-                SyncStatus::NotSynced => {
-                    // This item has just been locally created
-                    progress.debug(&format!("#   {} has been locally created", url));
+            }
+        }
+
+        for (url, change) in changes.local_only {
+            match change {
+                Change::LocalAddition => {
+                    progress.debug(&format!("#   {} has been locally created", redact(&url)));
                     local_additions.insert(url);
                 },
-                SyncStatus::LocallyDeleted(_) => {
-                    // This item has been deleted from both sources
-                    progress.debug(&format!("#   {} has been deleted from both sources", url));
+                Change::LocalModification => {
+                    progress.debug(&format!("*   {} is a local change", redact(&url)));
+                    local_changes.insert(url);
+                },
+                _ => {
+                    progress.debug(&format!("*   {} is a local deletion", redact(&url)));
+                    local_del.insert(url);
+                },
+            }
+        }
+
+        for (url, change) in changes.both {
+            match change {
+                Change::ModifiedOnBoth => {
+                    let remote_tag = match remote_items.get(&url) {
+                        None => continue,
+                        Some(tag) => tag.clone(),
+                    };
+                    let local_item = match cal_local.read().await.get_item_by_url(&url).await {
+                        None => continue,
+                        Some(item) => item.clone(),
+                    };
+                    let remote_item = match cal_remote.get_item_by_url(&url).await {
+                        Ok(remote_item) => remote_item,
+                        Err(err) => {
+                            // Resolving the conflict without the remote version could lose changes. This will be retried at the next sync
+                            progress.item_error(&cal_url, &url, &format!("unable to fetch the remote version to resolve a conflict: {}", err));
+                            continue;
+                        },
+                    };
+                    let merged = match (cal_local.read().await.get_synced_snapshot(&url).await, &remote_item) {
+                        (Some(base), Some(remote_item)) => Item::merge(base, &local_item, remote_item),
+                        _ => None,
+                    };
+                    if let Some(merged) = merged {
+                        progress.info(&format!("Conflict: task {} has been modified in both sources. Their changes have been merged.", redact(&url)));
+                        progress.report_mut().conflicts += 1;
+                        local_merges.push((url, remote_tag, merged));
+                        continue;
+                    }
+
+                    let local_sequence = local_item.sequence();
+                    match remote_item.map(|remote_item| remote_item.sequence()) {
+                        Some(remote_sequence) if local_sequence > remote_sequence => {
+                            progress.info(&format!("Conflict: task {} has been modified in both sources. Using the local version, which has a higher SEQUENCE.", redact(&url)));
+                            progress.report_mut().conflicts += 1;
+                            progress.debug(&format!("*   {} is considered a local change", redact(&url)));
+                            local_overrides.push((url, remote_tag));
+                        },
+                        _ => {
+                            progress.info(&format!("Conflict: task {} has been modified in both sources. Using the remote version.", redact(&url)));
+                            progress.report_mut().conflicts += 1;
+                            progress.debug(&format!("*   {} is considered a remote change", redact(&url)));
+                            remote_changes.insert(url);
+                        },
+                    }
+                },
+                Change::LocallyDeletedRemotelyModified => {
+                    progress.info(&format!("Conflict: task {} has been locally deleted and remotely modified. Reverting to the remote version.", redact(&url)));
+                    progress.report_mut().conflicts += 1;
+                    progress.debug(&format!("*   {} is a considered a remote change", redact(&url)));
+                    remote_changes.insert(url);
+                },
+                Change::LocallyModifiedRemotelyDeleted => {
+                    progress.info(&format!("Conflict: item {} has been deleted from the server and locally modified. Deleting the local copy, unless it has been moved to another calendar", redact(&url)));
+                    progress.report_mut().conflicts += 1;
                     remote_del.insert(url);
                 },
-                SyncStatus::LocallyModified(_) => {
-                    progress.info(&format!("Conflict: item {} has been deleted from the server and locally modified. Deleting the local copy", url));
+                _ => {
+                    progress.debug(&format!("#   {} has been deleted from both sources", redact(&url)));
                     remote_del.insert(url);
                 },
             }
         }
 
+        // Calendars that are not synced both ways ignore the changes of one of the sources
+        match sync_mode {
+            SyncMode::TwoWay => (),
+            SyncMode::PullOnly => {
+                // Local changes are kept, until the calendar is synced both ways again
+                local_del.clear();
+                local_changes.clear();
+                local_additions.clear();
+                local_overrides.clear();
+                local_merges.clear();
+            },
+            SyncMode::PushOnly => {
+                remote_changes.clear();
+                remote_additions.clear();
+                // Items that have been deleted from both sources can be forgotten, though
+                let mut deleted_from_both = HashSet::new();
+                let cal_local = cal_local.read().await;
+                for url in remote_del {
+                    if let Some(SyncStatus::LocallyDeleted(_)) = cal_local.get_item_by_url(&url).await.map(|item| item.sync_status()) {
+                        deleted_from_both.insert(url);
+                    }
+                }
+                remote_del = deleted_from_both;
+            },
+            SyncMode::Mirror => {
+                // Local changes are discarded, and replaced by the remote versions
+                remote_changes.extend(local_del.drain());
+                remote_changes.extend(local_changes.drain());
+                remote_changes.extend(local_overrides.drain(..).map(|(url, _remote_tag)| url));
+                remote_changes.extend(local_merges.drain(..).map(|(url, _remote_tag, _merged)| url));
+                remote_del.extend(local_additions.drain());
+            },
+        }
+
+        // Items that are kept out of syncs stay on their own side
+        let (calendar_exclusion, exclusions) = {
+            let cal_local = cal_local.read().await;
+            let calendar_exclusion = cal_local.calendar_sync_exclusion();
+            let exclusions: HashMap<Url, SyncExclusion> = local_statuses.keys().chain(remote_items.keys())
+                .filter_map(|url| calendar_exclusion.or_else(|| cal_local.sync_exclusion(url)).map(|exclusion| (url.clone(), exclusion)))
+                .collect();
+            (calendar_exclusion, exclusions)
+        };
+        // Local copies of remote-only items that would be updated by the remote version. They are removed instead
+        let mut outdated_copies = HashSet::new();
+        for (url, exclusion) in &exclusions {
+            match exclusion {
+                SyncExclusion::LocalOnly => {
+                    progress.debug(&format!("*   {} is local only, it is not synced", redact(url)));
+                    for set in [&mut local_del, &mut local_changes, &mut local_additions, &mut remote_del, &mut remote_changes, &mut remote_additions] {
+                        set.remove(url);
+                    }
+                },
+                SyncExclusion::RemoteOnly => {
+                    remote_additions.remove(url);
+                    if remote_changes.remove(url) {
+                        outdated_copies.insert(url.clone());
+                    }
+                },
+            }
+        }
+        local_overrides.retain(|(url, _remote_tag)| exclusions.get(url) != Some(&SyncExclusion::LocalOnly));
+        local_merges.retain(|(url, _remote_tag, _merged)| exclusions.get(url) != Some(&SyncExclusion::LocalOnly));
+
+        {
+            let mut cal_local = cal_local.write().await;
+            // Local versions that win a conflict will overwrite the current remote version
+            for (url, remote_tag) in local_overrides {
+                if let Some(mut local_item) = cal_local.get_item_by_url(&url).await.cloned() {
+                    local_item.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                    match cal_local.update_item(local_item).await {
+                        Ok(_) => { local_changes.insert(url); },
+                        Err(err) => progress.item_error(&cal_url, &url, &format!("unable to mark the local version as overwriting the remote one: {}", err)),
+                    }
+                }
+            }
+            // Merged versions replace the local ones, and will then overwrite the current remote version
+            for (url, remote_tag, mut merged) in local_merges {
+                merged.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                match cal_local.update_item(merged).await {
+                    Ok(_) => { local_changes.insert(url); },
+                    Err(err) => progress.item_error(&cal_url, &url, &format!("unable to store the merged version of the item: {}", err)),
+                }
+            }
+        }
 
-        // Step 2 - commit changes
-        progress.trace("Committing changes...");
+        // Step 2 - push local changes
+        progress.trace("Pushing local changes...");
+        // The items whose local changes could not be pushed (e.g. because of a network failure)
+        let mut failed_pushes = HashSet::new();
         for url_del in local_del {
-            progress.debug(&format!("> Pushing local deletion {} to the server", url_del));
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local deletion {} to the server", redact(&url_del)));
             progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
+            progress.step(SyncEvent::InProgress{
                 calendar: cal_name.clone(),
                 items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_del).await,
-            });
+                details: Self::item_name(&*cal_local, &url_del).await,
+            }).await;
 
             match cal_remote.delete_item(&url_del).await {
                 Err(err) => {
-                    progress.warn(&format!("Unable to delete remote item {}: {}", url_del, err));
+                    failed_pushes.insert(url_del.clone());
+                    progress.item_error(&cal_url, &url_del, &format!("unable to delete the remote item: {}", err));
                 },
                 Ok(()) => {
+                    progress.report_mut().deleted_remotely += 1;
                     // Change the local copy from "marked to deletion" to "actually deleted"
-                    if let Err(err) = cal_local.immediately_delete_item(&url_del).await {
-                        progress.error(&format!("Unable to permanently delete local item {}: {}", url_del, err));
+                    if let Err(err) = cal_local.write().await.immediately_delete_item(&url_del).await {
+                        progress.error(&format!("Unable to permanently delete local item {}: {}", redact(&url_del), err));
                     }
                 },
             }
         }
 
-        for url_del in remote_del {
-            progress.debug(&format!("> Applying remote deletion {} locally", url_del));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_del).await,
-            });
-            if let Err(err) = cal_local.immediately_delete_item(&url_del).await {
-                progress.warn(&format!("Unable to delete local item {}: {}", url_del, err));
-            }
-        }
-
-        Self::apply_remote_additions(
-            remote_additions,
-            &mut *cal_local,
-            &mut *cal_remote,
-            progress,
-            &cal_name
-        ).await;
-
-        Self::apply_remote_changes(
-            remote_changes,
-            &mut *cal_local,
-            &mut *cal_remote,
-            progress,
-            &cal_name
-        ).await;
-
-
         for url_add in local_additions {
-            progress.debug(&format!("> Pushing local addition {} to the server", url_add));
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local addition {} to the server", redact(&url_add)));
             progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
+            progress.step(SyncEvent::InProgress{
                 calendar: cal_name.clone(),
                 items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_add).await,
-            });
-            match cal_local.get_item_by_url_mut(&url_add).await {
+                details: Self::item_name(&*cal_local, &url_add).await,
+            }).await;
+            let item = match cal_local.read().await.get_item_by_url(&url_add).await {
                 None => {
-                    progress.error(&format!("Inconsistency: created item {} has been marked for upload but is locally missing", url_add));
+                    progress.error(&format!("Inconsistency: created item {} has been marked for upload but is locally missing", redact(&url_add)));
                     continue;
                 },
-                Some(item) => {
-                    match cal_remote.add_item(item.clone()).await {
-                        Err(err) => progress.error(&format!("Unable to add item {} to remote calendar: {}", url_add, err)),
-                        Ok(new_ss) => {
-                            // Update local sync status
-                            item.set_sync_status(new_ss);
-                        },
+                Some(item) => item.clone(),
+            };
+            if let Err(violations) = item.validate() {
+                progress.item_error(&cal_url, &url_add, &format!("invalid item, it will not be uploaded: {}", crate::validation::describe(&violations)));
+                continue;
+            }
+            let uploaded_version = item.version();
+            let stored = match Self::upload_addition(&cal_local, &mut *cal_remote, item, settings.href_strategy, &remote_urls).await {
+                Err(err) => {
+                    failed_pushes.insert(url_add.clone());
+                    if err.is::<QuotaExceeded>() {
+                        progress.report_mut().quota_exceeded = true;
                     }
+                    progress.item_error(&cal_url, &url_add, &format!("unable to add the item to the remote calendar: {}", err));
+                    continue;
                 },
+                Ok(stored) => stored,
             };
-        }
+            progress.report_mut().uploaded += 1;
 
-        for url_change in local_changes {
-            progress.debug(&format!("> Pushing local change {} to the server", url_change));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_change).await,
-            });
-            match cal_local.get_item_by_url_mut(&url_change).await {
-                None => {
-                    progress.error(&format!("Inconsistency: modified item {} has been marked for upload but is locally missing", url_change));
+            // Replace the local item by the version that has been stored (which has an updated sync status, and maybe a different URL or content)
+            let mut cal_local = cal_local.write().await;
+            let current = cal_local.get_item_by_url(&url_add).await.cloned();
+            let changed_meanwhile = changes_made_during_upload(&uploaded_version, current.as_ref(), &stored);
+            let new_url = stored.url().clone();
+            if new_url == url_add && current.is_some() {
+                if let Err(err) = cal_local.update_item(stored).await {
+                    progress.item_error(&cal_url, &url_add, &format!("unable to store the uploaded item locally: {}", err));
                     continue;
-                },
-                Some(item) => {
-                    match cal_remote.update_item(item.clone()).await {
-                        Err(err) => progress.error(&format!("Unable to update item {} in remote calendar: {}", url_change, err)),
-                        Ok(new_ss) => {
-                            // Update local sync status
-                            item.set_sync_status(new_ss);
-                        },
+                }
+            } else {
+                if new_url != url_add {
+                    progress.debug(&format!("> Item {} has been stored at {}", redact(&url_add), redact(&new_url)));
+                }
+                if let Err(err) = cal_local.add_item(stored).await {
+                    progress.item_error(&cal_url, &url_add, &format!("unable to store the uploaded item locally: {}", err));
+                    continue;
+                }
+                if current.is_some() {
+                    if let Err(err) = cal_local.immediately_delete_item(&url_add).await {
+                        progress.error(&format!("Unable to delete local item {} that has been moved to {}: {}", redact(&url_add), redact(&new_url), err));
+                    }
+                }
+            }
+            if let Err(err) = cal_local.save_synced_snapshot(&new_url).await {
+                progress.warn(&format!("Unable to save the synced version of {}: {}", redact(&new_url), err));
+            }
+            if let Some(newer) = changed_meanwhile {
+                progress.debug(&format!("> {} has been changed during its upload, these changes will be uploaded next time", redact(&new_url)));
+                if let Err(err) = cal_local.update_item(newer).await {
+                    progress.item_error(&cal_url, &new_url, &format!("unable to keep the changes made during the upload: {}", err));
+                }
+            }
+        }
+
+        for url_change in local_changes {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local change {} to the server", redact(&url_change)));
+            progress.increment_counter(1);
+            progress.step(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: Self::item_name(&*cal_local, &url_change).await,
+            }).await;
+            let item = {
+                let mut cal_local = cal_local.write().await;
+                let item = match cal_local.get_item_by_url(&url_change).await {
+                    None => {
+                        progress.error(&format!("Inconsistency: modified item {} has been marked for upload but is locally missing", redact(&url_change)));
+                        continue;
+                    },
+                    Some(item) => item.clone(),
+                };
+                if let Err(violations) = item.validate() {
+                    progress.item_error(&cal_url, &url_change, &format!("invalid item, it will not be uploaded: {}", crate::validation::describe(&violations)));
+                    continue;
+                }
+                // No-op edits (e.g. renaming a task to its current name) would only bump the timestamps of the item on the server
+                if let Some(synced) = cal_local.get_synced_snapshot(&url_change).await {
+                    if is_unchanged_since(&item, synced) {
+                        progress.debug(&format!("> {} is identical to its synced version, it will not be uploaded", redact(&url_change)));
+                        let synced = synced.clone();
+                        if let Err(err) = cal_local.update_item(synced).await {
+                            progress.item_error(&cal_url, &url_change, &format!("unable to restore the synced version of the item: {}", err));
+                        }
+                        continue;
+                    }
+                }
+                item
+            };
+            let uploaded_version = item.version();
+            match cal_remote.update_item(item.clone()).await {
+                Err(err) => {
+                    failed_pushes.insert(url_change.clone());
+                    if err.is::<QuotaExceeded>() {
+                        progress.report_mut().quota_exceeded = true;
+                    }
+                    progress.item_error(&cal_url, &url_change, &format!("unable to update the item in the remote calendar: {}", err));
+                },
+                Ok(new_ss) => {
+                    progress.report_mut().uploaded += 1;
+                    let mut cal_local = cal_local.write().await;
+                    let mut stored = item;
+                    stored.set_sync_status(new_ss);
+                    let current = cal_local.get_item_by_url(&url_change).await.cloned();
+                    let changed_meanwhile = changes_made_during_upload(&uploaded_version, current.as_ref(), &stored);
+                    // Update local sync status
+                    let local_update_result = match current {
+                        Some(_) => cal_local.update_item(stored).await,
+                        None => cal_local.add_item(stored).await,
                     };
+                    if let Err(err) = local_update_result {
+                        progress.item_error(&cal_url, &url_change, &format!("unable to store the uploaded item locally: {}", err));
+                        continue;
+                    }
+                    if let Err(err) = cal_local.save_synced_snapshot(&url_change).await {
+                        progress.warn(&format!("Unable to save the synced version of {}: {}", redact(&url_change), err));
+                    }
+                    if let Some(newer) = changed_meanwhile {
+                        progress.debug(&format!("> {} has been changed during its upload, these changes will be uploaded next time", redact(&url_change)));
+                        if let Err(err) = cal_local.update_item(newer).await {
+                            progress.item_error(&cal_url, &url_change, &format!("unable to keep the changes made during the upload: {}", err));
+                        }
+                    }
+                },
+            };
+        }
+
+        // Step 3 - apply remote changes
+        progress.trace("Applying remote changes...");
+        let with_local_versions = |urls: HashSet<Url>| -> HashMap<Url, Option<ItemVersion>> {
+            urls.into_iter().map(|url| {
+                let version = local_versions.get(&url).cloned();
+                (url, version)
+            }).collect()
+        };
+        Self::apply_remote_additions(
+            with_local_versions(remote_additions),
+            &cal_local,
+            &mut *cal_remote,
+            progress,
+            &mut unparsed_items,
+            settings.multiget,
+        ).await;
+        progress.check_cancelled()?;
+
+        // Remote deletions and changes may overwrite local data. In case the local changes of an item have not reached the server, the remote changes
+        // of this item are kept for the next sync, which will find them again
+        if !failed_pushes.is_empty() {
+            progress.info(&format!("{} local changes of calendar {} could not be pushed, their remote deletions and changes will be applied at the next sync", failed_pushes.len(), cal_name));
+            remote_del.retain(|url| !failed_pushes.contains(url));
+            remote_changes.retain(|url| !failed_pushes.contains(url));
+        }
+        for url_del in remote_del {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Applying remote deletion {} locally", redact(&url_del)));
+            progress.increment_counter(1);
+            progress.step(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: Self::item_name(&*cal_local, &url_del).await,
+            }).await;
+            let mut cal_local = cal_local.write().await;
+            let local_item = cal_local.get_item_by_url(&url_del).await.cloned();
+            // Items that are not found have been archived or deleted meanwhile, which does not prevent their deletion
+            if let (Some(expected), Some(local_item)) = (local_versions.get(&url_del), &local_item) {
+                if local_item.version() != *expected {
+                    progress.debug(&format!("> {} has been changed locally during the sync, it will be synced next time", redact(&url_del)));
+                    continue;
+                }
+            }
+            if let Some(item) = local_item {
+                if let SyncStatus::LocallyModified(_) = item.sync_status() {
+                    // It is kept until it is found in another calendar, see `restore_moved_items`
+                    let base = cal_local.get_synced_snapshot(&url_del).await.cloned();
+                    let mut vanished: Vec<VanishedItem> = cal_local.get_vanished_items().await.into_iter().cloned().collect();
+                    vanished.push(VanishedItem{ item, base, vanished_at: settings.clock.now() });
+                    if let Err(err) = cal_local.set_vanished_items(vanished).await {
+                        progress.warn(&format!("Unable to keep the local changes of vanished item {}: {}", redact(&url_del), err));
+                    }
+                }
+            }
+            match cal_local.immediately_delete_item(&url_del).await {
+                Err(err) => progress.item_error(&cal_url, &url_del, &format!("unable to delete the local item: {}", err)),
+                Ok(()) => progress.report_mut().deleted_locally += 1,
+            }
+        }
+
+        // Archived items that have changed on the server are taken out of the archive, and updated
+        {
+            let mut cal_local = cal_local.write().await;
+            let archived: HashSet<Url> = cal_local.get_archived_items().await?.into_keys().collect();
+            for url in remote_changes.iter().filter(|url| archived.contains(*url)) {
+                progress.debug(&format!("> Taking {} out of the archive, since it has changed on the server", redact(url)));
+                if let Err(err) = cal_local.unarchive_item(url).await {
+                    progress.item_error(&cal_url, url, &format!("unable to take the item out of the archive: {}", err));
+                }
+            }
+        }
+
+        Self::apply_remote_changes(
+            with_local_versions(remote_changes),
+            &cal_local,
+            &mut *cal_remote,
+            progress,
+            &mut unparsed_items,
+            settings.multiget,
+        ).await;
+        progress.check_cancelled()?;
+
+        // Remote-only items are not kept locally, once their local changes have been uploaded
+        {
+            let mut cal_local = cal_local.write().await;
+            for (url, exclusion) in &exclusions {
+                if *exclusion != SyncExclusion::RemoteOnly {
+                    continue;
+                }
+                let is_synced = match cal_local.get_item_by_url(url).await {
+                    None => continue,
+                    Some(item) => matches!(item.sync_status(), SyncStatus::Synced(_)),
+                };
+                if is_synced || outdated_copies.contains(url) {
+                    progress.debug(&format!("> Removing the local copy of remote-only item {}", redact(url)));
+                    if let Err(err) = cal_local.immediately_delete_item(url).await {
+                        progress.item_error(&cal_url, url, &format!("unable to remove the local copy of a remote-only item: {}", err));
+                    }
+                }
+            }
+        }
+
+        if settings.uid_dedup == UidDedup::KeepNewest && sync_mode == SyncMode::TwoWay && calendar_exclusion.is_none() {
+            Self::remove_duplicates(&cal_local, &mut *cal_remote, settings.skew_tolerance, progress).await?;
+        }
+
+        // Keep the raw content of the items that could not be parsed, until they are fixed or deleted on the server
+        let mut cal_local = cal_local.write().await;
+        let newly_unparsed: HashSet<Url> = unparsed_items.iter().map(|item| item.url.clone()).collect();
+        let still_unparsed: Vec<UnparsedItem> = cal_local.get_unparsed_items().await.into_iter()
+            .filter(|item| remote_urls.contains(&item.url) && !newly_unparsed.contains(&item.url))
+            .cloned()
+            .collect();
+        unparsed_items.extend(still_unparsed);
+        if let Err(err) = cal_local.set_unparsed_items(unparsed_items).await {
+            progress.warn(&format!("Unable to store the items of {} that could not be parsed: {}", cal_name, err));
+        }
+        cal_local.set_last_sync(settings.clock.now());
+
+        Ok(())
+    }
+
+
+    /// Upload a locally created item, to a URL that depends on `href_strategy`. Returns the item as it has been stored by the remote source
+    ///
+    /// A UID-based URL that is already used (by a remote item, or by another local item that has not been pushed yet) is not used, and the item keeps its URL.
+    async fn upload_addition(cal_local: &RwLock<T>, cal_remote: &mut U, item: Item, href_strategy: HrefStrategy, remote_urls: &HashSet<Url>) -> Result<Item, Box<dyn Error>> {
+        if href_strategy == HrefStrategy::ServerAssigned {
+            if let Some(stored) = cal_remote.add_item_at_server_url(item.clone()).await? {
+                return Ok(stored);
+            }
+        }
+
+        let task = match &item {
+            Item::Task(task) if href_strategy != HrefStrategy::Random => task,
+            _ => return cal_remote.add_item_and_fetch(item).await,
+        };
+        let new_url = match crate::utils::uid_url(cal_remote.url(), task.uid()) {
+            Some(uid_url) if !remote_urls.contains(&uid_url) => {
+                let taken_locally = &uid_url != task.url() && cal_local.read().await.get_item_by_url(&uid_url).await.is_some();
+                if taken_locally { task.url().clone() } else { uid_url }
+            },
+            _ => task.url().clone(),
+        };
+        let new_item = Item::Task(task.clone_with_identity(task.uid().to_string(), new_url, SyncStatus::NotSynced));
+        cal_remote.add_item_and_fetch(new_item).await
+    }
+
+    /// Items with local changes that have vanished from their remote calendar may have been moved to another calendar on the server.
+    /// In this case, a sync downloads them from their new calendar, and their local changes are applied (and pushed) there, instead of being lost.
+    ///
+    /// Vanished items are kept by their former calendar until their new copy is found, which may only happen after their new calendar has been synced (e.g. by a later [`Self::sync_calendar`]).
+    /// The ones that are still not found after the trash purge delay (see [`Self::set_trash_purge_delay`]) have been deleted from the server, and their local changes are discarded.
+    async fn restore_moved_items(&mut self, progress: &mut SyncProgress) {
+        let cals_local = match self.local.get_calendars().await {
+            Err(err) => {
+                progress.warn(&format!("Unable to look for the items that have been moved on the server: {}", err));
+                return;
+            },
+            Ok(cals) => cals,
+        };
+        let expired_before = self.settings.clock.now().checked_sub_signed(self.settings.trash_purge_delay);
+
+        for (calendar, cal_local) in &cals_local {
+            let vanished: Vec<VanishedItem> = cal_local.read().await.get_vanished_items().await.into_iter().cloned().collect();
+            if vanished.is_empty() {
+                continue;
+            }
+            let mut remaining = Vec::new();
+            for vanished_item in vanished {
+                if self.restore_moved_item(calendar, &vanished_item, &cals_local, progress).await {
+                    continue;
+                }
+                if expired_before.is_some_and(|before| vanished_item.vanished_at < before) {
+                    progress.warn(&format!("Item {} has vanished from calendar {} and has not been found in another calendar. Discarding its local changes", redact(vanished_item.item.url()), redact(calendar)));
+                } else {
+                    remaining.push(vanished_item);
+                }
+            }
+            if let Err(err) = cal_local.write().await.set_vanished_items(remaining).await {
+                progress.warn(&format!("Unable to update the vanished items of calendar {}: {}", redact(calendar), err));
+            }
+        }
+    }
+
+    /// Look for a vanished item in the local calendars other than its former one, and apply its local changes there.
+    /// Returns whether it has been found
+    async fn restore_moved_item(&mut self, calendar: &Url, vanished: &VanishedItem, cals_local: &HashMap<Url, Arc<RwLock<T>>>, progress: &mut SyncProgress) -> bool {
+        let VanishedItem{ item, base, .. } = vanished;
+        for (cal_url, cal_local) in cals_local {
+            if cal_url == calendar {
+                continue;
+            }
+            let moved = match cal_local.read().await.get_item_by_uid(item.uid()).await {
+                None => continue,
+                Some(moved) => moved.clone(),
+            };
+            let remote_tag = match moved.sync_status() {
+                SyncStatus::Synced(tag) => tag.clone(),
+                _ => continue,
+            };
+            progress.info(&format!("Item {} has been moved to calendar {} on the server. Applying its local changes there", redact(item.url()), redact(cal_url)));
+            let merged = base.as_ref()
+                .and_then(|base| Item::merge(base, item, &moved))
+                .unwrap_or_else(|| item.clone());
+            let mut restored = merged.clone_with_identity(merged.uid().to_string(), moved.url().clone(), SyncStatus::LocallyModified(remote_tag));
+
+            if let Some(cal_remote) = self.remote.get_calendar(cal_url).await {
+                match cal_remote.write().await.update_item(restored.clone()).await {
+                    Ok(new_ss) => {
+                        restored.set_sync_status(new_ss);
+                        progress.report_mut().uploaded += 1;
+                    },
+                    Err(err) => progress.warn(&format!("Unable to push the local changes of moved item {}, they will be pushed at the next sync: {}", redact(moved.url()), err)),
                 }
+            }
+            let synced = matches!(restored.sync_status(), SyncStatus::Synced(_));
+            let mut cal_local = cal_local.write().await;
+            let changed_meanwhile = match synced {
+                true => changes_made_during_upload(&moved.version(), cal_local.get_item_by_url(moved.url()).await, &restored),
+                false => None,
             };
+            if let Err(err) = cal_local.update_item(restored).await {
+                progress.item_error(cal_url, moved.url(), &format!("unable to apply the local changes of a moved item: {}", err));
+            } else if synced {
+                if let Err(err) = cal_local.save_synced_snapshot(moved.url()).await {
+                    progress.warn(&format!("Unable to save the synced version of {}: {}", redact(moved.url()), err));
+                }
+                if let Some(newer) = changed_meanwhile {
+                    if let Err(err) = cal_local.update_item(newer).await {
+                        progress.item_error(cal_url, moved.url(), &format!("unable to keep the changes made during the upload: {}", err));
+                    }
+                }
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Whether a calendar has never been synced
+    async fn is_first_sync(cal_local: &T) -> Result<bool, Box<dyn Error>> {
+        if cal_local.last_sync().is_some() {
+            return Ok(false);
+        }
+        // Caches written before the date of the last sync was stored
+        Ok(cal_local.get_items().await?.values().all(|item| item.sync_status() == &SyncStatus::NotSynced))
+    }
+
+    /// Reconcile the items that have been created locally before the first sync of a calendar with the items of the server (see [`BootstrapStrategy`]).
+    ///
+    /// Local items that are replaced by their remote version are deleted, so that the regular sync downloads them.
+    /// Local items that win over their remote version are moved to the URL of the remote item, so that the regular sync uploads them as a local change.
+    async fn bootstrap(cal_local: &RwLock<T>, cal_remote: &mut U, settings: &SyncSettings, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let strategy = settings.bootstrap_strategy;
+        let (cal_url, mut local_urls_by_uid) = {
+            let cal_local = cal_local.read().await;
+            let local_urls_by_uid: HashMap<String, Url> = cal_local.get_items().await?
+                .into_iter()
+                .filter(|(url, _item)| cal_local.sync_exclusion(url).is_none())
+                .map(|(url, item)| (item.uid().to_string(), url))
+                .collect();
+            (cal_local.url().clone(), local_urls_by_uid)
+        };
+        if local_urls_by_uid.is_empty() {
+            return Ok(());
+        }
+        let remote_tags = cal_remote.get_item_version_tags().await?;
+        let remote_urls: Vec<Url> = {
+            let cal_local = cal_local.read().await;
+            remote_tags.into_keys()
+                .filter(|url| cal_local.sync_exclusion(url).is_none())
+                .collect()
+        };
+        // There is nothing to reconcile with, and the local items must not be discarded
+        if remote_urls.is_empty() {
+            progress.debug(&format!("First sync of calendar {}: the server has no items yet, uploading the local ones", redact(&cal_url)));
+            return Ok(());
+        }
+        progress.info(&format!("First sync of calendar {}: reconciling {} local items with the server ({:?})", redact(&cal_url), local_urls_by_uid.len(), strategy));
+
+        for batch in remote_urls.chunks(DOWNLOAD_BATCH_SIZE) {
+            progress.check_cancelled()?;
+            let remote_items = Self::download_items(&*cal_remote, batch, settings.multiget).await?;
+            // Items that cannot be parsed will be reported by the regular sync
+            let _ = cal_remote.take_unparsed_items();
+
+            let mut cal_local = cal_local.write().await;
+            for remote_item in remote_items.into_iter().flatten() {
+                let local_url = match local_urls_by_uid.remove(remote_item.uid()) {
+                    None => continue,
+                    Some(url) => url,
+                };
+                let local_item = match cal_local.get_item_by_url(&local_url).await {
+                    None => continue,
+                    Some(item) => item,
+                };
+                let local_is_newer = is_newer(local_item, &remote_item, settings.skew_tolerance);
+                let adopted = match (local_item, remote_item.sync_status()) {
+                    (Item::Task(task), SyncStatus::Synced(remote_tag)) if strategy == BootstrapStrategy::MergeByUid && local_is_newer => {
+                        Some(Item::Task(task.clone_with_identity(task.uid().to_string(), remote_item.url().clone(), SyncStatus::LocallyModified(remote_tag.clone()))))
+                    },
+                    _ => None,
+                };
+
+                if let Err(err) = cal_local.immediately_delete_item(&local_url).await {
+                    progress.item_error(&cal_url, &local_url, &format!("unable to replace the local item by its remote version: {}", err));
+                    continue;
+                }
+                match adopted {
+                    None => progress.debug(&format!("*   {} is replaced by its remote version {}", redact(&local_url), redact(remote_item.url()))),
+                    Some(adopted) => {
+                        progress.debug(&format!("*   {} overwrites its remote version {}", redact(&local_url), redact(remote_item.url())));
+                        if let Err(err) = cal_local.add_item(adopted).await {
+                            progress.item_error(&cal_url, &local_url, &format!("unable to move the local item to the URL of its remote version: {}", err));
+                        }
+                    },
+                }
+            }
+        }
+
+        if strategy == BootstrapStrategy::DiscardLocal {
+            let mut cal_local = cal_local.write().await;
+            for local_url in local_urls_by_uid.into_values() {
+                progress.debug(&format!("*   {} does not exist on the server, discarding it", redact(&local_url)));
+                if let Err(err) = cal_local.immediately_delete_item(&local_url).await {
+                    progress.item_error(&cal_url, &local_url, &format!("unable to discard the local item: {}", err));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the items that have the same UID as a newer item, from both sources (see [`UidDedup::KeepNewest`])
+    async fn remove_duplicates(cal_local: &RwLock<T>, cal_remote: &mut U, skew_tolerance: chrono::Duration, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let (cal_url, duplicates, replacements) = {
+            let cal_local = cal_local.read().await;
+            let mut copies_by_uid: HashMap<String, Vec<(&Url, &Item)>> = HashMap::new();
+            let items = cal_local.get_items().await?;
+            for (url, item) in &items {
+                if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+                    continue;
+                }
+                if cal_local.sync_exclusion(url).is_some() {
+                    continue;
+                }
+                copies_by_uid.entry(item.uid().to_string()).or_default().push((url, *item));
+            }
+
+            let on_remote = |item: &Item| item.sync_status() != &SyncStatus::NotSynced;
+            let mut duplicates = Vec::new();
+            let mut replacements = Vec::new();
+            for (uid, mut copies) in copies_by_uid {
+                if copies.len() < 2 {
+                    continue;
+                }
+                copies.sort_by(|(url_a, _), (url_b, _)| url_a.as_str().cmp(url_b.as_str()));
+                let newest = copies.iter()
+                    .fold(copies[0], |newest, copy| if is_newer(copy.1, newest.1, skew_tolerance) { *copy } else { newest });
+                // The copy that is kept must exist on the server, otherwise deleting the other ones there would remove the item from the server.
+                // In case the newest copy has not been uploaded yet, its content replaces the one of the newest copy that exists on the server
+                let kept = match copies.iter().filter(|copy| on_remote(copy.1)).copied()
+                    .reduce(|newest, copy| if is_newer(copy.1, newest.1, skew_tolerance) { copy } else { newest })
+                {
+                    Some(newest_on_remote) if !on_remote(newest.1) => {
+                        if let Some(tag) = newest_on_remote.1.version_tag() {
+                            replacements.push(newest.1.clone_with_identity(uid.clone(), newest_on_remote.0.clone(), SyncStatus::LocallyModified(tag.clone())));
+                        }
+                        newest_on_remote.0
+                    },
+                    _ => newest.0,
+                };
+                progress.info(&format!("Item {} has {} duplicate(s), keeping {}", redact(&uid), copies.len() - 1, redact(kept)));
+                duplicates.extend(copies.into_iter()
+                    .filter(|(url, _)| *url != kept)
+                    .map(|(url, item)| (url.clone(), item.sync_status() != &SyncStatus::NotSynced)));
+            }
+            (cal_local.url().clone(), duplicates, replacements)
+        };
+
+        for (url, on_remote) in duplicates {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Removing duplicate {}", redact(&url)));
+            if on_remote {
+                if let Err(err) = cal_remote.delete_item(&url).await {
+                    progress.item_error(&cal_url, &url, &format!("unable to delete the remote duplicate: {}", err));
+                    continue;
+                }
+            }
+            match cal_local.write().await.immediately_delete_item(&url).await {
+                Err(err) => progress.item_error(&cal_url, &url, &format!("unable to delete the local duplicate: {}", err)),
+                Ok(()) => progress.report_mut().duplicates_removed += 1,
+            }
         }
 
+        for mut replacement in replacements {
+            progress.check_cancelled()?;
+            let url = replacement.url().clone();
+            match cal_remote.update_item(replacement.clone()).await {
+                Ok(new_ss) => {
+                    replacement.set_sync_status(new_ss);
+                    progress.report_mut().uploaded += 1;
+                },
+                Err(err) => progress.warn(&format!("Unable to push the newest copy of duplicate {}, it will be pushed at the next sync: {}", redact(&url), err)),
+            }
+            let synced = matches!(replacement.sync_status(), SyncStatus::Synced(_));
+            let mut cal_local = cal_local.write().await;
+            if let Err(err) = cal_local.update_item(replacement).await {
+                progress.item_error(&cal_url, &url, &format!("unable to keep the newest copy of a duplicate: {}", err));
+            } else if synced {
+                if let Err(err) = cal_local.save_synced_snapshot(&url).await {
+                    progress.warn(&format!("Unable to save the synced version of {}: {}", redact(&url), err));
+                }
+            }
+        }
         Ok(())
     }
 
+    async fn item_name(cal: &RwLock<T>, url: &Url) -> String {
+        let name = cal.read().await.get_item_by_url(url).await.map(|item| item.name()).unwrap_or_default().to_string();
+        name
+    }
 
-    async fn item_name(cal: &T, url: &Url) -> String {
-        cal.get_item_by_url(url).await.map(|item| item.name()).unwrap_or_default().to_string()
+    /// Download items in a single request, or one by one in case the server does not support it
+    async fn download_items(cal_remote: &U, urls: &[Url], multiget: bool) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+        if multiget {
+            return cal_remote.get_items_by_url(urls).await;
+        }
+        let mut items = Vec::new();
+        for url in urls {
+            items.push(cal_remote.get_item_by_url(url).await?);
+        }
+        Ok(items)
     }
 
+    /// Download and store the items that have been added on the server.
+    /// They are given with the version of their local counterpart when the changes have been found (i.e. `None`), see [`Self::fetch_batch_and_apply`]
     async fn apply_remote_additions(
-        mut remote_additions: HashSet<Url>,
-        cal_local: &mut T,
+        mut remote_additions: HashMap<Url, Option<ItemVersion>>,
+        cal_local: &RwLock<T>,
         cal_remote: &mut U,
         progress: &mut SyncProgress,
-        cal_name: &str
+        unparsed_items: &mut Vec<UnparsedItem>,
+        multiget: bool,
     ) {
         for batch in remote_additions.drain().chunks(DOWNLOAD_BATCH_SIZE).into_iter() {
-            Self::fetch_batch_and_apply(BatchDownloadType::RemoteAdditions, batch, cal_local, cal_remote, progress, cal_name).await;
+            if progress.is_cancelled() {
+                break;
+            }
+            Self::fetch_batch_and_apply(BatchDownloadType::RemoteAdditions, batch, cal_local, cal_remote, progress, unparsed_items, multiget).await;
         }
     }
 
+    /// Download and store the items that have been changed on the server.
+    /// They are given with the version of their local counterpart when the changes have been found, see [`Self::fetch_batch_and_apply`]
     async fn apply_remote_changes(
-        mut remote_changes: HashSet<Url>,
-        cal_local: &mut T,
+        mut remote_changes: HashMap<Url, Option<ItemVersion>>,
+        cal_local: &RwLock<T>,
         cal_remote: &mut U,
         progress: &mut SyncProgress,
-        cal_name: &str
+        unparsed_items: &mut Vec<UnparsedItem>,
+        multiget: bool,
     ) {
         for batch in remote_changes.drain().chunks(DOWNLOAD_BATCH_SIZE).into_iter() {
-            Self::fetch_batch_and_apply(BatchDownloadType::RemoteChanges, batch, cal_local, cal_remote, progress, cal_name).await;
+            if progress.is_cancelled() {
+                break;
+            }
+            Self::fetch_batch_and_apply(BatchDownloadType::RemoteChanges, batch, cal_local, cal_remote, progress, unparsed_items, multiget).await;
         }
     }
 
-    async fn fetch_batch_and_apply<I: Iterator<Item = Url>>(
+    /// Download a batch of items, and store them locally.
+    ///
+    /// The local calendar is not locked during the download. The items whose local version has changed meanwhile
+    /// (i.e. that is not the version they are given with anymore) are not overwritten, they will be synced next time
+    #[tracing::instrument(name = "download", target = "kitchen_fridge::sync", level = "debug", skip_all, fields(kind = %batch_type))]
+    async fn fetch_batch_and_apply<I: Iterator<Item = (Url, Option<ItemVersion>)>>(
         batch_type: BatchDownloadType,
-        remote_additions: I,
-        cal_local: &mut T,
+        remote_items: I,
+        cal_local: &RwLock<T>,
         cal_remote: &mut U,
         progress: &mut SyncProgress,
-        cal_name: &str
+        unparsed_items: &mut Vec<UnparsedItem>,
+        multiget: bool,
     ) {
         progress.debug(&format!("> Applying a batch of {} locally", batch_type) /* too bad Chunks does not implement ExactSizeIterator, that could provide useful debug info. See https://github.com/rust-itertools/itertools/issues/171 */);
 
-        let list_of_additions: Vec<Url> = remote_additions.map(|url| url.clone()).collect();
-        match cal_remote.get_items_by_url(&list_of_additions).await {
+        let expected_versions: HashMap<Url, Option<ItemVersion>> = remote_items.collect();
+        let list_of_additions: Vec<Url> = expected_versions.keys().cloned().collect();
+        let batch_result = Self::download_items(&*cal_remote, &list_of_additions, multiget).await;
+        let (cal_name, cal_url) = {
+            let cal_local = cal_local.read().await;
+            (cal_local.name().to_string(), cal_local.url().clone())
+        };
+        for unparsed in cal_remote.take_unparsed_items() {
+            progress.item_error(&cal_url, &unparsed.url, &unparsed.error);
+            unparsed_items.push(unparsed);
+        }
+        match batch_result {
             Err(err) => {
-                progress.warn(&format!("Unable to get the batch of {} {:?}: {}. Skipping them.", batch_type, list_of_additions, err));
+                progress.warn(&format!("Unable to get the batch of {} {:?}: {}. Skipping them.", batch_type, list_of_additions.iter().map(redact).collect::<Vec<_>>(), err));
             },
             Ok(items) => {
-                for item in items {
-                    match item {
-                        None => {
-                            progress.error(&format!("Inconsistency: an item from the batch has vanished from the remote end"));
-                            continue;
-                        },
-                        Some(new_item) => {
-                            let local_update_result = match batch_type {
-                                BatchDownloadType::RemoteAdditions => cal_local.add_item(new_item.clone()).await,
-                                BatchDownloadType::RemoteChanges => cal_local.update_item(new_item.clone()).await,
-                            };
-                            if let Err(err) = local_update_result {
-                                progress.error(&format!("Not able to add item {} to local calendar: {}", new_item.url(), err));
-                            }
-                        },
+                {
+                    let mut cal_local = cal_local.write().await;
+                    for item in items {
+                        match item {
+                            None => {
+                                progress.error(&format!("Inconsistency: an item from the batch has vanished from the remote end"));
+                                continue;
+                            },
+                            Some(new_item) => {
+                                if let Some(expected) = expected_versions.get(new_item.url()) {
+                                    let current = cal_local.get_item_by_url(new_item.url()).await.map(|item| item.version());
+                                if current.is_some() && current != *expected {
+                                        progress.debug(&format!("> {} has been changed locally during the sync, it will be synced next time", redact(new_item.url())));
+                                        continue;
+                                    }
+                                }
+                                let local_update_result = match batch_type {
+                                    BatchDownloadType::RemoteAdditions => cal_local.add_item(new_item.clone()).await,
+                                    BatchDownloadType::RemoteChanges => cal_local.update_item(new_item.clone()).await,
+                                };
+                                match local_update_result {
+                                    Err(err) => progress.item_error(&cal_url, new_item.url(), &format!("not able to add the item to the local calendar: {}", err)),
+                                    Ok(_) => {
+                                        progress.report_mut().downloaded += 1;
+                                        if let Err(err) = cal_local.save_synced_snapshot(new_item.url()).await {
+                                            progress.warn(&format!("Unable to save the synced version of {}: {}", redact(new_item.url()), err));
+                                        }
+                                    },
+                                }
+                            },
+                        }
                     }
                 }
 
                 // Notifying every item at the same time would not make sense. Let's notify only one of them
                 let one_item_name = match list_of_additions.get(0) {
-                    Some(url) => Self::item_name(&cal_local, &url).await,
+                    Some(url) => Self::item_name(cal_local, url).await,
                     None => String::from("<unable to get the name of the first batched item>"),
                 };
                 progress.increment_counter(list_of_additions.len());
-                progress.feedback(SyncEvent::InProgress{
-                    calendar: cal_name.to_string(),
+                progress.step(SyncEvent::InProgress{
+                    calendar: cal_name,
                     items_done_already: progress.counter(),
                     details: one_item_name,
-                });
+                }).await;
             },
         }
     }
 }
 
 
-async fn get_or_insert_counterpart_calendar<H, N, I>(haystack_descr: &str, haystack: &mut H, cal_url: &Url, needle: Arc<Mutex<N>>)
-    -> Result<Arc<Mutex<I>>, Box<dyn Error>>
+/// Whether an item that has been locally modified would be uploaded exactly as its synced version.
+///
+/// Its serialized iCal is compared with the one of its synced version (i.e. the one that has last been uploaded or downloaded),
+/// except for the `DTSTAMP`, `LAST-MODIFIED` and `SEQUENCE` properties, that every edit updates
+fn is_unchanged_since(item: &Item, synced: &Item) -> bool {
+    // The server may have changed since the synced version has been saved (e.g. for items whose local version overrides a remote change)
+    match (item.sync_status(), synced.sync_status()) {
+        (SyncStatus::LocallyModified(tag), SyncStatus::Synced(synced_tag)) if tag == synced_tag => (),
+        _ => return false,
+    }
+    let payload = |item: &Item| -> Option<Vec<String>> {
+        // Folded lines are joined, so that every line is a whole property
+        let ical = crate::ical::build_from(item).ok()?.replace("\r\n ", "");
+        Some(ical.lines()
+            .filter(|line| {
+                // The name of a property ends where its parameters (e.g. `SEQUENCE;X-SOMETHING=1:2`) or its value begin
+                let name = line.split([';', ':']).next().unwrap_or_default();
+                !["DTSTAMP", "LAST-MODIFIED", "SEQUENCE"].iter().any(|ignored| name.eq_ignore_ascii_case(ignored))
+            })
+            .map(String::from)
+            .collect())
+    };
+    match (payload(item), payload(synced)) {
+        (Some(payload), Some(synced_payload)) => payload == synced_payload,
+        _ => false,
+    }
+}
+
+/// The local changes made to an item while it was being uploaded (the local calendar is not locked meanwhile), on top of the version `stored` by the remote source,
+/// so that they are uploaded by the next sync. Returns `None` if the item has not been changed since its `uploaded` version.
+///
+/// An item that does not exist anymore (`current` is `None`) has been deleted in the meantime, so that it is deleted from the remote source by the next sync.
+fn changes_made_during_upload(uploaded: &ItemVersion, current: Option<&Item>, stored: &Item) -> Option<Item> {
+    let tag = stored.version_tag()?.clone();
+    match current {
+        Some(current) if current.version() == *uploaded => None,
+        Some(current) => {
+            let status = match current.sync_status() {
+                SyncStatus::LocallyDeleted(_) => SyncStatus::LocallyDeleted(tag),
+                _ => SyncStatus::LocallyModified(tag),
+            };
+            Some(current.clone_with_identity(current.uid().to_string(), stored.url().clone(), status))
+        },
+        None => Some(stored.clone_with_identity(stored.uid().to_string(), stored.url().clone(), SyncStatus::LocallyDeleted(tag))),
+    }
+}
+
+/// Run a sync, that is interrupted once `timeout` has elapsed (see [`Provider::set_sync_timeout`]).
+/// Syncs usually stop by themselves between two items by then, this is for the syncs that are stuck in a request
+async fn within_timeout<F>(timeout: Option<Duration>, sync: F) -> Result<(), Box<dyn Error>>
+where
+    F: Future<Output = Result<(), Box<dyn Error>>>
+{
+    match timeout {
+        None => sync.await,
+        Some(timeout) => tokio::time::timeout(timeout, sync).await
+            .unwrap_or_else(|_| Err("the sync has timed out".into())),
+    }
+}
+
+/// Whether a version of an item is newer than another one, according to their `SEQUENCE`, then their `LAST-MODIFIED` date.
+/// Dates that are closer than `skew_tolerance` are considered equal, since they may have been written by devices whose clocks disagree
+fn is_newer(item: &Item, other: &Item, skew_tolerance: chrono::Duration) -> bool {
+    match item.sequence().cmp(&other.sequence()) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Less => false,
+        // A tolerance that goes beyond the dates chrono can represent makes every date "close enough"
+        std::cmp::Ordering::Equal => match other.last_modified().checked_add_signed(skew_tolerance) {
+            Some(latest_equal) => *item.last_modified() > latest_equal,
+            None => false,
+        },
+    }
+}
+
+async fn get_or_insert_counterpart_calendar<H, N, I>(haystack_descr: &str, haystack: &mut H, cal_url: &Url, needle: Arc<RwLock<N>>)
+    -> Result<Arc<RwLock<I>>, Box<dyn Error>>
 where
     H: CalDavSource<I>,
     I: BaseCalendar,
@@ -496,20 +1798,973 @@ where
             break Ok(cal);
         }
 
+        // The calendar may be known under an equivalent URL (e.g. without its trailing slash)
+        if let Ok(id) = CalendarId::try_from(cal_url.clone()) {
+            let equivalent = haystack.get_calendars().await?.into_iter().find(|(url, _)| id.matches(url));
+            if let Some((url, cal)) = equivalent {
+                log::debug!("Using {} calendar {} as the counterpart of {}", haystack_descr, url, cal_url);
+                break Ok(cal);
+            }
+        }
+
         // This calendar does not exist locally yet, let's add it
         log::debug!("Adding a {} calendar {}", haystack_descr, cal_url);
-        let src = needle.lock().unwrap();
-        let name = src.name().to_string();
-        let supported_comps = src.supported_components();
-        let color = src.color();
+        let (name, supported_comps, color) = {
+            let src = needle.read().await;
+            (src.name().to_string(), src.supported_components(), src.color().cloned())
+        };
         if let Err(err) = haystack.create_calendar(
             cal_url.clone(),
             name,
             supported_comps,
-            color.cloned(),
+            color,
         ).await{
             return Err(err);
         }
     }
 }
 
+
+
+#[cfg(all(test, feature = "local_calendar_mocks_remote_calendars"))]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use crate::cache::Cache;
+    use crate::calendar::{ItemFilter, SupportedComponents};
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::mock_behaviour::MockBehaviour;
+    use crate::task::{CompletionStatus, Task};
+    use chrono::{TimeZone, Utc};
+
+    #[tokio::test]
+    async fn test_move_item() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_a: Url = "https://caldav.com/a".parse().unwrap();
+        let cal_b: Url = "https://caldav.com/b".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/move_item/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/move_item/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        for cal_url in [&cal_a, &cal_b] {
+            remote.create_calendar(cal_url.clone(), cal_url.to_string(), SupportedComponents::TODO, None).await.unwrap();
+        }
+        let task = Task::new("Task to move".to_string(), false, &cal_a);
+        let uid = task.uid().to_string();
+        let item_url = task.url().clone();
+        remote.get_calendar(&cal_a).await.unwrap().write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+
+        // Mocked remote calendars do not support MOVE, so this falls back to a copy and a deletion
+        let new_url = provider.move_item(&item_url, &cal_a, &cal_b).await.unwrap();
+        assert!(provider.move_item(&new_url, &cal_b, &cal_b).await.is_err());
+        let pending = provider.pending_changes().await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[&cal_a], PendingChanges{ deleted: vec![item_url.clone()], ..PendingChanges::default() });
+        assert_eq!(pending[&cal_b], PendingChanges{ created: vec![new_url.clone()], ..PendingChanges::default() });
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.uploaded(), report.deleted_remotely(), report.downloaded()), (1, 1, 0));
+        assert_eq!(report.to_string(), "synced 2 items");
+        let metrics = provider.metrics();
+        assert_eq!((metrics.syncs, metrics.failed_syncs, metrics.items_uploaded, metrics.items_deleted), (2, 0, 1, 1));
+        assert!(metrics.last_sync_duration.is_some() && metrics.last_successful_sync.is_some());
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+
+        for source in [provider.local(), provider.remote()] {
+            let source_a = source.get_calendar(&cal_a).await.unwrap();
+            assert!(source_a.read().await.get_item_by_uid_sync(&uid).is_none());
+            let source_b = source.get_calendar(&cal_b).await.unwrap();
+            let source_b = source_b.read().await;
+            let moved = source_b.get_item_by_uid_sync(&uid).unwrap();
+            assert_eq!(moved.url(), &new_url);
+            assert_eq!(moved.name(), "Task to move");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_href_strategy() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/tasks/".parse().unwrap();
+
+        let mut local = Cache::new(&PathBuf::from("test_cache/href_strategy/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/href_strategy/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        for source in [&mut local, &mut remote] {
+            source.create_calendar(cal_url.clone(), "Tasks".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        }
+        let task = Task::new("New task".to_string(), false, &cal_url);
+        let uid = task.uid().to_string();
+        let local_url = task.url().clone();
+        local.get_calendar(&cal_url).await.unwrap().write().await.add_item(Item::Task(task)).await.unwrap();
+
+        // Mocked remote calendars cannot choose URLs, so this falls back to UID-based URLs
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_href_strategy(HrefStrategy::ServerAssigned);
+        assert!(provider.sync().await);
+
+        let expected_url = cal_url.join(&format!("{}.ics", uid)).unwrap();
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_url).await.unwrap();
+            let cal = cal.read().await;
+            assert!(cal.get_item_by_url_sync(&local_url).is_none());
+            let item = cal.get_item_by_url_sync(&expected_url).unwrap();
+            assert_eq!(item.uid(), uid);
+            assert!(matches!(item.sync_status(), SyncStatus::Synced(_)));
+        }
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_href_strategy_local_collision() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/tasks/".parse().unwrap();
+
+        let mut local = Cache::new(&PathBuf::from("test_cache/href_strategy_collision/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/href_strategy_collision/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        for source in [&mut local, &mut remote] {
+            source.create_calendar(cal_url.clone(), "Tasks".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        }
+        let task = Task::new("New task".to_string(), false, &cal_url);
+        let uid = task.uid().to_string();
+        let local_url = task.url().clone();
+        let uid_url = cal_url.join(&format!("{}.ics", uid)).unwrap();
+        // Another local item, that cannot be moved to a UID-based URL, already lives at the UID-based URL of the first one
+        let squatter = Task::new("Squatter".to_string(), false, &cal_url)
+            .clone_with_identity("not a safe UID".to_string(), uid_url.clone(), SyncStatus::NotSynced);
+        {
+            let cal = local.get_calendar(&cal_url).await.unwrap();
+            let mut cal = cal.write().await;
+            cal.add_item(Item::Task(task)).await.unwrap();
+            cal.add_item(Item::Task(squatter)).await.unwrap();
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_href_strategy(HrefStrategy::Uid);
+        assert!(provider.sync().await);
+
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_url).await.unwrap();
+            let cal = cal.read().await;
+            assert_eq!(cal.get_item_by_url_sync(&local_url).unwrap().uid(), uid);
+            assert_eq!(cal.get_item_by_url_sync(&uid_url).unwrap().uid(), "not a safe UID");
+        }
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sequence_conflicts() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/conflicts".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/sequence_conflicts/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sequence_conflicts/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Conflicts".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Original".to_string(), false, &cal_url);
+        let (uid, item_url) = (task.uid().to_string(), task.url().clone());
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+
+        // Another client changed the remote item without incrementing its SEQUENCE: our local change has a higher SEQUENCE and wins
+        let edited = Task::new_with_parameters("Edited remotely".to_string(), uid, item_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::random_synced(), None, chrono::Utc::now(), crate::ical::default_prod_id(), Vec::new());
+        remote_cal.write().await.update_item(Item::Task(edited)).await.unwrap();
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name("Edited locally".to_string());
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().sequence(), 1);
+        assert!(provider.sync().await);
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&item_url).unwrap().name(), "Edited locally");
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&item_url).unwrap().sequence(), 1);
+
+        // Both versions are new revisions with the same SEQUENCE: the remote wins
+        remote_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Remote wins".to_string());
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name("Local loses".to_string());
+        let report = provider.sync_with_report().await;
+        assert_eq!((report.conflicts(), report.downloaded()), (1, 1));
+        assert!(report.item_errors().is_empty());
+        let local_item = local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().clone();
+        assert_eq!(local_item.name(), "Remote wins");
+        assert_eq!(local_item.sequence(), 2);
+        assert!(matches!(local_item.sync_status(), SyncStatus::Synced(_)));
+    }
+
+    #[tokio::test]
+    async fn test_uid_dedup() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/dedup/".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/uid_dedup/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/uid_dedup/remote"));
+        let behaviour = Arc::new(std::sync::Mutex::new(MockBehaviour::new()));
+        remote.set_mock_behaviour(Some(Arc::clone(&behaviour)));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Dedup".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Older copy".to_string(), false, &cal_url);
+        let (uid, older_url) = (task.uid().to_string(), task.url().clone());
+        let newer_url = cal_url.join("copy.ics").unwrap();
+        let sequence = ical::property::Property{ name: "SEQUENCE".to_string(), params: None, value: Some("3".to_string()) };
+        let newer = Task::new_with_parameters("Newer copy".to_string(), uid.clone(), newer_url.clone(), CompletionStatus::Uncompleted,
+            SyncStatus::random_synced(), None, chrono::Utc::now(), crate::ical::default_prod_id(), vec![sequence]);
+        remote_cal.write().await.add_items_sync(vec![Item::Task(task), Item::Task(newer)]).unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_uid_dedup(UidDedup::KeepNewest);
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.downloaded(), report.duplicates_removed()), (2, 1));
+        assert_eq!(report.to_string(), "synced 2 items, 1 duplicate removed");
+
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_url).await.unwrap();
+            let cal = cal.read().await;
+            assert!(cal.get_item_by_url_sync(&older_url).is_none());
+            assert_eq!(cal.get_item_by_uid_sync(&uid).unwrap().name(), "Newer copy");
+        }
+        assert!(provider.sync().await);
+
+        // The newest copy could not be uploaded: the copy of the server is kept, with the content of the newest one
+        let sequence = ical::property::Property{ name: "SEQUENCE".to_string(), params: None, value: Some("5".to_string()) };
+        let local_copy = Task::new_with_parameters("Local copy".to_string(), uid.clone(), cal_url.join("local-copy.ics").unwrap(), CompletionStatus::Uncompleted,
+            SyncStatus::NotSynced, None, chrono::Utc::now(), crate::ical::default_prod_id(), vec![sequence]);
+        let local_url = local_copy.url().clone();
+        provider.local().get_calendar(&cal_url).await.unwrap().write().await.add_item(Item::Task(local_copy)).await.unwrap();
+        behaviour.lock().unwrap().add_item_behaviour = (0, 1);
+        let report = provider.sync_with_report().await;
+        assert_eq!(report.duplicates_removed(), 1);
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_url).await.unwrap();
+            let cal = cal.read().await;
+            assert!(cal.get_item_by_url_sync(&local_url).is_none());
+            let kept = cal.get_item_by_uid_sync(&uid).unwrap();
+            assert_eq!((kept.url(), kept.name()), (&newer_url, "Local copy"));
+        }
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_newer_with_skew_tolerance() {
+        let cal_url: Url = "https://caldav.com/skew/".parse().unwrap();
+        let version = |sequence: &str, minutes: i64| {
+            let sequence = ical::property::Property{ name: "SEQUENCE".to_string(), params: None, value: Some(sequence.to_string()) };
+            Item::Task(Task::new_with_parameters("A task".to_string(), "uid".to_string(), cal_url.join("task.ics").unwrap(), CompletionStatus::Uncompleted,
+                SyncStatus::NotSynced, None, Utc.ymd(2021, 3, 1).and_hms(12, 0, 0) + chrono::Duration::minutes(minutes),
+                crate::ical::default_prod_id(), vec![sequence]))
+        };
+        let tolerance = chrono::Duration::minutes(5);
+
+        // A higher SEQUENCE always wins, whatever the dates
+        assert!(is_newer(&version("2", 0), &version("1", 60), tolerance));
+        assert!(!is_newer(&version("1", 60), &version("2", 0), tolerance));
+        // Dates are only compared for the same SEQUENCE, and only beyond the tolerance
+        assert!(is_newer(&version("1", 10), &version("1", 0), tolerance));
+        assert!(!is_newer(&version("1", 3), &version("1", 0), tolerance));
+        assert!(!is_newer(&version("1", 0), &version("1", 3), tolerance));
+        assert!(is_newer(&version("1", 3), &version("1", 0), chrono::Duration::zero()));
+        // Huge tolerances (see `set_clock_skew_tolerance`) do not overflow
+        assert!(!is_newer(&version("1", 10), &version("1", 0), chrono::Duration::max_value()));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_strategies() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/bootstrap/".parse().unwrap();
+
+        for strategy in [BootstrapStrategy::MergeByUid, BootstrapStrategy::AdoptServer, BootstrapStrategy::DiscardLocal] {
+            let folder = format!("test_cache/bootstrap/{:?}", strategy);
+            let mut local = Cache::new(&PathBuf::from(format!("{}/local", folder)));
+            let mut remote = Cache::new(&PathBuf::from(format!("{}/remote", folder)));
+            remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+            let remote_cal = remote.create_calendar(cal_url.clone(), "Bootstrap".to_string(), SupportedComponents::TODO, None).await.unwrap();
+            let local_cal = local.create_calendar(cal_url.clone(), "Bootstrap".to_string(), SupportedComponents::TODO, None).await.unwrap();
+
+            // The same item has been created on both sides (more recently on the local side), and each side has an item of its own
+            let shared = Task::new("Remote version".to_string(), false, &cal_url);
+            let (uid, remote_url) = (shared.uid().to_string(), shared.url().clone());
+            remote_cal.write().await.add_item(Item::Task(shared)).await.unwrap();
+            remote_cal.write().await.add_item(Item::Task(Task::new("Remote only".to_string(), false, &cal_url))).await.unwrap();
+            let local_copy = Task::new_with_parameters("Local version".to_string(), uid.clone(), cal_url.join("local-copy.ics").unwrap(),
+                CompletionStatus::Uncompleted, SyncStatus::NotSynced, None, chrono::Utc::now(), crate::ical::default_prod_id(), Vec::new());
+            local_cal.write().await.add_item(Item::Task(local_copy)).await.unwrap();
+            local_cal.write().await.add_item(Item::Task(Task::new("Local only".to_string(), false, &cal_url))).await.unwrap();
+
+            let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+            provider.set_bootstrap_strategy(strategy);
+            assert!(provider.sync().await);
+            assert!(local_cal.read().await.last_sync().is_some());
+
+            let (expected_name, expected_count) = match strategy {
+                BootstrapStrategy::MergeByUid => ("Local version", 3),
+                BootstrapStrategy::AdoptServer => ("Remote version", 3),
+                _ => ("Remote version", 2),
+            };
+            for source in [provider.local(), provider.remote()] {
+                let cal = source.get_calendar(&cal_url).await.unwrap();
+                let cal = cal.read().await;
+                assert_eq!(cal.get_items_sync().unwrap().len(), expected_count);
+                assert_eq!(cal.get_item_by_url_sync(&remote_url).unwrap().name(), expected_name);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_of_new_remote_calendar() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/bootstrap-new/".parse().unwrap();
+
+        // This calendar only exists locally, so that the sync creates an empty remote calendar
+        let mut local = Cache::new(&PathBuf::from("test_cache/bootstrap_new/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/bootstrap_new/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let local_cal = local.create_calendar(cal_url.clone(), "New".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        local_cal.write().await.add_item(Item::Task(Task::new("Created offline".to_string(), false, &cal_url))).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_bootstrap_strategy(BootstrapStrategy::DiscardLocal);
+        assert!(provider.sync().await);
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_url).await.unwrap();
+            assert_eq!(cal.read().await.get_items_sync().unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_pushes_only_delay_their_items() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/two-phases/".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/two_phases/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/two_phases/remote"));
+        let behaviour = Arc::new(std::sync::Mutex::new(MockBehaviour::new()));
+        remote.set_mock_behaviour(Some(Arc::clone(&behaviour)));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Two phases".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Deleted remotely".to_string(), false, &cal_url);
+        let deleted_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+        let task = Task::new("Deleted on both".to_string(), false, &cal_url);
+        let rejected_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        remote_cal.write().await.immediately_delete_item_sync(&deleted_url).unwrap();
+        local_cal.write().await.add_item(Item::Task(Task::new("Created locally".to_string(), false, &cal_url))).await.unwrap();
+        local_cal.write().await.mark_for_deletion(&rejected_url).await.unwrap();
+
+        // The local addition and deletion cannot be pushed, but the remote deletion of another item is applied anyway
+        behaviour.lock().unwrap().add_item_behaviour = (0, 1);
+        behaviour.lock().unwrap().delete_item_behaviour = (0, 1);
+        let report = provider.sync_with_report().await;
+        assert!(!report.is_success());
+        assert_eq!((report.uploaded(), report.deleted_locally()), (0, 1));
+        assert!(local_cal.read().await.get_item_by_url_sync(&deleted_url).is_none());
+        assert!(local_cal.read().await.get_item_by_url_sync(&rejected_url).is_some());
+
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.uploaded(), report.deleted_locally()), (1, 0));
+        assert!(local_cal.read().await.get_item_by_url_sync(&rejected_url).is_none());
+        assert!(remote_cal.read().await.get_item_by_url_sync(&rejected_url).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_moved_on_server() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_a: Url = "https://caldav.com/moves-a/".parse().unwrap();
+        let cal_b: Url = "https://caldav.com/moves-b/".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/moved_on_server/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/moved_on_server/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        for cal_url in [&cal_a, &cal_b] {
+            remote.create_calendar(cal_url.clone(), cal_url.to_string(), SupportedComponents::TODO, None).await.unwrap();
+        }
+        let task = Task::new("Original name".to_string(), false, &cal_a);
+        let (uid, old_url) = (task.uid().to_string(), task.url().clone());
+        remote.get_calendar(&cal_a).await.unwrap().write().await.add_item(Item::Task(task.clone())).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        provider.local().get_calendar(&cal_a).await.unwrap().write().await
+            .get_item_by_url_mut_sync(&old_url).unwrap().unwrap_task_mut().set_name("Edited locally".to_string());
+
+        // Another client moves the item to another calendar
+        let new_url = cal_b.join("moved.ics").unwrap();
+        provider.remote().get_calendar(&cal_a).await.unwrap().write().await.immediately_delete_item_sync(&old_url).unwrap();
+        let moved = task.clone_with_identity(uid.clone(), new_url.clone(), SyncStatus::NotSynced);
+        provider.remote().get_calendar(&cal_b).await.unwrap().write().await.add_item(Item::Task(moved)).await.unwrap();
+
+        assert!(provider.sync().await);
+        for source in [provider.local(), provider.remote()] {
+            assert!(source.get_calendar(&cal_a).await.unwrap().read().await.get_item_by_uid_sync(&uid).is_none());
+            let cal = source.get_calendar(&cal_b).await.unwrap();
+            let cal = cal.read().await;
+            let item = cal.get_item_by_uid_sync(&uid).unwrap();
+            assert_eq!(item.url(), &new_url);
+            assert_eq!(item.name(), "Edited locally");
+        }
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+
+        // The item is moved back while it is edited again, and its calendars are synced one at a time
+        provider.local().get_calendar(&cal_b).await.unwrap().write().await
+            .get_item_by_url_mut_sync(&new_url).unwrap().unwrap_task_mut().set_name("Edited again".to_string());
+        provider.remote().get_calendar(&cal_b).await.unwrap().write().await.immediately_delete_item_sync(&new_url).unwrap();
+        provider.remote().get_calendar(&cal_a).await.unwrap().write().await.add_item(Item::Task(task.clone())).await.unwrap();
+
+        assert!(provider.sync_calendar(&cal_b).await);
+        let local_b = provider.local().get_calendar(&cal_b).await.unwrap();
+        assert!(local_b.read().await.get_item_by_uid_sync(&uid).is_none());
+        assert_eq!(local_b.read().await.get_vanished_items_sync().len(), 1);
+
+        assert!(provider.sync_calendar(&cal_a).await);
+        assert!(local_b.read().await.get_vanished_items_sync().is_empty());
+        for source in [provider.local(), provider.remote()] {
+            let cal = source.get_calendar(&cal_a).await.unwrap();
+            let cal = cal.read().await;
+            let item = cal.get_item_by_uid_sync(&uid).unwrap();
+            assert_eq!(item.url(), &old_url);
+            assert_eq!(item.name(), "Edited again");
+        }
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_clock() {
+        use crate::clock::MockClock;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/clock/".parse().unwrap();
+        let clock = MockClock::new(Utc.ymd(2000, 1, 1).and_hms(8, 0, 0));
+
+        let local = Cache::builder(&PathBuf::from("test_cache/sync_clock/local")).clock(Arc::new(clock.clone())).build();
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sync_clock/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Clock".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Task".to_string(), false, &cal_url);
+        let item_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_clock(Arc::new(clock.clone()));
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        assert_eq!(local_cal.read().await.last_sync(), Some(clock.now()));
+        assert_eq!(provider.metrics().last_successful_sync, Some(clock.now()));
+
+        clock.advance(chrono::Duration::days(1));
+        local_cal.write().await.mark_completed_sync(std::slice::from_ref(&item_url)).unwrap();
+        let completed = local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().unwrap_task().completion_status().clone();
+        assert_eq!(completed, CompletionStatus::Completed(Some(Utc.ymd(2000, 1, 2).and_hms(8, 0, 0))));
+    }
+
+    #[tokio::test]
+    async fn test_field_merge() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/merge".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/field_merge/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/field_merge/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Merge".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Original".to_string(), false, &cal_url);
+        let item_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+
+        // The server renamed the task, while it has been completed locally: both changes are kept
+        remote_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Renamed remotely".to_string());
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_completion_status(CompletionStatus::Completed(None));
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.conflicts(), report.uploaded()), (1, 1));
+        for cal in [&local_cal, &remote_cal] {
+            let cal = cal.read().await;
+            let task = cal.get_item_by_url_sync(&item_url).unwrap().unwrap_task();
+            assert_eq!(task.name(), "Renamed remotely");
+            assert!(task.completed());
+            assert_eq!(task.sequence(), 2);
+        }
+        assert!(matches!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().sync_status(), SyncStatus::Synced(_)));
+        assert_eq!(local_cal.read().await.get_synced_snapshot_sync(&item_url).unwrap().name(), "Renamed remotely");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_sync() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/cancelled".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/cancelled_sync/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/cancelled_sync/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Cancelled".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        for name in ["First task", "Second task"] {
+            remote_cal.write().await.add_item(Item::Task(Task::new(name.to_string(), false, &cal_url))).await.unwrap();
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        let token = CancellationToken::new();
+        token.clone().cancel();
+        let report = provider.sync_with_cancellation(token).await;
+        assert!(report.is_cancelled());
+        assert!(!report.is_success());
+        assert_eq!((report.synced(), report.n_errors()), (0, 0));
+        assert!(provider.local().get_calendars_sync().unwrap().is_empty());
+
+        provider.set_sync_timeout(Some(Duration::from_secs(0)));
+        assert!(provider.sync_with_report().await.is_cancelled());
+        provider.set_sync_timeout(None);
+        // Syncs that are stuck in a request are interrupted as well
+        let stuck = futures_util::future::pending::<Result<(), Box<dyn Error>>>();
+        assert!(within_timeout(Some(Duration::from_millis(10)), stuck).await.is_err());
+
+        // Nothing is left half-done, so that the next sync can complete
+        let report = provider.sync_with_cancellation(CancellationToken::new()).await;
+        assert!(report.is_success());
+        assert_eq!(report.downloaded(), 2);
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sync_stream() {
+        use futures_util::StreamExt;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/streamed".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/sync_stream/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sync_stream/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Streamed".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        for name in ["First task", "Second task"] {
+            remote_cal.write().await.add_item(Item::Task(Task::new(name.to_string(), false, &cal_url))).await.unwrap();
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+
+        // The sync does not go further than the events that have been consumed
+        {
+            let mut stream = Box::pin(provider.sync_stream());
+            assert!(matches!(stream.next().await, Some(SyncEvent::Started)));
+        }
+        assert!(provider.local().get_calendars_sync().unwrap().is_empty());
+
+        let events: Vec<SyncEvent> = provider.sync_stream().collect().await;
+        assert!(matches!(events.first(), Some(SyncEvent::Started)));
+        assert!(matches!(events.last(), Some(SyncEvent::Finished{ success: true })));
+        assert!(events.iter().any(|event| matches!(event, SyncEvent::InProgress{ calendar, .. } if calendar == "Streamed")));
+        assert_eq!(provider.last_sync_report().downloaded(), 2);
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_local_changes_during_sync() {
+        use futures_util::StreamExt;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/changed_during_sync".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/changed_during_sync/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/changed_during_sync/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Changed during sync".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let pushed = Task::new("Pushed".to_string(), false, &cal_url);
+        let pushed_url = pushed.url().clone();
+        let edited = Task::new("Edited".to_string(), false, &cal_url);
+        let edited_url = edited.url().clone();
+        remote_cal.write().await.add_items_sync(vec![Item::Task(pushed), Item::Task(edited)]).unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        local_cal.write().await.get_item_by_url_mut_sync(&pushed_url).unwrap().unwrap_task_mut().set_name("Pushed locally".to_string());
+        remote_cal.write().await.get_item_by_url_mut_sync(&edited_url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Edited remotely".to_string());
+
+        // The local calendar is not locked while the sync is paused, and the remote change does not overwrite what is edited meanwhile
+        {
+            let mut stream = Box::pin(provider.sync_stream());
+            while let Some(event) = stream.next().await {
+                if matches!(&event, SyncEvent::InProgress{ details, .. } if details == "Pushed locally") {
+                    let mut local_cal = local_cal.try_write().unwrap();
+                    local_cal.get_item_by_url_mut_sync(&edited_url).unwrap().unwrap_task_mut().set_name("Edited locally".to_string());
+                }
+            }
+        }
+        assert!(provider.last_sync_report().is_success());
+        assert_eq!(provider.last_sync_report().uploaded(), 1);
+        assert_eq!(provider.last_sync_report().downloaded(), 0);
+        let edited = local_cal.read().await.get_item_by_url_sync(&edited_url).unwrap().clone();
+        assert_eq!(edited.name(), "Edited locally");
+        assert!(matches!(edited.sync_status(), SyncStatus::LocallyModified(_)));
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&pushed_url).unwrap().name(), "Pushed locally");
+
+        // It is synced next time
+        assert!(provider.sync().await);
+        assert_eq!(provider.last_sync_report().conflicts(), 1);
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_dyn_provider() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/dyn".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/dyn_provider/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/dyn_provider/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Dyn".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        remote_cal.write().await.add_item(Item::Task(Task::new("Task".to_string(), false, &cal_url))).await.unwrap();
+
+        // Apps can store providers without knowing the types of their sources
+        struct AppState {
+            provider: crate::DynProvider<CachedCalendar, CachedCalendar>,
+        }
+        let provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        let mut state = AppState { provider: provider.into_dyn() };
+        assert!(state.provider.sync().await);
+        assert_eq!(state.provider.last_sync_report().downloaded(), 1);
+        assert!(state.provider.local().get_calendar(&cal_url).await.is_some());
+        state.provider.local().save().await.unwrap();
+        assert!(Cache::from_folder(&PathBuf::from("test_cache/dyn_provider/local")).unwrap().get_calendar_sync(&cal_url).is_some());
+
+        state.provider = Provider::new(Box::new(Cache::new(&PathBuf::from("test_cache/dyn_provider/other_remote"))), Box::new(Cache::new(&PathBuf::from("test_cache/dyn_provider/other_local"))));
+        assert!(state.provider.sync().await);
+    }
+
+    #[tokio::test]
+    async fn test_trash() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/trash".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/trash/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/trash/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Trash".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut urls = Vec::new();
+        for name in ["Restored task", "Purged task"] {
+            let task = Task::new(name.to_string(), false, &cal_url);
+            urls.push(task.url().clone());
+            remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        {
+            let mut local_cal = local_cal.write().await;
+            for url in &urls {
+                local_cal.trash_item(url).await.unwrap();
+            }
+            assert!(local_cal.get_items_matching(&ItemFilter::default()).await.unwrap().is_empty());
+            assert_eq!(local_cal.get_trashed_items().await.unwrap().len(), 2);
+        }
+
+        // Trashed items are not deleted from the server yet
+        assert!(provider.sync().await);
+        assert_eq!(provider.remote().get_calendar(&cal_url).await.unwrap().read().await.get_item_urls_sync().unwrap().len(), 2);
+
+        {
+            let mut local_cal = local_cal.write().await;
+            local_cal.restore_item(&urls[0]).await.unwrap();
+            assert!(local_cal.restore_item(&urls[0]).await.is_err());
+            assert!(local_cal.trashed_at(&urls[0]).is_none());
+            assert_eq!(local_cal.get_items_matching(&ItemFilter::default()).await.unwrap().len(), 1);
+        }
+
+        provider.set_trash_purge_delay(Duration::from_secs(0));
+        assert!(provider.sync().await);
+        let remote_urls = provider.remote().get_calendar(&cal_url).await.unwrap().read().await.get_item_urls_sync().unwrap();
+        assert_eq!(remote_urls, HashSet::from([urls[0].clone()]));
+        assert!(local_cal.read().await.get_trashed_items().await.unwrap().is_empty());
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sync_modes() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/modes".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/sync_modes/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sync_modes/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Modes".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Task".to_string(), false, &cal_url);
+        let item_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+
+        // Pull only: local changes are kept for later
+        provider.set_sync_mode(cal_url.clone(), SyncMode::PullOnly);
+        assert_eq!(provider.sync_mode(&cal_url), SyncMode::PullOnly);
+        remote_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Pulled".to_string());
+        let local_task = Task::new("Local task".to_string(), false, &cal_url);
+        let local_url = local_task.url().clone();
+        local_cal.write().await.add_item(Item::Task(local_task)).await.unwrap();
+        assert!(provider.sync().await);
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().name(), "Pulled");
+        assert!(remote_cal.read().await.get_item_by_url_sync(&local_url).is_none());
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&local_url).unwrap().sync_status(), &SyncStatus::NotSynced);
+
+        // Push only: remote changes are ignored
+        provider.set_sync_mode(cal_url.clone(), SyncMode::PushOnly);
+        remote_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Not pulled".to_string());
+        assert!(provider.sync().await);
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().name(), "Pulled");
+        assert!(remote_cal.read().await.get_item_by_url_sync(&local_url).is_some());
+
+        // Mirror: local changes are discarded
+        provider.set_sync_mode(cal_url.clone(), SyncMode::Mirror);
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name("Discarded".to_string());
+        let discarded_task = Task::new("Discarded task".to_string(), false, &cal_url);
+        let discarded_url = discarded_task.url().clone();
+        local_cal.write().await.add_item(Item::Task(discarded_task)).await.unwrap();
+        local_cal.write().await.mark_for_deletion(&local_url).await.unwrap();
+        assert!(provider.sync().await);
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().name(), "Not pulled");
+        assert!(local_cal.read().await.get_item_by_url_sync(&discarded_url).is_none());
+        assert!(remote_cal.read().await.get_item_by_url_sync(&discarded_url).is_none());
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+
+        provider.set_sync_mode(cal_url.clone(), SyncMode::TwoWay);
+        assert_eq!(provider.sync_mode(&cal_url), SyncMode::TwoWay);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_sync() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let local = Cache::new(&PathBuf::from("test_cache/parallel_sync/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/parallel_sync/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        for i in 0..5 {
+            let cal_url: Url = format!("https://caldav.com/parallel/{}", i).parse().unwrap();
+            let remote_cal = remote.create_calendar(cal_url.clone(), format!("Calendar {}", i), SupportedComponents::TODO, None).await.unwrap();
+            for name in ["First task", "Second task"] {
+                remote_cal.write().await.add_item(Item::Task(Task::new(name.to_string(), false, &cal_url))).await.unwrap();
+            }
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        provider.set_max_concurrent_calendars(2);
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!(report.downloaded(), 10);
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+
+        // Calendars are synced one at a time with a limit of 0
+        provider.set_max_concurrent_calendars(0);
+        let cal_url: Url = "https://caldav.com/parallel/3".parse().unwrap();
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        local_cal.write().await.add_item(Item::Task(Task::new("Local task".to_string(), false, &cal_url))).await.unwrap();
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.uploaded(), report.downloaded()), (1, 0));
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_items_are_not_uploaded() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/unchanged".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/unchanged_items/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/unchanged_items/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Unchanged".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let task = Task::new("Task".to_string(), false, &cal_url);
+        let item_url = task.url().clone();
+        remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let synced = local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().clone();
+
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name("Task".to_string());
+        assert!(matches!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().sync_status(), SyncStatus::LocallyModified(_)));
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!(report.uploaded(), 0);
+        assert_eq!(local_cal.read().await.get_item_by_url_sync(&item_url).unwrap().version(), synced.version());
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&item_url).unwrap().sequence(), synced.sequence());
+
+        // Actual changes are uploaded
+        local_cal.write().await.get_item_by_url_mut_sync(&item_url).unwrap().unwrap_task_mut().set_name("Renamed".to_string());
+        let mut receiver = provider.local().subscribe();
+        let report = provider.sync_with_report().await;
+        assert_eq!(report.uploaded(), 1);
+        // Subscribers are told about the new sync status of the uploaded item
+        let updated = crate::cache::CacheEvent::ItemUpdated{ calendar: cal_url.clone(), item: item_url.clone() };
+        assert!(std::iter::from_fn(|| receiver.try_recv().ok()).any(|event| event == updated));
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&item_url).unwrap().name(), "Renamed");
+        assert!(provider.local().has_same_observable_content_as(provider.remote()).await.unwrap());
+    }
+
+    #[test]
+    fn test_is_unchanged_since() {
+        let cal_url: Url = "https://caldav.com/unchanged".parse().unwrap();
+        let sequence = ical::property::Property {
+            name: "SEQUENCE".to_string(),
+            params: Some(vec![("X-SOME-APP-ORIGIN".to_string(), vec!["import".to_string()])]),
+            value: Some("3".to_string()),
+        };
+        let synced = Task::new_with_parameters("Task".to_string(), "some-uid".to_string(), cal_url.join("task.ics").unwrap(), CompletionStatus::Uncompleted,
+            SyncStatus::Synced(crate::item::VersionTag::from("some-tag".to_string())), None, Utc.ymd(2021, 1, 1).and_hms(0, 0, 0), crate::ical::default_prod_id(), vec![sequence]);
+
+        // Properties that have parameters are recognized as well
+        let mut task = synced.clone();
+        task.set_name("Task".to_string());
+        assert_eq!(task.sequence(), 4);
+        assert!(is_unchanged_since(&Item::Task(task.clone()), &Item::Task(synced.clone())));
+
+        task.set_name("Renamed".to_string());
+        assert!(!is_unchanged_since(&Item::Task(task), &Item::Task(synced)));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_listings() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/truncated".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/truncated_listings/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/truncated_listings/remote"));
+        let mock_behaviour = Arc::new(std::sync::Mutex::new(MockBehaviour::new()));
+        remote.set_mock_behaviour(Some(Arc::clone(&mock_behaviour)));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Truncated".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut urls = Vec::new();
+        for name in ["First", "Second", "Third"] {
+            let task = Task::new(name.to_string(), false, &cal_url);
+            urls.push(task.url().clone());
+            remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+        }
+        urls.sort();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+
+        // The server now lists the first item only, and one of the unlisted ones has actually been deleted
+        mock_behaviour.lock().unwrap().max_listed_items = Some(1);
+        DavCalendar::delete_item(&mut *remote_cal.write().await, &urls[2]).await.unwrap();
+
+        // Until the server confirms it, the deletion is not synced
+        mock_behaviour.lock().unwrap().get_item_version_tags_behaviour = (1, 1);
+        provider.sync().await;
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        assert_eq!(local_cal.read().await.get_item_urls_sync().unwrap().len(), 3);
+
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!(report.deleted_locally(), 1);
+        let local_urls = local_cal.read().await.get_item_urls_sync().unwrap();
+        assert_eq!(local_urls, urls[..2].iter().cloned().collect());
+    }
+
+    #[tokio::test]
+    async fn test_sync_exclusions() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/exclusions".parse().unwrap();
+        let private_cal_url: Url = "https://caldav.com/private".parse().unwrap();
+
+        let mut local = Cache::new(&PathBuf::from("test_cache/sync_exclusions/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/sync_exclusions/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Exclusions".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let archived = Task::new("Archived".to_string(), true, &cal_url);
+        let archived_url = archived.url().clone();
+        let shared = Task::new("Shared".to_string(), false, &cal_url);
+        let shared_url = shared.url().clone();
+        remote_cal.write().await.add_item(Item::Task(archived)).await.unwrap();
+        remote_cal.write().await.add_item(Item::Task(shared)).await.unwrap();
+
+        let local_cal = local.create_calendar(cal_url.clone(), "Exclusions".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let scratch = Task::new("Scratch".to_string(), false, &cal_url);
+        let scratch_url = scratch.url().clone();
+        local_cal.write().await.add_item(Item::Task(scratch)).await.unwrap();
+        local_cal.write().await.set_sync_exclusion(&scratch_url, Some(SyncExclusion::LocalOnly)).unwrap();
+        local_cal.write().await.set_sync_exclusion(&archived_url, Some(SyncExclusion::RemoteOnly)).unwrap();
+        let private_cal = local.create_calendar(private_cal_url.clone(), "Private".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        private_cal.write().await.set_calendar_sync_exclusion(Some(SyncExclusion::LocalOnly)).unwrap();
+        private_cal.write().await.add_item(Item::Task(Task::new("Private".to_string(), false, &private_cal_url))).await.unwrap();
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.pending_changes().await.unwrap().is_empty());
+        assert!(provider.sync().await);
+        assert!(provider.remote().get_calendar(&private_cal_url).await.is_none());
+        assert_eq!(local_cal.read().await.get_item_urls_sync().unwrap(), HashSet::from([shared_url.clone(), scratch_url.clone()]));
+        assert_eq!(remote_cal.read().await.get_item_urls_sync().unwrap(), HashSet::from([shared_url.clone(), archived_url.clone()]));
+
+        // Local copies of items that become remote only are removed, once their changes have been uploaded
+        local_cal.write().await.get_item_by_url_mut_sync(&shared_url).unwrap().unwrap_task_mut().set_name("Renamed".to_string());
+        local_cal.write().await.set_sync_exclusion(&shared_url, Some(SyncExclusion::RemoteOnly)).unwrap();
+        assert!(provider.sync().await);
+        assert_eq!(local_cal.read().await.get_item_urls_sync().unwrap(), HashSet::from([scratch_url.clone()]));
+        assert_eq!(remote_cal.read().await.get_item_by_url_sync(&shared_url).unwrap().name(), "Renamed");
+
+        // Items that are synced again are uploaded
+        local_cal.write().await.set_sync_exclusion(&scratch_url, None).unwrap();
+        let report = provider.sync_with_report().await;
+        assert_eq!((report.uploaded(), report.downloaded()), (1, 0));
+        assert!(remote_cal.read().await.get_item_by_url_sync(&scratch_url).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_archived_items() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cal_url: Url = "https://caldav.com/archived".parse().unwrap();
+
+        let local = Cache::new(&PathBuf::from("test_cache/archived_items/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/archived_items/remote"));
+        remote.set_mock_behaviour(Some(Arc::new(std::sync::Mutex::new(MockBehaviour::new()))));
+        let remote_cal = remote.create_calendar(cal_url.clone(), "Archived".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut urls = Vec::new();
+        for name in ["Reopened", "Deleted", "Out of sync"] {
+            let task = Task::new(name.to_string(), true, &cal_url);
+            urls.push(task.url().clone());
+            remote_cal.write().await.add_item(Item::Task(task)).await.unwrap();
+        }
+
+        let mut provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        assert!(provider.sync().await);
+        let local_cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let policy = crate::calendar::ArchivePolicy::completed_for(chrono::Duration::zero());
+        let out_of_sync_policy = crate::calendar::ArchivePolicy{ exclude_from_sync: true, ..policy };
+        local_cal.write().await.unarchive_item(&urls[0]).await.unwrap_err();
+        assert_eq!(local_cal.write().await.archive_items(&out_of_sync_policy).await.unwrap().len(), 3);
+        for url in &urls[..2] {
+            local_cal.write().await.unarchive_item(url).await.unwrap();
+        }
+        assert_eq!(local_cal.write().await.archive_items(&policy).await.unwrap().len(), 2);
+        assert!(local_cal.read().await.get_items().await.unwrap().is_empty());
+
+        // Archived items are still synced, unless they have been kept out of syncs
+        {
+            let mut remote_cal = remote_cal.write().await;
+            let reopened = remote_cal.get_item_by_url_mut(&urls[0]).await.unwrap().unwrap_task_mut();
+            reopened.set_completion_status(CompletionStatus::Uncompleted);
+            reopened.set_sync_status(SyncStatus::random_synced());
+        }
+        for url in &urls[1..] {
+            DavCalendar::delete_item(&mut *remote_cal.write().await, url).await.unwrap();
+        }
+        let report = provider.sync_with_report().await;
+        assert!(report.is_success());
+        assert_eq!((report.downloaded(), report.deleted_locally()), (1, 1));
+
+        let local_cal = local_cal.read().await;
+        assert!(!local_cal.get_item_by_url_sync(&urls[0]).unwrap().unwrap_task().completed());
+        assert!(!local_cal.is_archived(&urls[1]));
+        assert!(local_cal.is_archived(&urls[2]));
+        assert_eq!(local_cal.get_archived_items().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,64 @@
+//! Support for server push notifications
+//!
+//! Some servers (e.g. Nextcloud or Fastmail) are able to notify clients whenever a calendar has changed (e.g. using WebSockets or WebPush). \
+//! This crate does not implement any of these protocols (neither Nextcloud's `notify_push`, nor WebDAV-Push or WebPush subscriptions), since they need
+//! authentication and subscription steps that are specific to each server and app. [`ChannelTransport`] is the supported transport: feed it with the
+//! URLs of the calendars that have changed, from the push client of your app (see [`channel_transport`]). \
+//! Then run [`Provider::sync_on_push`], that will only sync the calendars that have changed.
+//! Apps that already have an async stream of notifications can implement [`PushTransport`] for it instead.
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::traits::{CalDavSource, CompleteCalendar, DavCalendar};
+use super::Provider;
+
+/// Something that tells when a remote calendar has changed
+#[async_trait]
+pub trait PushTransport {
+    /// Wait for the next change notification, and return the URL of the calendar that has changed.
+    /// Returns `None` once the transport is closed.
+    async fn next_change(&mut self) -> Option<Url>;
+}
+
+/// A [`PushTransport`] that is fed by the [`ChannelNotifier`] created alongside it (see [`channel_transport`])
+#[derive(Debug)]
+pub struct ChannelTransport {
+    receiver: mpsc::UnboundedReceiver<Url>,
+}
+
+/// The sending half of a [`ChannelTransport`]. It is closed once every clone of it is dropped.
+pub type ChannelNotifier = mpsc::UnboundedSender<Url>;
+
+/// Create a [`ChannelTransport`], and a way to notify it whenever your own push client receives a message
+pub fn channel_transport() -> (ChannelNotifier, ChannelTransport) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (sender, ChannelTransport { receiver })
+}
+
+#[async_trait]
+impl PushTransport for ChannelTransport {
+    async fn next_change(&mut self) -> Option<Url> {
+        self.receiver.recv().await
+    }
+}
+
+
+impl<L, T, R, U> Provider<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    /// Sync every calendar that is announced by the push transport, until the transport is closed.
+    pub async fn sync_on_push<P: PushTransport + Send>(&mut self, transport: &mut P) {
+        while let Some(cal_url) = transport.next_change().await {
+            log::info!("Calendar {} has changed on the server", cal_url);
+            if !self.sync_calendar(&cal_url).await {
+                log::warn!("Sync of calendar {} did not complete. It will be retried at the next change or the next full sync", cal_url);
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Utilities to periodically run syncs in the background
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::traits::{CalDavSource, CompleteCalendar, DavCalendar};
+use super::Provider;
+
+/// Settings of a [`SyncScheduler`]
+#[derive(Clone, Debug)]
+pub struct SchedulerSettings {
+    /// The delay between two regular syncs
+    pub interval: Duration,
+    /// A random delay (between zero and this value) is added to every interval, so that many clients do not hit the server at the same time
+    pub jitter: Duration,
+    /// After this many consecutive failed syncs, the scheduler pauses for `pause_duration` instead of `interval` (a value of 0 disables this)
+    pub max_consecutive_failures: u32,
+    /// How long the scheduler pauses after too many consecutive failures
+    pub pause_duration: Duration,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15 * 60),
+            jitter: Duration::from_secs(30),
+            max_consecutive_failures: 5,
+            pause_duration: Duration::from_secs(2 * 60 * 60),
+        }
+    }
+}
+
+enum SchedulerCommand {
+    Trigger,
+    Stop,
+}
+
+/// A handle to control a running [`SyncScheduler`]
+#[derive(Clone, Debug)]
+pub struct SchedulerHandle {
+    sender: mpsc::UnboundedSender<SchedulerCommand>,
+}
+
+impl SchedulerHandle {
+    /// Ask for a sync to happen as soon as possible, even if the scheduler is paused because of previous failures.
+    /// Returns `false` in case the scheduler is not running anymore
+    pub fn trigger(&self) -> bool {
+        self.sender.send(SchedulerCommand::Trigger).is_ok()
+    }
+
+    /// Make [`SyncScheduler::run`] return (after the sync that may currently be in progress)
+    pub fn stop(&self) {
+        let _ = self.sender.send(SchedulerCommand::Stop);
+    }
+}
+
+
+/// Owns a [`Provider`] and periodically syncs it.
+///
+/// Call [`SyncScheduler::run`] (usually in a dedicated task), and control it from elsewhere with a [`SchedulerHandle`]. \
+/// Once `run` has returned, the provider can be accessed again (see [`SyncScheduler::provider_mut`] or [`SyncScheduler::into_provider`])
+pub struct SyncScheduler<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    provider: Provider<L, T, R, U>,
+    settings: SchedulerSettings,
+    consecutive_failures: u32,
+
+    sender: mpsc::UnboundedSender<SchedulerCommand>,
+    receiver: mpsc::UnboundedReceiver<SchedulerCommand>,
+}
+
+impl<L, T, R, U> SyncScheduler<L, T, R, U>
+where
+    L: CalDavSource<T>,
+    T: CompleteCalendar + Sync + Send,
+    R: CalDavSource<U>,
+    U: DavCalendar + Sync + Send,
+{
+    pub fn new(provider: Provider<L, T, R, U>, settings: SchedulerSettings) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self { provider, settings, consecutive_failures: 0, sender, receiver }
+    }
+
+    /// Returns a handle that can trigger or stop syncs
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle { sender: self.sender.clone() }
+    }
+
+    pub fn provider(&self) -> &Provider<L, T, R, U> { &self.provider }
+    pub fn provider_mut(&mut self) -> &mut Provider<L, T, R, U> { &mut self.provider }
+    pub fn into_provider(self) -> Provider<L, T, R, U> { self.provider }
+
+    /// Returns whether the scheduler has paused regular syncs, because too many of them failed in a row
+    pub fn is_paused(&self) -> bool {
+        is_paused(&self.settings, self.consecutive_failures)
+    }
+
+    /// Sync periodically, until [`SchedulerHandle::stop`] is called
+    pub async fn run(&mut self) {
+        loop {
+            let delay = delay_before_next_sync(&self.settings, self.consecutive_failures, random_fraction());
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    self.sync_once().await;
+                },
+                command = self.receiver.recv() => {
+                    match command {
+                        Some(SchedulerCommand::Trigger) => { self.sync_once().await; },
+                        Some(SchedulerCommand::Stop) | None => return,
+                    }
+                },
+            }
+        }
+    }
+
+    async fn sync_once(&mut self) {
+        if self.provider.sync().await {
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures += 1;
+            if self.is_paused() {
+                log::warn!("{} syncs failed in a row. Pausing automatic syncs for {:?}", self.consecutive_failures, self.settings.pause_duration);
+            }
+        }
+    }
+}
+
+fn is_paused(settings: &SchedulerSettings, consecutive_failures: u32) -> bool {
+    settings.max_consecutive_failures != 0 && consecutive_failures >= settings.max_consecutive_failures
+}
+
+/// Compute the delay before the next sync. `random_fraction` must be within [0, 1]
+fn delay_before_next_sync(settings: &SchedulerSettings, consecutive_failures: u32, random_fraction: f64) -> Duration {
+    let base = match is_paused(settings, consecutive_failures) {
+        true => settings.pause_duration,
+        false => settings.interval,
+    };
+    base + settings.jitter.mul_f64(random_fraction)
+}
+
+/// A random number within [0, 1]
+fn random_fraction() -> f64 {
+    let random = uuid::Uuid::new_v4().as_u128() as u32;
+    random as f64 / u32::MAX as f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_before_next_sync() {
+        let settings = SchedulerSettings {
+            interval: Duration::from_secs(60),
+            jitter: Duration::from_secs(10),
+            max_consecutive_failures: 3,
+            pause_duration: Duration::from_secs(3600),
+        };
+
+        assert_eq!(delay_before_next_sync(&settings, 0, 0.0), Duration::from_secs(60));
+        assert_eq!(delay_before_next_sync(&settings, 2, 1.0), Duration::from_secs(70));
+        assert_eq!(delay_before_next_sync(&settings, 3, 0.5), Duration::from_secs(3605));
+
+        let never_pause = SchedulerSettings { max_consecutive_failures: 0, ..settings };
+        assert_eq!(delay_before_next_sync(&never_pause, 100, 0.0), Duration::from_secs(60));
+    }
+
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        use std::path::PathBuf;
+        use std::sync::{Arc, Mutex};
+        use crate::cache::Cache;
+        use crate::calendar::SupportedComponents;
+        use crate::calendar::cached_calendar::CachedCalendar;
+        use crate::mock_behaviour::MockBehaviour;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+        let local = Cache::new(&PathBuf::from("test_cache/scheduler/local"));
+        let mut remote = Cache::new(&PathBuf::from("test_cache/scheduler/remote"));
+        // The first two syncs fail, and successful syncs are counted by the number of listings of the remote calendar
+        let mock = Arc::new(Mutex::new(MockBehaviour {
+            get_calendars_behaviour: (0, 2),
+            get_item_version_tags_behaviour: (100, 0),
+            ..MockBehaviour::new()
+        }));
+        remote.set_mock_behaviour(Some(mock.clone()));
+        remote.create_calendar("https://caldav.com/tasks".parse().unwrap(), "Tasks".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let failed_syncs = || 2 - mock.lock().unwrap().get_calendars_behaviour.1;
+        let successful_syncs = || 100 - mock.lock().unwrap().get_item_version_tags_behaviour.0;
+
+        let provider: Provider<Cache, CachedCalendar, Cache, CachedCalendar> = Provider::new(remote, local);
+        let settings = SchedulerSettings {
+            interval: Duration::from_secs(60),
+            jitter: Duration::from_secs(0),
+            max_consecutive_failures: 2,
+            pause_duration: Duration::from_secs(3600),
+        };
+        let mut scheduler = SyncScheduler::new(provider, settings);
+        let handle = scheduler.handle();
+        let driver = async {
+            // Syncs happen at every interval...
+            tokio::time::sleep(Duration::from_secs(59)).await;
+            assert_eq!(failed_syncs(), 0);
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            assert_eq!(failed_syncs(), 1);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            assert_eq!(failed_syncs(), 2);
+
+            // ...until too many of them fail in a row, and the scheduler backs off
+            tokio::time::sleep(Duration::from_secs(3500)).await;
+            assert_eq!(successful_syncs(), 0);
+            tokio::time::sleep(Duration::from_secs(100)).await;
+            assert_eq!(successful_syncs(), 1);
+
+            // A successful sync resets the interval
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            assert_eq!(successful_syncs(), 2);
+
+            // Syncs can also be triggered at any time
+            handle.trigger();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(successful_syncs(), 3);
+            handle.stop();
+        };
+        tokio::join!(scheduler.run(), driver);
+
+        assert!(!scheduler.is_paused());
+        let metrics = scheduler.provider().metrics();
+        assert_eq!((metrics.syncs, metrics.failed_syncs), (5, 2));
+    }
+}
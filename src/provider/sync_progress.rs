@@ -1,6 +1,12 @@
 //! Utilities to track the progression of a sync
 
+use std::collections::VecDeque;
 use std::fmt::{Display, Error, Formatter};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use url::Url;
 
 /// An event that happens during a sync
 #[derive(Clone, Debug)]
@@ -47,21 +53,208 @@ pub fn feedback_channel() -> (FeedbackSender, FeedbackReceiver) {
     tokio::sync::watch::channel(SyncEvent::default())
 }
 
+/// The events of a sync that have not been consumed yet, see [`Provider::sync_stream`](crate::provider::Provider::sync_stream)
+pub(crate) type EventQueue = Arc<Mutex<VecDeque<SyncEvent>>>;
+
+
+
+
+/// A handle to cancel a sync that is running (e.g. because the user quits the app), see [`Provider::sync_with_cancellation`](crate::provider::Provider::sync_with_cancellation)
+///
+/// Clones of a token share the same state, so that one of them can be given to the sync, and another one be kept to cancel it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the sync to stop. It will stop before handling the next item, so that no item is left half-synced
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+
+
+/// An item that could not be synced
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemSyncError {
+    pub calendar: Url,
+    pub item: Url,
+    pub error: String,
+}
+
+/// A summary of what happened during a sync, e.g. to display "synced 42 items, 1 error" to the user
+#[derive(Clone, Debug, Default)]
+pub struct SyncReport {
+    pub(crate) success: bool,
+    pub(crate) cancelled: bool,
+    pub(crate) quota_exceeded: bool,
+    pub(crate) uploaded: usize,
+    pub(crate) downloaded: usize,
+    pub(crate) deleted_locally: usize,
+    pub(crate) deleted_remotely: usize,
+    pub(crate) conflicts: usize,
+    pub(crate) duplicates_removed: usize,
+    pub(crate) n_errors: usize,
+    item_errors: Vec<ItemSyncError>,
+}
+
+impl SyncReport {
+    /// Whether the sync has completed without any error
+    pub fn is_success(&self) -> bool { self.success }
+    /// Whether the sync has been cancelled (or has reached its timeout) before it completed. Items that have been synced before it was cancelled are counted anyway
+    pub fn is_cancelled(&self) -> bool { self.cancelled }
+    /// Whether some items could not be uploaded because the storage quota on the server is exceeded (see [`QuotaExceeded`](crate::client::QuotaExceeded))
+    pub fn is_quota_exceeded(&self) -> bool { self.quota_exceeded }
+    /// The number of items that have been added or updated on the remote source
+    pub fn uploaded(&self) -> usize { self.uploaded }
+    /// The number of items that have been added or updated in the local source
+    pub fn downloaded(&self) -> usize { self.downloaded }
+    /// The number of local items that have been deleted because they had been deleted from the remote source
+    pub fn deleted_locally(&self) -> usize { self.deleted_locally }
+    /// The number of remote items that have been deleted because they had been locally deleted
+    pub fn deleted_remotely(&self) -> usize { self.deleted_remotely }
+    /// The number of items that have been modified on both sources, and whose conflict has been resolved
+    pub fn conflicts(&self) -> usize { self.conflicts }
+    /// The number of items that have been deleted because another item has the same UID (see [`UidDedup`](crate::provider::UidDedup))
+    pub fn duplicates_removed(&self) -> usize { self.duplicates_removed }
+    /// The number of errors. This includes [`Self::item_errors`], but also errors that are not related to a single item (e.g. network errors)
+    pub fn n_errors(&self) -> usize { self.n_errors }
+
+    /// The items that could not be synced (e.g. because they could not be parsed). The other items have been synced anyway
+    pub fn item_errors(&self) -> &[ItemSyncError] {
+        &self.item_errors
+    }
+
+    /// The number of items that have been synced
+    pub fn synced(&self) -> usize {
+        self.uploaded + self.downloaded + self.deleted_locally + self.deleted_remotely
+    }
+}
+
+impl Display for SyncReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "synced {} item{}", self.synced(), if self.synced() == 1 { "" } else { "s" })?;
+        if self.conflicts > 0 {
+            write!(f, ", {} conflict{} resolved", self.conflicts, if self.conflicts == 1 { "" } else { "s" })?;
+        }
+        if self.duplicates_removed > 0 {
+            write!(f, ", {} duplicate{} removed", self.duplicates_removed, if self.duplicates_removed == 1 { "" } else { "s" })?;
+        }
+        if self.n_errors > 0 {
+            write!(f, ", {} error{}", self.n_errors, if self.n_errors == 1 { "" } else { "s" })?;
+        }
+        if self.quota_exceeded {
+            write!(f, " (quota exceeded)")?;
+        }
+        if self.cancelled {
+            write!(f, " (cancelled)")?;
+        }
+        Ok(())
+    }
+}
 
 
 
 /// A structure that tracks the progression and the errors that happen during a sync
 pub struct SyncProgress {
     n_errors: u32,
-    feedback_channel: Option<FeedbackSender>,
+    feedback_channel: Option<Arc<FeedbackSender>>,
+    event_queue: Option<EventQueue>,
     counter: usize,
+    report: SyncReport,
+    cancellation: Option<CancellationToken>,
+    deadline: Option<Instant>,
 }
 impl SyncProgress {
     pub fn new() -> Self {
-        Self { n_errors: 0, feedback_channel: None, counter: 0 }
+        Self { n_errors: 0, feedback_channel: None, event_queue: None, counter: 0, report: SyncReport::default(), cancellation: None, deadline: None }
     }
     pub fn new_with_feedback_channel(channel: FeedbackSender) -> Self {
-        Self { n_errors: 0, feedback_channel: Some(channel), counter: 0 }
+        Self { n_errors: 0, feedback_channel: Some(Arc::new(channel)), event_queue: None, counter: 0, report: SyncReport::default(), cancellation: None, deadline: None }
+    }
+    /// Make this sync stop when the token is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Queue every event of this sync into `queue`, so that none of them is missed (unlike with a feedback channel, that only keeps the latest one)
+    pub(crate) fn with_event_queue(mut self, queue: EventQueue) -> Self {
+        self.event_queue = Some(queue);
+        self
+    }
+
+    /// A progress for a part of this sync that runs concurrently with other parts (e.g. the sync of a single calendar).
+    ///
+    /// It sends its events to the same listeners, and stops when this sync is cancelled. Its errors and counters are added to this sync by [`Self::merge`]
+    pub(crate) fn child(&self) -> Self {
+        Self {
+            n_errors: 0,
+            feedback_channel: self.feedback_channel.clone(),
+            event_queue: self.event_queue.clone(),
+            counter: 0,
+            report: SyncReport::default(),
+            cancellation: self.cancellation.clone(),
+            deadline: self.deadline,
+        }
+    }
+
+    /// Add the errors and the counters of a [child](Self::child) progress to this one
+    pub(crate) fn merge(&mut self, child: SyncProgress) {
+        self.n_errors += child.n_errors;
+        let report = &mut self.report;
+        report.cancelled |= child.report.cancelled;
+        report.quota_exceeded |= child.report.quota_exceeded;
+        report.uploaded += child.report.uploaded;
+        report.downloaded += child.report.downloaded;
+        report.deleted_locally += child.report.deleted_locally;
+        report.deleted_remotely += child.report.deleted_remotely;
+        report.conflicts += child.report.conflicts;
+        report.duplicates_removed += child.report.duplicates_removed;
+        report.item_errors.extend(child.report.item_errors);
+    }
+
+    /// Make this sync stop once `deadline` has passed
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        let timed_out = self.deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false);
+        timed_out || self.cancellation.as_ref().map(|token| token.is_cancelled()).unwrap_or(false)
+    }
+    /// Returns an error if the sync has been cancelled, so that it can be stopped with `?`
+    pub fn check_cancelled(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.is_cancelled() {
+            false => Ok(()),
+            true => {
+                self.report.cancelled = true;
+                Err("the sync has been cancelled".into())
+            },
+        }
+    }
+
+    /// The summary of the sync so far
+    pub fn report(&self) -> SyncReport {
+        SyncReport {
+            success: self.is_success(),
+            n_errors: self.n_errors as usize,
+            ..self.report.clone()
+        }
+    }
+    /// Update the counters of the report
+    pub(crate) fn report_mut(&mut self) -> &mut SyncReport {
+        &mut self.report
     }
 
     /// Reset the user-info counter
@@ -82,7 +275,7 @@ impl SyncProgress {
 
 
     pub fn is_success(&self) -> bool {
-        self.n_errors == 0
+        self.n_errors == 0 && !self.report.cancelled
     }
 
     /// Log an error
@@ -95,6 +288,11 @@ impl SyncProgress {
         log::warn!("{}", text);
         self.n_errors += 1;
     }
+    /// Log an error about a single item, and add it to the report
+    pub fn item_error(&mut self, calendar: &Url, item: &Url, error: &str) {
+        self.warn(&format!("Unable to sync item {}: {}", crate::utils::redact(item), error));
+        self.report.item_errors.push(ItemSyncError{ calendar: calendar.clone(), item: item.clone(), error: error.to_string() });
+    }
     /// Log an info
     pub fn info(&mut self, text: &str) {
         log::info!("{}", text);
@@ -107,8 +305,19 @@ impl SyncProgress {
     pub fn trace(&mut self, text: &str) {
         log::trace!("{}", text);
     }
+    /// Send an event as a feedback to the listener (if any), and give control back to the consumer of the events in case they are streamed,
+    /// so that the sync goes on only once this event has been consumed
+    pub(crate) async fn step(&mut self, event: SyncEvent) {
+        self.feedback(event);
+        if self.event_queue.is_some() {
+            tokio::task::yield_now().await;
+        }
+    }
     /// Send an event as a feedback to the listener (if any).
     pub fn feedback(&mut self, event: SyncEvent) {
+        if let Some(queue) = &self.event_queue {
+            queue.lock().unwrap().push_back(event.clone());
+        }
         self.feedback_channel
             .as_ref()
             .map(|sender| {
@@ -0,0 +1,159 @@
+//! A client that replays recorded traces instead of reaching a server
+//!
+//! A [`ReplayClient`] answers the requests of a sync with the replies of a trace that has been recorded against a real server
+//! (see [`crate::trace`]). This makes it possible to test how this crate behaves with the quirks of a given server (Nextcloud, Radicale, Baïkal...)
+//! without a live server, e.g. in CI.
+//!
+//! Each request is answered by the first reply of the trace to a request with the same method, path and body that has not been replayed yet
+//! (or, in case the body differs, e.g. because it contains a newly generated UID, with the same method and path only).
+//! Once every matching reply has been replayed, the last one is replayed again. Requests that have no matching reply get a `404 Not Found`.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use csscolorparser::Color;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{ResponseBuilderExt, StatusCode};
+use tokio::sync::RwLock;
+use url::Url;
+
+use crate::calendar::SupportedComponents;
+use crate::calendar::remote_calendar::RemoteCalendar;
+use crate::capabilities::ServerCapabilities;
+use crate::client::Client;
+use crate::metrics::RequestStats;
+use crate::resource::redact_url;
+use crate::trace::{SyncTrace, TraceEntry};
+use crate::traits::CalDavSource;
+use crate::utils::redact;
+
+/// The replies of a trace, and which ones have been replayed already
+#[derive(Debug)]
+pub(crate) struct TraceReplay {
+    entries: Vec<TraceEntry>,
+    replayed: Mutex<Vec<bool>>,
+}
+
+impl TraceReplay {
+    pub(crate) fn new(entries: Vec<TraceEntry>) -> Self {
+        // Requests that failed have no reply to replay
+        let entries: Vec<TraceEntry> = entries.into_iter().filter(|entry| entry.status.is_some()).collect();
+        let replayed = Mutex::new(vec![false; entries.len()]);
+        Self { entries, replayed }
+    }
+
+    /// Find the reply to a request, and build a response from it
+    pub(crate) fn reply(&self, request: &reqwest::Request) -> reqwest::Response {
+        let body = request.body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+        let same_target = |entry: &TraceEntry| {
+            entry.method == request.method().as_str()
+                && Url::parse(&entry.url).map(|url| url.path() == request.url().path() && url.query() == request.url().query()).unwrap_or(false)
+        };
+
+        let mut replayed = self.replayed.lock().unwrap();
+        let candidates: Vec<usize> = (0..self.entries.len()).filter(|&i| same_target(&self.entries[i])).collect();
+        let index = candidates.iter().copied().find(|&i| !replayed[i] && self.entries[i].request_body == body)
+            .or_else(|| candidates.iter().copied().find(|&i| !replayed[i]))
+            .or_else(|| candidates.last().copied());
+
+        match index {
+            None => {
+                log::warn!("No reply to {} {} in the trace", request.method(), redact(redact_url(request.url())));
+                build_response(request.url(), StatusCode::NOT_FOUND.as_u16(), &[], String::new())
+            },
+            Some(i) => {
+                replayed[i] = true;
+                let entry = &self.entries[i];
+                build_response(request.url(), entry.status.unwrap_or_default(), &entry.response_headers, entry.response_body.clone().unwrap_or_default())
+            },
+        }
+    }
+}
+
+fn build_response(url: &Url, status: u16, headers: &[(String, String)], body: String) -> reqwest::Response {
+    let mut builder = http::Response::builder()
+        .status(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .url(url.clone());
+    for (name, value) in headers {
+        // Recorded headers have already been parsed once, but they may have been edited by hand
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => builder = builder.header(name, value),
+            _ => log::warn!("Ignoring invalid header {:?} in the trace", name),
+        }
+    }
+    reqwest::Response::from(builder.body(body).unwrap(/* the status and headers are valid */))
+}
+
+
+/// A [`Client`] whose requests are answered by a recorded trace (see the [module documentation](self)).
+///
+/// It implements the same [`CalDavSource`] as a regular client, so that it can be used as the remote source of a [`Provider`](crate::provider::Provider).
+/// Every other function of [`Client`] is available as well.
+#[derive(Debug)]
+pub struct ReplayClient {
+    client: Client,
+}
+
+impl ReplayClient {
+    /// Create a client that replays a trace recorded with [`Provider::sync_with_trace`](crate::provider::Provider::sync_with_trace).
+    ///
+    /// `url` is the URL the traced client was created with
+    pub fn from_trace_file<S: AsRef<str>, P: AsRef<Path>>(url: S, trace_path: P) -> Result<Self, Box<dyn Error>> {
+        Self::new(url, crate::trace::read_trace(trace_path)?)
+    }
+
+    /// Create a client that replays the given entries of a trace
+    pub fn new<S: AsRef<str>>(url: S, entries: Vec<TraceEntry>) -> Result<Self, Box<dyn Error>> {
+        let client = Client::new(url, "", "")?.with_replay(Arc::new(TraceReplay::new(entries)));
+        Ok(Self { client })
+    }
+}
+
+impl Deref for ReplayClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl CalDavSource<RemoteCalendar> for ReplayClient {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<RemoteCalendar>>>, Box<dyn Error>> {
+        self.client.get_calendars().await
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<RemoteCalendar>>> {
+        self.client.get_calendar(url).await
+    }
+
+    async fn get_calendar_ids(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+        self.client.get_calendar_ids().await
+    }
+
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<RemoteCalendar>>> {
+        self.client.try_get_calendar(url)
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<RemoteCalendar>>, Box<dyn Error>> {
+        self.client.create_calendar(url, name, supported_components, color).await
+    }
+
+    fn capabilities(&self) -> Option<ServerCapabilities> {
+        self.client.capabilities()
+    }
+
+    fn request_stats(&self) -> RequestStats {
+        self.client.request_stats()
+    }
+
+    fn set_trace(&self, trace: Option<Arc<SyncTrace>>) {
+        self.client.set_trace(trace)
+    }
+}
@@ -0,0 +1,223 @@
+//! A simple full-text search index over items
+//!
+//! Every [`CachedCalendar`](crate::calendar::cached_calendar::CachedCalendar) maintains such an index whenever its items are added, updated or deleted. \
+//! It is usually queried through [`Cache::search`](crate::cache::Cache::search).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use url::Url;
+
+use crate::item::{Item, SyncStatus};
+
+/// How much a match in the name (`SUMMARY`) of an item is worth
+const NAME_WEIGHT: u32 = 3;
+/// How much a match in the categories of an item is worth
+const CATEGORY_WEIGHT: u32 = 2;
+/// How much a match in the description of an item is worth
+const DESCRIPTION_WEIGHT: u32 = 1;
+
+/// An inverted index of the words contained in items
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    /// For every word, the items that contain it, with their weight
+    postings: BTreeMap<String, HashMap<Url, u32>>,
+    /// For every item, the words it has been indexed with
+    words_by_item: HashMap<Url, Vec<String>>,
+    /// Items that may have been modified since they were indexed (e.g. using a mutable reference)
+    dirty: HashSet<Url>,
+    /// Whether items whose `CLASS` is `CONFIDENTIAL` are left out of the index
+    exclude_confidential: bool,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an empty index that will never contain confidential items (see [`crate::item::Classification`])
+    pub fn excluding_confidential() -> Self {
+        Self { exclude_confidential: true, ..Self::default() }
+    }
+
+    /// Whether an item must be kept out of this index
+    fn is_excluded(&self, item: &Item) -> bool {
+        if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+            return true;
+        }
+        self.exclude_confidential && item.classification().is_confidential()
+    }
+
+    /// Index (or re-index) an item. Items that are marked for deletion (or that are confidential, if this index excludes them) are removed from the index
+    pub fn index_item(&mut self, item: &Item) {
+        let url = item.url().clone();
+        self.remove_item(&url);
+        if self.is_excluded(item) {
+            return;
+        }
+
+        let words = item_words(item);
+        for (word, weight) in &words {
+            self.postings.entry(word.clone()).or_default().insert(url.clone(), *weight);
+        }
+        self.words_by_item.insert(url, words.into_keys().collect());
+    }
+
+    /// Remove an item from the index
+    pub fn remove_item(&mut self, url: &Url) {
+        self.dirty.remove(url);
+        if let Some(words) = self.words_by_item.remove(url) {
+            for word in words {
+                if let Some(items) = self.postings.get_mut(&word) {
+                    items.remove(url);
+                    if items.is_empty() {
+                        self.postings.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tell that an item may have changed without the index knowing it.
+    /// Until it is re-indexed, this item will be searched by scanning its content
+    pub fn mark_dirty(&mut self, url: &Url) {
+        self.dirty.insert(url.clone());
+    }
+
+    /// Returns the items that contain every word of the query (or words starting with them), with their score.
+    ///
+    /// `get_item` returns the items the index has been built from. It is only used for items that have been marked dirty.
+    pub fn search<'a, F>(&self, query: &str, get_item: F) -> HashMap<Url, u32>
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        let query_words = tokenize(query);
+        if query_words.is_empty() {
+            return HashMap::new();
+        }
+
+        // Indexed items
+        let mut scores: Option<HashMap<Url, u32>> = None;
+        for query_word in &query_words {
+            let mut word_scores: HashMap<Url, u32> = HashMap::new();
+            let matching_words = self.postings.range(query_word.clone()..)
+                .take_while(|(word, _)| word.starts_with(query_word.as_str()));
+            for (_word, matching_items) in matching_words {
+                for (url, weight) in matching_items {
+                    let score = word_scores.entry(url.clone()).or_insert(0);
+                    *score = (*score).max(*weight);
+                }
+            }
+
+            scores = Some(match scores {
+                None => word_scores,
+                Some(previous) => previous.into_iter()
+                    .filter_map(|(url, score)| word_scores.get(&url).map(|s| (url, score + s)))
+                    .collect(),
+            });
+        }
+        let mut scores = scores.unwrap_or_default();
+
+        // Dirty items
+        for url in &self.dirty {
+            scores.remove(url);
+            let item = match get_item(url) {
+                None => continue,
+                Some(item) => item,
+            };
+            if self.is_excluded(item) {
+                continue;
+            }
+            if let Some(score) = score_item(item, &query_words) {
+                scores.insert(url.clone(), score);
+            }
+        }
+
+        scores
+    }
+}
+
+/// Split a text into lowercase words
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Every word an item contains, with the weight of the most valuable field it appears in
+fn item_words(item: &Item) -> HashMap<String, u32> {
+    let mut words = HashMap::new();
+    let mut add = |text: &str, weight: u32| {
+        for word in tokenize(text) {
+            let w = words.entry(word).or_insert(0);
+            *w = weight.max(*w);
+        }
+    };
+
+    add(item.name(), NAME_WEIGHT);
+    if let Item::Task(task) = item {
+        for category in task.categories() {
+            add(category, CATEGORY_WEIGHT);
+        }
+        if let Some(description) = task.extra_parameter("DESCRIPTION") {
+            add(description, DESCRIPTION_WEIGHT);
+        }
+    }
+    words
+}
+
+/// The score of a single item, or `None` in case it does not match every word of the query
+fn score_item(item: &Item, query_words: &[String]) -> Option<u32> {
+    let words = item_words(item);
+    let mut total = 0;
+    for query_word in query_words {
+        let best = words.iter()
+            .filter(|(word, _)| word.starts_with(query_word.as_str()))
+            .map(|(_, weight)| *weight)
+            .max()?;
+        total += best;
+    }
+    Some(total)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::task_with;
+
+    #[test]
+    fn test_search_index() {
+        let groceries = task_with("Buy groceries", false, &[("CATEGORIES", "Home")]);
+        let list = task_with("Write a list", false, &[("DESCRIPTION", "For the groceries at home")]);
+        let mut items = HashMap::new();
+        let mut index = SearchIndex::new();
+        for item in [groceries, list] {
+            index.index_item(&item);
+            items.insert(item.url().clone(), item);
+        }
+        let groceries_url: Url = "https://some.calend.ar/cal/Buy-groceries.ics".parse().unwrap();
+        let list_url: Url = "https://some.calend.ar/cal/Write-a-list.ics".parse().unwrap();
+
+        let results = index.search("Groceries", |url| items.get(url));
+        assert_eq!(results.get(&groceries_url), Some(&NAME_WEIGHT));
+        assert_eq!(results.get(&list_url), Some(&DESCRIPTION_WEIGHT));
+
+        let results = index.search("groc hom", |url| items.get(url));
+        assert_eq!(results.get(&groceries_url), Some(&(NAME_WEIGHT + CATEGORY_WEIGHT)));
+        assert_eq!(results.get(&list_url), Some(&(DESCRIPTION_WEIGHT + DESCRIPTION_WEIGHT)));
+
+        assert!(!index.search("list groceries", |url| items.get(url)).contains_key(&groceries_url));
+        assert!(index.search("", |url| items.get(url)).is_empty());
+
+        // Items that are modified behind the index's back are still found
+        items.get_mut(&groceries_url).unwrap().unwrap_task_mut().set_name("Buy vegetables".to_string());
+        index.mark_dirty(&groceries_url);
+        assert!(index.search("vegetables", |url| items.get(url)).contains_key(&groceries_url));
+        assert!(!index.search("groceries", |url| items.get(url)).contains_key(&groceries_url));
+
+        index.remove_item(&list_url);
+        assert!(index.search("groceries", |url| items.get(url)).is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+//! Calendar sharing, using the `calendarserver-sharing` extension (supported by e.g. Nextcloud and Apple Calendar Server)
+//!
+//! The owner of a calendar can share it with [`Client::share_calendar`](crate::client::Client::share_calendar).
+//! The users it is shared with receive an [`Invitation`] (see [`Client::invitations`](crate::client::Client::invitations)),
+//! that they can accept or decline with [`Client::reply_to_invitation`](crate::client::Client::reply_to_invitation). \
+//! Who shared a calendar, and who it is shared with, is available in [`RemoteCalendar::sharing`](crate::calendar::remote_calendar::RemoteCalendar::sharing).
+
+use std::error::Error;
+
+use minidom::Element;
+use url::Url;
+
+use crate::utils::find_elem;
+
+/// The rights given to a user a calendar is shared with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShareAccess {
+    Read,
+    ReadWrite,
+}
+
+/// The answer of a user to an invitation to a shared calendar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteStatus {
+    NoResponse,
+    Accepted,
+    Declined,
+    /// The server does not know this user
+    Invalid,
+}
+
+/// The user that shares a calendar
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sharer {
+    /// Usually a `mailto:` URI, or the URL of a principal
+    pub href: String,
+    pub common_name: Option<String>,
+}
+
+/// A user a calendar is shared with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sharee {
+    /// Usually a `mailto:` URI, or the URL of a principal
+    pub href: String,
+    pub common_name: Option<String>,
+    pub access: ShareAccess,
+    pub status: InviteStatus,
+}
+
+/// Who shared a calendar, and who it is shared with
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CalendarSharing {
+    /// The owner of the calendar, if it has been shared with the current user
+    pub shared_by: Option<Sharer>,
+    /// The users this calendar is shared with
+    pub shared_with: Vec<Sharee>,
+}
+
+impl CalendarSharing {
+    pub fn is_shared(&self) -> bool {
+        self.shared_by.is_some() || !self.shared_with.is_empty()
+    }
+}
+
+/// An invitation to a calendar that has been shared with the current user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invitation {
+    /// The identifier of this invitation
+    pub uid: String,
+    pub sharer: Sharer,
+    /// The URL of the shared calendar
+    pub calendar_url: Url,
+    /// A message from the sharer
+    pub summary: Option<String>,
+    pub access: ShareAccess,
+    pub status: InviteStatus,
+}
+
+
+/// Parse the `invite` property of a calendar, from the reply of a `PROPFIND` request
+pub(crate) fn parse_sharing(rep: &Element) -> CalendarSharing {
+    let invite = match find_elem(rep, "invite") {
+        None => return CalendarSharing::default(),
+        Some(invite) => invite,
+    };
+
+    let shared_by = invite.children()
+        .find(|child| child.name() == "organizer")
+        .and_then(parse_sharer);
+    let shared_with = invite.children()
+        .filter(|child| child.name() == "user")
+        .filter_map(|user| {
+            Some(Sharee {
+                href: find_elem(user, "href")?.text(),
+                common_name: common_name(user),
+                access: parse_access(user),
+                status: parse_status(user),
+            })
+        })
+        .collect();
+
+    CalendarSharing { shared_by, shared_with }
+}
+
+/// Parse an `invite-notification`, from the content of a notification. Returns `None` for other kinds of notifications
+pub(crate) fn parse_invitation(text: &str, base_url: &Url) -> Result<Option<Invitation>, Box<dyn Error>> {
+    let root: Element = text.parse()?;
+    let notification = match find_elem(&root, "invite-notification") {
+        None => return Ok(None),
+        Some(notification) => notification,
+    };
+
+    let uid = find_elem(notification, "uid").ok_or("Missing UID in invitation")?.text();
+    let sharer = find_elem(notification, "organizer")
+        .and_then(parse_sharer)
+        .ok_or("Missing organizer in invitation")?;
+    let host_href = find_elem(notification, "hosturl")
+        .and_then(|host_url| find_elem(host_url, "href"))
+        .ok_or("Missing host URL in invitation")?
+        .text();
+    let summary = find_elem(notification, "summary").map(|summary| summary.text()).filter(|summary| !summary.is_empty());
+
+    Ok(Some(Invitation {
+        uid,
+        sharer,
+        calendar_url: base_url.join(host_href.trim())?,
+        summary,
+        access: parse_access(notification),
+        status: parse_status(notification),
+    }))
+}
+
+fn parse_sharer(elem: &Element) -> Option<Sharer> {
+    Some(Sharer {
+        href: find_elem(elem, "href")?.text(),
+        common_name: common_name(elem),
+    })
+}
+
+fn common_name(elem: &Element) -> Option<String> {
+    find_elem(elem, "common-name").map(|cn| cn.text()).filter(|cn| !cn.is_empty())
+}
+
+fn parse_access(elem: &Element) -> ShareAccess {
+    match find_elem(elem, "access").map(|access| find_elem(access, "read-write").is_some()) {
+        Some(true) => ShareAccess::ReadWrite,
+        _ => ShareAccess::Read,
+    }
+}
+
+fn parse_status(elem: &Element) -> InviteStatus {
+    if find_elem(elem, "invite-accepted").is_some() {
+        InviteStatus::Accepted
+    } else if find_elem(elem, "invite-declined").is_some() {
+        InviteStatus::Declined
+    } else if find_elem(elem, "invite-invalid").is_some() {
+        InviteStatus::Invalid
+    } else {
+        InviteStatus::NoResponse
+    }
+}
+
+
+/// The body of a request that shares a calendar with a user
+pub(crate) fn share_body(sharee: &str, access: ShareAccess, summary: Option<&str>) -> String {
+    let access = match access {
+        ShareAccess::Read => "<CS:read />",
+        ShareAccess::ReadWrite => "<CS:read-write />",
+    };
+    let summary = summary
+        .map(|summary| format!("<CS:summary>{}</CS:summary>", xml_escape(summary)))
+        .unwrap_or_default();
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+        <CS:share xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+            <CS:set>
+                <D:href>{}</D:href>
+                {}
+                {}
+            </CS:set>
+        </CS:share>
+        "#,
+        xml_escape(sharee),
+        summary,
+        access,
+    )
+}
+
+/// The body of a request that stops sharing a calendar with a user
+pub(crate) fn unshare_body(sharee: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+        <CS:share xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+            <CS:remove>
+                <D:href>{}</D:href>
+            </CS:remove>
+        </CS:share>
+        "#,
+        xml_escape(sharee),
+    )
+}
+
+/// The body of a request that accepts or declines an invitation
+pub(crate) fn invite_reply_body(invitation: &Invitation, accept: bool) -> String {
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+        <CS:invite-reply xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+            <D:href>{}</D:href>
+            {}
+            <CS:hosturl>
+                <D:href>{}</D:href>
+            </CS:hosturl>
+            <CS:in-reply-to>{}</CS:in-reply-to>
+        </CS:invite-reply>
+        "#,
+        xml_escape(&invitation.sharer.href),
+        if accept { "<CS:invite-accepted />" } else { "<CS:invite-declined />" },
+        xml_escape(invitation.calendar_url.path()),
+        xml_escape(&invitation.uid),
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sharing() {
+        let propfind = r#"<D:response xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+            <D:href>/dav/calendars/john/work/</D:href>
+            <D:propstat><D:prop><CS:invite>
+                <CS:user>
+                    <D:href>mailto:jane@example.com</D:href>
+                    <CS:common-name>Jane</CS:common-name>
+                    <CS:invite-accepted/>
+                    <CS:access><CS:read-write/></CS:access>
+                </CS:user>
+                <CS:user>
+                    <D:href>mailto:bob@example.com</D:href>
+                    <CS:invite-noresponse/>
+                    <CS:access><CS:read/></CS:access>
+                </CS:user>
+            </CS:invite></D:prop></D:propstat>
+        </D:response>"#;
+        let sharing = parse_sharing(&propfind.parse().unwrap());
+        assert!(sharing.is_shared());
+        assert_eq!(sharing.shared_by, None);
+        assert_eq!(sharing.shared_with, vec![
+            Sharee{ href: "mailto:jane@example.com".to_string(), common_name: Some("Jane".to_string()), access: ShareAccess::ReadWrite, status: InviteStatus::Accepted },
+            Sharee{ href: "mailto:bob@example.com".to_string(), common_name: None, access: ShareAccess::Read, status: InviteStatus::NoResponse },
+        ]);
+    }
+
+    #[test]
+    fn test_invitation() {
+        let notification = r#"<CS:notification xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+            <CS:dtstamp>20210401T080000Z</CS:dtstamp>
+            <CS:invite-notification shared-type="calendar">
+                <CS:uid>invitation-1</CS:uid>
+                <D:href>mailto:jane@example.com</D:href>
+                <CS:invite-noresponse/>
+                <CS:access><CS:read-write/></CS:access>
+                <CS:hosturl><D:href>/dav/calendars/john/work/</D:href></CS:hosturl>
+                <CS:organizer><D:href>mailto:john@example.com</D:href><CS:common-name>John</CS:common-name></CS:organizer>
+                <CS:summary>Team tasks</CS:summary>
+            </CS:invite-notification>
+        </CS:notification>"#;
+        let base: Url = "https://my.server.com/dav/".parse().unwrap();
+        let invitation = parse_invitation(notification, &base).unwrap().unwrap();
+        assert_eq!(invitation.uid, "invitation-1");
+        assert_eq!(invitation.sharer, Sharer{ href: "mailto:john@example.com".to_string(), common_name: Some("John".to_string()) });
+        assert_eq!(invitation.calendar_url.as_str(), "https://my.server.com/dav/calendars/john/work/");
+        assert_eq!((invitation.access, invitation.status), (ShareAccess::ReadWrite, InviteStatus::NoResponse));
+
+        let reply = invite_reply_body(&invitation, true);
+        assert!(reply.contains("<CS:invite-accepted />"));
+        assert!(reply.contains("<CS:in-reply-to>invitation-1</CS:in-reply-to>"));
+
+        let other: &str = r#"<CS:notification xmlns:CS="http://calendarserver.org/ns/"><CS:resource-changed/></CS:notification>"#;
+        assert_eq!(parse_invitation(other, &base).unwrap(), None);
+        assert!(share_body("mailto:a&b@example.com", ShareAccess::Read, Some("<hi>")).contains("mailto:a&amp;b@example.com"));
+    }
+}
@@ -1,13 +1,23 @@
 //! To-do tasks (iCal `VTODO` item)
 
+use std::error::Error;
+
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use csscolorparser::Color;
 use ical::property::Property;
 use url::Url;
 
-use crate::item::SyncStatus;
+use crate::event_time::EventTime;
+use crate::item::{Classification, SyncStatus};
+use crate::ical::escape::{escape_text, unescape_text};
 use crate::utils::random_url;
+use crate::validation::Violation;
+
+/// The property Apple Reminders and Nextcloud Tasks store the manual order of tasks as
+const SORT_ORDER_PROPERTY: &str = "X-APPLE-SORT-ORDER";
+/// The date `X-APPLE-SORT-ORDER` values are based on (2001-01-01T00:00:00Z), as a Unix timestamp
+const SORT_ORDER_EPOCH: i64 = 978_307_200;
 
 /// RFC5545 defines the completion as several optional fields, yet some combinations make no sense.
 /// This enum provides an API that forbids such impossible combinations.
@@ -29,6 +39,66 @@ impl CompletionStatus {
     }
 }
 
+/// The `STATUS` of a task (RFC 5545 section 3.8.1.11).
+///
+/// Whether a task is completed (and when) is also given by its [`CompletionStatus`], that is consistent with its `TaskStatus`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum TaskStatus {
+    #[default]
+    NeedsAction,
+    InProcess,
+    Completed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Parse the value of a `STATUS` property (e.g. `IN-PROCESS`)
+    pub fn from_ical(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => Some(Self::NeedsAction),
+            "IN-PROCESS" => Some(Self::InProcess),
+            "COMPLETED" => Some(Self::Completed),
+            "CANCELLED" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// The value of the `STATUS` property for this status
+    pub fn as_ical(&self) -> &'static str {
+        match self {
+            Self::NeedsAction => "NEEDS-ACTION",
+            Self::InProcess => "IN-PROCESS",
+            Self::Completed => "COMPLETED",
+            Self::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        *self == Self::Completed
+    }
+}
+
+/// How a task is flagged (or starred), since iCal has no such property and every client has its own convention.
+///
+/// The mapping of [`Task::flagged`] and [`Task::set_flagged`] is set in [`crate::config::FLAG_MAPPING`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlagMapping {
+    /// Tasks that have at least this priority (i.e. a `PRIORITY` from 1 to this value) are flagged.
+    /// Flagging a task gives it the highest priority (1), and unflagging it removes its priority.
+    ///
+    /// This is what Nextcloud Tasks does with `Priority(4)`, which is the default
+    Priority(u8),
+    /// Tasks that have this `X-` property (e.g. `X-MYAPP-FLAGGED`) set to `1` or `TRUE` are flagged.
+    /// Flagging a task sets it to `1`, and unflagging it removes it
+    ExtraProperty(String),
+}
+
+impl Default for FlagMapping {
+    fn default() -> Self {
+        Self::Priority(4)
+    }
+}
+
 /// A to-do task
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Task {
@@ -49,6 +119,9 @@ pub struct Task {
     last_modified: DateTime<Utc>,
     /// The completion status of this task
     completion_status: CompletionStatus,
+    /// The status of this task while it is not completed (see [`Self::status`])
+    #[serde(default)]
+    uncompleted_status: TaskStatus,
 
     /// The display name of the task
     name: String,
@@ -60,6 +133,14 @@ pub struct Task {
     /// Extra parameters that have not been parsed from the iCal file (because they're not supported (yet) by this crate).
     /// They are needed to serialize this item into an equivalent iCal file
     extra_parameters: Vec<Property>,
+
+    /// The iCal text this task has been downloaded as, as long as it has not been modified since (see [`Self::raw_ical`])
+    #[serde(default)]
+    raw_ical: Option<Box<str>>,
+
+    /// The definitions of the time zones (`VTIMEZONE` components, as iCal text) this task has been downloaded with, that its properties may refer to
+    #[serde(default)]
+    time_zones: Vec<String>,
 }
 
 
@@ -69,11 +150,12 @@ impl Task {
     pub fn new(name: String, completed: bool, parent_calendar_url: &Url) -> Self {
         let new_url = random_url(parent_calendar_url);
         let new_sync_status = SyncStatus::NotSynced;
-        let new_uid = Uuid::new_v4().to_hyphenated().to_string();
-        let new_creation_date = Some(Utc::now());
-        let new_last_modified = Utc::now();
+        let new_uid = crate::item::ItemUid::generate();
+        let now = crate::clock::now();
+        let new_creation_date = Some(now);
+        let new_last_modified = now;
         let new_completion_status = if completed {
-                CompletionStatus::Completed(Some(Utc::now()))
+                CompletionStatus::Completed(Some(now))
             } else { CompletionStatus::Uncompleted };
         let ical_prod_id = crate::ical::default_prod_id();
         let extra_parameters = Vec::new();
@@ -92,18 +174,55 @@ impl Task {
             uid,
             name,
             completion_status,
+            uncompleted_status: TaskStatus::NeedsAction,
             sync_status,
             creation_date,
             last_modified,
             ical_prod_id,
             extra_parameters,
+            raw_ical: None,
+            time_zones: Vec::new(),
+        }
+    }
+
+    /// Returns a copy of this task, with a different UID, URL and sync status.
+    /// Its raw iCal text is kept only if it has the same UID, since this is the only identity the iCal text contains
+    pub(crate) fn clone_with_identity(&self, uid: String, url: Url, sync_status: SyncStatus) -> Self {
+        let raw_ical = if uid == self.uid { self.raw_ical.clone() } else { None };
+        Self {
+            uid, url, sync_status, raw_ical,
+            ..self.clone()
         }
     }
 
+    /// Set the iCal text a task has been parsed from
+    pub(crate) fn with_raw_ical(mut self, raw_ical: String) -> Self {
+        self.raw_ical = Some(raw_ical.into_boxed_str());
+        self
+    }
+
+    /// Set the time zone definitions a task has been parsed with
+    pub(crate) fn with_time_zones(mut self, time_zones: Vec<String>) -> Self {
+        self.time_zones = time_zones;
+        self
+    }
+
+    /// The definitions of the time zones (`VTIMEZONE` components) this task has been parsed with, so that they are written along with it
+    pub(crate) fn time_zones(&self) -> &[String] {
+        &self.time_zones
+    }
+
     pub fn url(&self) -> &Url       { &self.url         }
     pub fn uid(&self) -> &str       { &self.uid         }
     pub fn name(&self) -> &str      { &self.name        }
     pub fn completed(&self) -> bool { self.completion_status.is_completed() }
+    /// The `STATUS` of this task, e.g. to display cancelled tasks differently. See also [`Self::completed`]
+    pub fn status(&self) -> TaskStatus {
+        match self.completion_status {
+            CompletionStatus::Completed(_) => TaskStatus::Completed,
+            CompletionStatus::Uncompleted => self.uncompleted_status,
+        }
+    }
     pub fn ical_prod_id(&self) -> &str            { &self.ical_prod_id }
     pub fn sync_status(&self) -> &SyncStatus      { &self.sync_status  }
     pub fn last_modified(&self) -> &DateTime<Utc> { &self.last_modified }
@@ -111,6 +230,153 @@ impl Task {
     pub fn completion_status(&self) -> &CompletionStatus    { &self.completion_status }
     pub fn extra_parameters(&self) -> &[Property]           { &self.extra_parameters }
 
+    /// The exact iCal text this task has been downloaded as, e.g. to show or debug what the server sent.
+    ///
+    /// This is `None` for tasks that have been created locally, and as soon as a task is modified, since its text has to be built again then (see [`crate::ical::build_from`]).
+    pub fn raw_ical(&self) -> Option<&str> {
+        self.raw_ical.as_deref()
+    }
+
+    /// Returns the value of the first extra parameter with the given name (e.g. `DESCRIPTION`)
+    pub fn extra_parameter(&self, name: &str) -> Option<&str> {
+        self.extra_parameters.iter()
+            .find(|prop| prop.name == name)
+            .and_then(|prop| prop.value.as_deref())
+    }
+
+    /// Returns the (unescaped) value of an app-specific `X-` property, see [`Self::set_extra_property`]
+    pub fn extra_property(&self, name: &str) -> Option<String> {
+        self.extra_parameters.iter()
+            .find(|prop| prop.name.eq_ignore_ascii_case(name))
+            .and_then(|prop| prop.value.as_deref())
+            .map(unescape_text)
+    }
+
+    /// The revision number of this task (its `SEQUENCE` property), that is incremented every time it is locally modified after a sync
+    pub fn sequence(&self) -> u32 {
+        self.extra_parameter("SEQUENCE")
+            .and_then(|seq| seq.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// The start date of this task (its `DTSTART` property), if any. Whole days are at midnight UTC, see [`Self::start_time`]
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.extra_parameter("DTSTART")
+            .and_then(|start| crate::ical::parse_date_or_date_time(start).ok())
+    }
+
+    /// The due date of this task (its `DUE` property, or its start date plus its `DURATION`), if any. Whole days are at midnight UTC, see [`Self::due_time`]
+    pub fn due(&self) -> Option<DateTime<Utc>> {
+        match self.extra_parameter("DUE") {
+            Some(due) => crate::ical::parse_date_or_date_time(due).ok(),
+            None => self.start()?.checked_add_signed(self.duration()?),
+        }
+    }
+
+    /// How long this task lasts: its `DURATION` property, or the time between its start and due dates (RFC 5545 allows either of them), if any
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        match self.extra_parameter("DURATION") {
+            Some(duration) => chrono::Duration::from_std(crate::ical::parse_duration(duration)?).ok(),
+            None => {
+                let due = crate::ical::parse_date_or_date_time(self.extra_parameter("DUE")?).ok()?;
+                Some(due - self.start()?)
+            },
+        }
+    }
+
+    /// The start date of this task, telling whole days apart from date-times (see [`crate::event_time`])
+    pub fn start_time(&self) -> Option<EventTime> {
+        self.extra_parameter("DTSTART").and_then(EventTime::parse)
+    }
+
+    /// The due date of this task, telling whole days apart from date-times (see [`crate::event_time`])
+    pub fn due_time(&self) -> Option<EventTime> {
+        match self.extra_parameter("DUE") {
+            Some(due) => EventTime::parse(due),
+            None => self.due().map(EventTime::DateTime),
+        }
+    }
+
+    /// The categories of this task (from all its `CATEGORIES` properties)
+    pub fn categories(&self) -> Vec<&str> {
+        self.extra_parameters.iter()
+            .filter(|prop| prop.name == "CATEGORIES")
+            .filter_map(|prop| prop.value.as_deref())
+            .flat_map(|value| value.split(','))
+            .map(|category| category.trim())
+            .filter(|category| !category.is_empty())
+            .collect()
+    }
+
+    /// The color of this task (its `COLOR` property, or the color properties of Apple or Outlook), if any
+    pub fn color(&self) -> Option<Color> {
+        crate::ical::parse_color(&self.extra_parameters)
+    }
+
+    /// The access classification of this task (its `CLASS` property). Tasks without one are public, and tasks with an unknown one are private
+    pub fn classification(&self) -> Classification {
+        match self.extra_parameter("CLASS") {
+            None => Classification::Public,
+            Some(value) => Classification::from_ical(value).unwrap_or(Classification::Private),
+        }
+    }
+
+    /// The priority of this task (its `PRIORITY` property), from 1 (the highest) to 9 (the lowest).
+    /// This is `None` for tasks that have no priority (RFC 5545 writes it as 0) or an invalid one
+    pub fn priority(&self) -> Option<u8> {
+        self.extra_parameter("PRIORITY")
+            .and_then(|priority| priority.trim().parse().ok())
+            .filter(|priority| (1..=9).contains(priority))
+    }
+
+    /// The position of this task in the order the user has set by hand (its `X-APPLE-SORT-ORDER` property), if it has ever been reordered.
+    /// Lower values come first. See [`Self::effective_sort_order`]
+    pub fn sort_order(&self) -> Option<i64> {
+        self.extra_parameters.iter()
+            .find(|prop| prop.name.eq_ignore_ascii_case(SORT_ORDER_PROPERTY))
+            .and_then(|prop| prop.value.as_deref())
+            .and_then(|order| order.trim().parse().ok())
+    }
+
+    /// The position of this task in the manual order, like Apple Reminders and Nextcloud Tasks see it:
+    /// its [`Self::sort_order`], or else the number of seconds between 2001-01-01 and its creation date (so that it is where new tasks would be).
+    /// This is `None` for tasks that have neither
+    pub fn effective_sort_order(&self) -> Option<i64> {
+        self.sort_order()
+            .or_else(|| self.creation_date().map(|created| created.timestamp() - SORT_ORDER_EPOCH))
+    }
+
+    /// Whether this task is flagged, according to [`crate::config::FLAG_MAPPING`]. See [`Self::flagged_with`]
+    pub fn flagged(&self) -> bool {
+        let mapping = crate::config::FLAG_MAPPING.lock().unwrap().clone();
+        self.flagged_with(&mapping)
+    }
+
+    /// Whether this task is flagged, according to a given mapping
+    pub fn flagged_with(&self, mapping: &FlagMapping) -> bool {
+        match mapping {
+            FlagMapping::Priority(lowest) => self.priority().map(|priority| priority <= *lowest).unwrap_or(false),
+            FlagMapping::ExtraProperty(name) => match self.extra_property(name) {
+                Some(value) => value.trim() == "1" || value.trim().eq_ignore_ascii_case("TRUE"),
+                None => false,
+            },
+        }
+    }
+
+    /// Check whether this task complies with RFC 5545, and returns every issue otherwise
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        if self.uid.is_empty() {
+            violations.push(Violation::MissingUid);
+        }
+        crate::validation::check_todo_properties(&self.extra_parameters, &mut violations);
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
     #[cfg(any(test, feature = "integration_tests"))]
     pub fn has_same_observable_content_as(&self, other: &Task) -> bool {
            self.url == other.url
@@ -120,19 +386,75 @@ impl Task {
         && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
         // completion status must be the same variant, but we ignore its embedded completion date (they are not totally mocked in integration tests)
         && std::mem::discriminant(&self.completion_status) == std::mem::discriminant(&other.completion_status)
+        && self.status() == other.status()
         // last modified dates are ignored (they are not totally mocked in integration tests)
     }
 
+    /// Merge the changes made to `local` and to `remote` since their common version `base`, field by field.
+    ///
+    /// Extra parameters are compared by property name. `None` is returned if the same field has been changed differently on both sides.
+    /// The merged task is a new revision of both versions, it keeps the sync status of `local`.
+    pub fn merge(base: &Task, local: &Task, remote: &Task) -> Option<Task> {
+        fn pick<T: PartialEq + Clone>(base: &T, local: &T, remote: &T) -> Option<T> {
+            if local == base {
+                Some(remote.clone())
+            } else if remote == base || local == remote {
+                Some(local.clone())
+            } else {
+                None
+            }
+        }
+        fn props<'a>(task: &'a Task, name: &str) -> Vec<&'a Property> {
+            task.extra_parameters.iter().filter(|prop| prop.name == name).collect()
+        }
+
+        let name = pick(&base.name, &local.name, &remote.name)?;
+        let completion_status = pick(&base.completion_status, &local.completion_status, &remote.completion_status)?;
+        let uncompleted_status = pick(&base.uncompleted_status, &local.uncompleted_status, &remote.uncompleted_status)?;
+
+        let mut names: Vec<&str> = Vec::new();
+        for prop in local.extra_parameters.iter().chain(&remote.extra_parameters).chain(&base.extra_parameters) {
+            if prop.name != "SEQUENCE" && !names.contains(&prop.name.as_str()) {
+                names.push(&prop.name);
+            }
+        }
+        let mut extra_parameters = Vec::new();
+        for name in names {
+            let merged = pick(&props(base, name), &props(local, name), &props(remote, name))?;
+            extra_parameters.extend(merged.into_iter().cloned());
+        }
+
+        let mut time_zones = local.time_zones.clone();
+        time_zones.extend(remote.time_zones.iter().filter(|time_zone| !local.time_zones.contains(time_zone)).cloned());
+        let mut merged = Task {
+            name,
+            completion_status,
+            uncompleted_status,
+            extra_parameters,
+            last_modified: std::cmp::max(local.last_modified, remote.last_modified),
+            raw_ical: None,
+            time_zones,
+            ..local.clone()
+        };
+        let sequence = std::cmp::max(local.sequence(), remote.sequence()) + 1;
+        merged.extra_parameters.push(Property { name: "SEQUENCE".to_string(), params: None, value: Some(sequence.to_string()) });
+        Some(merged)
+    }
+
     pub fn set_sync_status(&mut self, new_status: SyncStatus) {
         self.sync_status = new_status;
     }
 
+    /// Mark this task as locally modified (so that its raw iCal text is outdated).
+    /// The first modification since the last sync is a new revision, so that its `SEQUENCE` is incremented
     fn update_sync_status(&mut self) {
+        self.raw_ical = None;
         match &self.sync_status {
             SyncStatus::NotSynced => return,
             SyncStatus::LocallyModified(_) => return,
             SyncStatus::Synced(prev_vt) => {
                 self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+                self.increment_sequence();
             }
             SyncStatus::LocallyDeleted(_) => {
                 log::warn!("Trying to update an item that has previously been deleted. These changes will probably be ignored at next sync.");
@@ -141,8 +463,17 @@ impl Task {
         }
     }
 
+    /// Update the "last modified" field (which is also written as the `DTSTAMP` of this task)
     fn update_last_modified(&mut self) {
-        self.last_modified = Utc::now();
+        self.last_modified = crate::clock::now();
+    }
+
+    fn increment_sequence(&mut self) {
+        let new_sequence = (self.sequence() + 1).to_string();
+        match self.extra_parameters.iter_mut().find(|prop| prop.name == "SEQUENCE") {
+            Some(prop) => prop.value = Some(new_sequence),
+            None => self.extra_parameters.push(Property { name: "SEQUENCE".to_string(), params: None, value: Some(new_sequence) }),
+        }
     }
 
 
@@ -153,24 +484,208 @@ impl Task {
         self.update_last_modified();
         self.name = new_name;
     }
+    /// Set an app-specific `X-` property (e.g. `X-OC-HIDESUBTASKS`), that is written into the iCal file of this task and synced to the server,
+    /// so that other clients keep it.
+    ///
+    /// `name` must be a valid `X-` name (RFC 5545 section 3.1), it is stored in uppercase. The value is a text, that replaces any previous value of this property.
+    /// This updates the sync status and the "last modified" field of this task
+    pub fn set_extra_property(&mut self, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let name = x_name(name)?;
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !prop.name.eq_ignore_ascii_case(&name));
+        self.extra_parameters.push(Property { name, params: None, value: Some(escape_text(value)) });
+        Ok(())
+    }
+
+    /// Remove an app-specific `X-` property, and return whether this task had it.
+    /// This updates the sync status and the "last modified" field of this task, in case it had this property
+    pub fn remove_extra_property(&mut self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let name = x_name(name)?;
+        if !self.extra_parameters.iter().any(|prop| prop.name.eq_ignore_ascii_case(&name)) {
+            return Ok(false);
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !prop.name.eq_ignore_ascii_case(&name));
+        Ok(true)
+    }
+
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     /// Rename a task, but forces a "master" SyncStatus, just like CalDAV servers are always "masters"
     pub fn mock_remote_calendar_set_name(&mut self, new_name: String) {
         self.sync_status = SyncStatus::random_synced();
+        self.raw_ical = None;
+        self.increment_sequence();
         self.update_last_modified();
         self.name = new_name;
     }
 
-    /// Set the completion status
+    /// Set the color of this task (or remove it), as a `COLOR` property.
+    /// Other color properties are removed, so that every client shows the same color.
+    /// This updates the sync status and the "last modified" field of this task
+    pub fn set_color(&mut self, color: Option<&Color>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !crate::ical::COLOR_PROPERTIES.iter().any(|name| prop.name.eq_ignore_ascii_case(name)));
+        if let Some(color) = color {
+            self.extra_parameters.push(Property { name: "COLOR".to_string(), params: None, value: Some(color.to_hex_string()) });
+        }
+    }
+
+    /// Set the position of this task in the manual order (or remove it), as an `X-APPLE-SORT-ORDER` property.
+    /// This updates the sync status and the "last modified" field of this task
+    pub fn set_sort_order(&mut self, sort_order: Option<i64>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !prop.name.eq_ignore_ascii_case(SORT_ORDER_PROPERTY));
+        if let Some(sort_order) = sort_order {
+            self.extra_parameters.push(Property { name: SORT_ORDER_PROPERTY.to_string(), params: None, value: Some(sort_order.to_string()) });
+        }
+    }
+
+    /// Move this task between two others in the manual order (e.g. after a drag-and-drop), by setting its [`Self::sort_order`].
+    ///
+    /// `prev` and `next` are the tasks it is now displayed between, `None` meaning the start or the end of the list.
+    /// Neighbours that have no [`Self::effective_sort_order`] are considered absent, since they are displayed last anyway. \
+    /// This fails in case there is no room between both neighbours (e.g. their sort orders are consecutive): the following tasks then have to be moved as well.
+    /// This updates the sync status and the "last modified" field of this task, unless it has nothing to be moved after or before
+    pub fn reorder_between(&mut self, prev: Option<&Task>, next: Option<&Task>) -> Result<(), Box<dyn Error>> {
+        let prev = prev.and_then(|task| task.effective_sort_order());
+        let next = next.and_then(|task| task.effective_sort_order());
+        let sort_order = match (prev, next) {
+            (None, None) => return Ok(()),
+            (Some(prev), None) => prev.checked_add(1),
+            (None, Some(next)) => next.checked_sub(1),
+            (Some(prev), Some(next)) => match next.checked_sub(prev) {
+                Some(gap) if gap >= 2 => Some(prev + gap / 2),
+                _ => None,
+            },
+        };
+        match sort_order {
+            Some(sort_order) => {
+                self.set_sort_order(Some(sort_order));
+                Ok(())
+            },
+            None => Err(format!("No room to move task {} between sort orders {:?} and {:?}", self.url, prev, next).into()),
+        }
+    }
+
+    /// Set the priority of this task (from 1, the highest, to 9, the lowest), as a `PRIORITY` property. `None` (or 0) removes it, and higher values are the lowest priority.
+    /// This updates the sync status and the "last modified" field of this task
+    pub fn set_priority(&mut self, priority: Option<u8>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !prop.name.eq_ignore_ascii_case("PRIORITY"));
+        if let Some(priority) = priority.filter(|priority| *priority > 0) {
+            self.extra_parameters.push(Property { name: "PRIORITY".to_string(), params: None, value: Some(priority.min(9).to_string()) });
+        }
+    }
+
+    /// Flag or unflag this task, according to [`crate::config::FLAG_MAPPING`]. See [`Self::set_flagged_with`]
+    pub fn set_flagged(&mut self, flagged: bool) -> Result<(), Box<dyn Error>> {
+        let mapping = crate::config::FLAG_MAPPING.lock().unwrap().clone();
+        self.set_flagged_with(&mapping, flagged)
+    }
+
+    /// Flag or unflag this task, according to a given mapping.
+    /// This fails in case the mapping uses an invalid `X-` property name (see [`Self::set_extra_property`]).
+    /// This updates the sync status and the "last modified" field of this task, unless it already was flagged (or unflagged)
+    pub fn set_flagged_with(&mut self, mapping: &FlagMapping, flagged: bool) -> Result<(), Box<dyn Error>> {
+        if self.flagged_with(mapping) == flagged {
+            return Ok(());
+        }
+        match (mapping, flagged) {
+            (FlagMapping::Priority(_), true) => self.set_priority(Some(1)),
+            (FlagMapping::Priority(_), false) => self.set_priority(None),
+            (FlagMapping::ExtraProperty(name), true) => self.set_extra_property(name, "1")?,
+            (FlagMapping::ExtraProperty(name), false) => { self.remove_extra_property(name)?; },
+        }
+        Ok(())
+    }
+
+    /// Set the access classification of this task, as a `CLASS` property (that is omitted for public tasks, since this is the default).
+    /// This updates the sync status and the "last modified" field of this task
+    pub fn set_classification(&mut self, classification: Classification) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| !prop.name.eq_ignore_ascii_case("CLASS"));
+        if classification != Classification::Public {
+            self.extra_parameters.push(Property { name: "CLASS".to_string(), params: None, value: Some(classification.as_ical().to_string()) });
+        }
+    }
+
+    /// Set the status of a task parsed from an iCal file
+    pub(crate) fn with_status(mut self, status: TaskStatus) -> Self {
+        if !status.is_completed() {
+            self.uncompleted_status = status;
+        }
+        self
+    }
+
+    /// Set the completion status.
+    /// Tasks that are not completed any more are `NeedsAction` (see [`Self::set_status`])
     pub fn set_completion_status(&mut self, new_completion_status: CompletionStatus) {
         self.update_sync_status();
         self.update_last_modified();
         self.completion_status = new_completion_status;
+        self.uncompleted_status = TaskStatus::NeedsAction;
     }
+
+    /// Set the `STATUS` of this task. A task that is set `Completed` is completed now (see [`Self::complete`]).
+    ///
+    /// This updates its sync status and its "last modified" field
+    pub fn set_status(&mut self, status: TaskStatus) {
+        match status {
+            TaskStatus::Completed => self.complete(crate::clock::now()),
+            _ => {
+                self.update_sync_status();
+                self.update_last_modified();
+                self.completion_status = CompletionStatus::Uncompleted;
+                self.uncompleted_status = status;
+            },
+        }
+    }
+    /// Mark this task as completed at `now`.
+    ///
+    /// This updates its sync status and its "last modified" field, and drops any previous `PERCENT-COMPLETE`, so that
+    /// the resulting iCal file has consistent `STATUS`, `COMPLETED` and `PERCENT-COMPLETE:100` fields.
+    pub fn complete(&mut self, now: DateTime<Utc>) {
+        self.update_sync_status();
+        self.last_modified = now;
+        self.completion_status = CompletionStatus::Completed(Some(now));
+        self.uncompleted_status = TaskStatus::NeedsAction;
+        self.extra_parameters.retain(|prop| prop.name != "PERCENT-COMPLETE");
+    }
+
+    /// Mark this task as not completed (`STATUS:NEEDS-ACTION`, without a `COMPLETED` date nor a `PERCENT-COMPLETE`).
+    ///
+    /// This updates its sync status and its "last modified" field
+    pub fn uncomplete(&mut self) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.completion_status = CompletionStatus::Uncompleted;
+        self.uncompleted_status = TaskStatus::NeedsAction;
+        self.extra_parameters.retain(|prop| prop.name != "PERCENT-COMPLETE");
+    }
+
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     /// Set the completion status, but forces a "master" SyncStatus, just like CalDAV servers are always "masters"
     pub fn mock_remote_calendar_set_completion_status(&mut self, new_completion_status: CompletionStatus) {
         self.sync_status = SyncStatus::random_synced();
+        self.raw_ical = None;
+        self.increment_sequence();
         self.completion_status = new_completion_status;
+        self.uncompleted_status = TaskStatus::NeedsAction;
+    }
+}
+
+/// Check that a property name is an `X-` name (`X-` followed by letters, digits and dashes), and return it in uppercase
+fn x_name(name: &str) -> Result<String, Box<dyn Error>> {
+    let name = name.to_ascii_uppercase();
+    let suffix = name.strip_prefix("X-").ok_or_else(|| format!("Property {} is not an X- property", name))?;
+    if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid X- property name {:?}", name).into());
     }
+    Ok(name)
 }
@@ -0,0 +1,39 @@
+//! Fixtures shared by the unit tests of this crate
+
+use chrono::Utc;
+use ical::property::Property;
+use url::Url;
+
+use crate::event::Event;
+use crate::item::{Item, SyncStatus};
+use crate::task::{CompletionStatus, Task};
+
+/// An iCal property without parameters
+pub(crate) fn prop(name: &str, value: &str) -> Property {
+    Property { name: name.to_string(), params: None, value: Some(value.to_string()) }
+}
+
+/// A task whose URL is derived from its name, with additional iCal properties
+pub(crate) fn new_task(name: &str, uid: &str, completed: bool, extra_parameters: Vec<Property>) -> Task {
+    let url: Url = format!("https://some.calend.ar/cal/{}.ics", name.replace(' ', "-")).parse().unwrap();
+    let completion_status = match completed {
+        true => CompletionStatus::Completed(None),
+        false => CompletionStatus::Uncompleted,
+    };
+    Task::new_with_parameters(name.to_string(), uid.to_string(), url, completion_status, SyncStatus::NotSynced,
+        None, Utc::now(), "prod_id".to_string(), extra_parameters)
+}
+
+/// An event whose URL is derived from its name, with additional iCal properties (e.g. its `DTSTART`)
+pub(crate) fn new_event(name: &str, uid: &str, extra_parameters: Vec<Property>) -> Event {
+    let url: Url = format!("https://some.calend.ar/cal/{}.ics", name.replace(' ', "-")).parse().unwrap();
+    Event::new_with_parameters(name.to_string(), uid.to_string(), url, SyncStatus::NotSynced, Utc::now(), "prod_id".to_string(), extra_parameters)
+}
+
+/// A task whose UID is its name, and whose URL is `https://some.calend.ar/cal/<name, with dashes instead of spaces>.ics`
+pub(crate) fn task_with(name: &str, completed: bool, extra: &[(&str, &str)]) -> Item {
+    let extra_parameters = extra.iter()
+        .map(|(name, value)| prop(name, value))
+        .collect();
+    Item::Task(new_task(name, name, completed, extra_parameters))
+}
@@ -0,0 +1,234 @@
+//! An index of items by date, to quickly find the items within a time range (e.g. to display a month view)
+//!
+//! Items are sorted by their start date, so that a query only reads the items that start shortly before its range (up to the longest of them).
+//! Items that span more than [`LONG_SPAN_DAYS`] (e.g. a task that lasts for months) are kept apart and checked one by one instead,
+//! so that a single one of them does not widen every query.
+//!
+//! Every [`CachedCalendar`](crate::calendar::cached_calendar::CachedCalendar) maintains such an index whenever its items are added, updated or deleted. \
+//! It is usually queried through [`Cache::get_items_between`](crate::cache::Cache::get_items_between) or [`Cache::get_events_between`](crate::cache::Cache::get_events_between).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use url::Url;
+
+use crate::item::{Item, SyncStatus};
+
+/// The time span of an item: its start, and its end (which is the same as its start for items that have a single date)
+type Span = (DateTime<Utc>, DateTime<Utc>);
+
+/// Items that span more days than this are not sorted by their start date (see [`TimeIndex::long_spans`])
+pub const LONG_SPAN_DAYS: i64 = 31;
+
+/// An index of items, sorted by their start date
+#[derive(Clone, Debug, Default)]
+pub struct TimeIndex {
+    /// For every start date, the items that start then (except the long ones)
+    by_start: BTreeMap<DateTime<Utc>, HashSet<Url>>,
+    /// The items that span more than [`LONG_SPAN_DAYS`]. There are usually few of them, and they are checked against every query
+    long_spans: HashSet<Url>,
+    /// The span every item has been indexed with
+    spans: HashMap<Url, Span>,
+    /// For every duration, the number of items of `by_start` that span that long.
+    /// Items that start up to the longest of them before a time range may overlap it
+    durations: BTreeMap<Duration, usize>,
+    /// Items that may have been modified since they were indexed (e.g. using a mutable reference)
+    dirty: HashSet<Url>,
+}
+
+impl TimeIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index (or re-index) an item. Items that have no date, and items that are marked for deletion are removed from the index
+    pub fn index_item(&mut self, item: &Item) {
+        let url = item.url().clone();
+        self.remove_item(&url);
+        let span = match item_span(item) {
+            None => return,
+            Some(span) => span,
+        };
+
+        let duration = span.1 - span.0;
+        if duration > Duration::days(LONG_SPAN_DAYS) {
+            self.long_spans.insert(url.clone());
+        } else {
+            *self.durations.entry(duration).or_default() += 1;
+            self.by_start.entry(span.0).or_default().insert(url.clone());
+        }
+        self.spans.insert(url, span);
+    }
+
+    /// Remove an item from the index
+    pub fn remove_item(&mut self, url: &Url) {
+        self.dirty.remove(url);
+        if let Some((start, end)) = self.spans.remove(url) {
+            if self.long_spans.remove(url) {
+                return;
+            }
+            if let Some(items) = self.by_start.get_mut(&start) {
+                items.remove(url);
+                if items.is_empty() {
+                    self.by_start.remove(&start);
+                }
+            }
+            let duration = end - start;
+            if let Some(n_items) = self.durations.get_mut(&duration) {
+                *n_items -= 1;
+                if *n_items == 0 {
+                    self.durations.remove(&duration);
+                }
+            }
+        }
+    }
+
+    /// Tell that an item may have changed without the index knowing it.
+    /// Until it is re-indexed, this item will be checked by reading its content
+    pub fn mark_dirty(&mut self, url: &Url) {
+        self.dirty.insert(url.clone());
+    }
+
+    /// Re-index the items that have been marked dirty, so that they are not read anymore by [`Self::items_between`].
+    ///
+    /// `get_item` returns the items the index has been built from. Items it does not return anymore are removed from the index.
+    pub fn reindex_dirty<'a, F>(&mut self, get_item: F)
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        for url in std::mem::take(&mut self.dirty) {
+            match get_item(&url) {
+                Some(item) => self.index_item(item),
+                None => self.remove_item(&url),
+            }
+        }
+    }
+
+    /// Returns the items that overlap the `start..end` range, with their start date, sorted by start date.
+    ///
+    /// `get_item` returns the items the index has been built from. It is only used for items that have been marked dirty.
+    pub fn items_between<'a, F>(&self, start: DateTime<Utc>, end: DateTime<Utc>, get_item: F) -> Vec<(DateTime<Utc>, Url)>
+    where
+        F: Fn(&Url) -> Option<&'a Item>
+    {
+        if end <= start {
+            return Vec::new();
+        }
+
+        // Indexed items
+        let longest = self.durations.keys().next_back().copied().unwrap_or_else(Duration::zero);
+        let earliest = start - longest;
+        let mut found: Vec<(DateTime<Utc>, Url)> = self.by_start.range(earliest..end)
+            .flat_map(|(_start, urls)| urls.iter())
+            .chain(self.long_spans.iter())
+            .filter(|url| !self.dirty.contains(*url))
+            .filter_map(|url| {
+                let span = self.spans.get(url)?;
+                if overlaps(span, start, end) { Some((span.0, url.clone())) } else { None }
+            })
+            .collect();
+
+        // Dirty items
+        for url in &self.dirty {
+            if let Some(span) = get_item(url).and_then(item_span) {
+                if overlaps(&span, start, end) {
+                    found.push((span.0, url.clone()));
+                }
+            }
+        }
+
+        sort_by_start(&mut found);
+        found
+    }
+}
+
+/// Sort items by their start date (and by URL for items that start at the same time)
+pub(crate) fn sort_by_start(items: &mut [(DateTime<Utc>, Url)]) {
+    items.sort_by(|(start_a, url_a), (start_b, url_b)| {
+        start_a.cmp(start_b).then_with(|| url_a.as_str().cmp(url_b.as_str()))
+    });
+}
+
+/// Whether a span overlaps a time range. Items that have a single date overlap the ranges that contain it
+fn overlaps(span: &Span, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    span.0 < end && (span.1 > start || span.0 >= start)
+}
+
+/// The time span of an item, or `None` if it has no date (or is marked for deletion)
+fn item_span(item: &Item) -> Option<Span> {
+    if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+        return None;
+    }
+    match item {
+        // Events end when their DTEND (or DURATION) tells, which is exclusive: an event that ends at midnight is not the next day
+        Item::Event(event) => {
+            let start = event.start()?;
+            let end = event.end().filter(|end| *end > start).unwrap_or(start);
+            Some((start, end))
+        },
+        Item::Task(task) => {
+            let start = task.start();
+            let due = task.due();
+            let begin = start.or(due)?;
+            let end = due.filter(|due| *due > begin).unwrap_or(begin);
+            Some((begin, end))
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use crate::test_utils::{new_event, prop, task_with};
+
+    #[test]
+    fn test_time_index() {
+        let mut items = HashMap::new();
+        let mut index = TimeIndex::new();
+        for item in [task_with("long", false, &[("DTSTART", "20210101T000000Z"), ("DUE", "20210601T000000Z")]),
+            task_with("april", false, &[("DUE", "20210410")]),
+            task_with("may", false, &[("DTSTART", "20210503T080000Z"), ("DUE", "20210503T090000Z")]),
+            task_with("undated", false, &[]),
+            Item::Event(new_event("meeting", "meeting", vec![prop("DTSTART", "20210430T090000Z"), prop("DURATION", "P1D")])),
+            Item::Event(new_event("holiday", "holiday", vec![prop("DTSTART", "20210501")])),
+            Item::Event(new_event("holidays", "holidays", vec![prop("DTSTART", "20210701"), prop("DTEND", "20210901")]))] {
+            index.index_item(&item);
+            items.insert(item.url().clone(), item);
+        }
+        let names = |found: Vec<(DateTime<Utc>, Url)>| -> Vec<String> {
+            found.iter().map(|(_start, url)| url.path_segments().unwrap().next_back().unwrap().trim_end_matches(".ics").to_string()).collect()
+        };
+
+        let april = (Utc.ymd(2021, 4, 1).and_hms(0, 0, 0), Utc.ymd(2021, 5, 1).and_hms(0, 0, 0));
+        let may = (Utc.ymd(2021, 5, 1).and_hms(0, 0, 0), Utc.ymd(2021, 6, 1).and_hms(0, 0, 0));
+        let july = (Utc.ymd(2021, 7, 1).and_hms(0, 0, 0), Utc.ymd(2021, 8, 1).and_hms(0, 0, 0));
+        // Events end before their DTEND, and whole-day ones last until the next midnight
+        assert_eq!(names(index.items_between(april.0, april.1, |url| items.get(url))), vec!["long", "april", "meeting"]);
+        assert_eq!(names(index.items_between(may.0, may.1, |url| items.get(url))), vec!["long", "meeting", "holiday", "may"]);
+        assert_eq!(names(index.items_between(july.0, july.1, |url| items.get(url))), vec!["holidays"]);
+        // Long items do not widen the queries
+        assert_eq!(index.long_spans.len(), 2);
+        assert_eq!(index.durations.keys().next_back(), Some(&Duration::days(1)));
+
+        // Items that are modified behind the back of the index
+        let may_url: Url = "https://some.calend.ar/cal/may.ics".parse().unwrap();
+        index.mark_dirty(&may_url);
+        items.insert(may_url.clone(), task_with("may", false, &[("DTSTART", "20210705T080000Z")]));
+        assert_eq!(names(index.items_between(may.0, may.1, |url| items.get(url))), vec!["long", "meeting", "holiday"]);
+        assert_eq!(names(index.items_between(july.0, july.1, |url| items.get(url))), vec!["holidays", "may"]);
+
+        index.reindex_dirty(|url| items.get(url));
+        assert!(index.dirty.is_empty());
+        assert_eq!(names(index.items_between(july.0, july.1, |url| items.get(url))), vec!["holidays", "may"]);
+
+        index.remove_item(&"https://some.calend.ar/cal/long.ics".parse().unwrap());
+        assert_eq!(names(index.items_between(may.0, may.1, |url| items.get(url))), vec!["meeting", "holiday"]);
+        assert_eq!(index.long_spans.len(), 1);
+        index.remove_item(&"https://some.calend.ar/cal/meeting.ics".parse().unwrap());
+        index.remove_item(&"https://some.calend.ar/cal/holiday.ics".parse().unwrap());
+        assert_eq!(index.durations.keys().next_back(), Some(&Duration::zero()));
+    }
+}
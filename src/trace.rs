@@ -0,0 +1,198 @@
+//! Recording of the HTTP traffic of a sync, e.g. to be attached to bug reports
+//!
+//! Servers do not all implement CalDAV the same way, and interoperability bugs are hard to reproduce without the exact replies of the faulty server.
+//! [`Provider::sync_with_trace`](crate::provider::Provider::sync_with_trace) records every request made during a sync, and the reply of the server,
+//! into a trace file.
+//!
+//! Traces are [JSON Lines](https://jsonlines.org/) files, with one [`TraceEntry`] per line, in the order the requests have been sent.
+//! Credentials are stripped from them: `Authorization` and cookie headers are removed, and the password is replaced by `<redacted>` wherever it appears.
+//! Note that traces still contain the URLs, names and contents of the synced items, so that users should review them before sharing them.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// Headers whose values are never recorded, since they carry credentials
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "set-cookie"];
+
+/// What replaces secrets in traces
+const REDACTED: &str = "<redacted>";
+
+/// A request, and the reply of the server
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: Option<String>,
+    /// The HTTP status code of the reply, or `None` if no reply has been received
+    pub status: Option<u16>,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: Option<String>,
+    /// The error that prevented the request from being sent or its reply from being read, if any
+    pub error: Option<String>,
+}
+
+/// A trace file that requests are being recorded into
+#[derive(Debug)]
+pub struct SyncTrace {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl SyncTrace {
+    /// Create (or truncate) a trace file
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path.as_ref())
+            .map_err(|err| format!("Unable to create trace file {}: {}", path.as_ref().display(), err))?;
+        Ok(Self { writer: Mutex::new(BufWriter::new(file)) })
+    }
+
+    /// Append an entry to the trace. Entries are written right away, so that a trace is usable even if the app crashes during the sync
+    pub fn record(&self, entry: &TraceEntry) -> Result<(), Box<dyn Error>> {
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, entry)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read every entry of a trace file
+pub fn read_trace<P: AsRef<Path>>(path: P) -> Result<Vec<TraceEntry>, Box<dyn Error>> {
+    let file = File::open(path.as_ref())
+        .map_err(|err| format!("Unable to open trace file {}: {}", path.as_ref().display(), err))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
+
+/// The trace that is currently recorded (if any), shared by every [`Resource`](crate::resource::Resource) derived from the same [`Client`](crate::client::Client)
+#[derive(Debug, Default)]
+pub(crate) struct TraceHandle {
+    current: Mutex<Option<Arc<SyncTrace>>>,
+}
+
+impl TraceHandle {
+    pub(crate) fn set(&self, trace: Option<Arc<SyncTrace>>) {
+        *self.current.lock().unwrap() = trace;
+    }
+
+    pub(crate) fn current(&self) -> Option<Arc<SyncTrace>> {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+/// Send a request, and record it into `trace`.
+///
+/// The reply is read entirely to be recorded, and then given back as a new response
+pub(crate) async fn execute_traced(http_client: &reqwest::Client, request: reqwest::Request, trace: &SyncTrace, secrets: &[&str]) -> Result<reqwest::Response, reqwest::Error> {
+    let mut entry = TraceEntry {
+        method: request.method().to_string(),
+        url: sanitize(&crate::resource::redact_url(request.url()), secrets),
+        request_headers: sanitize_headers(request.headers(), secrets),
+        request_body: request.body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| sanitize(&String::from_utf8_lossy(bytes), secrets)),
+        status: None,
+        response_headers: Vec::new(),
+        response_body: None,
+        error: None,
+    };
+
+    let result = read_response(http_client, request).await;
+    match &result {
+        Ok((parts, body)) => {
+            entry.status = Some(parts.status.as_u16());
+            entry.response_headers = sanitize_headers(&parts.headers, secrets);
+            entry.response_body = Some(sanitize(&String::from_utf8_lossy(body), secrets));
+        },
+        Err(err) => entry.error = Some(sanitize(&err.to_string(), secrets)),
+    }
+    if let Err(err) = trace.record(&entry) {
+        log::warn!("Unable to record a request into the trace: {}", err);
+    }
+
+    let (parts, body) = result?;
+    Ok(reqwest::Response::from(http::Response::from_parts(parts, body)))
+}
+
+async fn read_response(http_client: &reqwest::Client, request: reqwest::Request) -> Result<(http::response::Parts, Vec<u8>), reqwest::Error> {
+    use reqwest::ResponseBuilderExt;
+
+    let response = http_client.execute(request).await?;
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    let body = response.bytes().await?.to_vec();
+    let (parts, ()) = builder.body(()).unwrap(/* the parts come from a valid response */).into_parts();
+    Ok((parts, body))
+}
+
+fn sanitize_headers(headers: &reqwest::header::HeaderMap, secrets: &[&str]) -> Vec<(String, String)> {
+    headers.iter()
+        .map(|(name, value)| match SENSITIVE_HEADERS.contains(&name.as_str()) {
+            true => (name.to_string(), REDACTED.to_string()),
+            false => (name.to_string(), sanitize(&String::from_utf8_lossy(value.as_bytes()), secrets)),
+        })
+        .collect()
+}
+
+/// Replace every secret in `text`
+fn sanitize(text: &str, secrets: &[&str]) -> String {
+    secrets.iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| text.replace(secret, REDACTED))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+    #[test]
+    fn test_trace_sanitization() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic am9objpodW50ZXIy"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/calendar"));
+        let sanitized = sanitize_headers(&headers, &["hunter2"]);
+        assert!(sanitized.contains(&("authorization".to_string(), REDACTED.to_string())));
+        assert!(sanitized.contains(&("content-type".to_string(), "text/calendar".to_string())));
+
+        assert_eq!(sanitize("<password>hunter2</password>", &["hunter2", ""]), "<password><redacted></password>");
+
+        let path = Path::new("test_cache/trace.jsonl");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let trace = SyncTrace::create(path).unwrap();
+        let entry = TraceEntry {
+            method: "PROPFIND".to_string(),
+            url: "https://caldav.com/dav/".to_string(),
+            request_headers: sanitized,
+            request_body: Some("<d:propfind xmlns:d=\"DAV:\"/>".to_string()),
+            status: Some(207),
+            response_headers: Vec::new(),
+            response_body: Some("<d:multistatus xmlns:d=\"DAV:\"/>".to_string()),
+            error: None,
+        };
+        trace.record(&entry).unwrap();
+        trace.record(&TraceEntry { status: None, error: Some("connection refused".to_string()), ..entry.clone() }).unwrap();
+        let entries = read_trace(path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry);
+        assert_eq!(entries[1].error.as_deref(), Some("connection refused"));
+    }
+}
@@ -1,36 +1,192 @@
 //! Traits used by multiple structs in this crate
+//!
+//! ## Other kinds of remote sources
+//!
+//! The [`Provider`](crate::provider::Provider) only relies on these traits, so it can sync a local cache with any remote source, not only CalDAV servers.
+//! Such a source must implement [`CalDavSource`], and its calendars must implement [`DavCalendar`]. In particular:
+//! * every item needs a stable URL. For REST APIs, it can be built from the identifiers of the API (e.g. `https://www.googleapis.com/calendar/v3/calendars/<calendar>/events/<event>`),
+//! * [`DavCalendar::get_item_version_tags`] must return a tag that changes whenever an item changes (e.g. the `etag` of the API, or its `updated` timestamp),
+//! * items are exchanged as [`Item`]s, so the source is responsible for converting them from and to its own format.
+//!
+//! See `examples/e2ee-source.rs` for such a source, that decrypts end-to-end encrypted items before they enter the local cache.
+//!
+//! The `google_tasks` and `google_calendar` features provide such sources for the Google Tasks and Google Calendar APIs (see the `google_tasks` and
+//! `google_calendar` modules). Getting their OAuth2 access token is up to the app.
+//!
+//! ## Trait objects
+//!
+//! These traits are object-safe (their functions are not generic, and constructors are only available on sized types), so that sources and calendars
+//! of different types can be handled together, e.g. as `Box<dyn CompleteCalendar + Send + Sync>`.
+//! In particular, a [`DynSource`] can wrap any source, and be plugged into a [`DynProvider`](crate::DynProvider).
 
 use std::error::Error;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::RwLock;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
 use url::Url;
 
 use crate::item::SyncStatus;
+use crate::metrics::RequestStats;
 use crate::item::Item;
 use crate::item::VersionTag;
-use crate::calendar::SupportedComponents;
+use crate::item::UnparsedItem;
+use crate::item::VanishedItem;
+use crate::calendar::{ArchivePolicy, CalendarMetadata, ItemFilter, ItemPage, Permissions, SortKey, SupportedComponents, SyncExclusion};
 use crate::resource::Resource;
+use crate::capabilities::ServerCapabilities;
+use crate::trace::SyncTrace;
+#[cfg(feature = "addressbook")]
+use crate::addressbook::AddressBookSource;
 
 /// This trait must be implemented by data sources (either local caches or remote CalDAV clients)
 ///
+/// Calendars are shared behind async-aware [`RwLock`]s, so that they can safely be held across `.await` points (e.g. during a sync).
+///
 /// Note that some concrete types (e.g. [`crate::cache::Cache`]) can also provide non-async versions of these functions
 #[async_trait]
 pub trait CalDavSource<T: BaseCalendar> {
     /// Returns the current calendars that this source contains
     /// This function may trigger an update (that can be a long process, or that can even fail, e.g. in case of a remote server)
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<T>>>, Box<dyn Error>>;
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<T>>>, Box<dyn Error>>;
     /// Returns the calendar matching the URL
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<T>>>;
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<T>>>;
+
+    /// Returns the URLs of the calendars that this source contains.
+    /// Like [`Self::get_calendars`], this may trigger an update, but it does not hand out every calendar
+    async fn get_calendar_ids(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+        Ok(self.get_calendars().await?.into_keys().collect())
+    }
+
+    /// Returns the calendar matching the URL, if it is available right away.
+    ///
+    /// Unlike [`Self::get_calendar`], this never waits (e.g. for a network request, or for a lock). It returns `None` in case the calendar
+    /// is not known yet, or in case this source cannot tell without waiting (which is the default)
+    fn try_get_calendar(&self, _url: &Url) -> Option<Arc<RwLock<T>>> {
+        None
+    }
+
+    /// Returns a stream of the calendars that this source contains.
+    ///
+    /// Calendars are fetched one at a time, when the stream is polled, so that apps can start working on the first calendars (or stop early)
+    /// without waiting for every calendar. Calendars that disappear while the stream is consumed are skipped.
+    async fn calendars<'a>(&'a self) -> Result<BoxStream<'a, (Url, Arc<RwLock<T>>)>, Box<dyn Error>>
+    where
+        T: Send + Sync + 'a,
+    {
+        let urls = self.get_calendar_ids().await?;
+        Ok(stream::iter(urls)
+            .filter_map(move |url| async move {
+                self.get_calendar(&url).await.map(|cal| (url, cal))
+            })
+            .boxed())
+    }
     /// Create a calendar if it did not exist, and return it
     async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>)
-        -> Result<Arc<Mutex<T>>, Box<dyn Error>>;
+        -> Result<Arc<RwLock<T>>, Box<dyn Error>>;
+
+    /// Returns the capabilities of the server behind this source, once they are known.
+    /// Sources that are not backed by a server return `None` (which is the default), and are assumed to support everything
+    fn capabilities(&self) -> Option<ServerCapabilities> {
+        None
+    }
+
+    /// Returns statistics about the requests made by this source. Sources that do not make network requests return zeros
+    fn request_stats(&self) -> RequestStats {
+        RequestStats::default()
+    }
+
+    /// Start (or stop, with `None`) recording the HTTP requests made by this source into a trace (see [`crate::trace`]).
+    /// Sources that do not make network requests ignore this (which is the default)
+    fn set_trace(&self, _trace: Option<Arc<SyncTrace>>) {}
+
+    /// Persist the current content of this source (e.g. write a cache to its folder), so that type-erased sources (see [`DynSource`]) can be saved as well.
+    /// Sources that have nothing to persist ignore this (which is the default)
+    async fn save(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Returns the address books of this source (see [`crate::addressbook`]), whose contacts are synced by the [`Provider`](crate::provider::Provider) along with calendars.
+    /// Sources that do not store contacts return `None` (which is the default)
+    #[cfg(feature = "addressbook")]
+    fn address_book_source(&self) -> Option<&(dyn AddressBookSource + Send + Sync)> {
+        None
+    }
+
+    /// The mutable version of [`Self::address_book_source`], e.g. to create address books
+    #[cfg(feature = "addressbook")]
+    fn address_book_source_mut(&mut self) -> Option<&mut (dyn AddressBookSource + Send + Sync)> {
+        None
+    }
 
     // Removing a calendar is not supported yet
 }
 
+/// A type-erased [`CalDavSource`], so that sources of different types can be used interchangeably (see [`DynProvider`](crate::DynProvider))
+pub type DynSource<T> = Box<dyn CalDavSource<T> + Send + Sync>;
+
+#[async_trait]
+impl<T: BaseCalendar + Send + Sync> CalDavSource<T> for DynSource<T> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<T>>>, Box<dyn Error>> {
+        (**self).get_calendars().await
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<T>>> {
+        (**self).get_calendar(url).await
+    }
+
+    async fn get_calendar_ids(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+        (**self).get_calendar_ids().await
+    }
+
+    fn try_get_calendar(&self, url: &Url) -> Option<Arc<RwLock<T>>> {
+        (**self).try_get_calendar(url)
+    }
+
+    async fn calendars<'a>(&'a self) -> Result<BoxStream<'a, (Url, Arc<RwLock<T>>)>, Box<dyn Error>>
+    where
+        T: Send + Sync + 'a,
+    {
+        (**self).calendars().await
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>)
+        -> Result<Arc<RwLock<T>>, Box<dyn Error>>
+    {
+        (**self).create_calendar(url, name, supported_components, color).await
+    }
+
+    fn capabilities(&self) -> Option<ServerCapabilities> {
+        (**self).capabilities()
+    }
+
+    fn request_stats(&self) -> RequestStats {
+        (**self).request_stats()
+    }
+
+    fn set_trace(&self, trace: Option<Arc<SyncTrace>>) {
+        (**self).set_trace(trace)
+    }
+
+    async fn save(&self) -> Result<(), Box<dyn Error>> {
+        (**self).save().await
+    }
+
+    #[cfg(feature = "addressbook")]
+    fn address_book_source(&self) -> Option<&(dyn AddressBookSource + Send + Sync)> {
+        (**self).address_book_source()
+    }
+
+    #[cfg(feature = "addressbook")]
+    fn address_book_source_mut(&mut self) -> Option<&mut (dyn AddressBookSource + Send + Sync)> {
+        (**self).address_book_source_mut()
+    }
+}
+
 /// This trait contains functions that are common to all calendars
 ///
 /// Note that some concrete types (e.g. [`crate::calendar::cached_calendar::CachedCalendar`]) can also provide non-async versions of these functions
@@ -48,6 +204,16 @@ pub trait BaseCalendar {
     /// Returns the user-defined color of this calendar
     fn color(&self) -> Option<&Color>;
 
+    /// Returns what the current user is allowed to do in this calendar, or `None` if this is unknown (which is the default)
+    fn permissions(&self) -> Option<Permissions> {
+        None
+    }
+
+    /// Returns what this calendar tells about itself (e.g. its description), see [`CalendarMetadata`]. This is empty by default
+    fn metadata(&self) -> CalendarMetadata {
+        CalendarMetadata::default()
+    }
+
     /// Add an item into this calendar, and return its new sync status.
     /// For local calendars, the sync status is not modified.
     /// For remote calendars, the sync status is updated by the server
@@ -75,7 +241,9 @@ pub trait BaseCalendar {
 #[async_trait]
 pub trait DavCalendar : BaseCalendar {
     /// Create a new calendar
-    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self;
+    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self
+    where
+        Self: Sized;
 
     /// Get the URLs and the version tags of every item in this calendar
     async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>>;
@@ -83,6 +251,19 @@ pub trait DavCalendar : BaseCalendar {
     /// Returns a particular item
     async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>>;
 
+    /// Returns the current version tags of some items, or `None` for the items that do not exist in this calendar (any more).
+    /// Items the source knows nothing about (e.g. because it did not reply about them) are missing from the result.
+    ///
+    /// Listings returned by [`DavCalendar::get_item_version_tags`] may be incomplete (e.g. servers may truncate them), so that an item missing from them
+    /// is only considered deleted once this has confirmed it.
+    /// The default implementation trusts these listings to be complete.
+    async fn get_version_tags_of(&self, urls: &[Url]) -> Result<HashMap<Url, Option<VersionTag>>, Box<dyn Error>> {
+        let version_tags = self.get_item_version_tags().await?;
+        Ok(urls.iter()
+            .map(|url| (url.clone(), version_tags.get(url).cloned()))
+            .collect())
+    }
+
     /// Returns a set of items.
     /// This is usually faster than calling multiple consecutive [`DavCalendar::get_item_by_url`], since it only issues one HTTP request.
     async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>>;
@@ -90,6 +271,37 @@ pub trait DavCalendar : BaseCalendar {
     /// Delete an item
     async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>>;
 
+    /// Move an item to another URL (that may belong to another calendar of the same source), and return its new sync status.
+    ///
+    /// Sources that are not able to do this return an error (which is the default), and callers should fall back to adding a copy and deleting the original item.
+    async fn move_item(&mut self, item_url: &Url, _destination: &Url) -> Result<SyncStatus, Box<dyn Error>> {
+        Err(format!("Moving item {} is not supported by this source", item_url).into())
+    }
+
+    /// Add an item, and return it as it is actually stored by the source (with its new sync status).
+    ///
+    /// Some servers store new items at another URL than the requested one, or modify them (e.g. their UID).
+    /// Sources that never do (which is the default) return `item` with the sync status returned by [`BaseCalendar::add_item`].
+    async fn add_item_and_fetch(&mut self, mut item: Item) -> Result<Item, Box<dyn Error>> {
+        let new_ss = self.add_item(item.clone()).await?;
+        item.set_sync_status(new_ss);
+        Ok(item)
+    }
+
+    /// Add an item at a URL chosen by the source, and return it as it is actually stored by the source (see [`DavCalendar::add_item_and_fetch`]).
+    ///
+    /// Sources that are not able to choose URLs return `None` (which is the default), and callers should rather use [`DavCalendar::add_item_and_fetch`].
+    async fn add_item_at_server_url(&mut self, _item: Item) -> Result<Option<Item>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Returns (and forgets) the items that have been skipped by the previous calls to [`DavCalendar::get_items_by_url`] because they could not be parsed.
+    ///
+    /// Sources that always parse their items successfully (or reject the whole batch otherwise) do not have to implement this.
+    fn take_unparsed_items(&self) -> Vec<UnparsedItem> {
+        Vec::new()
+    }
+
     /// Get the URLs of all current items in this calendar
     async fn get_item_urls(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
         let items = self.get_item_version_tags().await?;
@@ -111,7 +323,9 @@ pub trait DavCalendar : BaseCalendar {
 #[async_trait]
 pub trait CompleteCalendar : BaseCalendar {
     /// Create a new calendar
-    fn new(name: String, url: Url, supported_components: SupportedComponents, color: Option<Color>) -> Self;
+    fn new(name: String, url: Url, supported_components: SupportedComponents, color: Option<Color>) -> Self
+    where
+        Self: Sized;
 
     /// Get the URLs of all current items in this calendar
     async fn get_item_urls(&self) -> Result<HashSet<Url>, Box<dyn Error>>;
@@ -119,20 +333,317 @@ pub trait CompleteCalendar : BaseCalendar {
     /// Returns all items that this calendar contains
     async fn get_items(&self) -> Result<HashMap<Url, &Item>, Box<dyn Error>>;
 
+    /// Returns the items of this calendar that match a filter
+    async fn get_items_matching<'a>(&'a self, filter: &ItemFilter) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        let items = self.get_items().await?;
+        Ok(items.into_iter()
+            .filter(|(_url, item)| filter.matches(item))
+            .collect())
+    }
+
+    /// The number of items of this calendar (except the ones that are marked for deletion), e.g. to display a badge in a list of calendars.
+    ///
+    /// The default implementation goes through every item. Implementors should rather keep these counts up to date as items change
+    async fn count_items(&self) -> Result<usize, Box<dyn Error>> {
+        let items = self.get_items_matching(&ItemFilter::default()).await?;
+        Ok(items.values().filter(|item| !matches!(item.sync_status(), SyncStatus::LocallyDeleted(_))).count())
+    }
+
+    /// The number of completed tasks of this calendar (except the ones that are marked for deletion). See [`CompleteCalendar::count_items`]
+    async fn count_completed(&self) -> Result<usize, Box<dyn Error>> {
+        let items = self.get_items_matching(&ItemFilter{ completed: Some(true), ..ItemFilter::default() }).await?;
+        Ok(items.values().filter(|item| !matches!(item.sync_status(), SyncStatus::LocallyDeleted(_))).count())
+    }
+
+    /// The number of uncompleted tasks of this calendar that are due strictly before `date` (e.g. overdue tasks), except the ones that are marked for deletion.
+    /// See [`CompleteCalendar::count_items`]
+    async fn count_due_before(&self, date: DateTime<Utc>) -> Result<usize, Box<dyn Error>> {
+        let items = self.get_items_matching(&ItemFilter{ completed: Some(false), due_before: Some(date), ..ItemFilter::default() }).await?;
+        Ok(items.values().filter(|item| !matches!(item.sync_status(), SyncStatus::LocallyDeleted(_))).count())
+    }
+
+    /// Returns every item of this calendar, in the order given by `sort` (e.g. [`SortKey::Manual`] to show them in the order the user has chosen).
+    /// See [`CompleteCalendar::get_items_page`] to only get some of them
+    async fn get_items_sorted<'a>(&'a self, sort: SortKey) -> Result<Vec<&'a Item>, Box<dyn Error>> {
+        let mut items: Vec<&Item> = self.get_items().await?.into_values().collect();
+        items.sort_by(|a, b| sort.compare(a, b));
+        Ok(items)
+    }
+
+    /// Returns at most `limit` items, starting at `offset`, in the order given by `sort`.
+    ///
+    /// This is useful to incrementally display long lists of items. Use the `next_offset` of the returned page to request the next one.
+    async fn get_items_page<'a>(&'a self, offset: usize, limit: usize, sort: SortKey) -> Result<ItemPage<'a>, Box<dyn Error>> {
+        let mut items: Vec<&Item> = self.get_items().await?.into_values().collect();
+        items.sort_by(|a, b| sort.compare(a, b));
+        Ok(ItemPage::from_sorted(items.into_iter(), offset, limit))
+    }
+
+    /// Write every item of this calendar into a single iCal (`.ics`) file, e.g. to back up or migrate them. Items that have been deleted locally are not exported.
+    ///
+    /// Its PRODID is the one of this crate (see [`crate::config`]). \
+    /// Date-times set by this crate are written in UTC (or as floating times). Unparsed properties that refer to a `TZID` are written as-is,
+    /// along with the definition (`VTIMEZONE`) of their time zone that the server has sent.
+    async fn export_ics(&self, writer: &mut (dyn std::io::Write + Send)) -> Result<(), Box<dyn Error>> {
+        let mut items: Vec<&Item> = self.get_items().await?.into_values()
+            .filter(|item| !matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)))
+            .collect();
+        items.sort_by(|a, b| SortKey::Url.compare(a, b));
+        let ics = crate::ical::build_calendar(items, &crate::ical::default_prod_id(), self.color())?;
+        writer.write_all(ics.as_bytes())?;
+        Ok(())
+    }
+
     /// Returns all items that this calendar contains
     async fn get_items_mut(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>>;
 
     /// Returns a particular item
     async fn get_item_by_url<'a>(&'a self, url: &Url) -> Option<&'a Item>;
 
+    /// Returns the item that has a given UID
+    async fn get_item_by_uid<'a>(&'a self, uid: &str) -> Option<&'a Item> {
+        match self.get_items().await {
+            Err(_) => None,
+            Ok(items) => items.into_values()
+                .find(|item| item.uid() == uid),
+        }
+    }
+
     /// Returns a particular item
     async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item>;
 
+    /// Add a copy of an item to this calendar, and return the URL of the copy.
+    ///
+    /// The copy has a new UID, so that it is a distinct item, that will be uploaded at the next sync.
+    async fn duplicate_item(&mut self, url: &Url) -> Result<Url, Box<dyn Error>> {
+        let item = self.get_item_by_url(url).await.cloned()
+            .ok_or_else(|| format!("No item {} in calendar {}", url, self.url()))?;
+        let new_uid = crate::item::ItemUid::generate();
+        let new_url = crate::utils::random_url(self.url());
+        let copy = item.clone_with_identity(new_uid, new_url.clone(), SyncStatus::NotSynced);
+        self.add_item(copy).await?;
+        Ok(new_url)
+    }
+
+    /// Add several items at once.
+    ///
+    /// Implementors may override this to save or notify the whole batch at once, rather than once per item.
+    async fn add_items(&mut self, items: Vec<Item>) -> Result<Vec<SyncStatus>, Box<dyn Error>> {
+        let mut statuses = Vec::with_capacity(items.len());
+        for item in items {
+            statuses.push(self.add_item(item).await?);
+        }
+        Ok(statuses)
+    }
+
+    /// Mark several items for deletion at once. See [`CompleteCalendar::mark_for_deletion`]
+    async fn delete_items(&mut self, item_ids: &[Url]) -> Result<(), Box<dyn Error>> {
+        for item_id in item_ids {
+            self.mark_for_deletion(item_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Mark several tasks as completed at once (e.g. for a "complete all" feature).
+    ///
+    /// Tasks that are completed already are left untouched.
+    async fn mark_completed(&mut self, item_ids: &[Url]) -> Result<(), Box<dyn Error>> {
+        let now = crate::clock::now();
+        for item_id in item_ids {
+            match self.get_item_by_url_mut(item_id).await {
+                None => return Err(format!("No item {}", item_id).into()),
+                Some(Item::Event(_)) => return Err(format!("Item {} is an event, it cannot be completed", item_id).into()),
+                Some(Item::Task(task)) => {
+                    if !task.completed() {
+                        task.complete(now);
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
     /// Mark an item for deletion.
     /// This is required so that the upcoming sync will know it should also also delete this task from the server
     /// (and then call [`CompleteCalendar::immediately_delete_item`] once it has been successfully deleted on the server)
     async fn mark_for_deletion(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>>;
 
+    /// Move an item to the trash, e.g. to let the user undo its deletion.
+    ///
+    /// The item is hidden locally (i.e. from [`CompleteCalendar::get_items_matching`] and [`CompleteCalendar::get_items_page`]), but it is kept as-is,
+    /// and is not deleted from the server, until the trash is purged (see [`CompleteCalendar::purge_trash`]). Until then, it can be restored (see [`CompleteCalendar::restore_item`]).
+    /// Calendars that have no trash return an error (which is the default), and callers should rather use [`CompleteCalendar::mark_for_deletion`]
+    async fn trash_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        Err(format!("Item {} cannot be moved to the trash, calendar {} has no trash", item_id, self.url()).into())
+    }
+
+    /// Take an item out of the trash. See [`CompleteCalendar::trash_item`]
+    async fn restore_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        Err(format!("Item {} cannot be restored, calendar {} has no trash", item_id, self.url()).into())
+    }
+
+    /// When an item has been moved to the trash, or `None` if it is not in the trash (which is the default)
+    fn trashed_at(&self, _item_id: &Url) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Returns the items that are in the trash
+    async fn get_trashed_items<'a>(&'a self) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        let items = self.get_items().await?;
+        Ok(items.into_iter()
+            .filter(|(url, _item)| self.trashed_at(url).is_some())
+            .collect())
+    }
+
+    /// Mark the items that have been moved to the trash at or before `trashed_before` for deletion (see [`CompleteCalendar::mark_for_deletion`]),
+    /// so that the next sync deletes them from the server. Returns their URLs.
+    ///
+    /// The [`Provider`](crate::provider::Provider) calls this before syncing a calendar (see [`Provider::set_trash_purge_delay`](crate::provider::Provider::set_trash_purge_delay))
+    async fn purge_trash(&mut self, _trashed_before: DateTime<Utc>) -> Result<Vec<Url>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Move the items that match `policy` out of this calendar, into its archive, and return their URLs.
+    ///
+    /// Archived items are not part of [`CompleteCalendar::get_items`] and the like any more, so that day-to-day operations on long-lived calendars
+    /// do not have to go through all of their old items. They can still be queried (see [`CompleteCalendar::get_archived_items`]),
+    /// and moved back into this calendar (see [`CompleteCalendar::unarchive_item`]).
+    /// Calendars that have no archive return an error (which is the default)
+    async fn archive_items(&mut self, _policy: &ArchivePolicy) -> Result<Vec<Url>, Box<dyn Error>> {
+        Err(format!("Calendar {} has no archive", self.url()).into())
+    }
+
+    /// Returns the items that are in the archive of this calendar (which is empty by default). See [`CompleteCalendar::archive_items`]
+    async fn get_archived_items<'a>(&'a self) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        Ok(HashMap::new())
+    }
+
+    /// Returns the items of the archive of this calendar that match a filter
+    async fn get_archived_items_matching<'a>(&'a self, filter: &ItemFilter) -> Result<HashMap<Url, &'a Item>, Box<dyn Error>> {
+        let items = self.get_archived_items().await?;
+        Ok(items.into_iter()
+            .filter(|(_url, item)| filter.matches(item))
+            .collect())
+    }
+
+    /// Move an item out of the archive, back into this calendar. See [`CompleteCalendar::archive_items`]
+    async fn unarchive_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>> {
+        Err(format!("Item {} is not in the archive of calendar {}", item_id, self.url()).into())
+    }
+
+    /// Returns the items from the remote source that could not be parsed (see [`DavCalendar::take_unparsed_items`])
+    async fn get_unparsed_items<'a>(&'a self) -> Vec<&'a UnparsedItem> {
+        Vec::new()
+    }
+
+    /// Replace the items from the remote source that could not be parsed.
+    ///
+    /// Calendars that are not able to store them ignore them (which is the default).
+    async fn set_unparsed_items(&mut self, _items: Vec<UnparsedItem>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Returns the items with local changes that a sync has deleted because they vanished from the remote calendar (see [`VanishedItem`]).
+    ///
+    /// The [`Provider`](crate::provider::Provider) looks for them in the other calendars after every sync, in case they have been moved on the server.
+    async fn get_vanished_items<'a>(&'a self) -> Vec<&'a VanishedItem> {
+        Vec::new()
+    }
+
+    /// Replace the items with local changes that a sync has deleted because they vanished from the remote calendar.
+    ///
+    /// Calendars that are not able to store them ignore them (which is the default). Their local changes are lost when their item has been moved on the server.
+    async fn set_vanished_items(&mut self, _items: Vec<VanishedItem>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Returns the version of an item at its latest sync, which is used to merge changes made to both sources field by field.
+    ///
+    /// Calendars that do not keep track of them return `None` (which is the default), so that conflicts are resolved by keeping a single version.
+    async fn get_synced_snapshot<'a>(&'a self, _url: &Url) -> Option<&'a Item> {
+        None
+    }
+
+    /// Remember the current version of an item as the one both sources agree on. This is called by the [`Provider`](crate::provider::Provider) every time an item has been synced
+    async fn save_synced_snapshot(&mut self, _url: &Url) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Store the permissions of the current user on this calendar (usually, those reported by the server, see [`BaseCalendar::permissions`]).
+    /// Calendars that do not store them can ignore this (which is the default)
+    fn set_permissions(&mut self, _permissions: Option<Permissions>) {}
+
+    /// Store the metadata of this calendar (usually, the ones reported by the server, see [`BaseCalendar::metadata`]).
+    /// Calendars that do not store them can ignore this (which is the default)
+    fn set_metadata(&mut self, _metadata: CalendarMetadata) {}
+
+    /// When this calendar has last been synced, or `None` if it has never been synced (or if this is unknown, which is the default)
+    fn last_sync(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Remember when this calendar has been synced. This is called by the [`Provider`](crate::provider::Provider) at the end of every sync of this calendar.
+    /// Calendars that do not store it can ignore this (which is the default)
+    fn set_last_sync(&mut self, _last_sync: DateTime<Utc>) {}
+
+    /// How an item is kept out of syncs, or `None` if it is synced (which is the default).
+    /// This does not take the exclusion of the whole calendar into account (see [`CompleteCalendar::calendar_sync_exclusion`])
+    fn sync_exclusion(&self, _item_id: &Url) -> Option<SyncExclusion> {
+        None
+    }
+
+    /// Keep an item out of syncs, or sync it again with `None`. This is enforced by the [`Provider`](crate::provider::Provider).
+    ///
+    /// The item does not have to be in this calendar (e.g. to never download an item that is on the server only).
+    /// Calendars that cannot store this return an error (which is the default)
+    fn set_sync_exclusion(&mut self, item_id: &Url, _exclusion: Option<SyncExclusion>) -> Result<(), Box<dyn Error>> {
+        Err(format!("Item {} cannot be excluded from syncs, calendar {} does not support it", item_id, self.url()).into())
+    }
+
+    /// How this whole calendar is kept out of syncs, or `None` if it is synced (which is the default)
+    fn calendar_sync_exclusion(&self) -> Option<SyncExclusion> {
+        None
+    }
+
+    /// Keep this whole calendar out of syncs, or sync it again with `None`. See [`CompleteCalendar::set_sync_exclusion`]
+    fn set_calendar_sync_exclusion(&mut self, _exclusion: Option<SyncExclusion>) -> Result<(), Box<dyn Error>> {
+        Err(format!("Calendar {} cannot be excluded from syncs", self.url()).into())
+    }
+
     /// Immediately remove an item. See [`CompleteCalendar::mark_for_deletion`]
     async fn immediately_delete_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>>;
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::calendar::remote_calendar::RemoteCalendar;
+    use crate::task::Task;
+
+    // This would not build if the traits were not object-safe
+    fn _assert_object_safe(
+        _source: &dyn CalDavSource<RemoteCalendar>,
+        _base: &dyn BaseCalendar,
+        _dav: &dyn DavCalendar,
+        _complete: &dyn CompleteCalendar,
+    ) {}
+
+    #[tokio::test]
+    async fn test_calendar_trait_objects() {
+        let mut calendars: Vec<Box<dyn CompleteCalendar + Send + Sync>> = Vec::new();
+        for name in ["First", "Second"] {
+            let url: Url = format!("https://caldav.com/{}/", name).parse().unwrap();
+            calendars.push(Box::new(<CachedCalendar as CompleteCalendar>::new(name.to_string(), url, SupportedComponents::TODO, None)));
+        }
+
+        for calendar in calendars.iter_mut() {
+            let task = Task::new("Task".to_string(), false, calendar.url());
+            calendar.add_item(Item::Task(task)).await.unwrap();
+        }
+        for calendar in calendars.iter() {
+            assert_eq!(calendar.get_items().await.unwrap().len(), 1);
+        }
+    }
+}
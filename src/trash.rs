@@ -0,0 +1,104 @@
+//! Items that have been deleted, but can still be restored from the trash bin of the server
+//!
+//! This is a Nextcloud extension: deleted items are kept in a `trashbin` collection of the calendar home for some time.
+//! See [`Client::trashed_items`](crate::client::Client::trashed_items).
+
+use chrono::{DateTime, Utc};
+use minidom::Element;
+use url::Url;
+
+use crate::item::{Item, SyncStatus, VersionTag};
+use crate::resource::Resource;
+use crate::utils::{find_elem, redact};
+
+pub(crate) static TRASHED_ITEMS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:nc="http://nextcloud.com/ns">
+        <d:prop>
+            <d:getetag />
+            <d:resourcetype />
+            <nc:calendar-uri />
+            <nc:deleted-at />
+            <c:calendar-data />
+        </d:prop>
+    </d:propfind>
+"#;
+
+/// An item in the trash bin
+#[derive(Clone, Debug)]
+pub struct TrashedItem {
+    /// The URL of this item in the trash bin
+    pub url: Url,
+    /// The URL of the calendar this item has been deleted from
+    pub calendar_url: Url,
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The deleted item (or `None` if it cannot be parsed)
+    pub item: Option<Item>,
+}
+
+/// Parse a reply to a `PROPFIND` request on the `objects` collection of the trash bin
+pub(crate) fn parse_trashed_item(rep: &Element, trash: &Resource, cal_home_set: &Url) -> Option<TrashedItem> {
+    // The collection itself is part of the response
+    let is_collection = find_elem(rep, "resourcetype")
+        .map(|rt| rt.children().any(|child| child.name() == "collection"))
+        .unwrap_or(false);
+    if is_collection {
+        return None;
+    }
+
+    let url = trash.resolve_href(&find_elem(rep, "href")?.text()).url().clone();
+    let calendar_uri = find_elem(rep, "calendar-uri")?.text();
+    let calendar_url = cal_home_set.join(&format!("{}/", calendar_uri.trim_matches('/'))).ok()?;
+    let deleted_at = find_elem(rep, "deleted-at")
+        .and_then(|date| DateTime::parse_from_rfc3339(date.text().trim()).ok())
+        .map(|date| date.with_timezone(&Utc));
+
+    let item = find_elem(rep, "calendar-data").and_then(|data| {
+        let version_tag = VersionTag::from(find_elem(rep, "getetag").map(|etag| etag.text()).unwrap_or_default());
+        match crate::ical::parse(&data.text(), url.clone(), SyncStatus::Synced(version_tag)) {
+            Ok(item) => Some(item),
+            Err(err) => {
+                log::warn!("Unable to parse trashed item {}: {}", redact(&url), err);
+                None
+            },
+        }
+    });
+
+    Some(TrashedItem { url, calendar_url, deleted_at, item })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trashed_item() {
+        let response = r#"<d:response xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav" xmlns:nc="http://nextcloud.com/ns">
+            <d:href>/remote.php/dav/calendars/john/trashbin/objects/42-task.ics</d:href>
+            <d:propstat><d:prop>
+                <d:getetag>"abc"</d:getetag>
+                <d:resourcetype/>
+                <nc:calendar-uri>personal</nc:calendar-uri>
+                <nc:deleted-at>2021-10-26T13:00:00+00:00</nc:deleted-at>
+                <cal:calendar-data>BEGIN:VCALENDAR&#13;
+VERSION:2.0&#13;
+PRODID:-//Nextcloud//EN&#13;
+BEGIN:VTODO&#13;
+UID:deleted-task&#13;
+DTSTAMP:20211026T120000Z&#13;
+SUMMARY:Deleted task&#13;
+END:VTODO&#13;
+END:VCALENDAR&#13;
+</cal:calendar-data>
+            </d:prop></d:propstat>
+        </d:response>"#;
+        let home: Url = "https://cloud.example.com/remote.php/dav/calendars/john/".parse().unwrap();
+        let trash = Resource::new(home.join("trashbin/objects/").unwrap(), "john".to_string(), "pass".to_string());
+
+        let trashed = parse_trashed_item(&response.parse().unwrap(), &trash, &home).unwrap();
+        assert_eq!(trashed.calendar_url.as_str(), "https://cloud.example.com/remote.php/dav/calendars/john/personal/");
+        assert_eq!(trashed.url.as_str(), "https://cloud.example.com/remote.php/dav/calendars/john/trashbin/objects/42-task.ics");
+        assert_eq!(trashed.deleted_at.unwrap().to_rfc3339(), "2021-10-26T13:00:00+00:00");
+        assert_eq!(trashed.item.unwrap().name(), "Deleted task");
+    }
+}
@@ -1,11 +1,14 @@
 //! Some utility functions
 
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
-use std::hash::Hash;
+use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Display;
 use std::io::{stdin, stdout, Read, Write};
 
 use minidom::Element;
+use tokio::sync::RwLock;
 use url::Url;
 
 use crate::traits::CompleteCalendar;
@@ -62,13 +65,14 @@ pub fn print_xml(element: &Element) {
 }
 
 /// A debug utility that pretty-prints calendars
-pub async fn print_calendar_list<C>(cals: &HashMap<Url, Arc<Mutex<C>>>)
+pub async fn print_calendar_list<C>(cals: &HashMap<Url, Arc<RwLock<C>>>)
 where
     C: CompleteCalendar,
 {
     for (url, cal) in cals {
-        println!("CAL {} ({})", cal.lock().unwrap().name(), url);
-        match cal.lock().unwrap().get_items().await {
+        let cal = cal.read().await;
+        println!("CAL {} ({})", cal.name(), url);
+        match cal.get_items().await {
             Err(_err) => continue,
             Ok(map) => {
                 for (_, item) in map {
@@ -80,13 +84,14 @@ where
 }
 
 /// A debug utility that pretty-prints calendars
-pub async fn print_dav_calendar_list<C>(cals: &HashMap<Url, Arc<Mutex<C>>>)
+pub async fn print_dav_calendar_list<C>(cals: &HashMap<Url, Arc<RwLock<C>>>)
 where
     C: DavCalendar,
 {
     for (url, cal) in cals {
-        println!("CAL {} ({})", cal.lock().unwrap().name(), url);
-        match cal.lock().unwrap().get_item_version_tags().await {
+        let cal = cal.read().await;
+        println!("CAL {} ({})", cal.name(), url);
+        match cal.get_item_version_tags().await {
             Err(_err) => continue,
             Ok(map) => {
                 for (url, version_tag) in map {
@@ -114,6 +119,19 @@ pub fn print_task(item: &Item) {
 }
 
 
+/// Format a value that may contain personal data (e.g. an item URL or UID) for a log message.
+///
+/// Unless [`LOG_PERSONAL_DATA`](crate::config::LOG_PERSONAL_DATA) is enabled, this returns a short hash of the value instead
+pub fn redact<D: Display>(value: D) -> String {
+    let value = value.to_string();
+    if *crate::config::LOG_PERSONAL_DATA.lock().unwrap() {
+        return value;
+    }
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("<redacted {:08x}>", hasher.finish() as u32)
+}
+
 /// Compare keys of two hashmaps for equality
 pub fn keys_are_the_same<T, U, V>(left: &HashMap<T, U>, right: &HashMap<T, V>) -> bool
 where
@@ -155,3 +173,37 @@ pub fn random_url(parent_calendar: &Url) -> Url {
     let random = uuid::Uuid::new_v4().to_hyphenated().to_string();
     parent_calendar.join(&random).unwrap(/* this cannot panic since we've just created a string that is a valid URL */)
 }
+
+/// The URL of an item named after its UID (`<UID>.ics`) in a given calendar.
+///
+/// Returns `None` if the UID contains characters that are not safe in a URL.
+pub fn uid_url(parent_calendar: &Url, uid: &str) -> Option<Url> {
+    if uid.is_empty() || uid.chars().any(|c| !c.is_ascii_alphanumeric() && !"-_.@".contains(c)) {
+        return None;
+    }
+    parent_calendar.join(&format!("{}.ics", uid)).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        // Personal data is not logged by default
+        let url: Url = "https://my.server.com/calendars/john/doctor-appointment.ics".parse().unwrap();
+        let redacted = redact(&url);
+        assert!(!redacted.contains("doctor"));
+        assert_eq!(redacted, redact(url.as_str()));
+        assert_ne!(redacted, redact("another-item"));
+    }
+
+    #[test]
+    fn test_uid_url() {
+        let cal: Url = "https://my.server.com/calendars/john/tasks/".parse().unwrap();
+        assert_eq!(uid_url(&cal, "abc-123@example.com").unwrap().as_str(), "https://my.server.com/calendars/john/tasks/abc-123@example.com.ics");
+        assert_eq!(uid_url(&cal, "../other/item"), None);
+        assert_eq!(uid_url(&cal, ""), None);
+    }
+}
@@ -0,0 +1,252 @@
+//! Checks that items comply with RFC 5545 before they are uploaded
+//!
+//! Servers usually reject invalid items with an HTTP 400 error that does not tell what is wrong.
+//! Calling [`Item::validate`](crate::Item::validate) (which the [`Provider`](crate::provider::Provider) does before pushing local changes)
+//! gives the list of every [`Violation`] instead.
+//!
+//! Note that line lengths are not checked: long lines are folded when items are serialized.
+
+use std::fmt::{Display, Formatter};
+
+use ical::property::Property;
+
+/// Values of `STATUS` that are allowed in a `VTODO` (RFC 5545 section 3.8.1.11)
+const VTODO_STATUSES: [&str; 4] = ["NEEDS-ACTION", "COMPLETED", "IN-PROCESS", "CANCELLED"];
+/// Values of `STATUS` that are allowed in a `VEVENT`
+const VEVENT_STATUSES: [&str; 3] = ["TENTATIVE", "CONFIRMED", "CANCELLED"];
+
+/// A way an item does not comply with RFC 5545
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The item has no UID
+    MissingUid,
+    /// The `STATUS` is not one of the values allowed for this kind of item
+    InvalidStatus(String),
+    /// A date-time property cannot be parsed
+    InvalidDate{ property: String, value: String },
+    /// The item ends (or is due) before it starts
+    EndBeforeStart{ start: String, end: String },
+    /// A property that is not allowed in this kind of item (or not together with another one)
+    ForbiddenProperty(String),
+    /// A property that can be specified at most once is present several times
+    DuplicateProperty(String),
+    /// A property value is not in its allowed range
+    InvalidValue{ property: String, value: String },
+    /// A property name contains characters that would break the structure of the iCal file
+    InvalidPropertyName(String),
+    /// A property value contains control characters, that cannot be represented in iCal files
+    ControlCharacter(String),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingUid => write!(f, "missing UID"),
+            Self::InvalidStatus(status) => write!(f, "invalid STATUS {}", status),
+            Self::InvalidDate{ property, value } => write!(f, "invalid date {} for {}", value, property),
+            Self::EndBeforeStart{ start, end } => write!(f, "{} is before {}", end, start),
+            Self::ForbiddenProperty(name) => write!(f, "forbidden property {}", name),
+            Self::DuplicateProperty(name) => write!(f, "property {} is present several times", name),
+            Self::InvalidValue{ property, value } => write!(f, "invalid value {} for {}", value, property),
+            Self::InvalidPropertyName(name) => write!(f, "invalid property name {:?}", name),
+            Self::ControlCharacter(name) => write!(f, "property {} contains control characters", name),
+        }
+    }
+}
+
+/// Formats a list of violations, e.g. for logging
+pub fn describe(violations: &[Violation]) -> String {
+    violations.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Check the properties of a task that are not parsed by this crate (see [`Task::extra_parameters`](crate::Task::extra_parameters))
+pub(crate) fn check_todo_properties(properties: &[Property], violations: &mut Vec<Violation>) {
+    for prop in properties {
+        let value = prop.value.as_deref().unwrap_or("");
+        check_syntax(prop, violations);
+
+        match prop.name.as_str() {
+            "DTEND" => violations.push(Violation::ForbiddenProperty(prop.name.clone())),
+            "STATUS"
+                if !VTODO_STATUSES.contains(&value) => {
+                    violations.push(Violation::InvalidStatus(value.to_string()));
+                },
+            "DURATION"
+                if crate::ical::parse_duration(value).is_none() => {
+                    violations.push(Violation::InvalidValue{ property: prop.name.clone(), value: value.to_string() });
+                },
+            "PERCENT-COMPLETE" => check_integer(prop, 0, 100, violations),
+            "PRIORITY" => check_integer(prop, 0, 9, violations),
+            "SEQUENCE" => check_integer(prop, 0, i64::from(u32::MAX), violations),
+            _ => (),
+        }
+    }
+
+    for name in ["DTSTART", "DUE", "DURATION", "PRIORITY", "PERCENT-COMPLETE", "SEQUENCE", "DESCRIPTION", "STATUS"] {
+        if properties.iter().filter(|prop| prop.name == name).count() > 1 {
+            violations.push(Violation::DuplicateProperty(name.to_string()));
+        }
+    }
+    // A DURATION is relative to the DTSTART, and replaces the DUE
+    if has_property(properties, "DURATION") && (has_property(properties, "DUE") || !has_property(properties, "DTSTART")) {
+        violations.push(Violation::ForbiddenProperty("DURATION".to_string()));
+    }
+
+    let start = parse_date_property(properties, "DTSTART", violations);
+    let due = parse_date_property(properties, "DUE", violations);
+    if let (Some(start), Some(due)) = (start, due) {
+        if due < start {
+            violations.push(Violation::EndBeforeStart{ start: "DTSTART".to_string(), end: "DUE".to_string() });
+        }
+    }
+}
+
+/// Check the properties of an event that are not parsed by this crate (see [`Event::extra_parameters`](crate::Event::extra_parameters))
+pub(crate) fn check_event_properties(properties: &[Property], violations: &mut Vec<Violation>) {
+    for prop in properties {
+        let value = prop.value.as_deref().unwrap_or("");
+        check_syntax(prop, violations);
+
+        match prop.name.as_str() {
+            "DUE" | "COMPLETED" | "PERCENT-COMPLETE" => violations.push(Violation::ForbiddenProperty(prop.name.clone())),
+            "STATUS"
+                if !VEVENT_STATUSES.contains(&value) => {
+                    violations.push(Violation::InvalidStatus(value.to_string()));
+                },
+            "TRANSP"
+                if crate::event::Transparency::from_ical(value).is_none() => {
+                    violations.push(Violation::InvalidValue{ property: prop.name.clone(), value: value.to_string() });
+                },
+            "DURATION"
+                if crate::ical::parse_duration(value).is_none() => {
+                    violations.push(Violation::InvalidValue{ property: prop.name.clone(), value: value.to_string() });
+                },
+            "PRIORITY" => check_integer(prop, 0, 9, violations),
+            "SEQUENCE" => check_integer(prop, 0, i64::from(u32::MAX), violations),
+            _ => (),
+        }
+    }
+
+    for name in ["DTSTART", "DTEND", "DURATION", "PRIORITY", "SEQUENCE", "DESCRIPTION", "STATUS", "TRANSP"] {
+        if properties.iter().filter(|prop| prop.name == name).count() > 1 {
+            violations.push(Violation::DuplicateProperty(name.to_string()));
+        }
+    }
+    // An event ends either at its DTEND or after its DURATION, which is relative to its DTSTART
+    if has_property(properties, "DURATION") && (has_property(properties, "DTEND") || !has_property(properties, "DTSTART")) {
+        violations.push(Violation::ForbiddenProperty("DURATION".to_string()));
+    }
+
+    let start = parse_date_property(properties, "DTSTART", violations);
+    let end = parse_date_property(properties, "DTEND", violations);
+    if let (Some(start), Some(end)) = (start, end) {
+        if end < start {
+            violations.push(Violation::EndBeforeStart{ start: "DTSTART".to_string(), end: "DTEND".to_string() });
+        }
+    }
+}
+
+/// Check that a property can be written to an iCal file as it is
+fn check_syntax(prop: &Property, violations: &mut Vec<Violation>) {
+    if prop.name.is_empty() || prop.name.chars().any(|c| c.is_whitespace() || c.is_control() || ":;,\"".contains(c)) {
+        violations.push(Violation::InvalidPropertyName(prop.name.clone()));
+    }
+    if prop.value.as_deref().unwrap_or("").chars().any(|c| c.is_control() && c != '\t' && c != '\n' && c != '\r') {
+        violations.push(Violation::ControlCharacter(prop.name.clone()));
+    }
+}
+
+fn has_property(properties: &[Property], name: &str) -> bool {
+    properties.iter().any(|prop| prop.name == name)
+}
+
+fn check_integer(prop: &Property, min: i64, max: i64, violations: &mut Vec<Violation>) {
+    let value = prop.value.as_deref().unwrap_or("");
+    match value.parse::<i64>() {
+        Ok(n) if min <= n && n <= max => (),
+        _ => violations.push(Violation::InvalidValue{ property: prop.name.clone(), value: value.to_string() }),
+    }
+}
+
+fn parse_date_property(properties: &[Property], name: &str, violations: &mut Vec<Violation>) -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = properties.iter()
+        .find(|prop| prop.name == name)
+        .and_then(|prop| prop.value.as_deref())?;
+    match crate::ical::parse_date_or_date_time(value) {
+        Ok(dt) => Some(dt),
+        Err(_) => {
+            violations.push(Violation::InvalidDate{ property: name.to_string(), value: value.to_string() });
+            None
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{new_event, new_task, prop};
+
+    #[test]
+    fn test_task_validation() {
+        let valid = new_task("A task", "some-uid", false, vec![
+            prop("DTSTART", "20210401T080000Z"),
+            prop("DUE", "20210402"),
+            prop("PRIORITY", "1"),
+            prop("X-CUSTOM-PROP", "anything\twith a tab"),
+            prop("X_NON_STANDARD_BUT_HARMLESS", "value"),
+        ]);
+        assert_eq!(valid.validate(), Ok(()));
+
+        let invalid = new_task("A task", "", false, vec![
+            prop("DTSTART", "20210403T080000Z"),
+            prop("DUE", "20210402T080000Z"),
+            prop("STATUS", "DONE"),
+            prop("PERCENT-COMPLETE", "140"),
+            prop("PRIORITY", "1"),
+            prop("PRIORITY", "2"),
+            prop("DTEND", "20210404T080000Z"),
+            prop("BAD NAME", "value"),
+            prop("DESCRIPTION", "bell\u{7}"),
+        ]);
+        let violations = invalid.validate().unwrap_err();
+        assert_eq!(violations, vec![
+            Violation::MissingUid,
+            Violation::InvalidStatus("DONE".to_string()),
+            Violation::InvalidValue{ property: "PERCENT-COMPLETE".to_string(), value: "140".to_string() },
+            Violation::ForbiddenProperty("DTEND".to_string()),
+            Violation::InvalidPropertyName("BAD NAME".to_string()),
+            Violation::ControlCharacter("DESCRIPTION".to_string()),
+            Violation::DuplicateProperty("PRIORITY".to_string()),
+            Violation::EndBeforeStart{ start: "DTSTART".to_string(), end: "DUE".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_event_validation() {
+        let valid = new_event("An event", "some-uid", vec![
+            prop("DTSTART", "20210401T080000Z"),
+            prop("DURATION", "PT1H"),
+            prop("TRANSP", "TRANSPARENT"),
+        ]);
+        assert_eq!(valid.validate(), Ok(()));
+
+        let invalid = new_event("An event", "some-uid", vec![
+            prop("DTSTART", "20210403T080000Z"),
+            prop("DTEND", "20210402T080000Z"),
+            prop("DURATION", "PT1H"),
+            prop("STATUS", "COMPLETED"),
+            prop("DUE", "20210404T080000Z"),
+        ]);
+        let violations = invalid.validate().unwrap_err();
+        assert_eq!(violations, vec![
+            Violation::InvalidStatus("COMPLETED".to_string()),
+            Violation::ForbiddenProperty("DUE".to_string()),
+            Violation::ForbiddenProperty("DURATION".to_string()),
+            Violation::EndBeforeStart{ start: "DTSTART".to_string(), end: "DTEND".to_string() },
+        ]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Syncs against traces recorded with real servers (see `kitchen_fridge::replay`)
+
+use std::path::PathBuf;
+
+use url::Url;
+
+use kitchen_fridge::cache::Cache;
+use kitchen_fridge::provider::Provider;
+use kitchen_fridge::replay::ReplayClient;
+use kitchen_fridge::traits::{BaseCalendar, CalDavSource, CompleteCalendar};
+use kitchen_fridge::calendar::cached_calendar::CachedCalendar;
+use kitchen_fridge::calendar::remote_calendar::RemoteCalendar;
+
+type ReplayProvider = Provider<Cache, CachedCalendar, ReplayClient, RemoteCalendar>;
+
+/// Sync an empty cache with a recorded server
+async fn first_sync(server_url: &str, trace: &str) -> ReplayProvider {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let client = ReplayClient::from_trace_file(server_url, format!("tests/assets/traces/{}.jsonl", trace)).unwrap();
+    let cache = Cache::new(&PathBuf::from(format!("test_cache/replay_{}", trace)));
+    let mut provider = Provider::new(client, cache);
+    assert!(provider.sync().await);
+    provider
+}
+
+#[tokio::test]
+async fn replay_nextcloud_first_sync() {
+    let provider = first_sync("https://cloud.example.com/remote.php/dav/", "nextcloud-first-sync").await;
+    assert!(provider.remote().capabilities().unwrap().supports_multiget());
+
+    let cal_url = Url::parse("https://cloud.example.com/remote.php/dav/calendars/john/tasks/").unwrap();
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().await;
+    assert_eq!(cal.name(), "Tasks");
+    assert_eq!(cal.metadata().description.as_deref(), Some("Things to do at home"));
+    assert!(!cal.permissions().unwrap().is_read_only());
+
+    assert_eq!(cal.get_items().await.unwrap().len(), 2);
+    let milk = cal.get_item_by_uid("buy-milk@example.com").await.unwrap().unwrap_task();
+    assert_eq!(milk.name(), "Buy milk");
+    assert_eq!(milk.extra_property("X-OC-HIDESUBTASKS").as_deref(), Some("0"));
+    // The href of this item is percent-encoded by the server
+    let bike = cal.get_item_by_uid("fix-the-bike@example.com").await.unwrap().unwrap_task();
+    assert!(bike.completed());
+}
+
+#[tokio::test]
+async fn replay_radicale_first_sync() {
+    let provider = first_sync("https://radicale.example.com/", "radicale-first-sync").await;
+    let capabilities = provider.remote().capabilities().unwrap();
+    assert!(capabilities.supports_move() && capabilities.supports_multiget());
+
+    // Radicale serves the principal and the calendar home set at the same URL
+    let cal_url = Url::parse("https://radicale.example.com/john/4f7b5c3a-2b1e-4f5c-9d8e-1a2b3c4d5e6f/").unwrap();
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().await;
+    assert_eq!(cal.name(), "Work");
+    assert_eq!(cal.color(), Some(&csscolorparser::parse("#ff8000").unwrap()));
+
+    let invoices = cal.get_item_by_uid("a1b2c3").await.unwrap().unwrap_task();
+    assert_eq!(invoices.name(), "Send the invoices");
+    assert_eq!(invoices.color(), Some(csscolorparser::parse("tomato").unwrap()));
+}
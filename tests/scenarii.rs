@@ -12,6 +12,7 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::error::Error;
 use url::Url;
+use tokio::sync::RwLock;
 
 use chrono::Utc;
 
@@ -78,7 +79,7 @@ pub struct ItemScenario {
 ///     * server: A,    C, D,  E', F',  G✓, H , I',      K✓,    M✓, N , O, P✓,  Q
 ///
 /// Hence, here is the expected result after the sync:
-///     * both:   A,       D', E', F',  G✓, H✓, I',      K✓,   M, N, O, P', Q, R
+///     * both:   A,       D', E', F',  G✓, H✓, I✓',     K✓,   M, N, O, P', Q, R
 ///
 /// Notes:
 /// * X': name has been modified since the last sync
@@ -238,11 +239,11 @@ pub fn scenarii_basic() -> Vec<ItemScenario> {
             }),
             local_changes_to_apply: vec![ChangeToApply::SetCompletion(true)],
             remote_changes_to_apply: vec![ChangeToApply::Rename(String::from("Task I, remotely renamed"))],
-            // Conflict, the server wins
+            // Conflict on different fields: both changes are merged
             after_sync: LocatedState::BothSynced( ItemState{
                 calendar: second_cal.clone(),
                 name: String::from("Task I, remotely renamed"),
-                completed: false,
+                completed: true,
             }),
         }
     );
@@ -649,14 +650,14 @@ async fn populate_test_provider(scenarii: &[ItemScenario], mock_behaviour: Arc<M
         match required_state {
             LocatedState::None => panic!("Should not happen, we've continued already"),
             LocatedState::Local(s) => {
-                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().write().await.add_item(new_item).await.unwrap();
             },
             LocatedState::Remote(s) => {
-                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().write().await.add_item(new_item).await.unwrap();
             },
             LocatedState::BothSynced(s) => {
-                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().lock().unwrap().add_item(new_item.clone()).await.unwrap();
-                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().write().await.add_item(new_item.clone()).await.unwrap();
+                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().write().await.add_item(new_item).await.unwrap();
             },
         }
     }
@@ -687,7 +688,7 @@ async fn apply_changes_on_provider(provider: &mut Provider<Cache, CachedCalendar
 }
 
 async fn get_or_insert_calendar(source: &mut Cache, url: &Url)
-    -> Result<Arc<Mutex<CachedCalendar>>, Box<dyn Error>>
+    -> Result<Arc<RwLock<CachedCalendar>>, Box<dyn Error>>
 {
     match source.get_calendar(url).await {
         Some(cal) => Ok(cal),
@@ -729,7 +730,7 @@ where
     C: CompleteCalendar + DavCalendar, // in this test, we're using a calendar that mocks both kinds
 {
     let cal = source.get_calendar(calendar_url).await.unwrap();
-    let mut cal = cal.lock().unwrap();
+    let mut cal = cal.write().await;
     let task = cal.get_item_by_url_mut(item_url).await.unwrap().unwrap_task_mut();
 
     match change {
@@ -777,7 +778,7 @@ where
         }
         ChangeToApply::Create(calendar_url, item) => {
             let cal = source.get_calendar(calendar_url).await.unwrap();
-            cal.lock().unwrap().add_item(item.clone()).await.unwrap();
+            cal.write().await.add_item(item.clone()).await.unwrap();
             calendar_url.clone()
         },
     }